@@ -4,7 +4,7 @@ unsafe fn execute() {
     colog::init();
 
     let devices = MTLCopyAllDevices();
-    let device = devices.into_iter().find_map(|d| Some(d)).unwrap();
+    let device = DeviceSelector::new().select(devices).unwrap();
     log::log!(log::Level::Info, "Name: {}", device.get_name());
     log::log!(
         log::Level::Info,
@@ -1,5 +1,4 @@
 use iron_oxide::*;
-use std::fs::File;
 use std::os::raw::c_void;
 use winit::dpi::PhysicalSize;
 use winit::event::{Event, WindowEvent, StartCause};
@@ -17,7 +16,10 @@ struct MetalBoilerplate {
 impl MetalBoilerplate {
     unsafe fn new(window: &Window) -> MetalBoilerplate {
         let devices = MTLCopyAllDevices();
-        let device = devices.into_iter().find_map(|d| Some(d)).unwrap();
+        let device = DeviceSelector::new()
+            .prefer(DevicePreference::Discrete)
+            .select(devices)
+            .unwrap();
 
         let layer = CAMetalLayer::new();
         layer.set_device(&device);
@@ -128,39 +130,12 @@ struct GameState {
 
 impl GameState {
     unsafe fn new(boilerplate: &MetalBoilerplate) -> GameState {
-        let decoder = png::Decoder::new(File::open("examples/gol/gol_init_state.png").unwrap());
-        let (info, mut reader) = decoder.read_info().unwrap();
-        let mut img = vec![0; info.buffer_size()];
-        reader.next_frame(&mut img).unwrap();
-
-        let cell_state = boilerplate.device.new_texture_with_descriptor(&{
-            let desc = MTLTextureDescriptor::new();
-            desc.set_width(info.width as NSUInteger);
-            desc.set_height(info.height as NSUInteger);
-            desc.set_pixel_format(MTLPixelFormat::RGBA8Unorm);
-            desc.set_texture_type(MTLTextureType::D2);
-            desc.set_usage(MTLTextureUsage::ShaderRead | MTLTextureUsage::ShaderWrite);
-            desc
-        });
-        cell_state.replace_region(
-            MTLRegion {
-                origin: MTLSize {
-                    width: 0,
-                    height: 0,
-                    depth: 0,
-                },
-                size: MTLSize {
-                    width: info.width as NSUInteger,
-                    height: info.height as NSUInteger,
-                    depth: 1,
-                },
-            },
-            0,
-            0,
-            img.as_ptr() as *const c_void,
-            info.width as NSUInteger * 4,
-            0,
-        );
+        let cell_state = TextureImage::from_path("examples/gol/gol_init_state.png")
+            .unwrap()
+            .new_texture(
+                &boilerplate.device,
+                MTLTextureUsage::ShaderRead | MTLTextureUsage::ShaderWrite,
+            );
 
         let compute_fn = boilerplate
             .library
@@ -216,6 +191,7 @@ fn main() {
                 }
             },
             Event::RedrawRequested(_) => unsafe {
+                let _pool = AutoreleasePool::new();
                 if let Some(drawable) = boilerplate.layer.next_drawable() {
                     let command_buffer = boilerplate.queue.new_command_buffer(true);
 
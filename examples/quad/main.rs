@@ -31,7 +31,10 @@ const QUAD_INDICES: [u16; QUAD_LEN as usize] = [0, 1, 2, 2, 3, 0];
 impl MetalBoilerplate {
     unsafe fn new(window: &Window) -> MetalBoilerplate {
         let devices = MTLCopyAllDevices();
-        let device = devices.into_iter().find_map(|d| Some(d)).unwrap();
+        let device = DeviceSelector::new()
+            .prefer(DevicePreference::Discrete)
+            .select(devices)
+            .unwrap();
 
         let layer = CAMetalLayer::new();
         layer.set_device(&device);
@@ -102,13 +105,17 @@ fn main() {
         .unwrap();
 
     let boilerplate = unsafe { MetalBoilerplate::new(&window) };
+    let mut capture = FrameCapture::from_env();
 
     event_loop.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
 
         match event {
             Event::RedrawRequested(_) => unsafe {
+                // Frees the objects autoreleased while drawing the frame.
+                let _pool = AutoreleasePool::new();
                 if let Some(drawable) = boilerplate.layer.next_drawable() {
+                    capture.begin_frame(&boilerplate.queue).unwrap();
                     let command_buffer = boilerplate.queue.new_command_buffer(true);
 
                     let encoder = command_buffer.new_render_command_encoder_with_descriptor(&{
@@ -140,6 +147,7 @@ fn main() {
                     command_buffer.present_drawable(&drawable);
                     command_buffer.commit();
                     command_buffer.wait_until_completed();
+                    capture.end_frame();
                 }
             },
             Event::WindowEvent {
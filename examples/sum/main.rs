@@ -7,8 +7,13 @@ fn main() {
     colog::init();
 
     unsafe {
+        let _pool = AutoreleasePool::new();
         let devices = MTLCopyAllDevices();
-        let device = devices.into_iter().find_map(|d| Some(d)).unwrap();
+        let device = DeviceSelector::new()
+            .prefer(DevicePreference::Headless)
+            .prefer(DevicePreference::Discrete)
+            .select(devices)
+            .unwrap();
 
         let queue = device.new_command_queue();
 
@@ -0,0 +1,194 @@
+use crate::includes::strip_comments;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Punct(char),
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_alphabetic() || c == '_' {
+            let mut end = start + c.len_utf8();
+            while let Some(&(index, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = index + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Ident(&source[start..end]));
+        } else if !c.is_whitespace() {
+            tokens.push(Token::Punct(c));
+        }
+    }
+    tokens
+}
+
+/// Skips a type such as `float4`, `metal::vec<float, 4>` or `MyStruct`, returning the index
+/// of the token after it.
+fn skip_type(tokens: &[Token<'_>], mut index: usize) -> Option<usize> {
+    match tokens.get(index)? {
+        Token::Ident(_) => index += 1,
+        _ => return None,
+    }
+    while tokens.get(index) == Some(&Token::Punct(':'))
+        && tokens.get(index + 1) == Some(&Token::Punct(':'))
+    {
+        match tokens.get(index + 2)? {
+            Token::Ident(_) => index += 3,
+            _ => return None,
+        }
+    }
+    if tokens.get(index) == Some(&Token::Punct('<')) {
+        let mut depth = 0;
+        loop {
+            match tokens.get(index)? {
+                Token::Punct('<') => depth += 1,
+                Token::Punct('>') => depth -= 1,
+                _ => {}
+            }
+            index += 1;
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+    Some(index)
+}
+
+/// Blanks out preprocessor directives, including their continuation lines, so that macro
+/// definitions aren't mistaken for functions.
+fn strip_preprocessor(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut directive = false;
+    for line in source.lines() {
+        directive = directive || line.trim_start().starts_with('#');
+        if !directive {
+            stripped.push_str(line);
+        }
+        stripped.push('\n');
+        directive = directive && line.trim_end().ends_with('\\');
+    }
+    stripped
+}
+
+/// Returns the names of the vertex, fragment and kernel functions which the source
+/// declares, in order and without duplicates.
+///
+/// Both the `kernel void name(...)` and `[[kernel]] void name(...)` forms are recognized.
+/// Functions declared by macros are not.
+pub fn scan_functions(source: &str) -> Vec<String> {
+    let source = strip_preprocessor(&strip_comments(source));
+    let tokens = tokenize(&source);
+    let mut functions: Vec<String> = Vec::new();
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Ident("vertex") | Token::Ident("fragment") | Token::Ident("kernel") => {}
+            _ => continue,
+        }
+        let mut next = index + 1;
+        while tokens.get(next) == Some(&Token::Punct(']')) {
+            next += 1;
+        }
+        let name = skip_type(&tokens, next).and_then(|next| {
+            match (tokens.get(next), tokens.get(next + 1)) {
+                (Some(Token::Ident(name)), Some(Token::Punct('('))) => Some(*name),
+                _ => None,
+            }
+        });
+        if let Some(name) = name {
+            if !functions.iter().any(|function| function == name) {
+                functions.push(name.to_string());
+            }
+        }
+    }
+    functions
+}
+
+/// Converts a function name such as `quad_v` or `sumKernel` to the name of a Rust
+/// constant, such as `QUAD_V` or `SUM_KERNEL`.
+pub fn const_name(function: &str) -> String {
+    let mut name = String::with_capacity(function.len() + 4);
+    let mut last: Option<char> = None;
+    for c in function.chars() {
+        if c.is_uppercase() && last.is_some_and(|last| last.is_lowercase() || last.is_numeric()) {
+            name.push('_');
+        }
+        name.extend(c.to_uppercase());
+        last = Some(c);
+    }
+    if name.starts_with(|c: char| c.is_numeric()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn function_forms() {
+        let source = "
+            #include <metal_stdlib>
+            using namespace metal;
+
+            kernel void add(device float *a [[buffer(0)]]) {}
+            [[kernel]] void sum(uint id [[thread_position_in_grid]]) {}
+            [[ kernel ]]
+            void spaced() {}
+            vertex float4 quad_v(uint id [[vertex_id]]) {}
+            fragment half4 quad_f() {}
+            ";
+        assert_eq!(
+            scan_functions(source),
+            vec!["add", "sum", "spaced", "quad_v", "quad_f"]
+        );
+    }
+
+    #[test]
+    fn templated_and_qualified_return_types() {
+        let source = "
+            vertex metal::vec<float, 4> qualified() {}
+            fragment vec<vec<float, 2>, 2> nested() {}
+            vertex Out<T, 3>::type scoped() {}
+            ";
+        assert_eq!(scan_functions(source), vec!["qualified", "nested"]);
+        assert_eq!(
+            scan_functions("vertex ns::Out<float> templated() {}"),
+            vec!["templated"]
+        );
+    }
+
+    #[test]
+    fn ignored_declarations() {
+        let source = "
+            // kernel void line_comment() {}
+            /* fragment float4
+               block_comment() {} */
+            int kernel_size = 3;
+            float4 helper() {}
+            kernel void add() {}
+            kernel void add() {}
+            #define KERNEL(name) kernel void name()
+            #define FRAGMENT(name) \\
+                fragment float4 name()
+            KERNEL(from_macro) {}
+            ";
+        assert_eq!(scan_functions(source), vec!["add"]);
+    }
+
+    #[test]
+    fn const_names() {
+        assert_eq!(const_name("quad_v"), "QUAD_V");
+        assert_eq!(const_name("quadV"), "QUAD_V");
+        assert_eq!(const_name("sumKernel"), "SUM_KERNEL");
+        assert_eq!(const_name("blur3x3"), "BLUR3X3");
+        assert_eq!(const_name("vec3Normalize"), "VEC3_NORMALIZE");
+        assert_eq!(const_name("HTTPServer"), "HTTPSERVER");
+        assert_eq!(const_name("3d"), "_3D");
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The target of an `#include` directive.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Include {
+    /// `#include "header.h"`, searched for next to the including file, then in the include
+    /// directories.
+    Quoted(String),
+    /// `#include <header.h>`, searched for in the include directories only.
+    Angled(String),
+}
+
+/// Replaces every comment with a space, keeping line breaks so that line numbers still
+/// match the original source.
+pub fn strip_comments(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            stripped.push(c);
+            match c {
+                '\\' => stripped.extend(chars.next()),
+                '"' | '\n' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+                stripped.push(' ');
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut last = '\0';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        stripped.push('\n');
+                    }
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+                stripped.push(' ');
+            }
+            _ => {
+                in_string = c == '"';
+                stripped.push(c);
+            }
+        }
+    }
+    stripped
+}
+
+/// Parses a line without comments as an `#include` directive.
+pub fn parse_include(line: &str) -> Option<Include> {
+    let directive = line.trim_start().strip_prefix('#')?.trim_start();
+    let target = directive.strip_prefix("include")?.trim();
+    let (close, quoted) = match target.chars().next()? {
+        '"' => ('"', true),
+        '<' => ('>', false),
+        _ => return None,
+    };
+    let name = &target[1..];
+    let name = &name[..name.find(close)?];
+    Some(match quoted {
+        true => Include::Quoted(name.to_string()),
+        false => Include::Angled(name.to_string()),
+    })
+}
+
+/// Finds the header an include refers to, or `None` for headers outside of the include
+/// directories, such as `<metal_stdlib>`.
+pub fn resolve_include(
+    include: &Include,
+    including_dir: &Path,
+    include_dirs: &[PathBuf],
+) -> Option<PathBuf> {
+    let (name, local) = match include {
+        Include::Quoted(name) => (name, Some(including_dir)),
+        Include::Angled(name) => (name, None),
+    };
+    local
+        .into_iter()
+        .chain(include_dirs.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Returns every header which the sources include, directly or through other headers, in
+/// the order they are first found. Headers which can't be found are skipped.
+pub fn scan_includes(sources: &[PathBuf], include_dirs: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut seen = HashSet::new();
+    let mut headers = Vec::new();
+    let mut pending = sources.iter().rev().cloned().collect::<Vec<_>>();
+    while let Some(file) = pending.pop() {
+        let source = strip_comments(&std::fs::read_to_string(&file)?);
+        let dir = file.parent().unwrap_or_else(|| Path::new(""));
+        let mut found = Vec::new();
+        for include in source.lines().filter_map(parse_include) {
+            if let Some(header) = resolve_include(&include, dir, include_dirs) {
+                if seen.insert(canonical(&header)) {
+                    headers.push(header.clone());
+                    found.push(header);
+                }
+            }
+        }
+        // Scan the headers in the order they're included.
+        pending.extend(found.into_iter().rev());
+    }
+    Ok(headers)
+}
+
+/// Returns the source of the file with each header found replaced by its contents, so
+/// that it can be compiled without access to the headers. A header which has already been
+/// inlined into `inlined` is left out, and `#line` directives keep diagnostics pointing at
+/// the original files.
+pub fn inline_includes(
+    file: &Path,
+    include_dirs: &[PathBuf],
+    inlined: &mut HashSet<PathBuf>,
+) -> io::Result<String> {
+    let source = std::fs::read_to_string(file)?;
+    let stripped = strip_comments(&source);
+    let dir = file.parent().unwrap_or_else(|| Path::new(""));
+    let mut output = String::with_capacity(source.len());
+    for (index, (line, stripped)) in source.lines().zip(stripped.lines()).enumerate() {
+        let header = parse_include(stripped)
+            .and_then(|include| resolve_include(&include, dir, include_dirs));
+        match header {
+            Some(header) => {
+                if inlined.insert(canonical(&header)) {
+                    let contents = inline_includes(&header, include_dirs, inlined)?;
+                    writeln!(output, "#line 1 {:?}", header.to_string_lossy()).unwrap();
+                    output.push_str(&contents);
+                    writeln!(output, "#line {} {:?}", index + 2, file.to_string_lossy()).unwrap();
+                } else {
+                    output.push('\n');
+                }
+            }
+            None => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("iron-oxide-build-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn line_comments() {
+        assert_eq!(strip_comments("a // b\nc"), "a  \nc");
+        assert_eq!(strip_comments("a // b"), "a  ");
+        // comment markers in strings are kept, as are escaped quotes
+        assert_eq!(
+            strip_comments("\"// \\\" /*\" x // y"),
+            "\"// \\\" /*\" x  "
+        );
+    }
+
+    #[test]
+    fn block_comments() {
+        assert_eq!(strip_comments("a /* b */ c"), "a   c");
+        let source = "a /* b\n * c\n */ d\ne";
+        let stripped = strip_comments(source);
+        assert_eq!(stripped, "a \n\n  d\ne");
+        assert_eq!(stripped.lines().count(), source.lines().count());
+        // block comments don't nest, and may be unterminated
+        assert_eq!(strip_comments("/* /* */ a */"), "  a */");
+        assert_eq!(strip_comments("a /* b\nc"), "a \n ");
+        assert_eq!(strip_comments("a /*/ b */ c"), "a   c");
+    }
+
+    #[test]
+    fn includes() {
+        let quoted = Some(Include::Quoted("a.h".to_string()));
+        assert_eq!(parse_include("#include \"a.h\""), quoted);
+        assert_eq!(parse_include("  #  include   \"a.h\" trailing"), quoted);
+        assert_eq!(
+            parse_include("#include <metal_stdlib>"),
+            Some(Include::Angled("metal_stdlib".to_string()))
+        );
+        assert_eq!(
+            parse_include("#include <dir/b.h>"),
+            Some(Include::Angled("dir/b.h".to_string()))
+        );
+        assert_eq!(parse_include("#import \"a.h\""), None);
+        assert_eq!(parse_include("#include a.h"), None);
+        assert_eq!(parse_include("#include \"a.h"), None);
+        assert_eq!(parse_include("#include <a.h\""), None);
+        assert_eq!(parse_include("int include = 1;"), None);
+        assert_eq!(parse_include("#include"), None);
+    }
+
+    #[test]
+    fn quoted_includes_search_the_including_dir_first() {
+        let root = temp_dir("resolve");
+        let (local, include) = (root.join("local"), root.join("include"));
+        std::fs::create_dir_all(&local).unwrap();
+        std::fs::create_dir_all(&include).unwrap();
+        std::fs::write(local.join("a.h"), "").unwrap();
+        std::fs::write(include.join("a.h"), "").unwrap();
+        std::fs::write(include.join("b.h"), "").unwrap();
+        let dirs = [include.clone()];
+
+        let quoted = |name: &str| Include::Quoted(name.to_string());
+        let angled = |name: &str| Include::Angled(name.to_string());
+        assert_eq!(
+            resolve_include(&quoted("a.h"), &local, &dirs),
+            Some(local.join("a.h"))
+        );
+        assert_eq!(
+            resolve_include(&angled("a.h"), &local, &dirs),
+            Some(include.join("a.h"))
+        );
+        assert_eq!(
+            resolve_include(&quoted("b.h"), &local, &dirs),
+            Some(include.join("b.h"))
+        );
+        assert_eq!(
+            resolve_include(&angled("metal_stdlib"), &local, &dirs),
+            None
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// `main.metal` includes `a.h` and `<common.h>`; `a.h` includes `b.h`, which includes
+    /// `a.h` back, and `<common.h>` again.
+    fn cyclic_headers(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let root = temp_dir(name);
+        let (source, include) = (root.join("src"), root.join("include"));
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&include).unwrap();
+        std::fs::write(
+            source.join("main.metal"),
+            "#include \"a.h\"\n#include <common.h> // again\nkernel void k() {}\n",
+        )
+        .unwrap();
+        std::fs::write(
+            source.join("a.h"),
+            "#include \"b.h\"\n#include <common.h>\nint a;\n",
+        )
+        .unwrap();
+        std::fs::write(source.join("b.h"), "/* cycle */ #include \"a.h\"\nint b;\n").unwrap();
+        std::fs::write(include.join("common.h"), "int common;\n").unwrap();
+        (root, source, include)
+    }
+
+    #[test]
+    fn scan_cycles_and_repeats() {
+        let (root, source, include) = cyclic_headers("scan");
+        let dirs = [include.clone()];
+        let headers = scan_includes(&[source.join("main.metal")], &dirs).unwrap();
+        assert_eq!(
+            headers,
+            vec![
+                source.join("a.h"),
+                include.join("common.h"),
+                source.join("b.h"),
+            ]
+        );
+        // every header is found once, even when included by several sources
+        let sources = [source.join("main.metal"), source.join("b.h")];
+        assert_eq!(scan_includes(&sources, &dirs).unwrap().len(), 3);
+        assert!(scan_includes(&[root.join("missing.metal")], &[]).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn inline_cycles_and_repeats() {
+        let (root, source, include) = cyclic_headers("inline");
+        let line = |line: usize, file: &str| {
+            let path = match file {
+                "common.h" => include.join(file),
+                _ => source.join(file),
+            };
+            format!("#line {} {:?}\n", line, path.to_string_lossy())
+        };
+        let dirs = [include.clone()];
+        let mut inlined = HashSet::new();
+        let output = inline_includes(&source.join("main.metal"), &dirs, &mut inlined).unwrap();
+        let expected = [
+            line(1, "a.h"),
+            line(1, "b.h"),
+            // b.h's include of a.h is left out
+            "\n".to_string(),
+            "int b;\n".to_string(),
+            line(2, "a.h"),
+            line(1, "common.h"),
+            "int common;\n".to_string(),
+            line(3, "a.h"),
+            "int a;\n".to_string(),
+            line(2, "main.metal"),
+            // main.metal's repeated include of common.h is left out
+            "\n".to_string(),
+            "kernel void k() {}\n".to_string(),
+        ]
+        .concat();
+        assert_eq!(output, expected);
+        assert_eq!(inlined.len(), 3);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,570 @@
+//! `iron-oxide-build` compiles Metal shaders into a `.metallib` from a build script, so that
+//! they don't have to be compiled from source when the program starts.
+//!
+//! In `build.rs`:
+//!
+//! ```no_run
+//! iron_oxide_build::MetalBuild::new("shaders")
+//!     .source("shaders/quad.metal")
+//!     .include_dir("shaders/include")
+//!     .define("MAX_LIGHTS", Some("8"))
+//!     .language_version("2.2")
+//!     .compile()
+//!     .unwrap();
+//! ```
+//!
+//! The build writes `$OUT_DIR/shaders.rs`, which defines `LIBRARY`, an
+//! `iron_oxide::EmbeddedLibrary`, and a `functions` module with a constant for the name of
+//! each vertex, fragment and kernel function:
+//!
+//! ```ignore
+//! mod shaders {
+//!     include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+//! }
+//!
+//! let library = shaders::LIBRARY.new_library(&device).unwrap();
+//! let quad_v = library.new_function_with_name(shaders::functions::QUAD_V).unwrap();
+//! ```
+//!
+//! The Metal compiler is run through `xcrun`. On hosts without it, the sources are instead
+//! embedded, with their headers and macros inlined, to be compiled when the library is
+//! loaded. The language version is then left to the runtime compiler's default.
+//!
+//! This crate is licensed under the MIT license.
+
+mod functions;
+mod includes;
+pub use functions::*;
+pub use includes::*;
+
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    /// `OUT_DIR` isn't set, because the build isn't run by a build script, and no output
+    /// directory was given.
+    MissingOutDir,
+    /// The Metal compiler or linker failed.
+    Compiler {
+        command: String,
+        output: String,
+    },
+    /// Two functions have names which convert to the same Rust constant, such as `quadV`
+    /// and `quad_v`.
+    DuplicateConstName {
+        name: String,
+        functions: (String, String),
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::MissingOutDir => {
+                f.write_str("OUT_DIR is not set and no output directory was given")
+            }
+            Error::Compiler { command, output } => write!(f, "`{}` failed:\n{}", command, output),
+            Error::DuplicateConstName { name, functions } => write!(
+                f,
+                "the functions `{}` and `{}` would both be named {}",
+                functions.0, functions.1, name
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Platform {
+    MacOS,
+    IOS,
+}
+
+impl Platform {
+    /// The platform which Cargo is building for, defaulting to macOS.
+    pub fn from_target() -> Platform {
+        match std::env::var("CARGO_CFG_TARGET_OS").as_deref() {
+            Ok("ios") => Platform::IOS,
+            _ => Platform::MacOS,
+        }
+    }
+    pub fn get_sdk(&self) -> &'static str {
+        match self {
+            Platform::MacOS => "macosx",
+            Platform::IOS => "iphoneos",
+        }
+    }
+    /// The prefix of the `-std` option which selects the language version.
+    pub fn get_std_prefix(&self) -> &'static str {
+        match self {
+            Platform::MacOS => "macos-metal",
+            Platform::IOS => "ios-metal",
+        }
+    }
+}
+
+/// What a build produced.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BuildOutput {
+    /// The generated Rust module.
+    pub module: PathBuf,
+    /// The compiled library, or `None` if the sources were embedded instead.
+    pub metallib: Option<PathBuf>,
+    /// The embedded source, if the Metal compiler wasn't available.
+    pub embedded_source: Option<PathBuf>,
+    /// Every source and header which the library was built from.
+    pub dependencies: Vec<PathBuf>,
+    /// The names of the vertex, fragment and kernel functions.
+    pub functions: Vec<String>,
+}
+
+/// Compiles Metal sources into one library.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MetalBuild {
+    name: String,
+    sources: Vec<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    macros: Vec<(String, Option<String>)>,
+    language_version: Option<String>,
+    fast_math: bool,
+    platform: Platform,
+    out_dir: Option<PathBuf>,
+    embed_source: bool,
+}
+
+impl MetalBuild {
+    /// Names the library, which names the files written to the output directory.
+    pub fn new(name: &str) -> MetalBuild {
+        MetalBuild {
+            name: name.to_string(),
+            sources: Vec::new(),
+            include_dirs: Vec::new(),
+            macros: Vec::new(),
+            language_version: None,
+            fast_math: true,
+            platform: Platform::from_target(),
+            out_dir: None,
+            embed_source: false,
+        }
+    }
+    pub fn source<P: Into<PathBuf>>(mut self, path: P) -> MetalBuild {
+        self.sources.push(path.into());
+        self
+    }
+    pub fn sources<P: Into<PathBuf>, I: IntoIterator<Item = P>>(mut self, paths: I) -> MetalBuild {
+        self.sources.extend(paths.into_iter().map(Into::into));
+        self
+    }
+    pub fn include_dir<P: Into<PathBuf>>(mut self, path: P) -> MetalBuild {
+        self.include_dirs.push(path.into());
+        self
+    }
+    /// Defines a preprocessor macro, optionally with a value.
+    pub fn define(mut self, name: &str, value: Option<&str>) -> MetalBuild {
+        self.macros
+            .push((name.to_string(), value.map(ToString::to_string)));
+        self
+    }
+    /// Sets the Metal language version, such as `"2.2"`.
+    pub fn language_version(mut self, version: &str) -> MetalBuild {
+        self.language_version = Some(version.to_string());
+        self
+    }
+    /// Fast math is enabled by default, as it is by the Metal compiler.
+    pub fn fast_math(mut self, enabled: bool) -> MetalBuild {
+        self.fast_math = enabled;
+        self
+    }
+    /// Overrides the platform read from Cargo's target.
+    pub fn platform(mut self, platform: Platform) -> MetalBuild {
+        self.platform = platform;
+        self
+    }
+    /// Overrides the output directory, which is `OUT_DIR` by default.
+    pub fn out_dir<P: Into<PathBuf>>(mut self, path: P) -> MetalBuild {
+        self.out_dir = Some(path.into());
+        self
+    }
+    /// Embeds the sources even when the Metal compiler is available.
+    pub fn embed_source(mut self, embed: bool) -> MetalBuild {
+        self.embed_source = embed;
+        self
+    }
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+    pub fn get_sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+    pub fn get_include_dirs(&self) -> &[PathBuf] {
+        &self.include_dirs
+    }
+    /// Returns the sources followed by every header which they include.
+    pub fn get_dependencies(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut dependencies = self.sources.clone();
+        dependencies.extend(scan_includes(&self.sources, &self.include_dirs)?);
+        Ok(dependencies)
+    }
+
+    /// Builds the library and writes its Rust module, printing `cargo:rerun-if-changed`
+    /// for every source and header.
+    pub fn compile(&self) -> Result<BuildOutput, Error> {
+        self.compile_with(compiler_available())
+    }
+
+    fn compile_with(&self, compiler_available: bool) -> Result<BuildOutput, Error> {
+        let out_dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => PathBuf::from(std::env::var_os("OUT_DIR").ok_or(Error::MissingOutDir)?),
+        };
+        std::fs::create_dir_all(&out_dir)?;
+
+        let dependencies = self.get_dependencies()?;
+        for dependency in &dependencies {
+            println!("cargo:rerun-if-changed={}", dependency.display());
+        }
+
+        let source = self.get_embedded_source()?;
+        let functions = scan_functions(&source);
+
+        let (metallib, embedded_source) = if !self.embed_source && compiler_available {
+            (Some(self.compile_metallib(&out_dir)?), None)
+        } else {
+            if !self.embed_source {
+                println!(
+                    "cargo:warning=xcrun metal was not found; {} will be compiled from source \
+                     when it is loaded",
+                    self.name
+                );
+            }
+            let path = out_dir.join(format!("{}.metal", self.name));
+            std::fs::write(&path, &source)?;
+            (None, Some(path))
+        };
+
+        let module = out_dir.join(format!("{}.rs", self.name));
+        let library = match (&metallib, &embedded_source) {
+            (Some(metallib), _) => format!(
+                "iron_oxide::EmbeddedLibrary::Metallib(include_bytes!({:?}))",
+                metallib.to_string_lossy()
+            ),
+            (None, Some(source)) => format!(
+                "iron_oxide::EmbeddedLibrary::Source(include_str!({:?}))",
+                source.to_string_lossy()
+            ),
+            (None, None) => unreachable!(),
+        };
+        std::fs::write(&module, generate_module(&library, &functions)?)?;
+
+        Ok(BuildOutput {
+            module,
+            metallib,
+            embedded_source,
+            dependencies,
+            functions,
+        })
+    }
+
+    /// Returns the arguments passed to `xcrun` to compile a source into an AIR file.
+    pub fn get_compile_args(&self, source: &Path, air: &Path) -> Vec<String> {
+        let mut args = vec![
+            "-sdk".to_string(),
+            self.platform.get_sdk().to_string(),
+            "metal".to_string(),
+            "-c".to_string(),
+            source.to_string_lossy().into_owned(),
+            "-o".to_string(),
+            air.to_string_lossy().into_owned(),
+        ];
+        if let Some(version) = &self.language_version {
+            args.push(format!(
+                "-std={}{}",
+                self.platform.get_std_prefix(),
+                version
+            ));
+        }
+        if !self.fast_math {
+            args.push("-fno-fast-math".to_string());
+        }
+        for dir in &self.include_dirs {
+            args.push(format!("-I{}", dir.display()));
+        }
+        for (name, value) in &self.macros {
+            args.push(match value {
+                Some(value) => format!("-D{}={}", name, value),
+                None => format!("-D{}", name),
+            });
+        }
+        args
+    }
+
+    /// Returns the arguments passed to `xcrun` to link AIR files into a library.
+    pub fn get_link_args(&self, airs: &[PathBuf], metallib: &Path) -> Vec<String> {
+        let mut args = vec![
+            "-sdk".to_string(),
+            self.platform.get_sdk().to_string(),
+            "metallib".to_string(),
+        ];
+        args.extend(airs.iter().map(|air| air.to_string_lossy().into_owned()));
+        args.push("-o".to_string());
+        args.push(metallib.to_string_lossy().into_owned());
+        args
+    }
+
+    /// Returns the sources concatenated, with the macros defined and headers inlined.
+    pub fn get_embedded_source(&self) -> Result<String, Error> {
+        let mut source = String::new();
+        for (name, value) in &self.macros {
+            writeln!(
+                source,
+                "#define {} {}",
+                name,
+                value.as_deref().unwrap_or("1")
+            )
+            .unwrap();
+        }
+        let mut inlined = HashSet::new();
+        for file in &self.sources {
+            writeln!(source, "#line 1 {:?}", file.to_string_lossy()).unwrap();
+            source.push_str(&inline_includes(file, &self.include_dirs, &mut inlined)?);
+        }
+        Ok(source)
+    }
+
+    fn compile_metallib(&self, out_dir: &Path) -> Result<PathBuf, Error> {
+        let mut airs = Vec::with_capacity(self.sources.len());
+        for (index, source) in self.sources.iter().enumerate() {
+            let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+            let air = out_dir.join(format!("{}-{}-{}.air", self.name, index, stem));
+            run_xcrun(&self.get_compile_args(source, &air))?;
+            airs.push(air);
+        }
+        let metallib = out_dir.join(format!("{}.metallib", self.name));
+        run_xcrun(&self.get_link_args(&airs, &metallib))?;
+        Ok(metallib)
+    }
+}
+
+/// Whether `xcrun` can find the Metal compiler.
+pub fn compiler_available() -> bool {
+    Command::new("xcrun")
+        .args(["--find", "metal"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+fn run_xcrun(args: &[String]) -> Result<(), Error> {
+    let output = Command::new("xcrun").args(args).output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::Compiler {
+            command: format!("xcrun {}", args.join(" ")),
+            output: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+/// Returns the Rust module defining `LIBRARY` as the expression, and a constant for each
+/// function name.
+///
+/// Fails if two functions would have constants of the same name.
+pub fn generate_module(library: &str, functions: &[String]) -> Result<String, Error> {
+    let mut module = String::from("// Generated by iron-oxide-build.\n\n");
+    writeln!(
+        module,
+        "pub const LIBRARY: iron_oxide::EmbeddedLibrary = {};",
+        library
+    )
+    .unwrap();
+    module.push_str("\n/// The names of the library's vertex, fragment and kernel functions.\n");
+    module.push_str("pub mod functions {\n");
+    let mut names: Vec<(String, &String)> = Vec::with_capacity(functions.len());
+    for function in functions {
+        let name = const_name(function);
+        if let Some((_, first)) = names.iter().find(|(other, _)| *other == name) {
+            return Err(Error::DuplicateConstName {
+                name,
+                functions: (first.to_string(), function.clone()),
+            });
+        }
+        writeln!(module, "    pub const {}: &str = {:?};", name, function).unwrap();
+        names.push((name, function));
+    }
+    module.push_str("}\n");
+    Ok(module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("iron-oxide-build-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn compile_args() {
+        let build = MetalBuild::new("shaders")
+            .platform(Platform::MacOS)
+            .language_version("2.2")
+            .fast_math(false)
+            .include_dir("include")
+            .define("MAX_LIGHTS", Some("8"))
+            .define("SHADOWS", None);
+        assert_eq!(
+            build.get_compile_args(Path::new("quad.metal"), Path::new("out/quad.air")),
+            vec![
+                "-sdk",
+                "macosx",
+                "metal",
+                "-c",
+                "quad.metal",
+                "-o",
+                "out/quad.air",
+                "-std=macos-metal2.2",
+                "-fno-fast-math",
+                "-Iinclude",
+                "-DMAX_LIGHTS=8",
+                "-DSHADOWS",
+            ]
+        );
+        let build = MetalBuild::new("shaders").platform(Platform::IOS);
+        assert_eq!(
+            build.get_compile_args(Path::new("a.metal"), Path::new("a.air")),
+            vec!["-sdk", "iphoneos", "metal", "-c", "a.metal", "-o", "a.air"]
+        );
+        let build = build.language_version("2.4");
+        assert!(build
+            .get_compile_args(Path::new("a.metal"), Path::new("a.air"))
+            .contains(&"-std=ios-metal2.4".to_string()));
+    }
+
+    #[test]
+    fn link_args() {
+        let build = MetalBuild::new("shaders").platform(Platform::IOS);
+        let airs = [PathBuf::from("a.air"), PathBuf::from("b.air")];
+        assert_eq!(
+            build.get_link_args(&airs, Path::new("shaders.metallib")),
+            vec![
+                "-sdk",
+                "iphoneos",
+                "metallib",
+                "a.air",
+                "b.air",
+                "-o",
+                "shaders.metallib",
+            ]
+        );
+    }
+
+    #[test]
+    fn module() {
+        let functions = ["quad_v".to_string(), "sumKernel".to_string()];
+        let module = generate_module("LIB", &functions).unwrap();
+        assert!(module.contains("pub const LIBRARY: iron_oxide::EmbeddedLibrary = LIB;\n"));
+        assert!(module.contains("    pub const QUAD_V: &str = \"quad_v\";\n"));
+        assert!(module.contains("    pub const SUM_KERNEL: &str = \"sumKernel\";\n"));
+        assert!(module.ends_with("}\n"));
+    }
+
+    #[test]
+    fn duplicate_const_names() {
+        let functions = [
+            "quadV".to_string(),
+            "blur".to_string(),
+            "quad_v".to_string(),
+        ];
+        match generate_module("LIB", &functions) {
+            Err(Error::DuplicateConstName { name, functions }) => {
+                assert_eq!(name, "QUAD_V");
+                assert_eq!(functions, ("quadV".to_string(), "quad_v".to_string()));
+            }
+            other => panic!("expected a duplicate name, got {:?}", other),
+        }
+        let err = generate_module("LIB", &functions).unwrap_err().to_string();
+        assert!(
+            err.contains("`quadV`") && err.contains("`quad_v`"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn embedded_source() {
+        let dir = temp_dir("embedded");
+        std::fs::write(dir.join("common.h"), "#define SCALE 2\n").unwrap();
+        std::fs::write(
+            dir.join("quad.metal"),
+            "#include \"common.h\"\nvertex float4 quad_v() {}\n",
+        )
+        .unwrap();
+        let build = MetalBuild::new("shaders")
+            .source(dir.join("quad.metal"))
+            .define("MAX_LIGHTS", Some("8"))
+            .define("SHADOWS", None);
+        let source = build.get_embedded_source().unwrap();
+        let quad = dir.join("quad.metal").to_string_lossy().into_owned();
+        let common = dir.join("common.h").to_string_lossy().into_owned();
+        assert_eq!(
+            source,
+            format!(
+                "#define MAX_LIGHTS 8\n#define SHADOWS 1\n#line 1 {:?}\n#line 1 {:?}\n\
+                 #define SCALE 2\n#line 2 {:?}\nvertex float4 quad_v() {{}}\n",
+                quad, common, quad
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compiler_unavailable() {
+        let dir = temp_dir("fallback");
+        std::fs::write(dir.join("common.h"), "").unwrap();
+        std::fs::write(
+            dir.join("quad.metal"),
+            "#include \"common.h\"\nkernel void sumKernel() {}\n",
+        )
+        .unwrap();
+        let out_dir = dir.join("out");
+        let output = MetalBuild::new("shaders")
+            .source(dir.join("quad.metal"))
+            .out_dir(&out_dir)
+            .compile_with(false)
+            .unwrap();
+        assert_eq!(output.metallib, None);
+        assert_eq!(output.embedded_source, Some(out_dir.join("shaders.metal")));
+        assert_eq!(output.module, out_dir.join("shaders.rs"));
+        assert_eq!(
+            output.dependencies,
+            vec![dir.join("quad.metal"), dir.join("common.h")]
+        );
+        assert_eq!(output.functions, vec!["sumKernel"]);
+
+        let module = std::fs::read_to_string(&output.module).unwrap();
+        assert!(module.contains(&format!(
+            "iron_oxide::EmbeddedLibrary::Source(include_str!({:?}))",
+            out_dir.join("shaders.metal").to_string_lossy()
+        )));
+        assert!(module.contains("pub const SUM_KERNEL: &str = \"sumKernel\";"));
+        let source = std::fs::read_to_string(out_dir.join("shaders.metal")).unwrap();
+        assert!(source.ends_with("kernel void sumKernel() {}\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,178 @@
+use crate::import_objc_macros::*;
+use crate::ObjectPointer;
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+thread_local! {
+    /// The number of `AutoreleasePool`s alive on this thread.
+    static POOL_DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// Whether a method has already been reported as called outside a pool on this thread.
+    static WARNED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Drains the autoreleased objects created on its thread while it was alive when dropped.
+///
+/// Methods such as `next_drawable`, `new_command_buffer`, the command encoder constructors
+/// and string getters create autoreleased objects, which are only freed when the innermost
+/// pool around them is drained. Without a pool they accumulate for the life of the
+/// program, so a render loop should create one around each frame:
+///
+/// ```no_run
+/// use iron_oxide::*;
+///
+/// unsafe fn draw_frame(layer: &CAMetalLayer, queue: &MTLCommandQueue) {
+///     let _pool = AutoreleasePool::new();
+///     if let Some(drawable) = layer.next_drawable() {
+///         let command_buffer = queue.new_command_buffer(true);
+///         command_buffer.present_drawable(&drawable);
+///         command_buffer.commit();
+///     }
+/// }
+/// ```
+///
+/// Pools must be dropped in the reverse order of their creation, on the thread which
+/// created them.
+///
+/// In debug builds, the first autoreleasing method called on a thread without a live
+/// `AutoreleasePool` logs a warning. Pools created by other libraries, such as the one
+/// wrapping each iteration of an `NSApplication` run loop, are not detected.
+pub struct AutoreleasePool {
+    pool: ObjectPointer,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl AutoreleasePool {
+    pub unsafe fn new() -> AutoreleasePool {
+        POOL_DEPTH.with(|depth| depth.set(depth.get() + 1));
+        AutoreleasePool {
+            pool: ObjectPointer(msg_send![class!(NSAutoreleasePool), new]),
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for AutoreleasePool {
+    fn drop(&mut self) {
+        unsafe {
+            let _: () = msg_send![self.pool, drain];
+        }
+        POOL_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Runs the closure inside a new `AutoreleasePool`, draining it before returning.
+///
+/// Objects which must outlive the pool should be returned as handles, which own a
+/// reference to their object.
+pub unsafe fn autoreleasepool<T, F: FnOnce() -> T>(f: F) -> T {
+    let _pool = AutoreleasePool::new();
+    f()
+}
+
+/// Logs a warning in debug builds if the method is called without an `AutoreleasePool`
+/// alive on this thread, once per thread.
+pub(crate) fn check_autorelease_pool(method: &str) {
+    if !cfg!(debug_assertions) || POOL_DEPTH.with(Cell::get) > 0 || WARNED.with(Cell::get) {
+        return;
+    }
+    WARNED.with(|warned| warned.set(true));
+    log::log!(
+        log::Level::Warn,
+        "{} was called outside of an AutoreleasePool, so its autoreleased objects will \
+         not be freed; wrap the work in `autoreleasepool` or an `AutoreleasePool`",
+        method
+    );
+}
+
+#[cfg(all(test, not(any(target_os = "macos", target_os = "ios"))))]
+mod tests {
+    use super::*;
+    use crate::fakeobjc::FakeObject;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static LOGGED: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Records messages on the thread which logged them, so tests running in parallel
+    /// don't see each other's.
+    struct ThreadLogger;
+
+    impl log::Log for ThreadLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+        fn log(&self, record: &log::Record) {
+            LOGGED.with(|logged| logged.borrow_mut().push(record.args().to_string()));
+        }
+        fn flush(&self) {}
+    }
+
+    fn take_logged() -> Vec<String> {
+        let _ = log::set_logger(&ThreadLogger);
+        log::set_max_level(log::LevelFilter::Warn);
+        LOGGED.with(|logged| logged.borrow_mut().split_off(0))
+    }
+
+    fn depth() -> usize {
+        POOL_DEPTH.with(Cell::get)
+    }
+
+    #[test]
+    fn nested_pools() {
+        let outer = unsafe { AutoreleasePool::new() };
+        assert_eq!(depth(), 1);
+        let inner = unsafe { autoreleasepool(depth) };
+        assert_eq!(inner, 2);
+        assert_eq!(depth(), 1);
+        drop(outer);
+        assert_eq!(depth(), 0);
+
+        let result = std::panic::catch_unwind(|| unsafe {
+            autoreleasepool(|| autoreleasepool(|| panic!("inside two pools")))
+        });
+        assert!(result.is_err());
+        assert_eq!(depth(), 0);
+    }
+
+    #[test]
+    fn pools_are_drained() {
+        let pool = unsafe { AutoreleasePool::new() };
+        let object = FakeObject::get(pool.pool);
+        assert_eq!(object.get_references(), 1);
+        drop(pool);
+        assert_eq!((object.get_retains(), object.get_releases()), (0, 1));
+    }
+
+    #[test]
+    fn warns_once_per_thread() {
+        let expected = if cfg!(debug_assertions) { 1 } else { 0 };
+        let outside = std::thread::spawn(|| {
+            take_logged();
+            check_autorelease_pool("next_drawable");
+            check_autorelease_pool("new_command_buffer");
+            take_logged()
+        });
+        let logged = outside.join().unwrap();
+        assert_eq!(logged.len(), expected);
+        assert!(logged
+            .iter()
+            .all(|message| message.starts_with("next_drawable")));
+
+        let inside = std::thread::spawn(|| {
+            take_logged();
+            unsafe {
+                autoreleasepool(|| {
+                    check_autorelease_pool("next_drawable");
+                    autoreleasepool(|| check_autorelease_pool("new_command_buffer"));
+                });
+            }
+            let inside = take_logged();
+            check_autorelease_pool("new_command_buffer");
+            (inside, take_logged())
+        });
+        let (inside, after) = inside.join().unwrap();
+        assert!(inside.is_empty());
+        assert_eq!(after.len(), expected);
+    }
+}
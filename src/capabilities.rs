@@ -0,0 +1,383 @@
+use crate::import_objc_macros::*;
+use crate::{
+    MTLArgumentBuffersTier, MTLDevice, MTLDeviceLocation, MTLGPUFamily, MTLReadWriteTextureTier,
+    MTLSize, NSUInteger, Object,
+};
+use objc::runtime::Sel;
+
+/// An owned snapshot of what a device supports.
+///
+/// Properties which the running OS does not provide are left at their most conservative
+/// value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeviceCapabilities {
+    pub name: String,
+    pub registry_id: u64,
+    /// Every family which the device supports, in the order of `MTLGPUFamily::ALL`.
+    pub families: Vec<MTLGPUFamily>,
+    pub is_low_power: bool,
+    pub is_headless: bool,
+    pub is_removable: bool,
+    pub has_unified_memory: bool,
+    pub location: MTLDeviceLocation,
+    pub location_number: NSUInteger,
+    pub peer_group_id: u64,
+    pub read_write_texture_tier: MTLReadWriteTextureTier,
+    pub argument_buffers_tier: MTLArgumentBuffersTier,
+    pub max_argument_buffer_sampler_count: NSUInteger,
+    pub max_threads_per_threadgroup: MTLSize,
+    pub max_threadgroup_memory_length: NSUInteger,
+    pub max_buffer_length: NSUInteger,
+    pub recommended_max_working_set_size: u64,
+    pub sparse_tile_size_in_bytes: NSUInteger,
+    pub supports_raytracing: bool,
+    pub supports_function_pointers: bool,
+    pub supports_32bit_float_filtering: bool,
+    pub supports_bc_texture_compression: bool,
+    pub are_raster_order_groups_supported: bool,
+    pub is_d24_s8_pixel_format_supported: bool,
+}
+
+impl DeviceCapabilities {
+    pub fn supports_family(&self, family: MTLGPUFamily) -> bool {
+        self.families.contains(&family)
+    }
+    /// Whether `dispatch_threads` may be used, which requires threadgroups of non-uniform
+    /// size.
+    pub fn supports_non_uniform_threadgroups(&self) -> bool {
+        self.families.iter().any(|family| {
+            matches!(
+                family,
+                MTLGPUFamily::Apple4
+                    | MTLGPUFamily::Apple5
+                    | MTLGPUFamily::Apple6
+                    | MTLGPUFamily::Apple7
+                    | MTLGPUFamily::Apple8
+                    | MTLGPUFamily::Apple9
+                    | MTLGPUFamily::Mac1
+                    | MTLGPUFamily::Mac2
+                    | MTLGPUFamily::MacCatalyst1
+                    | MTLGPUFamily::MacCatalyst2
+                    | MTLGPUFamily::Metal3
+            )
+        })
+    }
+    /// The largest limits guaranteed by any of the supported families, or those of
+    /// `Common1` if none are known.
+    pub fn get_limits(&self) -> GPUFamilyLimits {
+        let mut families = self.families.iter();
+        match families.next() {
+            Some(first) => families.fold(first.get_limits(), |limits, family| {
+                limits.max(&family.get_limits())
+            }),
+            None => MTLGPUFamily::Common1.get_limits(),
+        }
+    }
+}
+
+impl MTLDevice {
+    /// Queries every capability of the device, skipping those the OS does not provide.
+    pub unsafe fn get_capabilities(&self) -> DeviceCapabilities {
+        let responds =
+            |selector: Sel| -> bool { msg_send![self.get_ptr(), respondsToSelector: selector] };
+        let has_families = responds(sel!(supportsFamily:));
+        DeviceCapabilities {
+            name: self.get_name().to_string(),
+            registry_id: if responds(sel!(registryID)) {
+                self.get_registry_id()
+            } else {
+                0
+            },
+            families: MTLGPUFamily::ALL
+                .iter()
+                .cloned()
+                .filter(|&family| has_families && self.supports_family(family))
+                .collect(),
+            is_low_power: responds(sel!(isLowPower)) && self.is_low_power(),
+            is_headless: responds(sel!(isHeadless)) && self.is_headless(),
+            is_removable: responds(sel!(isRemovable)) && self.is_removable(),
+            has_unified_memory: responds(sel!(hasUnifiedMemory)) && self.has_unified_memory(),
+            location: if responds(sel!(location)) {
+                self.get_location()
+            } else {
+                MTLDeviceLocation::Unspecified
+            },
+            location_number: if responds(sel!(locationNumber)) {
+                self.get_location_number()
+            } else {
+                0
+            },
+            peer_group_id: if responds(sel!(peerGroupID)) {
+                self.get_peer_group_id()
+            } else {
+                0
+            },
+            read_write_texture_tier: if responds(sel!(readWriteTextureSupport)) {
+                self.get_read_write_texture_support()
+            } else {
+                MTLReadWriteTextureTier::None
+            },
+            argument_buffers_tier: if responds(sel!(argumentBuffersSupport)) {
+                self.get_argument_buffers_support()
+            } else {
+                MTLArgumentBuffersTier::Tier1
+            },
+            max_argument_buffer_sampler_count: if responds(sel!(maxArgumentBufferSamplerCount)) {
+                self.get_max_argument_buffer_sampler_count()
+            } else {
+                0
+            },
+            max_threads_per_threadgroup: self.get_max_threads_per_threadgroup(),
+            max_threadgroup_memory_length: if responds(sel!(maxThreadgroupMemoryLength)) {
+                self.get_max_threadgroup_memory_length()
+            } else {
+                0
+            },
+            max_buffer_length: if responds(sel!(maxBufferLength)) {
+                self.get_max_buffer_length()
+            } else {
+                0
+            },
+            recommended_max_working_set_size: if responds(sel!(recommendedMaxWorkingSetSize)) {
+                self.get_recommended_max_working_set_size()
+            } else {
+                0
+            },
+            sparse_tile_size_in_bytes: if responds(sel!(sparseTileSizeInBytes)) {
+                self.get_sparse_tile_size_in_bytes()
+            } else {
+                0
+            },
+            supports_raytracing: responds(sel!(supportsRaytracing)) && self.supports_raytracing(),
+            supports_function_pointers: responds(sel!(supportsFunctionPointers))
+                && self.supports_function_pointers(),
+            supports_32bit_float_filtering: responds(sel!(supports32BitFloatFiltering))
+                && self.supports_32bit_float_filtering(),
+            supports_bc_texture_compression: responds(sel!(supportsBCTextureCompression))
+                && self.supports_bc_texture_compression(),
+            are_raster_order_groups_supported: responds(sel!(areRasterOrderGroupsSupported))
+                && self.are_raster_order_groups_supported(),
+            is_d24_s8_pixel_format_supported: responds(sel!(isDepth24Stencil8PixelFormatSupported))
+                && self.is_d24_s8_pixel_format_supported(),
+        }
+    }
+}
+
+/// Limits which every device in a GPU family guarantees, from Apple's Metal feature set
+/// tables.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GPUFamilyLimits {
+    /// Per shader stage.
+    pub max_buffer_arguments: NSUInteger,
+    /// Per shader stage.
+    pub max_texture_arguments: NSUInteger,
+    /// Per shader stage.
+    pub max_sampler_arguments: NSUInteger,
+    pub max_vertex_attributes: NSUInteger,
+    pub max_color_render_targets: NSUInteger,
+    /// The maximum width and height of 1D, 2D and cube textures.
+    pub max_texture_dimension_2d: NSUInteger,
+    pub max_texture_dimension_3d: NSUInteger,
+    pub max_texture_array_layers: NSUInteger,
+    pub max_threads_per_threadgroup: NSUInteger,
+    pub max_threadgroup_memory_length: NSUInteger,
+}
+
+impl GPUFamilyLimits {
+    /// Whether a 1D, 2D or cube texture of the given size fits within these limits.
+    pub fn fits_texture_2d(&self, width: NSUInteger, height: NSUInteger) -> bool {
+        width <= self.max_texture_dimension_2d && height <= self.max_texture_dimension_2d
+    }
+    pub fn fits_texture_3d(
+        &self,
+        width: NSUInteger,
+        height: NSUInteger,
+        depth: NSUInteger,
+    ) -> bool {
+        width <= self.max_texture_dimension_3d
+            && height <= self.max_texture_dimension_3d
+            && depth <= self.max_texture_dimension_3d
+    }
+    /// The larger of each limit, for a device which supports both families.
+    fn max(&self, other: &GPUFamilyLimits) -> GPUFamilyLimits {
+        GPUFamilyLimits {
+            max_buffer_arguments: self.max_buffer_arguments.max(other.max_buffer_arguments),
+            max_texture_arguments: self.max_texture_arguments.max(other.max_texture_arguments),
+            max_sampler_arguments: self.max_sampler_arguments.max(other.max_sampler_arguments),
+            max_vertex_attributes: self.max_vertex_attributes.max(other.max_vertex_attributes),
+            max_color_render_targets: self
+                .max_color_render_targets
+                .max(other.max_color_render_targets),
+            max_texture_dimension_2d: self
+                .max_texture_dimension_2d
+                .max(other.max_texture_dimension_2d),
+            max_texture_dimension_3d: self
+                .max_texture_dimension_3d
+                .max(other.max_texture_dimension_3d),
+            max_texture_array_layers: self
+                .max_texture_array_layers
+                .max(other.max_texture_array_layers),
+            max_threads_per_threadgroup: self
+                .max_threads_per_threadgroup
+                .max(other.max_threads_per_threadgroup),
+            max_threadgroup_memory_length: self
+                .max_threadgroup_memory_length
+                .max(other.max_threadgroup_memory_length),
+        }
+    }
+}
+
+impl MTLGPUFamily {
+    pub fn get_limits(&self) -> GPUFamilyLimits {
+        use MTLGPUFamily::*;
+        let limits = GPUFamilyLimits {
+            max_buffer_arguments: 31,
+            max_texture_arguments: 31,
+            max_sampler_arguments: 16,
+            max_vertex_attributes: 31,
+            max_color_render_targets: 8,
+            max_texture_dimension_2d: 16384,
+            max_texture_dimension_3d: 2048,
+            max_texture_array_layers: 2048,
+            max_threads_per_threadgroup: 1024,
+            max_threadgroup_memory_length: 32768,
+        };
+        match self {
+            // the common families only guarantee what the oldest devices support
+            Apple1 | Common1 | Common2 | Common3 => GPUFamilyLimits {
+                max_color_render_targets: 4,
+                max_texture_dimension_2d: 8192,
+                max_threads_per_threadgroup: 512,
+                max_threadgroup_memory_length: 16352,
+                ..limits
+            },
+            Apple2 => GPUFamilyLimits {
+                max_texture_dimension_2d: 8192,
+                max_threads_per_threadgroup: 512,
+                max_threadgroup_memory_length: 16384,
+                ..limits
+            },
+            Apple3 => GPUFamilyLimits {
+                max_threads_per_threadgroup: 512,
+                max_threadgroup_memory_length: 16384,
+                ..limits
+            },
+            Apple4 | Apple5 => limits,
+            Apple6 | Apple7 | Apple8 | Apple9 | Metal3 => GPUFamilyLimits {
+                max_texture_arguments: 96,
+                ..limits
+            },
+            Mac1 | Mac2 | MacCatalyst1 | MacCatalyst2 => GPUFamilyLimits {
+                max_texture_arguments: 128,
+                ..limits
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(families: &[MTLGPUFamily]) -> DeviceCapabilities {
+        DeviceCapabilities {
+            name: "Synthetic".to_string(),
+            registry_id: 0,
+            families: families.to_vec(),
+            is_low_power: false,
+            is_headless: false,
+            is_removable: false,
+            has_unified_memory: false,
+            location: MTLDeviceLocation::Unspecified,
+            location_number: 0,
+            peer_group_id: 0,
+            read_write_texture_tier: MTLReadWriteTextureTier::None,
+            argument_buffers_tier: MTLArgumentBuffersTier::Tier1,
+            max_argument_buffer_sampler_count: 0,
+            max_threads_per_threadgroup: MTLSize {
+                width: 1024,
+                height: 1024,
+                depth: 1024,
+            },
+            max_threadgroup_memory_length: 0,
+            max_buffer_length: 0,
+            recommended_max_working_set_size: 0,
+            sparse_tile_size_in_bytes: 0,
+            supports_raytracing: false,
+            supports_function_pointers: false,
+            supports_32bit_float_filtering: false,
+            supports_bc_texture_compression: false,
+            are_raster_order_groups_supported: false,
+            is_d24_s8_pixel_format_supported: false,
+        }
+    }
+
+    #[test]
+    fn family_limits() {
+        use MTLGPUFamily::*;
+        let common = Common1.get_limits();
+        assert_eq!(Apple1.get_limits(), common);
+        assert_eq!(common.max_color_render_targets, 4);
+        assert_eq!(common.max_texture_dimension_2d, 8192);
+        assert_eq!(common.max_threads_per_threadgroup, 512);
+        assert_eq!(common.max_threadgroup_memory_length, 16352);
+
+        let apple2 = Apple2.get_limits();
+        assert_eq!(apple2.max_color_render_targets, 8);
+        assert_eq!(apple2.max_threadgroup_memory_length, 16384);
+        assert_eq!(Apple3.get_limits().max_texture_dimension_2d, 16384);
+        assert_eq!(Apple3.get_limits().max_threads_per_threadgroup, 512);
+        assert_eq!(Apple4.get_limits(), Apple5.get_limits());
+        assert_eq!(Apple4.get_limits().max_threads_per_threadgroup, 1024);
+        assert_eq!(Apple4.get_limits().max_threadgroup_memory_length, 32768);
+        assert_eq!(Apple6.get_limits().max_texture_arguments, 96);
+        assert_eq!(Metal3.get_limits().max_texture_arguments, 96);
+        assert_eq!(Mac2.get_limits().max_texture_arguments, 128);
+        assert_eq!(MacCatalyst1.get_limits(), Mac1.get_limits());
+    }
+
+    #[test]
+    fn texture_fits() {
+        let limits = MTLGPUFamily::Apple2.get_limits();
+        assert!(limits.fits_texture_2d(8192, 8192));
+        assert!(!limits.fits_texture_2d(8193, 1));
+        assert!(!limits.fits_texture_2d(1, 8193));
+        assert!(limits.fits_texture_3d(2048, 2048, 2048));
+        assert!(!limits.fits_texture_3d(1, 1, 4096));
+    }
+
+    #[test]
+    fn supported_families() {
+        let device = capabilities(&[MTLGPUFamily::Apple3, MTLGPUFamily::Common1]);
+        assert!(device.supports_family(MTLGPUFamily::Apple3));
+        assert!(!device.supports_family(MTLGPUFamily::Apple4));
+        assert!(!device.supports_non_uniform_threadgroups());
+        assert!(capabilities(&[MTLGPUFamily::Apple4]).supports_non_uniform_threadgroups());
+        assert!(capabilities(&[MTLGPUFamily::Mac2]).supports_non_uniform_threadgroups());
+        assert!(!capabilities(&[]).supports_non_uniform_threadgroups());
+    }
+
+    #[test]
+    fn combined_limits() {
+        // the Common1 limits when no family is known
+        assert_eq!(
+            capabilities(&[]).get_limits(),
+            MTLGPUFamily::Common1.get_limits()
+        );
+        assert_eq!(
+            capabilities(&[MTLGPUFamily::Apple2]).get_limits(),
+            MTLGPUFamily::Apple2.get_limits()
+        );
+        // each limit is the largest of any family
+        let limits = capabilities(&[
+            MTLGPUFamily::Common1,
+            MTLGPUFamily::Apple6,
+            MTLGPUFamily::Mac2,
+        ])
+        .get_limits();
+        assert_eq!(limits.max_texture_arguments, 128);
+        assert_eq!(limits.max_color_render_targets, 8);
+        assert_eq!(limits.max_texture_dimension_2d, 16384);
+        assert_eq!(limits.max_threadgroup_memory_length, 32768);
+    }
+}
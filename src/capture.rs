@@ -0,0 +1,339 @@
+use crate::import_objc_macros::*;
+use crate::{
+    handle, DeviceCreated, Labeled, MTLCommandQueue, MTLDevice, NSError, NSInteger, Object,
+    ObjectPointer, NSURL,
+};
+use std::path::Path;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(i64)]
+pub enum MTLCaptureDestination {
+    DeveloperTools = 1,
+    /// Requires the `MTL_CAPTURE_ENABLED` environment variable to be set to 1, or the
+    /// `MetalCaptureEnabled` key in the app's Info.plist.
+    GPUTraceDocument = 2,
+}
+
+pub const MTL_CAPTURE_ERROR_DOMAIN: &str = "MTLCaptureErrorDomain";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum MTLCaptureError {
+    NotSupported = 1,
+    AlreadyCapturing = 2,
+    InvalidDescriptor = 3,
+}
+
+/// Objects whose commands may be captured: devices, command queues and capture scopes.
+pub trait MTLCaptureObject: Object {}
+
+impl MTLCaptureObject for MTLDevice {}
+impl MTLCaptureObject for MTLCommandQueue {}
+impl MTLCaptureObject for MTLCaptureScope {}
+
+pub struct MTLCaptureDescriptor(ObjectPointer);
+handle!(MTLCaptureDescriptor);
+
+impl MTLCaptureDescriptor {
+    pub unsafe fn new() -> MTLCaptureDescriptor {
+        MTLCaptureDescriptor::from_ptr(msg_send![class!(MTLCaptureDescriptor), new])
+    }
+    pub unsafe fn set_capture_object<T: MTLCaptureObject>(&self, object: &T) {
+        msg_send![self.get_ptr(), setCaptureObject:object.get_ptr()]
+    }
+    pub unsafe fn set_destination(&self, destination: MTLCaptureDestination) {
+        msg_send![self.get_ptr(), setDestination: destination]
+    }
+    pub unsafe fn get_destination(&self) -> MTLCaptureDestination {
+        msg_send![self.get_ptr(), destination]
+    }
+    /// Sets the path of the `.gputrace` document written for `GPUTraceDocument`.
+    pub unsafe fn set_output_path<P: AsRef<Path>>(&self, path: P) {
+        let url = NSURL::from_path(path.as_ref());
+        msg_send![self.get_ptr(), setOutputURL:url.get_ptr()]
+    }
+}
+
+impl Object for MTLCaptureDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLCaptureDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+/// Delimits the commands captured when the scope is the capture object, or when it is the
+/// default scope and a capture is started from Xcode.
+pub struct MTLCaptureScope(ObjectPointer);
+handle!(MTLCaptureScope);
+
+impl MTLCaptureScope {
+    pub unsafe fn begin_scope(&self) {
+        msg_send![self.get_ptr(), beginScope]
+    }
+    pub unsafe fn end_scope(&self) {
+        msg_send![self.get_ptr(), endScope]
+    }
+    /// Returns the command queue the scope was created with, if any.
+    pub unsafe fn get_command_queue(&self) -> Option<MTLCommandQueue> {
+        let queue = ObjectPointer(msg_send![self.get_ptr(), commandQueue]);
+        if queue.0.is_null() {
+            None
+        } else {
+            Some(MTLCommandQueue::from_ptr(msg_send![queue, retain]))
+        }
+    }
+}
+
+impl Object for MTLCaptureScope {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLCaptureScope(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+impl DeviceCreated for MTLCaptureScope {}
+
+impl Labeled for MTLCaptureScope {}
+
+pub struct MTLCaptureManager(ObjectPointer);
+handle!(MTLCaptureManager);
+
+impl MTLCaptureManager {
+    pub unsafe fn shared() -> MTLCaptureManager {
+        MTLCaptureManager::from_ptr({
+            let k = ObjectPointer(msg_send![class!(MTLCaptureManager), sharedCaptureManager]);
+            msg_send![k, retain]
+        })
+    }
+    pub unsafe fn new_capture_scope_with_device(&self, device: &MTLDevice) -> MTLCaptureScope {
+        MTLCaptureScope::from_ptr(msg_send![
+            self.get_ptr(),
+            newCaptureScopeWithDevice:device.get_ptr()
+        ])
+    }
+    pub unsafe fn new_capture_scope_with_command_queue(
+        &self,
+        queue: &MTLCommandQueue,
+    ) -> MTLCaptureScope {
+        MTLCaptureScope::from_ptr(msg_send![
+            self.get_ptr(),
+            newCaptureScopeWithCommandQueue:queue.get_ptr()
+        ])
+    }
+    pub unsafe fn set_default_capture_scope(&self, scope: &MTLCaptureScope) {
+        msg_send![self.get_ptr(), setDefaultCaptureScope:scope.get_ptr()]
+    }
+    pub unsafe fn get_default_capture_scope(&self) -> Option<MTLCaptureScope> {
+        let scope = ObjectPointer(msg_send![self.get_ptr(), defaultCaptureScope]);
+        if scope.0.is_null() {
+            None
+        } else {
+            Some(MTLCaptureScope::from_ptr(msg_send![scope, retain]))
+        }
+    }
+    pub unsafe fn supports_destination(&self, destination: MTLCaptureDestination) -> bool {
+        msg_send![self.get_ptr(), supportsDestination: destination]
+    }
+    pub unsafe fn start_capture(&self, desc: &MTLCaptureDescriptor) -> Result<(), NSError> {
+        let mut err = ObjectPointer(std::ptr::null_mut());
+        let started: bool = msg_send![
+            self.get_ptr(),
+            startCaptureWithDescriptor:desc.get_ptr()
+            error:&mut err
+        ];
+        if started {
+            Ok(())
+        } else if err.0.is_null() {
+            Err(NSError::new(
+                MTL_CAPTURE_ERROR_DOMAIN,
+                MTLCaptureError::NotSupported as NSInteger,
+                "the capture could not be started",
+            ))
+        } else {
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        }
+    }
+    pub unsafe fn stop_capture(&self) {
+        msg_send![self.get_ptr(), stopCapture]
+    }
+    pub unsafe fn is_capturing(&self) -> bool {
+        msg_send![self.get_ptr(), isCapturing]
+    }
+}
+
+impl Object for MTLCaptureManager {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLCaptureManager(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+/// Captures a single frame, chosen when the app is launched.
+///
+/// `from_env` reads the frame from `--capture-frame <n>` on the command line or the
+/// `IRON_OXIDE_CAPTURE_FRAME` environment variable, and the path of the `.gputrace`
+/// document to write from `--capture-path <path>` or `IRON_OXIDE_CAPTURE_PATH`. Without a
+/// path, the frame is captured to Xcode.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FrameCapture {
+    /// The frame to capture, counting from 0.
+    pub frame: Option<u64>,
+    pub path: Option<String>,
+    current_frame: u64,
+    capturing: bool,
+}
+
+impl FrameCapture {
+    pub fn new(frame: Option<u64>, path: Option<String>) -> FrameCapture {
+        FrameCapture {
+            frame,
+            path,
+            ..FrameCapture::default()
+        }
+    }
+    /// Reads the frame and path from the command line, falling back to the environment.
+    ///
+    /// Arguments which are not valid UTF-8 are skipped.
+    pub fn from_env() -> FrameCapture {
+        let mut capture = FrameCapture::from_args(
+            std::env::args_os()
+                .skip(1)
+                .filter_map(|arg| arg.into_string().ok()),
+        );
+        if capture.frame.is_none() {
+            capture.frame = std::env::var("IRON_OXIDE_CAPTURE_FRAME")
+                .ok()
+                .and_then(|frame| frame.trim().parse().ok());
+        }
+        if capture.path.is_none() {
+            capture.path = std::env::var("IRON_OXIDE_CAPTURE_PATH").ok();
+        }
+        capture
+    }
+    /// Reads `--capture-frame` and `--capture-path`, each followed by its value either as
+    /// the next argument or after `=`, ignoring any other arguments.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> FrameCapture {
+        let mut capture = FrameCapture::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, value) = match arg.find('=') {
+                Some(index) => (arg[..index].to_string(), Some(arg[index + 1..].to_string())),
+                None => (arg, None),
+            };
+            match flag.as_str() {
+                "--capture-frame" => {
+                    capture.frame = value
+                        .or_else(|| args.next())
+                        .and_then(|frame| frame.parse().ok())
+                }
+                "--capture-path" => capture.path = value.or_else(|| args.next()),
+                _ => {}
+            }
+        }
+        capture
+    }
+    pub fn get_current_frame(&self) -> u64 {
+        self.current_frame
+    }
+    pub fn is_capturing(&self) -> bool {
+        self.capturing
+    }
+    /// Starts capturing the commands of the object if this is the chosen frame, returning
+    /// whether it did.
+    pub unsafe fn begin_frame<T: MTLCaptureObject>(&mut self, object: &T) -> Result<bool, NSError> {
+        if self.capturing || self.frame != Some(self.current_frame) {
+            return Ok(false);
+        }
+        let desc = MTLCaptureDescriptor::new();
+        desc.set_capture_object(object);
+        match &self.path {
+            Some(path) => {
+                desc.set_destination(MTLCaptureDestination::GPUTraceDocument);
+                desc.set_output_path(path);
+            }
+            None => desc.set_destination(MTLCaptureDestination::DeveloperTools),
+        }
+        MTLCaptureManager::shared().start_capture(&desc)?;
+        self.capturing = true;
+        Ok(true)
+    }
+    /// Stops capturing if the frame was captured, and advances to the next frame.
+    pub unsafe fn end_frame(&mut self) {
+        if self.capturing {
+            MTLCaptureManager::shared().stop_capture();
+            self.capturing = false;
+        }
+        self.current_frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_args(args: &[&str]) -> FrameCapture {
+        FrameCapture::from_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn separate_values() {
+        let capture = from_args(&[
+            "--verbose",
+            "--capture-frame",
+            "3",
+            "--capture-path",
+            "/tmp/frame.gputrace",
+        ]);
+        assert_eq!(capture.frame, Some(3));
+        assert_eq!(capture.path.as_deref(), Some("/tmp/frame.gputrace"));
+    }
+
+    #[test]
+    fn inline_values() {
+        let capture = from_args(&["--capture-frame=12", "--capture-path=a=b.gputrace"]);
+        assert_eq!(capture.frame, Some(12));
+        assert_eq!(capture.path.as_deref(), Some("a=b.gputrace"));
+        // the last occurrence wins
+        assert_eq!(
+            from_args(&["--capture-frame=1", "--capture-frame=2"]).frame,
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn missing_or_invalid_values() {
+        let capture = from_args(&["--capture-frame=x", "--capture-path"]);
+        assert_eq!(capture, FrameCapture::default());
+        assert_eq!(from_args(&["--capture-frame"]).frame, None);
+        assert_eq!(from_args(&["--capture-frame", "-1"]).frame, None);
+        assert_eq!(
+            from_args(&["--capture-frames=1", "capture-frame=1"]).frame,
+            None
+        );
+    }
+
+    #[test]
+    fn initial_state() {
+        let capture = FrameCapture::new(Some(1), None);
+        assert_eq!(capture.get_current_frame(), 0);
+        assert!(!capture.is_capturing());
+    }
+}
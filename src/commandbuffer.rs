@@ -1,9 +1,13 @@
+use crate::autorelease::check_autorelease_pool;
 use crate::import_objc_macros::*;
 use crate::{
-    handle, DeviceCreated, MTLComputeCommandEncoder, MTLDrawable, MTLParallelRenderCommandEncoder,
-    MTLRenderCommandEncoder, MTLRenderPassDescriptor, NSError, Object, ObjectPointer,
+    handle, DebugGroups, DeviceCreated, Labeled, MTLBlitCommandEncoder, MTLBlitPassDescriptor,
+    MTLComputeCommandEncoder, MTLComputePassDescriptor, MTLDrawable,
+    MTLParallelRenderCommandEncoder, MTLRenderCommandEncoder, MTLRenderPassDescriptor, NSError,
+    Object, ObjectPointer,
 };
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLCommandBufferStatus {
     NotEnqueued = 0,
@@ -16,6 +20,7 @@ pub enum MTLCommandBufferStatus {
 
 pub struct MTLCommandBuffer(ObjectPointer);
 handle!(MTLCommandBuffer);
+unsafe impl Send for MTLCommandBuffer {}
 
 impl MTLCommandBuffer {
     pub unsafe fn enqueue(&self) {
@@ -51,7 +56,7 @@ impl MTLCommandBuffer {
         if err.0.is_null() {
             None
         } else {
-            Some(NSError::from_ptr(err))
+            Some(NSError::from_ptr(msg_send![err, retain]))
         }
     }
     pub unsafe fn get_kernel_start_time(&self) -> f64 {
@@ -64,6 +69,7 @@ impl MTLCommandBuffer {
         &self,
         desc: &MTLRenderPassDescriptor,
     ) -> MTLRenderCommandEncoder {
+        check_autorelease_pool("new_render_command_encoder_with_descriptor");
         MTLRenderCommandEncoder::from_ptr({
             let k = ObjectPointer(msg_send![
                 self.get_ptr(),
@@ -76,6 +82,7 @@ impl MTLCommandBuffer {
         &self,
         desc: &MTLRenderPassDescriptor,
     ) -> MTLParallelRenderCommandEncoder {
+        check_autorelease_pool("new_parallel_render_command_encoder_with_descriptor");
         MTLParallelRenderCommandEncoder::from_ptr({
             let k = ObjectPointer(msg_send![
                 self.get_ptr(),
@@ -85,11 +92,45 @@ impl MTLCommandBuffer {
         })
     }
     pub unsafe fn new_compute_encoder(&self) -> MTLComputeCommandEncoder {
+        check_autorelease_pool("new_compute_encoder");
         MTLComputeCommandEncoder::from_ptr({
             let k = ObjectPointer(msg_send![self.get_ptr(), computeCommandEncoder]);
             msg_send![k, retain]
         })
     }
+    pub unsafe fn new_compute_encoder_with_descriptor(
+        &self,
+        desc: &MTLComputePassDescriptor,
+    ) -> MTLComputeCommandEncoder {
+        check_autorelease_pool("new_compute_encoder_with_descriptor");
+        MTLComputeCommandEncoder::from_ptr({
+            let k = ObjectPointer(msg_send![
+                self.get_ptr(),
+                computeCommandEncoderWithDescriptor: desc.get_ptr()
+            ]);
+            msg_send![k, retain]
+        })
+    }
+    pub unsafe fn new_blit_command_encoder(&self) -> MTLBlitCommandEncoder {
+        check_autorelease_pool("new_blit_command_encoder");
+        MTLBlitCommandEncoder::from_ptr({
+            let k = ObjectPointer(msg_send![self.get_ptr(), blitCommandEncoder]);
+            msg_send![k, retain]
+        })
+    }
+    pub unsafe fn new_blit_command_encoder_with_descriptor(
+        &self,
+        desc: &MTLBlitPassDescriptor,
+    ) -> MTLBlitCommandEncoder {
+        check_autorelease_pool("new_blit_command_encoder_with_descriptor");
+        MTLBlitCommandEncoder::from_ptr({
+            let k = ObjectPointer(msg_send![
+                self.get_ptr(),
+                blitCommandEncoderWithDescriptor: desc.get_ptr()
+            ]);
+            msg_send![k, retain]
+        })
+    }
 }
 
 impl Object for MTLCommandBuffer {
@@ -106,3 +147,7 @@ impl Object for MTLCommandBuffer {
 }
 
 impl DeviceCreated for MTLCommandBuffer {}
+
+impl Labeled for MTLCommandBuffer {}
+
+impl DebugGroups for MTLCommandBuffer {}
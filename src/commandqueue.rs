@@ -1,11 +1,15 @@
+use crate::autorelease::check_autorelease_pool;
 use crate::import_objc_macros::*;
-use crate::{handle, DeviceCreated, MTLCommandBuffer, Object, ObjectPointer};
+use crate::{handle, DeviceCreated, Labeled, MTLCommandBuffer, Object, ObjectPointer};
 
 pub struct MTLCommandQueue(ObjectPointer);
 handle!(MTLCommandQueue);
+unsafe impl Send for MTLCommandQueue {}
+unsafe impl Sync for MTLCommandQueue {}
 
 impl MTLCommandQueue {
     pub unsafe fn new_command_buffer(&self, retain_references: bool) -> MTLCommandBuffer {
+        check_autorelease_pool("new_command_buffer");
         MTLCommandBuffer::from_ptr({
             let pointer = ObjectPointer(match retain_references {
                 true => msg_send![self.get_ptr(), commandBuffer],
@@ -30,3 +34,5 @@ impl Object for MTLCommandQueue {
 }
 
 impl DeviceCreated for MTLCommandQueue {}
+
+impl Labeled for MTLCommandQueue {}
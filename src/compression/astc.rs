@@ -0,0 +1,729 @@
+/// The magenta that invalid blocks and HDR content decode to.
+const ERROR_COLOR: [u8; 4] = [0xFF, 0, 0xFF, 0xFF];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Bits,
+    Trits,
+    Quints,
+}
+
+/// The integer sequence encodings as their number of levels, encoding and bits per
+/// value, from the fewest levels to the most.
+const RANGES: [(u32, Encoding, u32); 21] = [
+    (2, Encoding::Bits, 1),
+    (3, Encoding::Trits, 0),
+    (4, Encoding::Bits, 2),
+    (5, Encoding::Quints, 0),
+    (6, Encoding::Trits, 1),
+    (8, Encoding::Bits, 3),
+    (10, Encoding::Quints, 1),
+    (12, Encoding::Trits, 2),
+    (16, Encoding::Bits, 4),
+    (20, Encoding::Quints, 2),
+    (24, Encoding::Trits, 3),
+    (32, Encoding::Bits, 5),
+    (40, Encoding::Quints, 3),
+    (48, Encoding::Trits, 4),
+    (64, Encoding::Bits, 6),
+    (80, Encoding::Quints, 4),
+    (96, Encoding::Trits, 5),
+    (128, Encoding::Bits, 7),
+    (160, Encoding::Quints, 5),
+    (192, Encoding::Trits, 6),
+    (256, Encoding::Bits, 8),
+];
+
+struct WeightGrid {
+    width: usize,
+    height: usize,
+    range: usize,
+    dual_plane: bool,
+}
+
+/// Decodes an LDR block. HDR blocks and invalid blocks decode to the error color.
+pub(super) fn decode(
+    block: &[u8],
+    block_width: usize,
+    block_height: usize,
+    srgb: bool,
+    texels: &mut [[u8; 4]],
+) {
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&block[..16]);
+    let bits = u128::from_le_bytes(bytes);
+    if decode_block(bits, (block_width, block_height), srgb, texels).is_none() {
+        texels.iter_mut().for_each(|texel| *texel = ERROR_COLOR);
+    }
+}
+
+fn decode_block(
+    bits: u128,
+    (block_width, block_height): (usize, usize),
+    srgb: bool,
+    texels: &mut [[u8; 4]],
+) -> Option<()> {
+    let field = |start: usize, count: usize| (bits >> start) as u32 & ((1 << count) - 1);
+
+    if field(0, 9) == 0x1FC {
+        // a void extent block of a single color, with an HDR flag
+        if field(9, 1) == 1 {
+            return None;
+        }
+        let mut color = [0; 4];
+        for (c, value) in color.iter_mut().enumerate() {
+            *value = (field(64 + c * 16, 16) >> 8) as u8;
+        }
+        texels.iter_mut().for_each(|texel| *texel = color);
+        return Some(());
+    }
+
+    let grid = weight_grid(field(0, 11))?;
+    let planes = 1 + grid.dual_plane as usize;
+    let weight_count = grid.width * grid.height * planes;
+    if grid.width > block_width || grid.height > block_height || weight_count > 64 {
+        return None;
+    }
+    let weight_bits = ise_bits(grid.range, weight_count);
+    if !(24..=96).contains(&weight_bits) {
+        return None;
+    }
+
+    let partitions = field(11, 2) as usize + 1;
+    if grid.dual_plane && partitions == 4 {
+        return None;
+    }
+    let mut below_weights = 128 - weight_bits;
+    let mut modes = [0; 4];
+    let color_start = if partitions == 1 {
+        modes[0] = field(13, 4);
+        17
+    } else {
+        let selector = field(23, 6);
+        if selector & 3 == 0 {
+            modes = [selector >> 2; 4];
+        } else {
+            // the rest of the modes are stored right below the weights
+            let extra = 3 * partitions - 4;
+            below_weights -= extra;
+            let combined = selector | field(below_weights, extra) << 6;
+            let class = (selector & 3) - 1;
+            for (i, mode) in modes.iter_mut().enumerate().take(partitions) {
+                let offset = combined >> (2 + i) & 1;
+                let low = combined >> (2 + partitions + 2 * i) & 3;
+                *mode = (class + offset) << 2 | low;
+            }
+        }
+        29
+    };
+    let plane2_channel = if grid.dual_plane {
+        below_weights -= 2;
+        Some(field(below_weights, 2) as usize)
+    } else {
+        None
+    };
+
+    let value_count = modes
+        .iter()
+        .take(partitions)
+        .map(|mode| (mode >> 2) as usize * 2 + 2)
+        .sum::<usize>();
+    if value_count > 18 || below_weights < color_start {
+        return None;
+    }
+    let color_bits = below_weights - color_start;
+    let color_range = (0..RANGES.len())
+        .rev()
+        .find(|&range| RANGES[range].0 >= 6 && ise_bits(range, value_count) <= color_bits)?;
+    let colors = decode_ise(
+        bits >> color_start & mask(color_bits),
+        color_range,
+        value_count,
+    )
+    .into_iter()
+    .map(|(value, digit)| unquantize_color(value, digit, color_range))
+    .collect::<Vec<_>>();
+    let mut endpoints = [[[0; 4]; 2]; 4];
+    let mut values = &colors[..];
+    for (endpoint, mode) in endpoints.iter_mut().zip(modes.iter()).take(partitions) {
+        let count = (mode >> 2) as usize * 2 + 2;
+        *endpoint = decode_endpoints(*mode, &values[..count])?;
+        values = &values[count..];
+    }
+
+    // the weights are stored from the top of the block down
+    let weights = decode_ise(
+        bits.reverse_bits() & mask(weight_bits),
+        grid.range,
+        weight_count,
+    )
+    .into_iter()
+    .map(|(value, digit)| unquantize_weight(value, digit, grid.range))
+    .collect::<Vec<_>>();
+
+    let seed = field(13, 10);
+    let small_block = block_width * block_height < 31;
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i % block_width, i / block_width);
+        let partition = if partitions == 1 {
+            0
+        } else {
+            select_partition(seed, x as u32, y as u32, partitions as u32, small_block)
+        };
+        let infill = |plane: usize| {
+            infill(
+                &weights,
+                (planes, plane),
+                (grid.width, grid.height),
+                (block_width, block_height),
+                (x, y),
+            )
+        };
+        let (weight, weight2) = (infill(0), plane2_channel.map(|_| infill(1)));
+        let [e0, e1] = endpoints[partition];
+        for c in 0..4 {
+            let weight = match weight2 {
+                Some(weight2) if plane2_channel == Some(c) => weight2,
+                _ => weight,
+            };
+            let (c0, c1) = if srgb && c < 3 {
+                (e0[c] << 8 | 0x80, e1[c] << 8 | 0x80)
+            } else {
+                (e0[c] * 257, e1[c] * 257)
+            };
+            texel[c] = ((c0 * (64 - weight) + c1 * weight + 32) >> 6 >> 8) as u8;
+        }
+    }
+    Some(())
+}
+
+/// Decodes the block mode to the size, range and plane count of the weight grid.
+fn weight_grid(mode: u32) -> Option<WeightGrid> {
+    let a = (mode >> 5 & 3) as usize;
+    let b = (mode >> 7 & 3) as usize;
+    let (width, height, precision, high, dual_plane);
+    if mode & 3 != 0 {
+        precision = mode >> 4 & 1 | (mode & 3) << 1;
+        let size = match mode >> 2 & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if mode & 0x100 == 0 => (a + 2, (b & 1) + 6),
+            _ => ((b & 1) + 2, a + 2),
+        };
+        width = size.0;
+        height = size.1;
+        high = mode >> 9 & 1;
+        dual_plane = mode >> 10 & 1 == 1;
+    } else {
+        if mode & 0xF == 0 {
+            return None;
+        }
+        precision = mode >> 4 & 1 | (mode >> 2 & 3) << 1;
+        let size = match b {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => (a + 6, (mode >> 9 & 3) as usize + 6),
+            _ => match a {
+                0 => (6, 10),
+                1 => (10, 6),
+                _ => return None,
+            },
+        };
+        width = size.0;
+        height = size.1;
+        // the larger grids use the bits of the high precision and dual plane flags
+        if b == 2 {
+            high = 0;
+            dual_plane = false;
+        } else {
+            high = mode >> 9 & 1;
+            dual_plane = mode >> 10 & 1 == 1;
+        }
+    }
+    Some(WeightGrid {
+        width,
+        height,
+        range: (precision - 2 + high * 6) as usize,
+        dual_plane,
+    })
+}
+
+fn mask(bits: usize) -> u128 {
+    if bits >= 128 {
+        !0
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+/// The number of bits a sequence of `count` values takes.
+fn ise_bits(range: usize, count: usize) -> usize {
+    let (_, encoding, bits) = RANGES[range];
+    count * bits as usize
+        + match encoding {
+            Encoding::Bits => 0,
+            Encoding::Trits => (count * 8).div_ceil(5),
+            Encoding::Quints => (count * 7).div_ceil(3),
+        }
+}
+
+/// Decodes an integer sequence to the low bits and the trit or quint of each value.
+fn decode_ise(stream: u128, range: usize, count: usize) -> Vec<(u32, u32)> {
+    let (_, encoding, bits) = RANGES[range];
+    let mut position = 0;
+    let mut read = |count: u32| {
+        let value = if position >= 128 {
+            0
+        } else {
+            (stream >> position) as u32 & ((1 << count) - 1)
+        };
+        position += count;
+        value
+    };
+
+    let mut values = Vec::with_capacity(count + 4);
+    while values.len() < count {
+        match encoding {
+            Encoding::Bits => values.push((read(bits), 0)),
+            Encoding::Trits => {
+                let mut low = [0; 5];
+                let mut packed = 0;
+                for (i, &(shift, length)) in
+                    [(0, 2), (2, 2), (4, 1), (5, 2), (7, 1)].iter().enumerate()
+                {
+                    low[i] = read(bits);
+                    packed |= read(length) << shift;
+                }
+                let trits = decode_trits(packed);
+                values.extend(low.iter().cloned().zip(trits.iter().cloned()));
+            }
+            Encoding::Quints => {
+                let mut low = [0; 3];
+                let mut packed = 0;
+                for (i, &(shift, length)) in [(0, 3), (3, 2), (5, 2)].iter().enumerate() {
+                    low[i] = read(bits);
+                    packed |= read(length) << shift;
+                }
+                let quints = decode_quints(packed);
+                values.extend(low.iter().cloned().zip(quints.iter().cloned()));
+            }
+        }
+    }
+    values.truncate(count);
+    values
+}
+
+fn decode_trits(t: u32) -> [u32; 5] {
+    let bit = |value: u32, bit: u32| value >> bit & 1;
+    let (c, t3, t4);
+    if t >> 2 & 7 == 7 {
+        c = (t >> 5 & 7) << 2 | t & 3;
+        t4 = 2;
+        t3 = 2;
+    } else {
+        c = t & 0x1F;
+        if t >> 5 & 3 == 3 {
+            t4 = 2;
+            t3 = bit(t, 7);
+        } else {
+            t4 = bit(t, 7);
+            t3 = t >> 5 & 3;
+        }
+    }
+    let (t0, t1, t2);
+    if c & 3 == 3 {
+        t2 = 2;
+        t1 = bit(c, 4);
+        t0 = bit(c, 3) << 1 | bit(c, 2) & !bit(c, 3) & 1;
+    } else if c >> 2 & 3 == 3 {
+        t2 = 2;
+        t1 = 2;
+        t0 = c & 3;
+    } else {
+        t2 = bit(c, 4);
+        t1 = c >> 2 & 3;
+        t0 = bit(c, 1) << 1 | bit(c, 0) & !bit(c, 1) & 1;
+    }
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(q: u32) -> [u32; 3] {
+    let bit = |value: u32, bit: u32| value >> bit & 1;
+    if q >> 1 & 3 == 3 && q >> 5 & 3 == 0 {
+        let not_q0 = !q & 1;
+        let q2 = bit(q, 0) << 2 | (bit(q, 4) & not_q0) << 1 | bit(q, 3) & not_q0;
+        return [4, 4, q2];
+    }
+    let (q2, c) = if q >> 1 & 3 == 3 {
+        (4, (q >> 3 & 3) << 3 | (!q >> 5 & 3) << 1 | q & 1)
+    } else {
+        (q >> 5 & 3, q & 0x1F)
+    };
+    let (q0, q1) = if c & 7 == 5 {
+        (c >> 3 & 3, 4)
+    } else {
+        (c & 7, c >> 3 & 3)
+    };
+    [q0, q1, q2]
+}
+
+/// Repeats the bits of a value to fill a wider one.
+fn replicate(value: u32, bits: u32, to: u32) -> u32 {
+    let mut result = 0;
+    let mut shift = to as i32 - bits as i32;
+    while shift > -(bits as i32) {
+        result |= if shift >= 0 {
+            value << shift
+        } else {
+            value >> -shift
+        };
+        shift -= bits as i32;
+    }
+    result & ((1 << to) - 1)
+}
+
+/// Unquantizes a trit or quint encoded value given the multiplier for its digit and
+/// the pattern each of its low bits above the first is repeated in.
+fn unquantize(value: u32, digit: u32, multiplier: u32, patterns: &[u32], top_bit: u32) -> u32 {
+    let a = if value & 1 == 1 {
+        (top_bit << 2) - 1
+    } else {
+        0
+    };
+    let b = patterns
+        .iter()
+        .enumerate()
+        .map(|(i, pattern)| (value >> (i + 1) & 1) * pattern)
+        .sum::<u32>();
+    let t = (digit * multiplier + b) ^ a;
+    a & top_bit | t >> 2
+}
+
+/// Unquantizes a color endpoint value to 8 bits.
+fn unquantize_color(value: u32, digit: u32, range: usize) -> u32 {
+    let (_, encoding, bits) = RANGES[range];
+    let (multiplier, patterns): (u32, &[u32]) = match (encoding, bits) {
+        (Encoding::Bits, _) => return replicate(value, bits, 8),
+        (Encoding::Trits, 1) => (204, &[]),
+        (Encoding::Trits, 2) => (93, &[0x116]),
+        (Encoding::Trits, 3) => (44, &[0x085, 0x10A]),
+        (Encoding::Trits, 4) => (22, &[0x041, 0x082, 0x104]),
+        (Encoding::Trits, 5) => (11, &[0x020, 0x040, 0x081, 0x102]),
+        (Encoding::Trits, _) => (5, &[0x010, 0x020, 0x040, 0x080, 0x101]),
+        (Encoding::Quints, 1) => (113, &[]),
+        (Encoding::Quints, 2) => (54, &[0x10C]),
+        (Encoding::Quints, 3) => (26, &[0x082, 0x105]),
+        (Encoding::Quints, 4) => (13, &[0x040, 0x081, 0x102]),
+        (Encoding::Quints, _) => (6, &[0x020, 0x040, 0x080, 0x101]),
+    };
+    unquantize(value, digit, multiplier, patterns, 0x80)
+}
+
+/// Unquantizes a weight to a value out of 64.
+fn unquantize_weight(value: u32, digit: u32, range: usize) -> u32 {
+    let (_, encoding, bits) = RANGES[range];
+    let weight = match (encoding, bits) {
+        (Encoding::Bits, _) => replicate(value, bits, 6),
+        (Encoding::Trits, 0) => [0, 32, 63][digit as usize],
+        (Encoding::Trits, 1) => unquantize(value, digit, 50, &[], 0x20),
+        (Encoding::Trits, 2) => unquantize(value, digit, 23, &[0x45], 0x20),
+        (Encoding::Trits, _) => unquantize(value, digit, 11, &[0x21, 0x42], 0x20),
+        (Encoding::Quints, 0) => [0, 16, 32, 47, 63][digit as usize],
+        (Encoding::Quints, 1) => unquantize(value, digit, 28, &[], 0x20),
+        (Encoding::Quints, _) => unquantize(value, digit, 13, &[0x42], 0x20),
+    };
+    if weight > 32 {
+        weight + 1
+    } else {
+        weight
+    }
+}
+
+/// Decodes the endpoints of an LDR color endpoint mode.
+fn decode_endpoints(mode: u32, values: &[u32]) -> Option<[[u32; 4]; 2]> {
+    let v = values.iter().map(|&value| value as i32).collect::<Vec<_>>();
+    let endpoints = match mode {
+        0 => [[v[0], v[0], v[0], 0xFF], [v[1], v[1], v[1], 0xFF]],
+        1 => {
+            let l0 = v[0] >> 2 | v[1] & 0xC0;
+            let l1 = (l0 + (v[1] & 0x3F)).min(0xFF);
+            [[l0, l0, l0, 0xFF], [l1, l1, l1, 0xFF]]
+        }
+        4 => [[v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]],
+        5 => {
+            let (l_offset, l) = bit_transfer_signed(v[1], v[0]);
+            let (a_offset, a) = bit_transfer_signed(v[3], v[2]);
+            let l1 = l + l_offset;
+            [[l, l, l, a], [l1, l1, l1, a + a_offset]]
+        }
+        6 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                0xFF,
+            ],
+            [v[0], v[1], v[2], 0xFF],
+        ],
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 {
+                (v[6], v[7])
+            } else {
+                (0xFF, 0xFF)
+            };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                [[v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1]]
+            } else {
+                [
+                    blue_contract([v[1], v[3], v[5], a1]),
+                    blue_contract([v[0], v[2], v[4], a0]),
+                ]
+            }
+        }
+        9 | 13 => {
+            let (r_offset, r) = bit_transfer_signed(v[1], v[0]);
+            let (g_offset, g) = bit_transfer_signed(v[3], v[2]);
+            let (b_offset, b) = bit_transfer_signed(v[5], v[4]);
+            let (a_offset, a) = if mode == 13 {
+                bit_transfer_signed(v[7], v[6])
+            } else {
+                (0, 0xFF)
+            };
+            let offset = [r + r_offset, g + g_offset, b + b_offset, a + a_offset];
+            if r_offset + g_offset + b_offset >= 0 {
+                [[r, g, b, a], offset]
+            } else {
+                [blue_contract(offset), blue_contract([r, g, b, a])]
+            }
+        }
+        10 => [
+            [
+                (v[0] * v[3]) >> 8,
+                (v[1] * v[3]) >> 8,
+                (v[2] * v[3]) >> 8,
+                v[4],
+            ],
+            [v[0], v[1], v[2], v[5]],
+        ],
+        // the remaining modes are HDR
+        _ => return None,
+    };
+    let clamp = |color: [i32; 4]| {
+        let mut clamped = [0; 4];
+        for (clamped, value) in clamped.iter_mut().zip(color.iter()) {
+            *clamped = (*value).clamp(0, 0xFF) as u32;
+        }
+        clamped
+    };
+    Some([clamp(endpoints[0]), clamp(endpoints[1])])
+}
+
+/// Moves the top bit of `a` to `b`, returning `a` as a signed 6 bit offset and `b`.
+fn bit_transfer_signed(a: i32, b: i32) -> (i32, i32) {
+    let b = b >> 1 | a & 0x80;
+    let a = a >> 1 & 0x3F;
+    let a = if a & 0x20 != 0 { a - 0x40 } else { a };
+    (a, b)
+}
+
+fn blue_contract(color: [i32; 4]) -> [i32; 4] {
+    [
+        (color[0] + color[2]) >> 1,
+        (color[1] + color[2]) >> 1,
+        color[2],
+        color[3],
+    ]
+}
+
+/// Bilinearly interpolates the weight grid at a texel.
+fn infill(
+    weights: &[u32],
+    (planes, plane): (usize, usize),
+    (grid_width, grid_height): (usize, usize),
+    (block_width, block_height): (usize, usize),
+    (x, y): (usize, usize),
+) -> u32 {
+    let ds = (1024 + block_width / 2) / (block_width - 1);
+    let dt = (1024 + block_height / 2) / (block_height - 1);
+    let gs = (ds * x * (grid_width - 1) + 32) >> 6;
+    let gt = (dt * y * (grid_height - 1) + 32) >> 6;
+    let (js, fs) = (gs >> 4, gs & 0xF);
+    let (jt, ft) = (gt >> 4, gt & 0xF);
+
+    let w11 = (fs * ft + 8) >> 4;
+    let (w10, w01) = (ft - w11, fs - w11);
+    let w00 = 16 + w11 - fs - ft;
+    let weight = |x: usize, y: usize| {
+        if x < grid_width && y < grid_height {
+            weights[(y * grid_width + x) * planes + plane] as usize
+        } else {
+            0
+        }
+    };
+    ((weight(js, jt) * w00
+        + weight(js + 1, jt) * w01
+        + weight(js, jt + 1) * w10
+        + weight(js + 1, jt + 1) * w11
+        + 8)
+        >> 4) as u32
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> usize {
+    let (x, y) = if small_block {
+        (x << 1, y << 1)
+    } else {
+        (x, y)
+    };
+    let seed = seed + (partitions - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut seeds = [0u32; 8];
+    for (i, value) in seeds.iter_mut().enumerate() {
+        let nibble = rnum >> (i * 4) & 0xF;
+        *value = nibble * nibble;
+    }
+    // only the x and y seeds matter in two dimensions
+    let (sh1, sh2) = if seed & 1 == 1 {
+        (
+            if seed & 2 != 0 { 4 } else { 5 },
+            if partitions == 3 { 6 } else { 5 },
+        )
+    } else {
+        (
+            if partitions == 3 { 6 } else { 5 },
+            if seed & 2 != 0 { 4 } else { 5 },
+        )
+    };
+    for (i, value) in seeds.iter_mut().enumerate() {
+        *value >>= if i % 2 == 0 { sh1 } else { sh2 };
+    }
+
+    let a = (seeds[0] * x + seeds[1] * y + (rnum >> 14)) & 0x3F;
+    let b = (seeds[2] * x + seeds[3] * y + (rnum >> 10)) & 0x3F;
+    let c = if partitions < 3 {
+        0
+    } else {
+        (seeds[4] * x + seeds[5] * y + (rnum >> 6)) & 0x3F
+    };
+    let d = if partitions < 4 {
+        0
+    } else {
+        (seeds[6] * x + seeds[7] * y + (rnum >> 2)) & 0x3F
+    };
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn decode_4x4(bits: u128, srgb: bool) -> [[u8; 4]; 16] {
+        let mut texels = [[0; 4]; 16];
+        decode(&bits.to_le_bytes(), 4, 4, srgb, &mut texels);
+        texels
+    }
+
+    /// A block with a 4x4 grid of 2 bit weights, one partition and direct RGB endpoints
+    /// from black to white, weighted by column.
+    fn gradient_block() -> u128 {
+        let mut bits: u128 = 0x42 | 8 << 13;
+        for (i, value) in [0u128, 255, 0, 255, 0, 255].iter().enumerate() {
+            bits |= value << (17 + 8 * i);
+        }
+        // weights are stored from the top of the block down, bit reversed
+        let mut weights: u128 = 0;
+        for i in 0..16 {
+            weights |= (i % 4) << (2 * i);
+        }
+        bits | weights.reverse_bits()
+    }
+
+    #[test]
+    fn void_extent() {
+        let bits: u128 = 0x1FC
+            | 0x3 << 10
+            | 0x1FFF << 12
+            | 0x1FFF << 25
+            | 0x1FFF << 38
+            | 0x1FFF << 51
+            | 0x1234 << 64
+            | 0x5678 << 80
+            | 0x9ABC << 96
+            | 0xFFFF << 112;
+        assert_eq!(decode_4x4(bits, false), [[0x12, 0x56, 0x9A, 0xFF]; 16]);
+    }
+
+    #[test]
+    fn weight_grid() {
+        let texels = decode_4x4(gradient_block(), false);
+        let row = texels[..4].iter().map(|texel| texel[0]).collect::<Vec<_>>();
+        assert_eq!(row, [0, 84, 171, 255]);
+        assert_eq!(texels[4], [0, 0, 0, 255]);
+        assert_eq!(decode_4x4(gradient_block(), true)[0][0], 0);
+
+        // a larger block infills the grid, keeping its corners
+        let mut texels = [[0; 4]; 36];
+        decode(&gradient_block().to_le_bytes(), 6, 6, false, &mut texels);
+        assert_eq!(texels[0][0], 0);
+        assert_eq!(texels[5][0], 255);
+    }
+
+    #[test]
+    fn error_color() {
+        // a reserved block mode
+        assert_eq!(decode_4x4(0, false), [ERROR_COLOR; 16]);
+    }
+
+    #[test]
+    fn scaled_endpoints() {
+        // RGB scale and RGB scale with two alphas multiply by the fourth value out of 256
+        let endpoints = decode_endpoints(6, &[200, 100, 50, 128]).unwrap();
+        assert_eq!(endpoints, [[100, 50, 25, 255], [200, 100, 50, 255]]);
+        let endpoints = decode_endpoints(10, &[200, 100, 50, 64, 10, 20]).unwrap();
+        assert_eq!(endpoints, [[50, 25, 12, 10], [200, 100, 50, 20]]);
+        // HDR modes are unsupported
+        assert_eq!(decode_endpoints(2, &[0; 8]), None);
+    }
+
+    #[test]
+    fn trits_and_quints() {
+        let mut seen = HashSet::new();
+        for t in 0..256 {
+            let digits = decode_trits(t);
+            assert!(digits.iter().all(|&digit| digit < 3));
+            seen.insert(digits);
+        }
+        assert_eq!(seen.len(), 243);
+
+        let mut seen = HashSet::new();
+        for q in 0..128 {
+            let digits = decode_quints(q);
+            assert!(digits.iter().all(|&digit| digit < 5));
+            seen.insert(digits);
+        }
+        assert_eq!(seen.len(), 125);
+    }
+}
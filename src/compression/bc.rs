@@ -0,0 +1,353 @@
+/// Decodes a BC1 color block. BC2 and BC3 color blocks never use the three color mode.
+pub(super) fn decode_bc1(block: &[u8], texels: &mut [[u8; 4]], allow_three_colors: bool) {
+    let palette = bc1_palette(
+        u16::from_le_bytes([block[0], block[1]]),
+        u16::from_le_bytes([block[2], block[3]]),
+        allow_three_colors,
+    );
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[(indices >> (i * 2)) as usize & 3];
+    }
+}
+
+/// Decodes the explicit 4 bit alpha of a BC2 block.
+pub(super) fn decode_bc2_alpha(block: &[u8]) -> [u8; 16] {
+    let mut alpha = [0; 16];
+    for (i, value) in alpha.iter_mut().enumerate() {
+        *value = (block[i / 2] >> (i % 2 * 4) & 0xF) * 17;
+    }
+    alpha
+}
+
+/// Decodes a BC4 block, or the alpha of a BC3 block or a channel of a BC5 block, to
+/// values in `[0, 1]`, or `[-1, 1]` if signed.
+pub(super) fn decode_bc4(block: &[u8], signed: bool) -> [f32; 16] {
+    let palette = bc4_palette(block[0], block[1], signed);
+    let mut indices = [0; 8];
+    indices[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(indices);
+
+    let mut values = [0.0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = palette[(indices >> (i * 3)) as usize & 7];
+    }
+    values
+}
+
+/// Compresses a block of RGBA8 texels, choosing the three color mode with transparent
+/// black when `allow_alpha` is set and any texel is less than half opaque.
+pub(super) fn encode_bc1(texels: &[[u8; 4]; 16], allow_alpha: bool) -> [u8; 8] {
+    let transparent = allow_alpha && texels.iter().any(|texel| texel[3] < 0x80);
+    let opaque = texels
+        .iter()
+        .filter(|texel| !transparent || texel[3] >= 0x80)
+        .map(|texel| [texel[0] as f32, texel[1] as f32, texel[2] as f32])
+        .collect::<Vec<_>>();
+    let (start, end) = principal_endpoints(&opaque);
+    let (mut color0, mut color1) = (to_rgb565(end), to_rgb565(start));
+    // the order of the endpoints selects between the four and three color modes
+    if (color0 < color1) != transparent {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+    let palette = bc1_palette(color0, color1, transparent);
+
+    let mut indices = 0u32;
+    for (i, texel) in texels.iter().enumerate() {
+        let index = if transparent && texel[3] < 0x80 {
+            3
+        } else {
+            nearest(
+                palette.iter().take(if transparent { 3 } else { 4 }),
+                |color| {
+                    (0..3)
+                        .map(|c| (color[c] as i32 - texel[c] as i32).pow(2))
+                        .sum::<i32>()
+                },
+            )
+        };
+        indices |= (index as u32) << (i * 2);
+    }
+
+    let mut block = [0; 8];
+    block[..2].copy_from_slice(&color0.to_le_bytes());
+    block[2..4].copy_from_slice(&color1.to_le_bytes());
+    block[4..].copy_from_slice(&indices.to_le_bytes());
+    block
+}
+
+/// Compresses a block of unsigned normalized values with the eight value palette.
+pub(super) fn encode_bc4(values: &[u8; 16]) -> [u8; 8] {
+    let max = *values.iter().max().unwrap();
+    let min = *values.iter().min().unwrap();
+    let palette = bc4_palette(max, min, false);
+
+    let mut indices = 0u64;
+    for (i, value) in values.iter().enumerate() {
+        let index = nearest(palette.iter(), |entry| {
+            ((entry * 255.0 - *value as f32).abs() * 16.0) as i32
+        });
+        indices |= (index as u64) << (i * 3);
+    }
+
+    let mut block = [0; 8];
+    block[0] = max;
+    block[1] = min;
+    block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+    block
+}
+
+fn bc1_palette(color0: u16, color1: u16, allow_three_colors: bool) -> [[u8; 4]; 4] {
+    let (a, b) = (from_rgb565(color0), from_rgb565(color1));
+    let mix = |weight_a: u16, weight_b: u16| {
+        let mut color = [0xFF; 4];
+        for c in 0..3 {
+            color[c] =
+                ((a[c] as u16 * weight_a + b[c] as u16 * weight_b) / (weight_a + weight_b)) as u8;
+        }
+        color
+    };
+    if color0 > color1 || !allow_three_colors {
+        [mix(1, 0), mix(0, 1), mix(2, 1), mix(1, 2)]
+    } else {
+        [mix(1, 0), mix(0, 1), mix(1, 1), [0; 4]]
+    }
+}
+
+fn bc4_palette(value0: u8, value1: u8, signed: bool) -> [f32; 8] {
+    let (a, b, min, greater) = if signed {
+        let (a, b) = ((value0 as i8).max(-127), (value1 as i8).max(-127));
+        (a as f32 / 127.0, b as f32 / 127.0, -1.0, a > b)
+    } else {
+        (
+            value0 as f32 / 255.0,
+            value1 as f32 / 255.0,
+            0.0,
+            value0 > value1,
+        )
+    };
+    let mut palette = [a, b, 0.0, 0.0, 0.0, 0.0, min, 1.0];
+    let steps = if greater { 7 } else { 5 };
+    for i in 1..steps {
+        palette[i + 1] = (a * (steps - i) as f32 + b * i as f32) / steps as f32;
+    }
+    palette
+}
+
+fn from_rgb565(color: u16) -> [u8; 3] {
+    let (r, g, b) = (
+        (color >> 11) as u8,
+        (color >> 5 & 0x3F) as u8,
+        (color & 0x1F) as u8,
+    );
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+fn to_rgb565(color: [f32; 3]) -> u16 {
+    let quantize = |value: f32, max: f32| (value.clamp(0.0, 255.0) * max / 255.0).round() as u16;
+    quantize(color[0], 31.0) << 11 | quantize(color[1], 63.0) << 5 | quantize(color[2], 31.0)
+}
+
+/// Picks the endpoints of the line through the colors along their principal axis.
+fn principal_endpoints(colors: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    if colors.is_empty() {
+        return ([0.0; 3], [0.0; 3]);
+    }
+    let mut mean = [0.0; 3];
+    for color in colors {
+        for c in 0..3 {
+            mean[c] += color[c] / colors.len() as f32;
+        }
+    }
+    let mut covariance = [[0.0; 3]; 3];
+    for color in colors {
+        for i in 0..3 {
+            for j in 0..3 {
+                covariance[i][j] += (color[i] - mean[i]) * (color[j] - mean[j]);
+            }
+        }
+    }
+    // a few rounds of power iteration find the dominant eigenvector
+    let mut axis = [1.0f32, 1.0, 1.0];
+    for _ in 0..8 {
+        let mut next = [0.0; 3];
+        for i in 0..3 {
+            next[i] = (0..3).map(|j| covariance[i][j] * axis[j]).sum();
+        }
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            break;
+        }
+        axis = [next[0] / length, next[1] / length, next[2] / length];
+    }
+
+    let project = |color: &[f32; 3]| (0..3).map(|c| (color[c] - mean[c]) * axis[c]).sum::<f32>();
+    let (min, max) = colors
+        .iter()
+        .map(project)
+        .fold((f32::MAX, f32::MIN), |(min, max), t| {
+            (min.min(t), max.max(t))
+        });
+    let point = |t: f32| {
+        [
+            mean[0] + axis[0] * t,
+            mean[1] + axis[1] * t,
+            mean[2] + axis[2] * t,
+        ]
+    };
+    (point(min), point(max))
+}
+
+fn nearest<T, I: Iterator<Item = T>, F: Fn(T) -> i32>(candidates: I, distance: F) -> usize {
+    candidates
+        .map(distance)
+        .enumerate()
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(index, _)| index)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ImagePixels, MTLPixelFormat, NSUInteger, ReadbackImage};
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    fn decode(block: &[u8], allow_three_colors: bool) -> [[u8; 4]; 16] {
+        let mut texels = [[0; 4]; 16];
+        decode_bc1(block, &mut texels, allow_three_colors);
+        texels
+    }
+
+    #[test]
+    fn bc1_four_colors() {
+        // red and blue endpoints, each row using one index
+        let texels = decode(&[0x00, 0xF8, 0x1F, 0x00, 0x00, 0x55, 0xAA, 0xFF], true);
+        let rows = [RED, BLUE, [170, 0, 85, 255], [85, 0, 170, 255]];
+        for (i, texel) in texels.iter().enumerate() {
+            assert_eq!(*texel, rows[i / 4]);
+        }
+    }
+
+    #[test]
+    fn bc1_three_colors() {
+        let block = [0x1F, 0x00, 0x00, 0xF8, 0xAA, 0xFF, 0, 0];
+        let texels = decode(&block, true);
+        assert_eq!(texels[0], [127, 0, 127, 255]);
+        assert_eq!(texels[4], [0; 4]);
+        assert_eq!(texels[8], BLUE);
+        // BC2 and BC3 always use four colors
+        let texels = decode(&block, false);
+        assert_eq!(texels[0], [85, 0, 170, 255]);
+        assert_eq!(texels[4], [170, 0, 85, 255]);
+    }
+
+    #[test]
+    fn bc2_alpha() {
+        let alpha = decode_bc2_alpha(&[0xF0, 0x8F, 0, 0, 0, 0, 0, 0xFF]);
+        assert_eq!(alpha[..4], [0, 255, 255, 136]);
+        assert_eq!(alpha[14..], [255, 255]);
+    }
+
+    #[test]
+    fn bc4() {
+        // texels 0, 1 and 2 use indices 0, 1 and 2
+        let values = decode_bc4(&[255, 0, 0x88, 0, 0, 0, 0, 0], false);
+        assert_eq!(values[..3], [1.0, 0.0, 6.0 / 7.0]);
+        // six interpolated values, then 0 and 1
+        let values = decode_bc4(&[0, 255, 0x88, 0x7C, 0, 0, 0, 0], false);
+        assert_eq!(values[..5], [0.0, 1.0, 0.2, 0.0, 1.0]);
+        // -128 reads as -127
+        let values = decode_bc4(&[0x80, 0x7F, 0x08, 0, 0, 0, 0, 0], true);
+        assert_eq!(values[..2], [-1.0, 1.0]);
+    }
+
+    #[test]
+    fn exact_round_trips() {
+        let solid = [[255, 0, 0, 255]; 16];
+        assert_eq!(decode(&encode_bc1(&solid, false), true), solid);
+
+        let mut texels = [BLUE; 16];
+        texels[5] = [10, 20, 30, 0];
+        let decoded = decode(&encode_bc1(&texels, true), true);
+        assert_eq!(decoded[5], [0; 4]);
+        assert_eq!(decoded[0], BLUE);
+
+        let mut values = [0; 16];
+        values[3] = 255;
+        let decoded = decode_bc4(&encode_bc4(&values), false);
+        assert_eq!(decoded[3], 1.0);
+        assert_eq!(decoded[0], 0.0);
+    }
+
+    fn gradient(width: usize, height: usize) -> ReadbackImage {
+        let mut pixels = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                pixels.extend_from_slice(&[
+                    (x * 255 / (width - 1)) as u8,
+                    (y * 255 / (height - 1)) as u8,
+                    128,
+                    if x < 2 { 0 } else { 255 },
+                ]);
+            }
+        }
+        ReadbackImage {
+            width: width as NSUInteger,
+            height: height as NSUInteger,
+            pixels: ImagePixels::Rgba8(pixels),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        use MTLPixelFormat::*;
+
+        // a size which is not a multiple of the block size
+        let image = gradient(10, 7);
+        for &(format, block_size, tolerance) in &[
+            (BC1_RGBA, 8, 0.25),
+            (BC3_RGBA, 16, 0.25),
+            (BC4_RUnorm, 8, 0.03),
+            (BC5_RGUnorm, 16, 0.03),
+        ] {
+            let data = image.to_compressed(format).unwrap();
+            assert_eq!(data.len(), 3 * 2 * block_size);
+            let decoded = ReadbackImage::from_compressed(format, 10, 7, &data).unwrap();
+
+            let mut expected = image.to_rgba8();
+            for texel in expected.chunks_mut(4) {
+                match format {
+                    BC1_RGBA if texel[3] == 0 => texel.copy_from_slice(&[0; 4]),
+                    BC4_RUnorm => texel[1..].copy_from_slice(&[0, 0, 255]),
+                    BC5_RGUnorm => texel[2..].copy_from_slice(&[0, 255]),
+                    _ => (),
+                }
+            }
+            let expected = ReadbackImage {
+                pixels: ImagePixels::Rgba8(expected),
+                ..image.clone()
+            };
+            let difference = expected.max_difference(&decoded).unwrap();
+            assert!(difference < tolerance, "{:?} {}", format, difference);
+        }
+        assert!(image.to_compressed(BC7_RGBAUnorm).is_err());
+    }
+
+    #[test]
+    fn opaque_gray_round_trip() {
+        let mut image = gradient(16, 16);
+        if let ImagePixels::Rgba8(pixels) = &mut image.pixels {
+            for texel in pixels.chunks_mut(4) {
+                texel[1] = texel[0];
+                texel[3] = 255;
+            }
+        }
+        let data = image.to_compressed(MTLPixelFormat::BC1_RGBA).unwrap();
+        let decoded =
+            ReadbackImage::from_compressed(MTLPixelFormat::BC1_RGBA, 16, 16, &data).unwrap();
+        assert!(image.max_difference(&decoded).unwrap() < 0.05);
+    }
+}
@@ -0,0 +1,619 @@
+use super::bc7::{interpolate, weights, ANCHORS_2, PARTITIONS_2};
+use super::BitReader;
+use crate::f16_to_f32;
+
+// the fields a mode's bits are scattered across: the endpoints w, x, y and z of each
+// channel, then the partition
+const RW: u8 = 0;
+const GW: u8 = 1;
+const BW: u8 = 2;
+const RX: u8 = 3;
+const GX: u8 = 4;
+const BX: u8 = 5;
+const RY: u8 = 6;
+const GY: u8 = 7;
+const BY: u8 = 8;
+const RZ: u8 = 9;
+const GZ: u8 = 10;
+const BZ: u8 = 11;
+const D: u8 = 12;
+
+struct Mode {
+    value: u32,
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// Runs of bits following the mode bits, as a field and its first and last bit.
+    layout: &'static [(u8, u8, u8)],
+}
+
+const MODES: [Mode; 14] = [
+    Mode {
+        value: 0x00,
+        transformed: true,
+        endpoint_bits: 10,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (GY, 4, 4),
+            (BY, 4, 4),
+            (BZ, 4, 4),
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x01,
+        transformed: true,
+        endpoint_bits: 7,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (GY, 5, 5),
+            (GZ, 4, 4),
+            (GZ, 5, 5),
+            (RW, 0, 6),
+            (BZ, 0, 0),
+            (BZ, 1, 1),
+            (BY, 4, 4),
+            (GW, 0, 6),
+            (BY, 5, 5),
+            (BZ, 2, 2),
+            (GY, 4, 4),
+            (BW, 0, 6),
+            (BZ, 3, 3),
+            (BZ, 5, 5),
+            (BZ, 4, 4),
+            (RX, 0, 5),
+            (GY, 0, 3),
+            (GX, 0, 5),
+            (GZ, 0, 3),
+            (BX, 0, 5),
+            (BY, 0, 3),
+            (RY, 0, 5),
+            (RZ, 0, 5),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x02,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [5, 4, 4],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 4),
+            (RW, 10, 10),
+            (GY, 0, 3),
+            (GX, 0, 3),
+            (GW, 10, 10),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 3),
+            (BW, 10, 10),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x06,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 5, 4],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 3),
+            (RW, 10, 10),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (GW, 10, 10),
+            (GZ, 0, 3),
+            (BX, 0, 3),
+            (BW, 10, 10),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 3),
+            (BZ, 0, 0),
+            (BZ, 2, 2),
+            (RZ, 0, 3),
+            (GY, 4, 4),
+            (BZ, 3, 3),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x0A,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 4, 5],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 3),
+            (RW, 10, 10),
+            (BY, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 3),
+            (GW, 10, 10),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BW, 10, 10),
+            (BY, 0, 3),
+            (RY, 0, 3),
+            (BZ, 1, 1),
+            (BZ, 2, 2),
+            (RZ, 0, 3),
+            (BZ, 4, 4),
+            (BZ, 3, 3),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x0E,
+        transformed: true,
+        endpoint_bits: 9,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (RW, 0, 8),
+            (BY, 4, 4),
+            (GW, 0, 8),
+            (GY, 4, 4),
+            (BW, 0, 8),
+            (BZ, 4, 4),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x12,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [6, 5, 5],
+        layout: &[
+            (RW, 0, 7),
+            (GZ, 4, 4),
+            (BY, 4, 4),
+            (GW, 0, 7),
+            (BZ, 2, 2),
+            (GY, 4, 4),
+            (BW, 0, 7),
+            (BZ, 3, 3),
+            (BZ, 4, 4),
+            (RX, 0, 5),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 5),
+            (RZ, 0, 5),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x16,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 6, 5],
+        layout: &[
+            (RW, 0, 7),
+            (BZ, 0, 0),
+            (BY, 4, 4),
+            (GW, 0, 7),
+            (GY, 5, 5),
+            (GY, 4, 4),
+            (BW, 0, 7),
+            (GZ, 5, 5),
+            (BZ, 4, 4),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 5),
+            (GZ, 0, 3),
+            (BX, 0, 4),
+            (BZ, 1, 1),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x1A,
+        transformed: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 5, 6],
+        layout: &[
+            (RW, 0, 7),
+            (BZ, 1, 1),
+            (BY, 4, 4),
+            (GW, 0, 7),
+            (BY, 5, 5),
+            (GY, 4, 4),
+            (BW, 0, 7),
+            (BZ, 5, 5),
+            (BZ, 4, 4),
+            (RX, 0, 4),
+            (GZ, 4, 4),
+            (GY, 0, 3),
+            (GX, 0, 4),
+            (BZ, 0, 0),
+            (GZ, 0, 3),
+            (BX, 0, 5),
+            (BY, 0, 3),
+            (RY, 0, 4),
+            (BZ, 2, 2),
+            (RZ, 0, 4),
+            (BZ, 3, 3),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x1E,
+        transformed: false,
+        endpoint_bits: 6,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (RW, 0, 5),
+            (GZ, 4, 4),
+            (BZ, 0, 0),
+            (BZ, 1, 1),
+            (BY, 4, 4),
+            (GW, 0, 5),
+            (GY, 5, 5),
+            (BY, 5, 5),
+            (BZ, 2, 2),
+            (GY, 4, 4),
+            (BW, 0, 5),
+            (GZ, 5, 5),
+            (BZ, 3, 3),
+            (BZ, 5, 5),
+            (BZ, 4, 4),
+            (RX, 0, 5),
+            (GY, 0, 3),
+            (GX, 0, 5),
+            (GZ, 0, 3),
+            (BX, 0, 5),
+            (BY, 0, 3),
+            (RY, 0, 5),
+            (RZ, 0, 5),
+            (D, 0, 4),
+        ],
+    },
+    Mode {
+        value: 0x03,
+        transformed: false,
+        endpoint_bits: 10,
+        delta_bits: [10, 10, 10],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 9),
+            (GX, 0, 9),
+            (BX, 0, 9),
+        ],
+    },
+    Mode {
+        value: 0x07,
+        transformed: true,
+        endpoint_bits: 11,
+        delta_bits: [9, 9, 9],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 8),
+            (RW, 10, 10),
+            (GX, 0, 8),
+            (GW, 10, 10),
+            (BX, 0, 8),
+            (BW, 10, 10),
+        ],
+    },
+    // the high bits of the base endpoint are stored most significant bit first
+    Mode {
+        value: 0x0B,
+        transformed: true,
+        endpoint_bits: 12,
+        delta_bits: [8, 8, 8],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 7),
+            (RW, 11, 10),
+            (GX, 0, 7),
+            (GW, 11, 10),
+            (BX, 0, 7),
+            (BW, 11, 10),
+        ],
+    },
+    Mode {
+        value: 0x0F,
+        transformed: true,
+        endpoint_bits: 16,
+        delta_bits: [4, 4, 4],
+        layout: &[
+            (RW, 0, 9),
+            (GW, 0, 9),
+            (BW, 0, 9),
+            (RX, 0, 3),
+            (RW, 15, 10),
+            (GX, 0, 3),
+            (GW, 15, 10),
+            (BX, 0, 3),
+            (BW, 15, 10),
+        ],
+    },
+];
+
+pub(super) fn decode(block: &[u8], signed: bool, texels: &mut [[f32; 4]]) {
+    let mut bits = BitReader::new(block);
+    let mut value = bits.read(2);
+    if value > 1 {
+        value |= bits.read(3) << 2;
+    }
+    let mode = match MODES.iter().find(|mode| mode.value == value) {
+        Some(mode) => mode,
+        // reserved modes decode to black
+        None => {
+            return texels
+                .iter_mut()
+                .for_each(|texel| *texel = [0.0, 0.0, 0.0, 1.0])
+        }
+    };
+
+    let mut fields = [0i32; 13];
+    for &(field, first, last) in mode.layout {
+        let mut read = |bit: u8| fields[field as usize] |= (bits.read(1) as i32) << bit;
+        if first <= last {
+            (first..=last).for_each(&mut read);
+        } else {
+            (last..=first).rev().for_each(&mut read);
+        }
+    }
+
+    let two_regions = mode.layout.iter().any(|&(field, _, _)| field == D);
+    let endpoint_count = if two_regions { 4 } else { 2 };
+    let endpoint_bits = mode.endpoint_bits;
+    let mut endpoints = [[0i32; 3]; 4];
+    for (i, endpoint) in endpoints.iter_mut().enumerate().take(endpoint_count) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            *value = fields[i * 3 + channel];
+            if i == 0 {
+                if signed {
+                    *value = sign_extend(*value, endpoint_bits);
+                }
+                continue;
+            }
+            if mode.transformed || signed {
+                let bits = if mode.transformed {
+                    mode.delta_bits[channel]
+                } else {
+                    endpoint_bits
+                };
+                *value = sign_extend(*value, bits);
+            }
+        }
+    }
+    if mode.transformed {
+        let base = endpoints[0];
+        for endpoint in endpoints.iter_mut().take(endpoint_count).skip(1) {
+            for (channel, value) in endpoint.iter_mut().enumerate() {
+                *value = (base[channel] + *value) & ((1 << endpoint_bits) - 1);
+                if signed {
+                    *value = sign_extend(*value, endpoint_bits);
+                }
+            }
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(endpoint_count) {
+        for value in endpoint.iter_mut() {
+            *value = unquantize(*value, endpoint_bits, signed);
+        }
+    }
+
+    let partition = fields[D as usize] as usize;
+    let index_bits = if two_regions { 3 } else { 4 };
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let is_anchor = i == 0 || two_regions && i == ANCHORS_2[partition] as usize;
+        let index = bits.read(index_bits - is_anchor as u32);
+        let region = if two_regions {
+            (PARTITIONS_2[partition] >> i) as usize & 1
+        } else {
+            0
+        };
+        let weight = weights(index_bits)[index as usize];
+        let (a, b) = (endpoints[region * 2], endpoints[region * 2 + 1]);
+        for channel in 0..3 {
+            let value = if signed {
+                // interpolate signed values with the same rounding as unsigned ones
+                let offset = 0x8000;
+                interpolate(
+                    (a[channel] + offset) as u32,
+                    (b[channel] + offset) as u32,
+                    weight,
+                ) as i32
+                    - offset
+            } else {
+                interpolate(a[channel] as u32, b[channel] as u32, weight) as i32
+            };
+            texel[channel] = f16_to_f32(finish_unquantize(value, signed));
+        }
+        texel[3] = 1.0;
+    }
+}
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 {
+            value
+        } else if value == 0 {
+            0
+        } else if value == (1 << bits) - 1 {
+            0xFFFF
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else if bits >= 16 {
+        value
+    } else {
+        let magnitude = value.abs();
+        let unquantized = if magnitude == 0 {
+            0
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7FFF
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    }
+}
+
+/// Scales an interpolated value to the bits of a half float.
+fn finish_unquantize(value: i32, signed: bool) -> u16 {
+    if !signed {
+        ((value * 31) >> 6) as u16
+    } else if value < 0 {
+        0x8000 | (((-value) * 31) >> 5) as u16
+    } else {
+        ((value * 31) >> 5) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::pack_bits;
+
+    fn decode_block(block: &[u8], signed: bool) -> [[f32; 4]; 16] {
+        let mut texels = [[0.0; 4]; 16];
+        decode(block, signed, &mut texels);
+        texels
+    }
+
+    #[test]
+    fn layouts_cover_every_field() {
+        let mask = |bits: u32| if bits >= 32 { !0 } else { (1u32 << bits) - 1 };
+        for (m, mode) in MODES.iter().enumerate() {
+            let mode_bits = if mode.value < 2 { 2 } else { 5 };
+            let two_subsets = mode.layout.iter().any(|&(field, _, _)| field == D);
+            let mut seen = [0u32; 13];
+            let mut total = mode_bits;
+            for &(field, first, last) in mode.layout {
+                for bit in first.min(last)..=first.max(last) {
+                    assert_eq!(seen[field as usize] >> bit & 1, 0, "mode {}", m);
+                    seen[field as usize] |= 1 << bit;
+                    total += 1;
+                }
+            }
+            // the indices fill the rest of the block
+            assert_eq!(total, if two_subsets { 82 } else { 65 }, "mode {}", m);
+            for c in 0..3 {
+                assert_eq!(
+                    seen[RW as usize + c],
+                    mask(mode.endpoint_bits),
+                    "mode {}",
+                    m
+                );
+                assert_eq!(
+                    seen[RX as usize + c],
+                    mask(mode.delta_bits[c]),
+                    "mode {}",
+                    m
+                );
+                if two_subsets {
+                    assert_eq!(
+                        seen[RY as usize + c],
+                        mask(mode.delta_bits[c]),
+                        "mode {}",
+                        m
+                    );
+                    assert_eq!(
+                        seen[RZ as usize + c],
+                        mask(mode.delta_bits[c]),
+                        "mode {}",
+                        m
+                    );
+                }
+            }
+            if two_subsets {
+                assert_eq!(seen[D as usize], 31);
+            }
+        }
+    }
+
+    #[test]
+    fn untransformed_single_subset() {
+        // mode 3: 10 bit endpoints 0 and 1023, 4 bit indices alternating between them
+        let mut fields = vec![(0b00011, 5), (0, 30), (0x3FF, 10), (0x3FF, 10), (0x3FF, 10)];
+        fields.push((0, 3));
+        fields.extend((1..16).map(|i| (if i % 2 == 1 { 15 } else { 0 }, 4)));
+        let texels = decode_block(&pack_bits(&fields), false);
+        assert_eq!(texels[0], [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texels[1], [65504.0, 65504.0, 65504.0, 1.0]);
+        assert_eq!(texels[2], texels[0]);
+
+        // signed endpoints are sign extended
+        let fields = [(0b00011, 5), (0x200, 10), (0x200, 10), (0x200, 10)];
+        let texels = decode_block(&pack_bits(&fields), true);
+        assert_eq!(texels[0], [-65504.0, -65504.0, -65504.0, 1.0]);
+    }
+
+    #[test]
+    fn reserved_mode() {
+        let texels = decode_block(&pack_bits(&[(0b10011, 5), (!0, 123)]), false);
+        assert_eq!(texels, [[0.0, 0.0, 0.0, 1.0]; 16]);
+    }
+}
@@ -0,0 +1,458 @@
+use super::BitReader;
+
+/// The subset of each texel of the two subset partitions, one bit per texel. Also used by
+/// BC6H.
+pub(super) const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// The subset of each texel of the three subset partitions, two bits per texel.
+const PARTITIONS_3: [u32; 64] = [
+    0xAA68_5050,
+    0x6A5A_5040,
+    0x5A5A_4200,
+    0x5450_A0A8,
+    0xA5A5_0000,
+    0xA0A0_5050,
+    0x5555_A0A0,
+    0x5A5A_5050,
+    0xAA55_0000,
+    0xAA55_5500,
+    0xAAAA_5500,
+    0x9090_9090,
+    0x9494_9494,
+    0xA4A4_A4A4,
+    0xA9A5_9450,
+    0x2A0A_4250,
+    0xA594_5040,
+    0x0A42_5054,
+    0xA5A5_A500,
+    0x55A0_A0A0,
+    0xA8A8_5454,
+    0x6A6A_4040,
+    0xA4A4_5000,
+    0x1A1A_0500,
+    0x0050_A4A4,
+    0xAAA5_9090,
+    0x1469_6914,
+    0x6969_1400,
+    0xA085_85A0,
+    0xAA82_1414,
+    0x50A4_A450,
+    0x6A5A_0200,
+    0xA9A5_8000,
+    0x5090_A0A8,
+    0xA8A0_9050,
+    0x2424_2424,
+    0x00AA_5500,
+    0x2492_4924,
+    0x2449_9224,
+    0x50A5_0A50,
+    0x500A_A550,
+    0xAAAA_4444,
+    0x6666_0000,
+    0xA5A0_A5A0,
+    0x50A0_50A0,
+    0x6928_6928,
+    0x44AA_AA44,
+    0x6666_6600,
+    0xAA44_4444,
+    0x54A8_54A8,
+    0x9580_9580,
+    0x9696_9600,
+    0xA854_54A8,
+    0x8095_9580,
+    0xAA14_1414,
+    0x9696_0000,
+    0xAAAA_1414,
+    0xA050_50A0,
+    0xA0A5_A5A0,
+    0x9600_0000,
+    0x4080_4080,
+    0xA9A8_A9A8,
+    0xAAAA_AA44,
+    0x2A4A_5254,
+];
+
+/// The anchor texel of the second subset of the two subset partitions. Also used by BC6H.
+pub(super) const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// The anchor texels of the second and third subsets of the three subset partitions.
+const ANCHORS_3: [[u8; 2]; 64] = [
+    [3, 15],
+    [3, 8],
+    [15, 8],
+    [15, 3],
+    [8, 15],
+    [3, 15],
+    [15, 3],
+    [15, 8],
+    [8, 15],
+    [8, 15],
+    [6, 15],
+    [6, 15],
+    [6, 15],
+    [5, 15],
+    [3, 15],
+    [3, 8],
+    [3, 15],
+    [3, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [3, 8],
+    [6, 15],
+    [10, 8],
+    [5, 3],
+    [8, 15],
+    [8, 6],
+    [6, 10],
+    [8, 15],
+    [5, 15],
+    [15, 10],
+    [15, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [5, 10],
+    [6, 10],
+    [10, 8],
+    [8, 9],
+    [15, 10],
+    [15, 6],
+    [3, 15],
+    [15, 8],
+    [5, 15],
+    [15, 3],
+    [15, 6],
+    [15, 6],
+    [15, 8],
+    [3, 15],
+    [15, 3],
+    [5, 15],
+    [5, 15],
+    [5, 15],
+    [8, 15],
+    [5, 15],
+    [10, 15],
+    [5, 15],
+    [10, 15],
+    [8, 15],
+    [13, 15],
+    [15, 3],
+    [12, 15],
+    [3, 15],
+    [3, 8],
+];
+
+pub(super) const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+pub(super) const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+pub(super) const WEIGHTS_4: [u32; 16] =
+    [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_p_bits: bool,
+    shared_p_bits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const MODES: [Mode; 8] = [
+    Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: true,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 3,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 2,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 4,
+        secondary_index_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+];
+
+/// Interpolates between two endpoints with a weight out of 64.
+pub(super) fn interpolate(a: u32, b: u32, weight: u32) -> u32 {
+    (a * (64 - weight) + b * weight + 32) >> 6
+}
+
+pub(super) fn weights(bits: u32) -> &'static [u32] {
+    match bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+pub(super) fn decode(block: &[u8], texels: &mut [[u8; 4]]) {
+    let mut bits = BitReader::new(block);
+    let mode = match (0..8).find(|_| bits.read(1) == 1) {
+        Some(mode) => &MODES[mode],
+        // the reserved mode decodes to transparent black
+        None => return texels.iter_mut().for_each(|texel| *texel = [0; 4]),
+    };
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..4 {
+        let channel_bits = if channel == 3 {
+            mode.alpha_bits
+        } else {
+            mode.color_bits
+        };
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = bits.read(channel_bits);
+        }
+    }
+    let mut p_bits = [0; 6];
+    if mode.endpoint_p_bits {
+        for p_bit in p_bits.iter_mut().take(endpoint_count) {
+            *p_bit = bits.read(1);
+        }
+    } else if mode.shared_p_bits {
+        for subset in 0..mode.subsets {
+            let p_bit = bits.read(1);
+            p_bits[subset * 2] = p_bit;
+            p_bits[subset * 2 + 1] = p_bit;
+        }
+    }
+    let has_p_bit = mode.endpoint_p_bits || mode.shared_p_bits;
+    for (endpoint, p_bit) in endpoints.iter_mut().zip(p_bits.iter()).take(endpoint_count) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            let channel_bits = if channel == 3 {
+                mode.alpha_bits
+            } else {
+                mode.color_bits
+            };
+            if channel_bits == 0 {
+                *value = 0xFF;
+                continue;
+            }
+            let (raw, bit_count) = if has_p_bit {
+                (*value << 1 | p_bit, channel_bits + 1)
+            } else {
+                (*value, channel_bits)
+            };
+            *value = raw << (8 - bit_count) | raw >> (2 * bit_count - 8);
+        }
+    }
+
+    let subset = |texel: usize| match mode.subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> texel) as usize & 1,
+        _ => (PARTITIONS_3[partition] >> (texel * 2)) as usize & 3,
+    };
+    let is_anchor = |texel: usize| {
+        texel == 0
+            || match mode.subsets {
+                1 => false,
+                2 => texel == ANCHORS_2[partition] as usize,
+                _ => ANCHORS_3[partition].contains(&(texel as u8)),
+            }
+    };
+    let mut indices = [0; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        *index = bits.read(mode.index_bits - is_anchor(texel) as u32);
+    }
+    let mut secondary_indices = [0; 16];
+    if mode.secondary_index_bits > 0 {
+        for (texel, index) in secondary_indices.iter_mut().enumerate() {
+            *index = bits.read(mode.secondary_index_bits - (texel == 0) as u32);
+        }
+    }
+
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (color_index, color_bits, alpha_index, alpha_bits) =
+            match (mode.secondary_index_bits, index_selection) {
+                (0, _) => (indices[i], mode.index_bits, indices[i], mode.index_bits),
+                (_, 0) => (
+                    indices[i],
+                    mode.index_bits,
+                    secondary_indices[i],
+                    mode.secondary_index_bits,
+                ),
+                _ => (
+                    secondary_indices[i],
+                    mode.secondary_index_bits,
+                    indices[i],
+                    mode.index_bits,
+                ),
+            };
+        let subset = subset(i);
+        let (a, b) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        for channel in 0..4 {
+            let (index, index_bits) = if channel == 3 {
+                (alpha_index, alpha_bits)
+            } else {
+                (color_index, color_bits)
+            };
+            let weight = weights(index_bits)[index as usize];
+            texel[channel] = interpolate(a[channel], b[channel], weight) as u8;
+        }
+        match rotation {
+            1 => texel.swap(0, 3),
+            2 => texel.swap(1, 3),
+            3 => texel.swap(2, 3),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::pack_bits;
+
+    fn decode_block(block: &[u8]) -> [[u8; 4]; 16] {
+        let mut texels = [[0; 4]; 16];
+        decode(block, &mut texels);
+        texels
+    }
+
+    #[test]
+    fn anchors_lie_in_their_subsets() {
+        for p in 0..64 {
+            assert_eq!(PARTITIONS_2[p] & 1, 0);
+            assert_eq!(PARTITIONS_2[p] >> ANCHORS_2[p] & 1, 1, "partition {}", p);
+            let subset = |texel: u8| PARTITIONS_3[p] >> (texel * 2) & 3;
+            assert_eq!(subset(0), 0);
+            assert_eq!(subset(ANCHORS_3[p][0]), 1, "partition {}", p);
+            assert_eq!(subset(ANCHORS_3[p][1]), 2, "partition {}", p);
+        }
+    }
+
+    #[test]
+    fn mode_6() {
+        // 7 bit RGBA endpoints with a p-bit each, black to white, 4 bit indices
+        let mut fields = vec![(1 << 6, 7)];
+        for _ in 0..4 {
+            fields.extend_from_slice(&[(0, 7), (0x7F, 7)]);
+        }
+        fields.extend_from_slice(&[(0, 1), (1, 1)]);
+        // the anchor texel has one index bit less
+        fields.extend_from_slice(&[(0, 3), (15, 4), (8, 4)]);
+        let texels = decode_block(&pack_bits(&fields));
+        assert_eq!(texels[0], [0; 4]);
+        assert_eq!(texels[1], [255; 4]);
+        assert_eq!(texels[2], [135; 4]);
+        assert_eq!(texels[3], [0; 4]);
+    }
+
+    #[test]
+    fn mode_5_rotation() {
+        // rotation 1 swaps red and alpha
+        let mut fields = vec![(1 << 5, 6), (1, 2)];
+        fields.extend_from_slice(&[(0x7F, 7), (0x7F, 7), (0, 7), (0, 7), (0, 7), (0, 7)]);
+        fields.extend_from_slice(&[(0x10, 8), (0x10, 8)]);
+        let texels = decode_block(&pack_bits(&fields));
+        assert_eq!(texels[0], [0x10, 0, 0, 255]);
+    }
+
+    #[test]
+    fn reserved_mode() {
+        assert_eq!(decode_block(&[0; 16]), [[0; 4]; 16]);
+    }
+}
@@ -0,0 +1,318 @@
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// Decodes an ETC2 RGB block, or an RGB block with punch through alpha. ETC1 blocks
+/// decode the same way.
+pub(super) fn decode_etc2(block: &[u8], punch_through: bool, texels: &mut [[u8; 4]]) {
+    let hi = u32::from_be_bytes([block[0], block[1], block[2], block[3]]);
+    let lo = u32::from_be_bytes([block[4], block[5], block[6], block[7]]);
+    // with punch through alpha the differential bit instead marks the block as opaque
+    let differential = punch_through || hi >> 1 & 1 == 1;
+    let opaque = !punch_through || hi >> 1 & 1 == 1;
+
+    // the two bit index of each texel, stored column major
+    let index = |i: usize| {
+        let bit = i % 4 * 4 + i / 4;
+        ((lo >> (16 + bit) & 1) << 1 | lo >> bit & 1) as usize
+    };
+
+    if !differential {
+        let first = expand4([hi >> 28 & 15, hi >> 20 & 15, hi >> 12 & 15]);
+        let second = expand4([hi >> 24 & 15, hi >> 16 & 15, hi >> 8 & 15]);
+        return decode_subblocks(hi, first, second, opaque, index, texels);
+    }
+
+    let (r, g, b) = (hi >> 27 & 31, hi >> 19 & 31, hi >> 11 & 31);
+    let delta = |shift: u32| ((hi >> shift & 7) as i32) << 29 >> 29;
+    let (dr, dg, db) = (
+        r as i32 + delta(24),
+        g as i32 + delta(16),
+        b as i32 + delta(8),
+    );
+
+    let paint = if !(0..32).contains(&dr) {
+        // T mode
+        let base1 = expand4([
+            (hi >> 27 & 3) << 2 | hi >> 24 & 3,
+            hi >> 20 & 15,
+            hi >> 16 & 15,
+        ]);
+        let base2 = expand4([hi >> 12 & 15, hi >> 8 & 15, hi >> 4 & 15]);
+        let distance = DISTANCES[((hi >> 2 & 3) << 1 | hi & 1) as usize];
+        [
+            base1,
+            offset(base2, distance),
+            base2,
+            offset(base2, -distance),
+        ]
+    } else if !(0..32).contains(&dg) {
+        // H mode
+        let base1 = [
+            hi >> 27 & 15,
+            (hi >> 24 & 7) << 1 | hi >> 20 & 1,
+            (hi >> 19 & 1) << 3 | hi >> 15 & 7,
+        ];
+        let base2 = [hi >> 11 & 15, hi >> 7 & 15, hi >> 3 & 15];
+        let packed = |base: [u32; 3]| base[0] << 8 | base[1] << 4 | base[2];
+        let order = (packed(base1) >= packed(base2)) as u32;
+        let distance = DISTANCES[((hi >> 2 & 1) << 2 | (hi & 1) << 1 | order) as usize];
+        let (base1, base2) = (expand4(base1), expand4(base2));
+        [
+            offset(base1, distance),
+            offset(base1, -distance),
+            offset(base2, distance),
+            offset(base2, -distance),
+        ]
+    } else if !(0..32).contains(&db) {
+        // planar mode, which has no transparent texels
+        let expand6 = |value: u32| (value << 2 | value >> 4) as i32;
+        let expand7 = |value: u32| (value << 1 | value >> 6) as i32;
+        let origin = [
+            expand6(hi >> 25 & 63),
+            expand7((hi >> 24 & 1) << 6 | hi >> 17 & 63),
+            expand6((hi >> 16 & 1) << 5 | (hi >> 11 & 3) << 3 | hi >> 7 & 7),
+        ];
+        let horizontal = [
+            expand6((hi >> 2 & 31) << 1 | hi & 1),
+            expand7(lo >> 25 & 127),
+            expand6(lo >> 19 & 63),
+        ];
+        let vertical = [
+            expand6(lo >> 13 & 63),
+            expand7(lo >> 6 & 127),
+            expand6(lo & 63),
+        ];
+        for (i, texel) in texels.iter_mut().enumerate() {
+            let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+            for c in 0..3 {
+                let value = (x * (horizontal[c] - origin[c])
+                    + y * (vertical[c] - origin[c])
+                    + 4 * origin[c]
+                    + 2)
+                    >> 2;
+                texel[c] = value.clamp(0, 255) as u8;
+            }
+            texel[3] = 0xFF;
+        }
+        return;
+    } else {
+        let expand5 = |value: u32| (value << 3 | value >> 2) as u8;
+        let first = [expand5(r), expand5(g), expand5(b), 0xFF];
+        let (dr, dg, db) = (dr as u32, dg as u32, db as u32);
+        let second = [expand5(dr), expand5(dg), expand5(db), 0xFF];
+        return decode_subblocks(hi, first, second, opaque, index, texels);
+    };
+
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let index = index(i);
+        *texel = if !opaque && index == 2 {
+            [0; 4]
+        } else {
+            paint[index]
+        };
+    }
+}
+
+/// Decodes the individual and differential modes, which split the block into two
+/// subblocks with a base color and a modifier table each.
+fn decode_subblocks<F: Fn(usize) -> usize>(
+    hi: u32,
+    first: [u8; 4],
+    second: [u8; 4],
+    opaque: bool,
+    index: F,
+    texels: &mut [[u8; 4]],
+) {
+    let tables = [(hi >> 5 & 7) as usize, (hi >> 2 & 7) as usize];
+    let flip = hi & 1 == 1;
+    for (i, texel) in texels.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let subblock = if flip { y >= 2 } else { x >= 2 } as usize;
+        let base = if subblock == 0 { first } else { second };
+        // indices 0 and 1 are the positive modifiers and 2 and 3 the negative ones,
+        // except without alpha, where 0 leaves the base color and 2 is transparent
+        let index = index(i);
+        let modifier = match (opaque, index) {
+            (false, 0) => 0,
+            (false, 2) => {
+                *texel = [0; 4];
+                continue;
+            }
+            (_, index) if index & 2 != 0 => -MODIFIERS[tables[subblock]][index & 1],
+            (_, index) => MODIFIERS[tables[subblock]][index & 1],
+        };
+        *texel = offset(base, modifier);
+    }
+}
+
+/// Decodes an EAC block of 8 bit alpha values.
+pub(super) fn decode_eac_alpha(block: &[u8]) -> [u8; 16] {
+    let bits = u64::from_be_bytes(eac_bytes(block));
+    let (base, multiplier, table) = eac_header(bits);
+    let mut values = [0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        let modifier = EAC_MODIFIERS[table][eac_index(bits, i)];
+        *value = (base as i32 + modifier * multiplier as i32).clamp(0, 255) as u8;
+    }
+    values
+}
+
+/// Decodes an 11 bit EAC block to values in `[0, 1]`, or `[-1, 1]` if signed.
+pub(super) fn decode_eac_r11(block: &[u8], signed: bool) -> [f32; 16] {
+    let bits = u64::from_be_bytes(eac_bytes(block));
+    let (base, multiplier, table) = eac_header(bits);
+    let multiplier = multiplier as i32;
+    let mut values = [0.0; 16];
+    for (i, value) in values.iter_mut().enumerate() {
+        let modifier = EAC_MODIFIERS[table][eac_index(bits, i)];
+        let scaled = if multiplier == 0 {
+            modifier
+        } else {
+            modifier * multiplier * 8
+        };
+        *value = if signed {
+            let base = (base as i8).max(-127) as i32;
+            (base * 8 + scaled).clamp(-1023, 1023) as f32 / 1023.0
+        } else {
+            (base as i32 * 8 + 4 + scaled).clamp(0, 2047) as f32 / 2047.0
+        };
+    }
+    values
+}
+
+fn eac_bytes(block: &[u8]) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&block[..8]);
+    bytes
+}
+
+fn eac_header(bits: u64) -> (u8, u8, usize) {
+    (
+        (bits >> 56) as u8,
+        (bits >> 52 & 15) as u8,
+        (bits >> 48 & 15) as usize,
+    )
+}
+
+/// The three bit index of the texel at row major index `i`, stored column major.
+fn eac_index(bits: u64, i: usize) -> usize {
+    let k = i % 4 * 4 + i / 4;
+    (bits >> (45 - 3 * k) & 7) as usize
+}
+
+fn offset(color: [u8; 4], amount: i32) -> [u8; 4] {
+    let add = |value: u8| (value as i32 + amount).clamp(0, 255) as u8;
+    [add(color[0]), add(color[1]), add(color[2]), color[3]]
+}
+
+/// Expands 4 bit color channels to an opaque 8 bit color.
+fn expand4(channels: [u32; 3]) -> [u8; 4] {
+    let expand = |value: u32| (value << 4 | value) as u8;
+    [
+        expand(channels[0]),
+        expand(channels[1]),
+        expand(channels[2]),
+        0xFF,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(hi: u32, lo: u32, punch_through: bool) -> [[u8; 4]; 16] {
+        let mut block = hi.to_be_bytes().to_vec();
+        block.extend_from_slice(&lo.to_be_bytes());
+        let mut texels = [[0; 4]; 16];
+        decode_etc2(&block, punch_through, &mut texels);
+        texels
+    }
+
+    #[test]
+    fn individual() {
+        // black base colors with the smallest modifier
+        assert_eq!(decode(0, 0, false), [[2, 2, 2, 255]; 16]);
+        // the second subblock is the right half unless the flip bit is set
+        let texels = decode(0x0F00_0000, 0, false);
+        assert_eq!(texels[0], [2, 2, 2, 255]);
+        assert_eq!(texels[2], [255, 2, 2, 255]);
+        let texels = decode(0x0F00_0001, 0, false);
+        assert_eq!(texels[2], [2, 2, 2, 255]);
+        assert_eq!(texels[8], [255, 2, 2, 255]);
+    }
+
+    #[test]
+    fn differential() {
+        // base 16 expands to 132; table 7 with every index 3 subtracts 183
+        let hi = 16 << 27 | 16 << 19 | 16 << 11 | 7 << 5 | 7 << 2 | 2;
+        assert_eq!(decode(hi, !0, false), [[0, 0, 0, 255]; 16]);
+        // index 0 adds 47
+        assert_eq!(decode(hi, 0, false)[0], [179, 179, 179, 255]);
+    }
+
+    #[test]
+    fn punch_through() {
+        // without the opaque bit, index 2 is transparent and index 0 is the base color
+        let hi = 16 << 27 | 16 << 19 | 16 << 11;
+        let texels = decode(hi, 0xFFFF_0000, true);
+        assert_eq!(texels[0], [0; 4]);
+        assert_eq!(decode(hi, 0, true)[0], [132, 132, 132, 255]);
+        // with it, index 2 is opaque
+        assert_ne!(decode(hi | 2, 0xFFFF_0000, true)[0][3], 0);
+    }
+
+    #[test]
+    fn planar() {
+        // blue overflows: an origin blue of 26 (105), green rising to 127 across the block
+        let hi = 31 << 11 | 1 << 8 | 2;
+        let texels = decode(hi, 127 << 25, false);
+        assert_eq!(texels[0], [0, 0, 105, 255]);
+        assert_eq!(texels[1], [0, 64, 79, 255]);
+        assert_eq!(texels[3], [0, 191, 26, 255]);
+        assert_eq!(texels[4], [0, 0, 79, 255]);
+    }
+
+    #[test]
+    fn eac() {
+        // base 128, multiplier 1, table 0, every index 7 (+14)
+        let bits: u64 = 128 << 56 | 1 << 52 | 0xFFFF_FFFF_FFFF;
+        assert_eq!(decode_eac_alpha(&bits.to_be_bytes()), [142; 16]);
+        let red = decode_eac_r11(&bits.to_be_bytes(), false);
+        assert_eq!(red[0], (128 * 8 + 4 + 14 * 8) as f32 / 2047.0);
+        // a zero multiplier uses the modifier unscaled, and -128 reads as -127
+        let bits: u64 = 0x80 << 56 | 0xFFFF_FFFF_FFFF;
+        assert_eq!(
+            decode_eac_r11(&bits.to_be_bytes(), true)[0],
+            -1002.0 / 1023.0
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use crate::{ImagePixels, MTLPixelFormat, NSUInteger, ReadbackError, ReadbackImage};
+
+mod astc;
+mod bc;
+mod bc6h;
+mod bc7;
+mod etc;
+mod pvrtc;
+
+impl ReadbackImage {
+    /// Decodes a block compressed image, with rows of blocks tightly packed as in a
+    /// `TextureSubresource`.
+    ///
+    /// Unsigned normalized formats decode to RGBA8 and signed normalized formats and BC6H
+    /// to RGBA32Float. Single and two channel formats fill the missing color channels with
+    /// 0 and alpha with 1. sRGB formats are not converted to linear.
+    pub fn from_compressed(
+        format: MTLPixelFormat,
+        width: NSUInteger,
+        height: NSUInteger,
+        data: &[u8],
+    ) -> Result<ReadbackImage, ReadbackError> {
+        use MTLPixelFormat::*;
+
+        if !format.is_compressed() {
+            return Err(ReadbackError::UnsupportedFormat(format));
+        }
+        let info = format.info().unwrap();
+        let minimum_blocks = if format.is_pvrtc() { 2 } else { 1 };
+        let blocks_wide = info.blocks_wide(width).max(minimum_blocks) as usize;
+        let blocks_high = info.blocks_high(height).max(minimum_blocks) as usize;
+        let block_size = info.bytes_per_block as usize;
+        if data.len() < blocks_wide * blocks_high * block_size {
+            return Err(ReadbackError::Truncated);
+        }
+        let (block_width, block_height) = (info.block_width as usize, info.block_height as usize);
+        let (width, height) = (width as usize, height as usize);
+
+        let pixels = match format {
+            PVRTC_RGB_2BPP | PVRTC_RGB_2BPP_sRGB | PVRTC_RGBA_2BPP | PVRTC_RGBA_2BPP_sRGB
+            | PVRTC_RGB_4BPP | PVRTC_RGB_4BPP_sRGB | PVRTC_RGBA_4BPP | PVRTC_RGBA_4BPP_sRGB => {
+                let texels = pvrtc::decode(data, blocks_wide, blocks_high, block_width == 8);
+                ImagePixels::Rgba8(crop(&texels, blocks_wide * block_width, width, height))
+            }
+            BC4_RSnorm | BC5_RGSnorm | EAC_R11Snorm | EAC_RG11Snorm | BC6H_RGBFloat
+            | BC6H_RGBUfloat => ImagePixels::Rgba32Float(decode_blocks(
+                data,
+                (blocks_wide, blocks_high),
+                (block_width, block_height, block_size),
+                (width, height),
+                |block, texels: &mut [[f32; 4]]| match format {
+                    BC4_RSnorm => {
+                        for (texel, red) in
+                            texels.iter_mut().zip(bc::decode_bc4(block, true).iter())
+                        {
+                            *texel = [*red, 0.0, 0.0, 1.0];
+                        }
+                    }
+                    BC5_RGSnorm => {
+                        let red = bc::decode_bc4(&block[..8], true);
+                        let green = bc::decode_bc4(&block[8..], true);
+                        for (i, texel) in texels.iter_mut().enumerate() {
+                            *texel = [red[i], green[i], 0.0, 1.0];
+                        }
+                    }
+                    EAC_R11Snorm => {
+                        for (texel, red) in texels
+                            .iter_mut()
+                            .zip(etc::decode_eac_r11(block, true).iter())
+                        {
+                            *texel = [*red, 0.0, 0.0, 1.0];
+                        }
+                    }
+                    EAC_RG11Snorm => {
+                        let red = etc::decode_eac_r11(&block[..8], true);
+                        let green = etc::decode_eac_r11(&block[8..], true);
+                        for (i, texel) in texels.iter_mut().enumerate() {
+                            *texel = [red[i], green[i], 0.0, 1.0];
+                        }
+                    }
+                    _ => bc6h::decode(block, format == BC6H_RGBFloat, texels),
+                },
+            )),
+            _ => ImagePixels::Rgba8(decode_blocks(
+                data,
+                (blocks_wide, blocks_high),
+                (block_width, block_height, block_size),
+                (width, height),
+                |block, texels: &mut [[u8; 4]]| match format {
+                    BC1_RGBA | BC1_RGBA_sRGB => bc::decode_bc1(block, texels, true),
+                    BC2_RGBA | BC2_RGBA_sRGB => {
+                        bc::decode_bc1(&block[8..], texels, false);
+                        for (texel, alpha) in
+                            texels.iter_mut().zip(bc::decode_bc2_alpha(block).iter())
+                        {
+                            texel[3] = *alpha;
+                        }
+                    }
+                    BC3_RGBA | BC3_RGBA_sRGB => {
+                        bc::decode_bc1(&block[8..], texels, false);
+                        for (texel, alpha) in
+                            texels.iter_mut().zip(bc::decode_bc4(block, false).iter())
+                        {
+                            texel[3] = unorm8(*alpha);
+                        }
+                    }
+                    BC4_RUnorm => {
+                        for (texel, red) in
+                            texels.iter_mut().zip(bc::decode_bc4(block, false).iter())
+                        {
+                            *texel = [unorm8(*red), 0, 0, 0xFF];
+                        }
+                    }
+                    BC5_RGUnorm => {
+                        let red = bc::decode_bc4(&block[..8], false);
+                        let green = bc::decode_bc4(&block[8..], false);
+                        for (i, texel) in texels.iter_mut().enumerate() {
+                            *texel = [unorm8(red[i]), unorm8(green[i]), 0, 0xFF];
+                        }
+                    }
+                    BC7_RGBAUnorm | BC7_RGBAUnorm_sRGB => bc7::decode(block, texels),
+                    ETC2_RGB8 | ETC2_RGB8_sRGB => etc::decode_etc2(block, false, texels),
+                    ETC2_RGB8A1 | ETC2_RGB8A1_sRGB => etc::decode_etc2(block, true, texels),
+                    EAC_RGBA8 | EAC_RGBA8_sRGB => {
+                        etc::decode_etc2(&block[8..], false, texels);
+                        for (texel, alpha) in
+                            texels.iter_mut().zip(etc::decode_eac_alpha(block).iter())
+                        {
+                            texel[3] = *alpha;
+                        }
+                    }
+                    EAC_R11Unorm => {
+                        for (texel, red) in texels
+                            .iter_mut()
+                            .zip(etc::decode_eac_r11(block, false).iter())
+                        {
+                            *texel = [unorm8(*red), 0, 0, 0xFF];
+                        }
+                    }
+                    EAC_RG11Unorm => {
+                        let red = etc::decode_eac_r11(&block[..8], false);
+                        let green = etc::decode_eac_r11(&block[8..], false);
+                        for (i, texel) in texels.iter_mut().enumerate() {
+                            *texel = [unorm8(red[i]), unorm8(green[i]), 0, 0xFF];
+                        }
+                    }
+                    // every remaining compressed format is ASTC
+                    _ => astc::decode(block, block_width, block_height, format.is_srgb(), texels),
+                },
+            )),
+        };
+        Ok(ReadbackImage {
+            width: width as NSUInteger,
+            height: height as NSUInteger,
+            pixels,
+        })
+    }
+    /// Compresses the image to `BC1_RGBA`, `BC3_RGBA`, `BC4_RUnorm`, `BC5_RGUnorm` or the
+    /// sRGB variants of the first two, converting it with `to_rgba8` first.
+    ///
+    /// BC4 compresses the red channel and BC5 the red and green channels. The encoder
+    /// favors speed over quality and is meant for tooling and tests.
+    pub fn to_compressed(&self, format: MTLPixelFormat) -> Result<Vec<u8>, ReadbackError> {
+        use MTLPixelFormat::*;
+
+        let block_size = match format {
+            BC1_RGBA | BC1_RGBA_sRGB | BC4_RUnorm => 8,
+            BC3_RGBA | BC3_RGBA_sRGB | BC5_RGUnorm => 16,
+            _ => return Err(ReadbackError::UnsupportedFormat(format)),
+        };
+        let rgba = self.to_rgba8();
+        let (width, height) = (self.width as usize, self.height as usize);
+        let (blocks_wide, blocks_high) = (width.div_ceil(4), height.div_ceil(4));
+
+        let mut data = Vec::with_capacity(blocks_wide * blocks_high * block_size);
+        for block_y in 0..blocks_high {
+            for block_x in 0..blocks_wide {
+                // edge blocks repeat the last row and column
+                let mut texels = [[0u8; 4]; 16];
+                for (i, texel) in texels.iter_mut().enumerate() {
+                    let x = (block_x * 4 + i % 4).min(width - 1);
+                    let y = (block_y * 4 + i / 4).min(height - 1);
+                    texel.copy_from_slice(&rgba[(y * width + x) * 4..][..4]);
+                }
+                let channel = |index: usize| {
+                    let mut values = [0; 16];
+                    for (value, texel) in values.iter_mut().zip(texels.iter()) {
+                        *value = texel[index];
+                    }
+                    values
+                };
+                match format {
+                    BC1_RGBA | BC1_RGBA_sRGB => {
+                        data.extend_from_slice(&bc::encode_bc1(&texels, true))
+                    }
+                    BC3_RGBA | BC3_RGBA_sRGB => {
+                        data.extend_from_slice(&bc::encode_bc4(&channel(3)));
+                        data.extend_from_slice(&bc::encode_bc1(&texels, false));
+                    }
+                    BC4_RUnorm => data.extend_from_slice(&bc::encode_bc4(&channel(0))),
+                    _ => {
+                        data.extend_from_slice(&bc::encode_bc4(&channel(0)));
+                        data.extend_from_slice(&bc::encode_bc4(&channel(1)));
+                    }
+                }
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// Decodes every block of an image and crops the result to its size.
+fn decode_blocks<T: Copy + Default, F: Fn(&[u8], &mut [[T; 4]])>(
+    data: &[u8],
+    (blocks_wide, blocks_high): (usize, usize),
+    (block_width, block_height, block_size): (usize, usize, usize),
+    (width, height): (usize, usize),
+    decode: F,
+) -> Vec<T> {
+    let padded_width = blocks_wide * block_width;
+    let mut texels = vec![[T::default(); 4]; padded_width * blocks_high * block_height];
+    let mut block_texels = vec![[T::default(); 4]; block_width * block_height];
+
+    for (index, block) in data
+        .chunks_exact(block_size)
+        .take(blocks_wide * blocks_high)
+        .enumerate()
+    {
+        decode(block, &mut block_texels);
+        let (block_x, block_y) = (index % blocks_wide, index / blocks_wide);
+        for (row, texel_row) in block_texels.chunks_exact(block_width).enumerate() {
+            let start = (block_y * block_height + row) * padded_width + block_x * block_width;
+            texels[start..start + block_width].copy_from_slice(texel_row);
+        }
+    }
+    crop(&texels, padded_width, width, height)
+}
+
+/// Copies the top left `width` by `height` texels of an image into a flat vector of
+/// channels.
+fn crop<T: Copy>(texels: &[[T; 4]], stride: usize, width: usize, height: usize) -> Vec<T> {
+    (0..height)
+        .flat_map(|y| texels[y * stride..][..width].iter())
+        .flat_map(|texel| texel.iter().cloned())
+        .collect()
+}
+
+fn unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Reads little endian bit fields, least significant bit first.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> BitReader<'a> {
+        BitReader { bytes, position: 0 }
+    }
+    fn read(&mut self, count: u32) -> u32 {
+        let mut value = 0;
+        for bit in 0..count {
+            let position = self.position + bit as usize;
+            value |= ((self.bytes[position / 8] >> (position % 8)) as u32 & 1) << bit;
+        }
+        self.position += count as usize;
+        value
+    }
+}
+
+/// Packs `(value, bits)` fields into a 128 bit block, least significant bit first, as
+/// `BitReader` reads them.
+#[cfg(test)]
+fn pack_bits(fields: &[(u128, u32)]) -> [u8; 16] {
+    let mut block = 0u128;
+    let mut position = 0;
+    for &(value, bits) in fields {
+        block |= value << position;
+        position += bits;
+    }
+    assert!(position <= 128);
+    block.to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_reader() {
+        let block = pack_bits(&[(0b101, 3), (0x3FF, 10), (0, 1), (1, 1)]);
+        let mut bits = BitReader::new(&block);
+        assert_eq!(bits.read(3), 0b101);
+        assert_eq!(bits.read(10), 0x3FF);
+        assert_eq!(bits.read(2), 0b10);
+    }
+
+    #[test]
+    fn from_compressed() {
+        use MTLPixelFormat::*;
+
+        assert!(matches!(
+            ReadbackImage::from_compressed(RGBA8Unorm, 4, 4, &[0; 64]),
+            Err(ReadbackError::UnsupportedFormat(_))
+        ));
+        // 5x5 needs four blocks
+        assert!(matches!(
+            ReadbackImage::from_compressed(BC1_RGBA, 5, 5, &[0; 24]),
+            Err(ReadbackError::Truncated)
+        ));
+        let image = ReadbackImage::from_compressed(BC1_RGBA, 5, 5, &[0; 32]).unwrap();
+        assert_eq!((image.width, image.height), (5, 5));
+        assert_eq!(image.to_rgba8().len(), 5 * 5 * 4);
+        // signed formats decode to floats
+        let image = ReadbackImage::from_compressed(BC4_RSnorm, 4, 4, &[0x81; 8]).unwrap();
+        assert_eq!(
+            image.pixels,
+            ImagePixels::Rgba32Float([-1.0, 0.0, 0.0, 1.0].repeat(16))
+        );
+    }
+}
@@ -0,0 +1,237 @@
+/// The modulation weights out of 8 of the two bit modulation values.
+const WEIGHTS: [u32; 4] = [0, 3, 5, 8];
+
+/// Decodes a whole image, as the colors of each texel are interpolated from the four
+/// nearest blocks, wrapping around the edges of the image.
+pub(super) fn decode(
+    data: &[u8],
+    blocks_wide: usize,
+    blocks_high: usize,
+    two_bpp: bool,
+) -> Vec<[u8; 4]> {
+    let block_width = if two_bpp { 8 } else { 4 };
+    let (width, height) = (blocks_wide * block_width, blocks_high * 4);
+
+    // blocks are stored in twiddled order
+    let mut blocks = Vec::with_capacity(blocks_wide * blocks_high);
+    for y in 0..blocks_high {
+        for x in 0..blocks_wide {
+            let offset = twiddle(x, y, blocks_wide, blocks_high) * 8;
+            let word = |offset: usize| {
+                u32::from_le_bytes([
+                    data[offset],
+                    data[offset + 1],
+                    data[offset + 2],
+                    data[offset + 3],
+                ])
+            };
+            blocks.push((word(offset), word(offset + 4)));
+        }
+    }
+
+    // the raw two bit modulation value and the modulation mode of each texel
+    let mut values = vec![0; width * height];
+    let mut modes = vec![0; width * height];
+    for (index, &(modulation, color)) in blocks.iter().enumerate() {
+        let (block_x, block_y) = (index % blocks_wide, index / blocks_wide);
+        let mut texel = |x: usize, y: usize, value: u32, mode: u32| {
+            let i = (block_y * 4 + y) * width + block_x * block_width + x;
+            values[i] = value;
+            modes[i] = mode;
+        };
+        let punch_through = color & 1;
+        if !two_bpp {
+            for i in 0..16 {
+                texel(i % 4, i / 4, modulation >> (i * 2) & 3, punch_through);
+            }
+        } else if punch_through == 0 {
+            // one bit per texel
+            for i in 0..32 {
+                texel(i % 8, i / 8, (modulation >> i & 1) * 3, 0);
+            }
+        } else {
+            // two bits for every other texel, with the rest interpolated horizontally and
+            // vertically, horizontally only or vertically only
+            let mut bits = modulation;
+            let mut mode = 1;
+            if bits & 1 == 1 {
+                mode = if bits >> 20 & 1 == 1 { 3 } else { 2 };
+                bits = bits & !(1 << 20) | (bits >> 21 & 1) << 20;
+            }
+            bits = bits & !1 | bits >> 1 & 1;
+            for y in 0..4 {
+                for x in 0..8 {
+                    if (x ^ y) & 1 == 0 {
+                        texel(x, y, bits & 3, mode);
+                        bits >>= 2;
+                    } else {
+                        texel(x, y, 0, mode);
+                    }
+                }
+            }
+        }
+    }
+    let wrap = |value: isize, size: usize| value.rem_euclid(size as isize) as usize;
+    let weight = |x: usize, y: usize| {
+        let i = y * width + x;
+        let value = |dx: isize, dy: isize| {
+            let (x, y) = (wrap(x as isize + dx, width), wrap(y as isize + dy, height));
+            WEIGHTS[values[y * width + x] as usize]
+        };
+        // the weight and whether the texel is transparent
+        match (two_bpp, modes[i], values[i]) {
+            (false, 0, _) => (value(0, 0), false),
+            // with punch through alpha the third value is a transparent midpoint
+            (false, _, raw) => ([0, 4, 4, 8][raw as usize], raw == 2),
+            (true, 0, _) => (value(0, 0), false),
+            (true, _, _) if (x ^ y) & 1 == 0 => (value(0, 0), false),
+            (true, 1, _) => (
+                (value(0, -1) + value(0, 1) + value(-1, 0) + value(1, 0) + 2) / 4,
+                false,
+            ),
+            (true, 2, _) => ((value(-1, 0) + value(1, 0)).div_ceil(2), false),
+            (true, _, _) => ((value(0, -1) + value(0, 1)).div_ceil(2), false),
+        }
+    };
+
+    let colors = blocks
+        .iter()
+        .map(|&(_, color)| block_colors(color))
+        .collect::<Vec<_>>();
+    let shift = if two_bpp { 5 } else { 4 };
+    let mut texels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            // interpolate between the centers of the surrounding blocks
+            let u = x as isize - block_width as isize / 2;
+            let v = y as isize - 2;
+            let (x0, fx) = (
+                u.div_euclid(block_width as isize),
+                u.rem_euclid(block_width as isize),
+            );
+            let (y0, fy) = (v.div_euclid(4), v.rem_euclid(4));
+            let block = |dx: isize, dy: isize| {
+                &colors[wrap(y0 + dy, blocks_high) * blocks_wide + wrap(x0 + dx, blocks_wide)]
+            };
+            let (p, q, r, s) = (block(0, 0), block(1, 0), block(0, 1), block(1, 1));
+            let (fx, fy, size) = (fx as u32, fy as u32, block_width as u32);
+
+            let mut endpoints = [[0; 4]; 2];
+            for (e, endpoint) in endpoints.iter_mut().enumerate() {
+                for (c, value) in endpoint.iter_mut().enumerate() {
+                    let sum = p[e][c] * (size - fx) * (4 - fy)
+                        + q[e][c] * fx * (4 - fy)
+                        + r[e][c] * (size - fx) * fy
+                        + s[e][c] * fx * fy;
+                    // expand the five bit colors and four bit alpha to eight bits
+                    *value = if c < 3 {
+                        (sum >> (shift + 2)) + (sum >> (shift - 3))
+                    } else {
+                        (sum >> shift) + (sum >> (shift - 4))
+                    };
+                }
+            }
+            let (weight, transparent) = weight(x, y);
+            let mut texel = [0; 4];
+            for (c, value) in texel.iter_mut().enumerate() {
+                *value = ((endpoints[0][c] * (8 - weight) + endpoints[1][c] * weight) / 8) as u8;
+            }
+            if transparent {
+                texel[3] = 0;
+            }
+            texels.push(texel);
+        }
+    }
+    texels
+}
+
+/// The index of a block, interleaving the bits of its coordinates up to the smaller
+/// dimension and then using the remaining bits of the larger one.
+fn twiddle(x: usize, y: usize, blocks_wide: usize, blocks_high: usize) -> usize {
+    let minimum = blocks_wide.min(blocks_high);
+    let mut index = 0;
+    let mut bit = 0;
+    while 1 << bit < minimum {
+        index |= (x >> bit & 1) << (2 * bit) | (y >> bit & 1) << (2 * bit + 1);
+        bit += 1;
+    }
+    let rest = if blocks_wide < blocks_high { y } else { x };
+    index | (rest >> bit) << (2 * bit)
+}
+
+/// Unpacks the two colors of a block to five bit color and four bit alpha channels.
+fn block_colors(color: u32) -> [[u32; 4]; 2] {
+    let expand4 = |value: u32| value << 1 | value >> 3;
+    let a = if color & 0x8000 != 0 {
+        let blue = color >> 1 & 0xF;
+        [color >> 10 & 0x1F, color >> 5 & 0x1F, expand4(blue), 0xF]
+    } else {
+        let blue = color >> 1 & 7;
+        [
+            expand4(color >> 8 & 0xF),
+            expand4(color >> 4 & 0xF),
+            blue << 2 | blue >> 1,
+            (color >> 12 & 7) << 1,
+        ]
+    };
+    let b = if color & 0x8000_0000 != 0 {
+        [
+            color >> 26 & 0x1F,
+            color >> 21 & 0x1F,
+            color >> 16 & 0x1F,
+            0xF,
+        ]
+    } else {
+        [
+            expand4(color >> 24 & 0xF),
+            expand4(color >> 20 & 0xF),
+            expand4(color >> 16 & 0xF),
+            (color >> 28 & 7) << 1,
+        ]
+    };
+    [a, b]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn twiddled_order() {
+        assert_eq!(twiddle(1, 0, 2, 4), 1);
+        assert_eq!(twiddle(0, 1, 2, 4), 2);
+        assert_eq!(twiddle(1, 2, 2, 4), 5);
+        // the larger dimension's remaining bits follow the interleaved ones
+        assert_eq!(twiddle(2, 0, 4, 2), 4);
+        assert_eq!(twiddle(3, 1, 4, 2), 7);
+    }
+
+    #[test]
+    fn block_color_modes() {
+        // opaque 5 bit colors, and translucent colors with 3 bit alpha
+        let colors = block_colors(0x8000 | 31 << 10 | 0x8000_0000 | 31 << 16);
+        assert_eq!(colors, [[31, 0, 0, 0xF], [0, 0, 31, 0xF]]);
+        let colors = block_colors(7 << 12 | 0xF << 8 | 3 << 28 | 0xF << 16);
+        assert_eq!(colors, [[31, 0, 0, 14], [0, 0, 31, 6]]);
+    }
+
+    #[test]
+    fn decode_4bpp() {
+        // 2x2 blocks, color A opaque red and B opaque blue, only the second block in
+        // twiddled order fully modulated to B
+        let color: u32 = 0x8000 | 31 << 10 | 0x8000_0000 | 31 << 16;
+        let mut data = Vec::new();
+        for i in 0..4 {
+            let modulation: u32 = if i == 1 { !0 } else { 0 };
+            data.extend_from_slice(&modulation.to_le_bytes());
+            data.extend_from_slice(&color.to_le_bytes());
+        }
+        let texels = decode(&data, 2, 2, false);
+        assert_eq!(texels.len(), 64);
+        assert_eq!(texels[0], [255, 0, 0, 255]);
+        // block 1 is at (1, 0), starting at texel (4, 0)
+        assert_eq!(texels[4], [0, 0, 255, 255]);
+        assert_eq!(texels[4 * 8], [255, 0, 0, 255]);
+        assert_eq!(decode(&data, 2, 2, true).len(), 128);
+    }
+}
@@ -0,0 +1,660 @@
+use crate::{MTLPixelFormat, NSUInteger};
+use std::fmt::{Display, Formatter};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The pixel format's texels cannot be read or written on the CPU.
+    UnsupportedFormat(MTLPixelFormat),
+    /// The data is shorter than the texels it should contain.
+    Truncated,
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::UnsupportedFormat(format) => {
+                write!(f, "cannot convert texels of pixel format {:?}", format)
+            }
+            ConversionError::Truncated => f.write_str("texel data is truncated"),
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// A texel read from or written to texture data on the CPU, holding the channels a
+/// shader would see.
+///
+/// Normalized and float formats use `Float`, with sRGB channels in linear space, and
+/// integer formats use `Uint` or `Sint`. Channels a format lacks read as 0, except alpha,
+/// which reads as 1. Any variant can be written to any format, converting channels by
+/// value.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Texel {
+    Float([f32; 4]),
+    Uint([u32; 4]),
+    Sint([i32; 4]),
+}
+
+impl Texel {
+    /// Returns the channels as floats, converting integers by value.
+    pub fn to_float(&self) -> [f32; 4] {
+        let values = self.values();
+        [
+            values[0] as f32,
+            values[1] as f32,
+            values[2] as f32,
+            values[3] as f32,
+        ]
+    }
+    fn values(&self) -> [f64; 4] {
+        let mut values = [0.0; 4];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = match self {
+                Texel::Float(channels) => channels[i] as f64,
+                Texel::Uint(channels) => channels[i] as f64,
+                Texel::Sint(channels) => channels[i] as f64,
+            };
+        }
+        values
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Channel {
+    Unorm,
+    Snorm,
+    Uint,
+    Sint,
+    Float,
+    /// Unsigned floats with a 5 bit exponent, as in `RG11B10Float`.
+    UnsignedFloat,
+    /// 10 bit fixed point covering `[-0.752941, 1.25098]`, as in the XR formats.
+    ExtendedRange,
+    /// Unsigned 9 bit mantissas sharing a 5 bit exponent.
+    SharedExponent,
+}
+
+/// Marks bits of a texel which belong to no channel.
+const PADDING: usize = 4;
+
+/// Describes the channels of a texel as their kind and, for each field from the least
+/// significant bit of the texel read as a little endian integer, its RGBA index and bits.
+fn layout(format: MTLPixelFormat) -> Option<(Channel, &'static [(usize, u32)])> {
+    use Channel::*;
+    use MTLPixelFormat::*;
+
+    const R8: &[(usize, u32)] = &[(0, 8)];
+    const RG8: &[(usize, u32)] = &[(0, 8), (1, 8)];
+    const RGBA8: &[(usize, u32)] = &[(0, 8), (1, 8), (2, 8), (3, 8)];
+    const R16: &[(usize, u32)] = &[(0, 16)];
+    const RG16: &[(usize, u32)] = &[(0, 16), (1, 16)];
+    const RGBA16: &[(usize, u32)] = &[(0, 16), (1, 16), (2, 16), (3, 16)];
+    const R32: &[(usize, u32)] = &[(0, 32)];
+    const RG32: &[(usize, u32)] = &[(0, 32), (1, 32)];
+    const RGBA32: &[(usize, u32)] = &[(0, 32), (1, 32), (2, 32), (3, 32)];
+    const RGB10A2: &[(usize, u32)] = &[(0, 10), (1, 10), (2, 10), (3, 2)];
+
+    Some(match format {
+        A8Unorm => (Unorm, &[(3, 8)]),
+        R8Unorm | R8Unorm_sRGB => (Unorm, R8),
+        R8Snorm => (Snorm, R8),
+        R8Uint | Stencil8 => (Uint, R8),
+        R8Sint => (Sint, R8),
+        R16Unorm | Depth16Unorm => (Unorm, R16),
+        R16Snorm => (Snorm, R16),
+        R16Uint => (Uint, R16),
+        R16Sint => (Sint, R16),
+        R16Float => (Float, R16),
+        RG8Unorm | RG8Unorm_sRGB => (Unorm, RG8),
+        RG8Snorm => (Snorm, RG8),
+        RG8Uint => (Uint, RG8),
+        RG8Sint => (Sint, RG8),
+        B5G6R5Unorm => (Unorm, &[(2, 5), (1, 6), (0, 5)]),
+        A1BGR5Unorm => (Unorm, &[(3, 1), (2, 5), (1, 5), (0, 5)]),
+        ABGR4Unorm => (Unorm, &[(3, 4), (2, 4), (1, 4), (0, 4)]),
+        BGR5A1Unorm => (Unorm, &[(2, 5), (1, 5), (0, 5), (3, 1)]),
+        R32Uint => (Uint, R32),
+        R32Sint => (Sint, R32),
+        R32Float | Depth32Float => (Float, R32),
+        RG16Unorm => (Unorm, RG16),
+        RG16Snorm => (Snorm, RG16),
+        RG16Uint => (Uint, RG16),
+        RG16Sint => (Sint, RG16),
+        RG16Float => (Float, RG16),
+        RGBA8Unorm | RGBA8Unorm_sRGB => (Unorm, RGBA8),
+        RGBA8Snorm => (Snorm, RGBA8),
+        RGBA8Uint => (Uint, RGBA8),
+        RGBA8Sint => (Sint, RGBA8),
+        BGRA8Unorm | BGRA8Unorm_sRGB => (Unorm, &[(2, 8), (1, 8), (0, 8), (3, 8)]),
+        RGB10A2Unorm => (Unorm, RGB10A2),
+        RGB10A2Uint => (Uint, RGB10A2),
+        BGR10A2Unorm => (Unorm, &[(2, 10), (1, 10), (0, 10), (3, 2)]),
+        RG11B10Float => (UnsignedFloat, &[(0, 11), (1, 11), (2, 10)]),
+        RGB9E5Float => (SharedExponent, &[(0, 9), (1, 9), (2, 9), (PADDING, 5)]),
+        BGR10_XR | BGR10_XR_SRGB => (ExtendedRange, &[(2, 10), (1, 10), (0, 10)]),
+        // each channel is in the top 10 bits of 16
+        BGRA10_XR | BGRA10_XR_SRGB => (
+            ExtendedRange,
+            &[
+                (PADDING, 6),
+                (2, 10),
+                (PADDING, 6),
+                (1, 10),
+                (PADDING, 6),
+                (0, 10),
+                (PADDING, 6),
+                (3, 10),
+            ],
+        ),
+        RG32Uint => (Uint, RG32),
+        RG32Sint => (Sint, RG32),
+        RG32Float => (Float, RG32),
+        RGBA16Unorm => (Unorm, RGBA16),
+        RGBA16Snorm => (Snorm, RGBA16),
+        RGBA16Uint => (Uint, RGBA16),
+        RGBA16Sint => (Sint, RGBA16),
+        RGBA16Float => (Float, RGBA16),
+        RGBA32Uint => (Uint, RGBA32),
+        RGBA32Sint => (Sint, RGBA32),
+        RGBA32Float => (Float, RGBA32),
+        _ => return None,
+    })
+}
+
+impl MTLPixelFormat {
+    /// Whether texels of this format can be read and written on the CPU. Compressed, 422
+    /// and combined depth and stencil formats cannot be.
+    pub fn is_convertible(&self) -> bool {
+        layout(*self).is_some()
+    }
+    /// Reads the texel at the start of `bytes`.
+    pub fn read_texel(&self, bytes: &[u8]) -> Result<Texel, ConversionError> {
+        let (channel, fields) = layout(*self).ok_or(ConversionError::UnsupportedFormat(*self))?;
+        let size = self.info().unwrap().bytes_per_block as usize;
+        if bytes.len() < size {
+            return Err(ConversionError::Truncated);
+        }
+        let mut word = [0; 16];
+        word[..size].copy_from_slice(&bytes[..size]);
+        let word = u128::from_le_bytes(word);
+
+        let mut values = [0.0, 0.0, 0.0, 1.0];
+        let mut shift = 0;
+        for &(index, bits) in fields {
+            let raw = (word >> shift) as u64 & mask(bits);
+            shift += bits;
+            if index != PADDING {
+                values[index] = decode(channel, raw, bits);
+            }
+        }
+        if channel == Channel::SharedExponent {
+            let scale = 2f64.powi((word >> 27) as i32 & 0x1F) / (1 << 24) as f64;
+            values.iter_mut().take(3).for_each(|value| *value *= scale);
+        }
+        if self.is_srgb() {
+            values
+                .iter_mut()
+                .take(3)
+                .for_each(|value| *value = srgb_to_linear(*value));
+        }
+
+        Ok(match channel {
+            Channel::Uint => Texel::Uint([
+                values[0] as u32,
+                values[1] as u32,
+                values[2] as u32,
+                values[3] as u32,
+            ]),
+            Channel::Sint => Texel::Sint([
+                values[0] as i32,
+                values[1] as i32,
+                values[2] as i32,
+                values[3] as i32,
+            ]),
+            _ => Texel::Float([
+                values[0] as f32,
+                values[1] as f32,
+                values[2] as f32,
+                values[3] as f32,
+            ]),
+        })
+    }
+    /// Writes a texel to the start of `bytes`, clamping channels to the range of the format
+    /// and rounding to the nearest representable value.
+    pub fn write_texel(&self, texel: Texel, bytes: &mut [u8]) -> Result<(), ConversionError> {
+        let (channel, fields) = layout(*self).ok_or(ConversionError::UnsupportedFormat(*self))?;
+        let size = self.info().unwrap().bytes_per_block as usize;
+        if bytes.len() < size {
+            return Err(ConversionError::Truncated);
+        }
+        let mut values = texel.values();
+        if self.is_srgb() {
+            values
+                .iter_mut()
+                .take(3)
+                .for_each(|value| *value = linear_to_srgb(*value));
+        }
+
+        let word = if channel == Channel::SharedExponent {
+            encode_rgb9e5(&values) as u128
+        } else {
+            let mut word = 0u128;
+            let mut shift = 0;
+            for &(index, bits) in fields {
+                if index != PADDING {
+                    word |= (encode(channel, values[index], bits) as u128) << shift;
+                }
+                shift += bits;
+            }
+            word
+        };
+        bytes[..size].copy_from_slice(&word.to_le_bytes()[..size]);
+        Ok(())
+    }
+    /// Reads `width` texels stored one after another.
+    pub fn read_row(&self, bytes: &[u8], width: NSUInteger) -> Result<Vec<Texel>, ConversionError> {
+        let size = self.texel_size()?;
+        if bytes.len() < width as usize * size {
+            return Err(ConversionError::Truncated);
+        }
+        bytes
+            .chunks_exact(size)
+            .take(width as usize)
+            .map(|texel| self.read_texel(texel))
+            .collect()
+    }
+    /// Writes texels one after another.
+    pub fn write_row(&self, texels: &[Texel], bytes: &mut [u8]) -> Result<(), ConversionError> {
+        let size = self.texel_size()?;
+        if bytes.len() < texels.len() * size {
+            return Err(ConversionError::Truncated);
+        }
+        for (texel, bytes) in texels.iter().zip(bytes.chunks_exact_mut(size)) {
+            self.write_texel(*texel, bytes)?;
+        }
+        Ok(())
+    }
+    fn texel_size(&self) -> Result<usize, ConversionError> {
+        if !self.is_convertible() {
+            return Err(ConversionError::UnsupportedFormat(*self));
+        }
+        Ok(self.info().unwrap().bytes_per_block as usize)
+    }
+}
+
+/// Converts an image from one format to another, returning its rows tightly packed.
+pub fn convert_texels(
+    from: MTLPixelFormat,
+    to: MTLPixelFormat,
+    width: NSUInteger,
+    height: NSUInteger,
+    bytes_per_row: NSUInteger,
+    bytes: &[u8],
+) -> Result<Vec<u8>, ConversionError> {
+    let source_row = from.texel_size()? * width as usize;
+    let row = to.texel_size()? * width as usize;
+    let mut converted = vec![0; row * height as usize];
+    for (y, destination) in converted.chunks_exact_mut(row.max(1)).enumerate() {
+        let start = y * bytes_per_row as usize;
+        let source = bytes
+            .get(start..start + source_row)
+            .ok_or(ConversionError::Truncated)?;
+        to.write_row(&from.read_row(source, width)?, destination)?;
+    }
+    Ok(converted)
+}
+
+fn mask(bits: u32) -> u64 {
+    if bits == 64 {
+        !0
+    } else {
+        (1 << bits) - 1
+    }
+}
+
+fn decode(channel: Channel, raw: u64, bits: u32) -> f64 {
+    let signed = || ((raw << (64 - bits)) as i64 >> (64 - bits)) as f64;
+    match channel {
+        Channel::Unorm => raw as f64 / mask(bits) as f64,
+        Channel::Snorm => (signed() / mask(bits - 1) as f64).max(-1.0),
+        Channel::Uint | Channel::SharedExponent => raw as f64,
+        Channel::Sint => signed(),
+        Channel::Float if bits == 16 => f16_to_f32(raw as u16) as f64,
+        Channel::Float => f32::from_bits(raw as u32) as f64,
+        Channel::UnsignedFloat => {
+            // the same exponent as a half float, with a shorter mantissa and no sign
+            f16_to_f32((raw << (15 - bits)) as u16) as f64
+        }
+        Channel::ExtendedRange => (raw as f64 - 384.0) / 510.0,
+    }
+}
+
+fn encode(channel: Channel, value: f64, bits: u32) -> u64 {
+    let clamp = |value: f64, min: f64, max: f64| value.max(min).min(max);
+    match channel {
+        Channel::Unorm => (clamp(value, 0.0, 1.0) * mask(bits) as f64).round() as u64,
+        Channel::Snorm => {
+            let max = mask(bits - 1) as f64;
+            ((clamp(value, -1.0, 1.0) * max).round() as i64) as u64 & mask(bits)
+        }
+        Channel::Uint | Channel::SharedExponent => {
+            clamp(value.round(), 0.0, mask(bits) as f64) as u64
+        }
+        Channel::Sint => {
+            let max = mask(bits - 1) as f64;
+            (clamp(value.round(), -max - 1.0, max) as i64) as u64 & mask(bits)
+        }
+        Channel::Float if bits == 16 => f32_to_f16(value as f32) as u64,
+        Channel::Float => (value as f32).to_bits() as u64,
+        Channel::UnsignedFloat => {
+            let shift = 15 - bits;
+            if value.is_nan() {
+                return 0x1F << (bits - 5) | 1;
+            } else if value <= 0.0 {
+                return 0;
+            }
+            let half = f32_to_f16(value as f32) as u64;
+            if half == 0x7C00 && value.is_infinite() {
+                return 0x1F << (bits - 5);
+            }
+            // round the mantissa to nearest even, saturating at the largest finite value
+            let rounded = (half + (1 << (shift - 1)) - 1 + (half >> shift & 1)) >> shift;
+            rounded.min((0x1F << (bits - 5)) - 1)
+        }
+        Channel::ExtendedRange => clamp((value * 510.0 + 384.0).round(), 0.0, 1023.0) as u64,
+    }
+}
+
+/// Packs three channels into `RGB9E5Float`, choosing the smallest exponent which fits the
+/// largest channel.
+fn encode_rgb9e5(values: &[f64; 4]) -> u32 {
+    const MAX: f64 = 511.0 / 512.0 * 65536.0;
+    let mut channels = [0.0; 3];
+    for (channel, value) in channels.iter_mut().zip(values.iter()) {
+        // NaN becomes 0
+        *channel = if value.is_nan() {
+            0.0
+        } else {
+            value.clamp(0.0, MAX)
+        };
+    }
+    let max = channels[0].max(channels[1]).max(channels[2]);
+    let mut exponent = max.log2().floor().max(-16.0) as i32 + 16;
+    if (max / 2f64.powi(exponent - 24) + 0.5).floor() as u32 == 512 {
+        exponent += 1;
+    }
+    let scale = 2f64.powi(exponent - 24);
+    let mantissa = |value: f64| (value / scale + 0.5).floor() as u32 & 0x1FF;
+    (exponent as u32) << 27
+        | mantissa(channels[2]) << 18
+        | mantissa(channels[1]) << 9
+        | mantissa(channels[0])
+}
+
+fn srgb_to_linear(value: f64) -> f64 {
+    // the XR formats extend the curve symmetrically below 0
+    let magnitude = value.abs();
+    let linear = if magnitude <= 0.04045 {
+        magnitude / 12.92
+    } else {
+        ((magnitude + 0.055) / 1.055).powf(2.4)
+    };
+    linear.copysign(value)
+}
+
+fn linear_to_srgb(value: f64) -> f64 {
+    let magnitude = value.abs();
+    let encoded = if magnitude <= 0.003_130_8 {
+        magnitude * 12.92
+    } else {
+        1.055 * magnitude.powf(1.0 / 2.4) - 0.055
+    };
+    encoded.copysign(value)
+}
+
+/// Widens an IEEE 754 half precision float.
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = (bits as u32 & 0x8000) << 16;
+    let exponent = (bits as u32 >> 10) & 0x1F;
+    let mantissa = bits as u32 & 0x3FF;
+    match exponent {
+        0 => {
+            // zero or subnormal
+            let magnitude = mantissa as f32 / (1 << 24) as f32;
+            f32::from_bits(sign | magnitude.to_bits())
+        }
+        0x1F => f32::from_bits(sign | 0x7F80_0000 | mantissa << 13),
+        _ => f32::from_bits(sign | (exponent + 112) << 23 | mantissa << 13),
+    }
+}
+
+/// Narrows a float to IEEE 754 half precision, rounding to nearest even.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = (bits >> 16 & 0x8000) as u16;
+    let exponent = (bits >> 23 & 0xFF) as i32;
+    let mantissa = bits & 0x7F_FFFF;
+    if exponent == 0xFF {
+        // infinity, or a quiet NaN
+        return sign | 0x7C00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let round = |value: u32, shift: u32| {
+        let (quotient, remainder) = (value >> shift, value & ((1 << shift) - 1));
+        let half = 1 << (shift - 1);
+        if remainder > half || remainder == half && quotient & 1 == 1 {
+            quotient + 1
+        } else {
+            quotient
+        }
+    };
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1F {
+        sign | 0x7C00
+    } else if exponent > 0 {
+        // a carry out of the mantissa correctly rounds up to the next exponent
+        sign | (((exponent as u32) << 10) as u16 + round(mantissa, 13) as u16)
+    } else if exponent >= -10 {
+        sign | round(mantissa | 0x80_0000, (14 - exponent) as u32) as u16
+    } else {
+        sign
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use MTLPixelFormat::*;
+
+    fn write(format: MTLPixelFormat, texel: Texel) -> Vec<u8> {
+        let mut bytes = vec![0; format.info().unwrap().bytes_per_block as usize];
+        format.write_texel(texel, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn read(format: MTLPixelFormat, bytes: &[u8]) -> [f32; 4] {
+        format.read_texel(bytes).unwrap().to_float()
+    }
+
+    #[test]
+    fn halves() {
+        // every half other than NaN survives a round trip
+        for bits in 0..=0xFFFF {
+            let value = f16_to_f32(bits);
+            if value.is_nan() {
+                assert!(f16_to_f32(f32_to_f16(value)).is_nan());
+            } else {
+                assert_eq!(f32_to_f16(value), bits, "{:#X}", bits);
+            }
+        }
+        assert_eq!(f16_to_f32(0x3C00), 1.0);
+        assert_eq!(f16_to_f32(0xC000), -2.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7C00), f32::INFINITY);
+        // ties round to even
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3C00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3C02);
+        assert_eq!(f32_to_f16(65519.0), 0x7BFF);
+        assert_eq!(f32_to_f16(70000.0), 0x7C00);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+    }
+
+    #[test]
+    fn rgb9e5() {
+        let bytes = write(RGB9E5Float, Texel::Float([1.0, 0.5, 0.25, 1.0]));
+        // an exponent of 16 and 256, 128 and 64 out of 512
+        assert_eq!(
+            u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            16 << 27 | 64 << 18 | 128 << 9 | 256
+        );
+        assert_eq!(read(RGB9E5Float, &bytes), [1.0, 0.5, 0.25, 1.0]);
+        // negative and NaN channels become 0 and large ones saturate
+        let bytes = write(RGB9E5Float, Texel::Float([-1.0, f32::NAN, 1e9, 1.0]));
+        assert_eq!(read(RGB9E5Float, &bytes), [0.0, 0.0, 65408.0, 1.0]);
+    }
+
+    #[test]
+    fn rg11b10() {
+        let bytes = write(RG11B10Float, Texel::Float([2.0, 0.125, 3.5, 1.0]));
+        let word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        // half float exponents and mantissas truncated to 6 and 5 bits
+        assert_eq!(word & 0x7FF, 0x4000 >> 4);
+        assert_eq!(read(RG11B10Float, &bytes), [2.0, 0.125, 3.5, 1.0]);
+        let bytes = write(RG11B10Float, Texel::Float([-1.0, f32::INFINITY, 1e6, 1.0]));
+        let value = read(RG11B10Float, &bytes);
+        assert_eq!(value[0], 0.0);
+        assert_eq!(value[1], f32::INFINITY);
+        assert_eq!(value[2], 64512.0);
+    }
+
+    #[test]
+    fn srgb() {
+        let bytes = write(RGBA8Unorm_sRGB, Texel::Float([0.5, 0.0, 1.0, 0.5]));
+        // alpha is linear
+        assert_eq!(bytes, [188, 0, 255, 128]);
+        let value = read(RGBA8Unorm_sRGB, &bytes);
+        assert!((value[0] - 0.5).abs() < 0.003);
+        assert_eq!(
+            read(BGRA8Unorm_sRGB, &[0, 0, 255, 255]),
+            [1.0, 0.0, 0.0, 1.0]
+        );
+        // the linear segment near 0
+        assert_eq!(
+            write(R8Unorm_sRGB, Texel::Float([0.001, 0.0, 0.0, 1.0])),
+            [3]
+        );
+    }
+
+    #[test]
+    fn extended_range() {
+        // blue 1023, green 384 and red 0
+        let word: u32 = 1023 | 384 << 10;
+        let value = read(BGR10_XR, &word.to_le_bytes());
+        assert_eq!(value[1], 0.0);
+        assert!((value[0] + 0.752_941).abs() < 1e-6);
+        assert!((value[2] - 1.252_941).abs() < 1e-6);
+        assert_eq!(write(BGR10_XR, Texel::Float(value)), word.to_le_bytes());
+        // each channel in the top 10 bits of 16, alpha included
+        let bytes = write(BGRA10_XR, Texel::Float([0.0, 0.0, 0.0, 1.0]));
+        let word = u64::from_le_bytes([
+            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+        ]);
+        assert_eq!(word, 384 << 6 | 384 << 22 | 384 << 38 | 894 << 54);
+    }
+
+    #[test]
+    fn integers_and_packed() {
+        assert_eq!(read(RGBA8Snorm, &[0x80, 0x7F, 0, 0]), [-1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(
+            RGBA16Sint.read_texel(&[0xFF, 0xFF, 2, 0, 0, 0, 0, 0x80]),
+            Ok(Texel::Sint([-1, 2, 0, -32768]))
+        );
+        assert_eq!(R8Uint.read_texel(&[200]), Ok(Texel::Uint([200, 0, 0, 1])));
+        assert_eq!(write(R8Sint, Texel::Float([-300.0, 0.0, 0.0, 1.0])), [0x80]);
+        assert_eq!(
+            write(BGRA8Unorm, Texel::Float([1.0, 0.0, 0.0, 1.0])),
+            [0, 0, 255, 255]
+        );
+        assert_eq!(
+            write(B5G6R5Unorm, Texel::Float([1.0, 0.0, 0.0, 1.0])),
+            [0x00, 0xF8]
+        );
+        assert_eq!(read(A8Unorm, &[255]), [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn round_trips() {
+        let formats = [
+            R8Unorm,
+            R8Snorm,
+            R16Unorm,
+            R16Float,
+            RG8Unorm,
+            RG16Snorm,
+            RG16Float,
+            R32Float,
+            RGBA8Unorm,
+            RGBA8Unorm_sRGB,
+            BGRA8Unorm,
+            RGB10A2Unorm,
+            BGR10A2Unorm,
+            RG11B10Float,
+            RGB9E5Float,
+            BGR10_XR,
+            BGRA10_XR_SRGB,
+            RG32Float,
+            RGBA16Unorm,
+            RGBA16Float,
+            RGBA32Float,
+            Depth16Unorm,
+            Depth32Float,
+        ];
+        let texel = Texel::Float([0.25, 0.5, 0.75, 1.0]);
+        for &format in &formats {
+            // a written texel reads back as the nearest representable value, which writes
+            // back to the same bytes
+            let bytes = write(format, texel);
+            assert_eq!(
+                write(format, format.read_texel(&bytes).unwrap()),
+                bytes,
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            BC1_RGBA.read_texel(&[0; 8]),
+            Err(ConversionError::UnsupportedFormat(BC1_RGBA))
+        );
+        assert_eq!(
+            Depth32Float_Stencil8.read_row(&[0; 8], 1),
+            Err(ConversionError::UnsupportedFormat(Depth32Float_Stencil8))
+        );
+        assert_eq!(
+            RGBA8Unorm.read_texel(&[0; 3]),
+            Err(ConversionError::Truncated)
+        );
+        assert_eq!(
+            RGBA8Unorm.write_row(&[Texel::Uint([0; 4]); 2], &mut [0; 7]),
+            Err(ConversionError::Truncated)
+        );
+    }
+
+    #[test]
+    fn convert() {
+        let bytes = [188, 0, 255, 255, 0, 0, 0, 0, 9, 9, 9, 9];
+        let converted = convert_texels(RGBA8Unorm_sRGB, RGBA16Float, 2, 1, 12, &bytes).unwrap();
+        assert_eq!(converted.len(), 16);
+        let red = f16_to_f32(u16::from_le_bytes([converted[0], converted[1]]));
+        assert!((red - 0.5).abs() < 0.003);
+        assert_eq!(u16::from_le_bytes([converted[4], converted[5]]), 0x3C00);
+        // rows past the end of the data
+        assert_eq!(
+            convert_texels(RGBA8Unorm, R8Unorm, 2, 2, 12, &bytes),
+            Err(ConversionError::Truncated)
+        );
+    }
+}
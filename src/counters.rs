@@ -0,0 +1,250 @@
+use crate::import_objc_macros::*;
+use crate::misc::get_label;
+use crate::{
+    handle, DeviceCreated, Labeled, MTLDevice, MTLStorageMode, NSArray, NSError, NSRange, NSString,
+    NSUInteger, NSUIntegerRange, Object, ObjectPointer,
+};
+
+/// The counter sets which Metal names; a device may provide any of them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MTLCommonCounterSet {
+    Timestamp,
+    StageUtilization,
+    Statistic,
+}
+
+impl MTLCommonCounterSet {
+    /// The name of the set, as returned by `MTLCounterSet::get_name`.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            MTLCommonCounterSet::Timestamp => "timestamp",
+            MTLCommonCounterSet::StageUtilization => "stageutilization",
+            MTLCommonCounterSet::Statistic => "statistic",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum MTLCounterSamplingPoint {
+    AtStageBoundary = 0,
+    AtDrawBoundary = 1,
+    AtDispatchBoundary = 2,
+    AtTileDispatchBoundary = 3,
+    AtBlitBoundary = 4,
+}
+
+/// The layout of each sample resolved from a buffer of the timestamp counter set.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct MTLCounterResultTimestamp {
+    pub timestamp: u64,
+}
+
+pub struct MTLCounter(ObjectPointer);
+handle!(MTLCounter);
+
+impl MTLCounter {
+    pub unsafe fn get_name(&self) -> String {
+        NSString::copy_from(ObjectPointer(msg_send![self.get_ptr(), name])).unwrap_or_default()
+    }
+}
+
+impl Object for MTLCounter {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLCounter(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLCounterSet(ObjectPointer);
+handle!(MTLCounterSet);
+
+impl MTLCounterSet {
+    pub unsafe fn get_name(&self) -> String {
+        NSString::copy_from(ObjectPointer(msg_send![self.get_ptr(), name])).unwrap_or_default()
+    }
+    pub unsafe fn get_counters(&self) -> Vec<MTLCounter> {
+        NSArray::copy_from(ObjectPointer(msg_send![self.get_ptr(), counters]))
+    }
+}
+
+impl Object for MTLCounterSet {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLCounterSet(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLCounterSampleBufferDescriptor(ObjectPointer);
+handle!(MTLCounterSampleBufferDescriptor);
+
+impl MTLCounterSampleBufferDescriptor {
+    pub unsafe fn new() -> MTLCounterSampleBufferDescriptor {
+        MTLCounterSampleBufferDescriptor::from_ptr(msg_send![
+            class!(MTLCounterSampleBufferDescriptor),
+            new
+        ])
+    }
+    pub unsafe fn set_counter_set(&self, counter_set: &MTLCounterSet) {
+        msg_send![self.get_ptr(), setCounterSet:counter_set.get_ptr()]
+    }
+    pub unsafe fn get_counter_set(&self) -> Option<MTLCounterSet> {
+        let counter_set = ObjectPointer(msg_send![self.get_ptr(), counterSet]);
+        if counter_set.0.is_null() {
+            None
+        } else {
+            Some(MTLCounterSet::from_ptr(msg_send![counter_set, retain]))
+        }
+    }
+    /// Only `Shared` and `Private` are supported.
+    pub unsafe fn set_storage_mode(&self, mode: MTLStorageMode) {
+        msg_send![self.get_ptr(), setStorageMode: mode]
+    }
+    pub unsafe fn get_storage_mode(&self) -> MTLStorageMode {
+        msg_send![self.get_ptr(), storageMode]
+    }
+    pub unsafe fn set_sample_count(&self, count: NSUInteger) {
+        msg_send![self.get_ptr(), setSampleCount: count]
+    }
+    pub unsafe fn get_sample_count(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), sampleCount]
+    }
+}
+
+impl Object for MTLCounterSampleBufferDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLCounterSampleBufferDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+impl Labeled for MTLCounterSampleBufferDescriptor {}
+
+pub struct MTLCounterSampleBuffer(ObjectPointer);
+handle!(MTLCounterSampleBuffer);
+
+impl MTLCounterSampleBuffer {
+    /// The sample index which tells a pass descriptor not to sample at a stage boundary.
+    pub const DONT_SAMPLE: NSUInteger = NSUInteger::MAX;
+    /// The value of a resolved sample which the GPU failed to take.
+    pub const ERROR_VALUE: u64 = u64::MAX;
+
+    pub unsafe fn get_label(&self) -> Option<String> {
+        get_label(self.get_ptr())
+    }
+    pub unsafe fn get_sample_count(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), sampleCount]
+    }
+    /// Copies out the samples in the range, laid out as the results of the buffer's
+    /// counter set, or returns None if they could not be resolved.
+    ///
+    /// The samples must no longer be written to by any command buffer.
+    pub unsafe fn resolve_counter_range(&self, range: NSUIntegerRange) -> Option<Vec<u8>> {
+        let range: NSRange = range.into();
+        let data = ObjectPointer(msg_send![self.get_ptr(), resolveCounterRange: range]);
+        if data.0.is_null() {
+            return None;
+        }
+        let bytes: *const u8 = msg_send![data, bytes];
+        let length: NSUInteger = msg_send![data, length];
+        if length == 0 {
+            return Some(Vec::new());
+        }
+        Some(std::slice::from_raw_parts(bytes, length as usize).to_vec())
+    }
+    /// Resolves the samples in the range of a buffer of the timestamp counter set.
+    ///
+    /// Samples which the GPU failed to take are `ERROR_VALUE`.
+    pub unsafe fn resolve_timestamps(&self, range: NSUIntegerRange) -> Option<Vec<u64>> {
+        let size = std::mem::size_of::<MTLCounterResultTimestamp>();
+        let data = self.resolve_counter_range(range)?;
+        Some(
+            data.chunks_exact(size)
+                .map(|sample| {
+                    let mut timestamp = [0u8; 8];
+                    timestamp.copy_from_slice(sample);
+                    u64::from_ne_bytes(timestamp)
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Object for MTLCounterSampleBuffer {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLCounterSampleBuffer(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+impl DeviceCreated for MTLCounterSampleBuffer {}
+
+impl MTLDevice {
+    pub unsafe fn get_counter_sets(&self) -> Vec<MTLCounterSet> {
+        NSArray::copy_from(ObjectPointer(msg_send![self.get_ptr(), counterSets]))
+    }
+    /// Returns the counter set of the given name, if the device provides it.
+    pub unsafe fn get_common_counter_set(&self, set: MTLCommonCounterSet) -> Option<MTLCounterSet> {
+        self.get_counter_sets()
+            .into_iter()
+            .find(|counter_set| counter_set.get_name() == set.get_name())
+    }
+    pub unsafe fn supports_counter_sampling(&self, point: MTLCounterSamplingPoint) -> bool {
+        msg_send![self.get_ptr(), supportsCounterSampling: point]
+    }
+    pub unsafe fn new_counter_sample_buffer_with_descriptor(
+        &self,
+        desc: &MTLCounterSampleBufferDescriptor,
+    ) -> Result<MTLCounterSampleBuffer, NSError> {
+        let mut err = ObjectPointer(std::ptr::null_mut());
+        let b = ObjectPointer(msg_send![
+            self.get_ptr(),
+            newCounterSampleBufferWithDescriptor:desc.get_ptr()
+            error:&mut err
+        ]);
+        if !err.0.is_null() {
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        } else {
+            Ok(MTLCounterSampleBuffer::from_ptr(b))
+        }
+    }
+    /// Samples the CPU and GPU clocks at the same moment, returning the CPU timestamp in
+    /// nanoseconds and the GPU timestamp.
+    ///
+    /// Two such pairs correlate GPU timestamps with CPU time; see `TimestampCorrelation`.
+    pub unsafe fn sample_timestamps(&self) -> (u64, u64) {
+        let mut cpu = 0u64;
+        let mut gpu = 0u64;
+        let _: () = msg_send![
+            self.get_ptr(),
+            sampleTimestamps:&mut cpu as *mut u64
+            gpuTimestamp:&mut gpu as *mut u64
+        ];
+        (cpu, gpu)
+    }
+}
@@ -1,6 +1,11 @@
 use crate::import_objc_macros::*;
-use crate::{handle, DeviceCreated, MTLCompareFunction, Object, ObjectPointer};
+use crate::misc::get_label;
+use crate::{
+    handle, DeviceCreated, Labeled, MTLCompareFunction, MTLPixelFormat, Object, ObjectPointer,
+};
+use std::fmt::{Display, Formatter};
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLStencilOperation {
     Keep = 0,
@@ -23,18 +28,39 @@ impl MTLStencilDescriptor {
     pub unsafe fn set_stencil_fail_operation(&self, operation: MTLStencilOperation) {
         msg_send![self.get_ptr(), setStencilFailureOperation: operation]
     }
+    pub unsafe fn get_stencil_fail_operation(&self) -> MTLStencilOperation {
+        msg_send![self.get_ptr(), stencilFailureOperation]
+    }
     pub unsafe fn set_depth_fail_operation(&self, operation: MTLStencilOperation) {
         msg_send![self.get_ptr(), setDepthFailureOperation: operation]
     }
+    pub unsafe fn get_depth_fail_operation(&self) -> MTLStencilOperation {
+        msg_send![self.get_ptr(), depthFailureOperation]
+    }
     pub unsafe fn set_depth_stencil_pass_operation(&self, operation: MTLStencilOperation) {
         msg_send![self.get_ptr(), setDepthStencilPassOperation: operation]
     }
+    pub unsafe fn get_depth_stencil_pass_operation(&self) -> MTLStencilOperation {
+        msg_send![self.get_ptr(), depthStencilPassOperation]
+    }
+    pub unsafe fn set_stencil_compare_function(&self, function: MTLCompareFunction) {
+        msg_send![self.get_ptr(), setStencilCompareFunction: function]
+    }
+    pub unsafe fn get_stencil_compare_function(&self) -> MTLCompareFunction {
+        msg_send![self.get_ptr(), stencilCompareFunction]
+    }
     pub unsafe fn set_read_mask(&self, mask: u32) {
         msg_send![self.get_ptr(), setReadMask: mask]
     }
+    pub unsafe fn get_read_mask(&self) -> u32 {
+        msg_send![self.get_ptr(), readMask]
+    }
     pub unsafe fn set_write_mask(&self, mask: u32) {
         msg_send![self.get_ptr(), setWriteMask: mask]
     }
+    pub unsafe fn get_write_mask(&self) -> u32 {
+        msg_send![self.get_ptr(), writeMask]
+    }
 }
 
 impl Object for MTLStencilDescriptor {
@@ -60,15 +86,33 @@ impl MTLDepthStencilDescriptor {
     pub unsafe fn set_depth_compare_function(&self, function: MTLCompareFunction) {
         msg_send![self.get_ptr(), setDepthCompareFunction: function]
     }
+    pub unsafe fn get_depth_compare_function(&self) -> MTLCompareFunction {
+        msg_send![self.get_ptr(), depthCompareFunction]
+    }
     pub unsafe fn set_depth_write_enabled(&self, enabled: bool) {
         msg_send![self.get_ptr(), setDepthWriteEnabled: enabled]
     }
+    pub unsafe fn is_depth_write_enabled(&self) -> bool {
+        msg_send![self.get_ptr(), isDepthWriteEnabled]
+    }
     pub unsafe fn set_back_face_stencil(&self, stencil: &MTLStencilDescriptor) {
         msg_send![self.get_ptr(), setBackFaceStencil:stencil.get_ptr()]
     }
+    pub unsafe fn get_back_face_stencil(&self) -> MTLStencilDescriptor {
+        MTLStencilDescriptor::from_ptr({
+            let ptr = ObjectPointer(msg_send![self.get_ptr(), backFaceStencil]);
+            msg_send![ptr, retain]
+        })
+    }
     pub unsafe fn set_front_face_stencil(&self, stencil: &MTLStencilDescriptor) {
         msg_send![self.get_ptr(), setFrontFaceStencil:stencil.get_ptr()]
     }
+    pub unsafe fn get_front_face_stencil(&self) -> MTLStencilDescriptor {
+        MTLStencilDescriptor::from_ptr({
+            let ptr = ObjectPointer(msg_send![self.get_ptr(), frontFaceStencil]);
+            msg_send![ptr, retain]
+        })
+    }
 }
 
 impl Object for MTLDepthStencilDescriptor {
@@ -84,8 +128,18 @@ impl Object for MTLDepthStencilDescriptor {
     }
 }
 
+impl Labeled for MTLDepthStencilDescriptor {}
+
 pub struct MTLDepthStencilState(ObjectPointer);
 handle!(MTLDepthStencilState);
+unsafe impl Send for MTLDepthStencilState {}
+unsafe impl Sync for MTLDepthStencilState {}
+
+impl MTLDepthStencilState {
+    pub unsafe fn get_label(&self) -> Option<String> {
+        get_label(self.get_ptr())
+    }
+}
 
 impl Object for MTLDepthStencilState {
     unsafe fn from_ptr(ptr: ObjectPointer) -> Self
@@ -101,3 +155,299 @@ impl Object for MTLDepthStencilState {
 }
 
 impl DeviceCreated for MTLDepthStencilState {}
+
+/// A plain description of how stencil values of one face are tested and updated.
+///
+/// The default matches the default `MTLStencilDescriptor`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StencilDesc {
+    pub stencil_compare_function: MTLCompareFunction,
+    pub stencil_fail_operation: MTLStencilOperation,
+    pub depth_fail_operation: MTLStencilOperation,
+    pub depth_stencil_pass_operation: MTLStencilOperation,
+    pub read_mask: u32,
+    pub write_mask: u32,
+}
+
+impl Default for StencilDesc {
+    fn default() -> Self {
+        StencilDesc {
+            stencil_compare_function: MTLCompareFunction::Always,
+            stencil_fail_operation: MTLStencilOperation::Keep,
+            depth_fail_operation: MTLStencilOperation::Keep,
+            depth_stencil_pass_operation: MTLStencilOperation::Keep,
+            read_mask: 0xFFFF_FFFF,
+            write_mask: 0xFFFF_FFFF,
+        }
+    }
+}
+
+impl StencilDesc {
+    /// Whether every texel passes the stencil test and keeps its stencil value.
+    pub fn is_noop(&self) -> bool {
+        self.stencil_compare_function == MTLCompareFunction::Always
+            && self.stencil_fail_operation == MTLStencilOperation::Keep
+            && self.depth_fail_operation == MTLStencilOperation::Keep
+            && self.depth_stencil_pass_operation == MTLStencilOperation::Keep
+    }
+    pub unsafe fn to_descriptor(&self) -> MTLStencilDescriptor {
+        let desc = MTLStencilDescriptor::new();
+        desc.set_stencil_compare_function(self.stencil_compare_function);
+        desc.set_stencil_fail_operation(self.stencil_fail_operation);
+        desc.set_depth_fail_operation(self.depth_fail_operation);
+        desc.set_depth_stencil_pass_operation(self.depth_stencil_pass_operation);
+        desc.set_read_mask(self.read_mask);
+        desc.set_write_mask(self.write_mask);
+        desc
+    }
+}
+
+/// A plain description of a depth stencil state, which can be compared and hashed to
+/// reuse depth stencil states.
+///
+/// The default matches the default `MTLDepthStencilDescriptor`, which neither tests nor
+/// writes depth and leaves stencil testing disabled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DepthStencilDesc {
+    pub depth_compare_function: MTLCompareFunction,
+    pub depth_write_enabled: bool,
+    /// Stencil testing is disabled for faces without a stencil description.
+    pub front_face_stencil: Option<StencilDesc>,
+    pub back_face_stencil: Option<StencilDesc>,
+}
+
+impl Default for DepthStencilDesc {
+    fn default() -> Self {
+        DepthStencilDesc {
+            depth_compare_function: MTLCompareFunction::Always,
+            depth_write_enabled: false,
+            front_face_stencil: None,
+            back_face_stencil: None,
+        }
+    }
+}
+
+impl DepthStencilDesc {
+    /// Keeps the nearest fragments, writing their depth.
+    pub fn depth_less() -> DepthStencilDesc {
+        DepthStencilDesc {
+            depth_compare_function: MTLCompareFunction::Less,
+            depth_write_enabled: true,
+            ..Default::default()
+        }
+    }
+    /// Keeps the nearest fragments with a reversed depth range, where the near plane has
+    /// a depth of 1, writing their depth.
+    pub fn reverse_z() -> DepthStencilDesc {
+        DepthStencilDesc {
+            depth_compare_function: MTLCompareFunction::Greater,
+            depth_write_enabled: true,
+            ..Default::default()
+        }
+    }
+    /// Tests fragments against existing depth without writing it, as for transparent
+    /// geometry.
+    pub fn depth_read_only() -> DepthStencilDesc {
+        DepthStencilDesc {
+            depth_compare_function: MTLCompareFunction::LessEqual,
+            depth_write_enabled: false,
+            ..Default::default()
+        }
+    }
+    /// Writes the stencil reference value wherever fragments pass the depth test.
+    pub fn stencil_mask() -> DepthStencilDesc {
+        let stencil = StencilDesc {
+            depth_stencil_pass_operation: MTLStencilOperation::Replace,
+            ..Default::default()
+        };
+        DepthStencilDesc {
+            depth_compare_function: MTLCompareFunction::Less,
+            depth_write_enabled: true,
+            front_face_stencil: Some(stencil),
+            back_face_stencil: Some(stencil),
+        }
+    }
+    /// Only draws where the stencil value differs from the reference value, as for an
+    /// outline around geometry drawn with `stencil_mask`.
+    pub fn stencil_outline() -> DepthStencilDesc {
+        let stencil = StencilDesc {
+            stencil_compare_function: MTLCompareFunction::NotEqual,
+            write_mask: 0,
+            ..Default::default()
+        };
+        DepthStencilDesc {
+            depth_compare_function: MTLCompareFunction::Always,
+            depth_write_enabled: false,
+            front_face_stencil: Some(stencil),
+            back_face_stencil: Some(stencil),
+        }
+    }
+    /// Checks that the depth and stencil tests have an attachment to operate on, given the
+    /// depth and stencil attachment pixel formats of the render pipeline, either of which
+    /// may be `MTLPixelFormat::Invalid`.
+    pub fn validate(
+        &self,
+        depth_format: MTLPixelFormat,
+        stencil_format: MTLPixelFormat,
+    ) -> Result<(), DepthStencilDescError> {
+        let uses_depth =
+            self.depth_write_enabled || self.depth_compare_function != MTLCompareFunction::Always;
+        if uses_depth && !depth_format.is_depth() {
+            return Err(DepthStencilDescError::NoDepthAttachment);
+        }
+        let uses_stencil = |stencil: &Option<StencilDesc>| match stencil {
+            Some(stencil) => !stencil.is_noop(),
+            None => false,
+        };
+        if (uses_stencil(&self.front_face_stencil) || uses_stencil(&self.back_face_stencil))
+            && !stencil_format.is_stencil()
+        {
+            return Err(DepthStencilDescError::NoStencilAttachment);
+        }
+        Ok(())
+    }
+    pub unsafe fn to_descriptor(&self) -> MTLDepthStencilDescriptor {
+        let desc = MTLDepthStencilDescriptor::new();
+        desc.set_depth_compare_function(self.depth_compare_function);
+        desc.set_depth_write_enabled(self.depth_write_enabled);
+        if let Some(stencil) = &self.front_face_stencil {
+            desc.set_front_face_stencil(&stencil.to_descriptor());
+        }
+        if let Some(stencil) = &self.back_face_stencil {
+            desc.set_back_face_stencil(&stencil.to_descriptor());
+        }
+        desc
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DepthStencilDescError {
+    /// The depth test or depth writes are enabled without a depth attachment.
+    NoDepthAttachment,
+    /// Stencil operations are set without a stencil attachment.
+    NoStencilAttachment,
+}
+
+impl Display for DepthStencilDescError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DepthStencilDescError::NoDepthAttachment => {
+                f.write_str("depth testing requires a depth attachment")
+            }
+            DepthStencilDescError::NoStencilAttachment => {
+                f.write_str("stencil operations require a stencil attachment")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DepthStencilDescError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use MTLPixelFormat::{Depth32Float, Depth32Float_Stencil8, Invalid, Stencil8};
+
+    #[test]
+    fn validate_depth() {
+        assert_eq!(
+            DepthStencilDesc::default().validate(Invalid, Invalid),
+            Ok(())
+        );
+        for desc in &[
+            DepthStencilDesc::depth_less(),
+            DepthStencilDesc::reverse_z(),
+            DepthStencilDesc::depth_read_only(),
+        ] {
+            assert_eq!(
+                desc.validate(Invalid, Invalid),
+                Err(DepthStencilDescError::NoDepthAttachment)
+            );
+            assert_eq!(desc.validate(Depth32Float, Invalid), Ok(()));
+            // a stencil only format has no depth
+            assert_eq!(
+                desc.validate(Stencil8, Stencil8),
+                Err(DepthStencilDescError::NoDepthAttachment)
+            );
+        }
+        // writing without testing still needs depth
+        let desc = DepthStencilDesc {
+            depth_write_enabled: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            desc.validate(Invalid, Invalid),
+            Err(DepthStencilDescError::NoDepthAttachment)
+        );
+    }
+
+    #[test]
+    fn validate_stencil() {
+        assert_eq!(
+            DepthStencilDesc::stencil_mask().validate(Depth32Float, Invalid),
+            Err(DepthStencilDescError::NoStencilAttachment)
+        );
+        assert_eq!(
+            DepthStencilDesc::stencil_mask().validate(Depth32Float_Stencil8, Depth32Float_Stencil8),
+            Ok(())
+        );
+        assert_eq!(
+            DepthStencilDesc::stencil_outline().validate(Invalid, Stencil8),
+            Ok(())
+        );
+        // a stencil description which keeps every value does not need an attachment
+        let desc = DepthStencilDesc {
+            front_face_stencil: Some(StencilDesc::default()),
+            ..Default::default()
+        };
+        assert_eq!(desc.validate(Invalid, Invalid), Ok(()));
+        // either face is enough to need one
+        let desc = DepthStencilDesc {
+            back_face_stencil: DepthStencilDesc::stencil_outline().back_face_stencil,
+            ..Default::default()
+        };
+        assert_eq!(
+            desc.validate(Invalid, Invalid),
+            Err(DepthStencilDescError::NoStencilAttachment)
+        );
+    }
+
+    #[test]
+    fn presets() {
+        let default = DepthStencilDesc::default();
+        assert_eq!(default.depth_compare_function, MTLCompareFunction::Always);
+        assert!(!default.depth_write_enabled);
+        assert!(StencilDesc::default().is_noop());
+        assert!(!StencilDesc {
+            write_mask: 0,
+            stencil_compare_function: MTLCompareFunction::Never,
+            ..Default::default()
+        }
+        .is_noop());
+
+        assert_eq!(
+            DepthStencilDesc::reverse_z().depth_compare_function,
+            MTLCompareFunction::Greater
+        );
+        assert!(!DepthStencilDesc::depth_read_only().depth_write_enabled);
+        assert_ne!(
+            DepthStencilDesc::depth_less(),
+            DepthStencilDesc::depth_read_only()
+        );
+        let mask = DepthStencilDesc::stencil_mask();
+        assert_eq!(mask.front_face_stencil, mask.back_face_stencil);
+        assert_eq!(
+            mask.front_face_stencil
+                .unwrap()
+                .depth_stencil_pass_operation,
+            MTLStencilOperation::Replace
+        );
+        assert_eq!(
+            DepthStencilDesc::stencil_outline()
+                .front_face_stencil
+                .unwrap()
+                .write_mask,
+            0
+        );
+    }
+}
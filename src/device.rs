@@ -1,23 +1,48 @@
 use crate::import_objc_macros::*;
+#[cfg(target_os = "macos")]
+use crate::NSArray;
 use crate::{
-    handle, MTLBuffer, MTLCommandQueue, MTLCompileOptions, MTLComputePipelineState,
-    MTLDepthStencilDescriptor, MTLDepthStencilState, MTLFunction, MTLLibrary,
-    MTLRenderPipelineDescriptor, MTLRenderPipelineState, MTLResourceOptions, MTLSamplePosition,
-    MTLSamplerDescriptor, MTLSamplerState, MTLSize, MTLTexture, MTLTextureDescriptor, NSError,
-    NSUInteger, Object, ObjectPointer,
+    handle, LinearTextureLayout, MTLBinaryArchive, MTLBinaryArchiveDescriptor, MTLBuffer,
+    MTLCommandQueue, MTLCompileOptions, MTLComputePipelineDescriptor, MTLComputePipelineState,
+    MTLDepthStencilDescriptor, MTLDepthStencilState, MTLFunction, MTLLibrary, MTLLibraryError,
+    MTLPixelFormat, MTLRenderPipelineDescriptor, MTLRenderPipelineState, MTLResourceOptions,
+    MTLSamplePosition, MTLSamplerDescriptor, MTLSamplerState, MTLSize, MTLTexture,
+    MTLTextureDescriptor, MTLTextureType, NSBundle, NSError, NSInteger, NSString, NSUInteger,
+    Object, ObjectPointer, MTL_LIBRARY_ERROR_DOMAIN, NSURL,
 };
+use block::{ConcreteBlock, RcBlock};
 use std::os::raw::c_void;
+use std::path::Path;
 
 mod externs {
     use crate::ObjectPointer;
     use std::os::raw::c_void;
 
-    #[link(name = "Metal", kind = "framework")]
+    #[cfg_attr(
+        any(target_os = "macos", target_os = "ios"),
+        link(name = "Metal", kind = "framework")
+    )]
     extern "C" {
         pub fn MTLCreateSystemDefaultDevice() -> ObjectPointer;
+    }
+    #[cfg(target_os = "macos")]
+    #[link(name = "Metal", kind = "framework")]
+    extern "C" {
         pub fn MTLCopyAllDevices() -> ObjectPointer;
+        pub fn MTLCopyAllDevicesWithObserver(
+            observer: *mut ObjectPointer,
+            handler: *const c_void,
+        ) -> ObjectPointer;
+        pub fn MTLRemoveDeviceObserver(observer: ObjectPointer);
+
+        pub static MTLDeviceWasAddedNotification: ObjectPointer;
+        pub static MTLDeviceRemovalRequestedNotification: ObjectPointer;
+        pub static MTLDeviceWasRemovedNotification: ObjectPointer;
     }
-    #[link(name = "CoreGraphics", kind = "framework")]
+    #[cfg_attr(
+        any(target_os = "macos", target_os = "ios"),
+        link(name = "CoreGraphics", kind = "framework")
+    )]
     extern "C" {
         pub fn CGDirectDisplayCopyCurrentMetalDevice(display_id: u32) -> ObjectPointer;
     }
@@ -46,54 +71,242 @@ mod externs {
 
 #[allow(non_snake_case)]
 pub unsafe fn MTLCreateSystemDefaultDevice() -> MTLDevice {
-    MTLDevice::from_ptr({
-        let obj = externs::MTLCreateSystemDefaultDevice();
-        msg_send![obj, retain]
-    })
+    MTLDevice::from_ptr(externs::MTLCreateSystemDefaultDevice())
 }
 
 #[allow(non_snake_case)]
 /// Does not function exactly as the `MTLCopyAllDevices` Metal function. Will, if the device
 /// running this function is iOS, instead create the system default device and put it
-/// into a vector.
+/// into a vector, and on other platforms, return an empty vector.
 pub unsafe fn MTLCopyAllDevices() -> Vec<MTLDevice> {
     #[cfg(target_os = "macos")]
     {
-        let devices = externs::MTLCopyAllDevices();
-        let length: NSUInteger = msg_send![devices, count];
-        (0..length)
-            .map(|index| {
-                let obj = ObjectPointer(msg_send![devices, objectAtIndex: index]);
-                MTLDevice::from_ptr(msg_send![obj, retain])
-            })
-            .collect()
+        NSArray::<MTLDevice>::from_ptr(externs::MTLCopyAllDevices()).to_vec()
     }
     #[cfg(target_os = "ios")]
     {
         vec![MTLCreateSystemDefaultDevice()]
     }
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    {
+        Vec::new()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MTLDeviceNotification {
+    WasAdded,
+    RemovalRequested,
+    WasRemoved,
+}
+
+/// Delivers device notifications to its handler until dropped.
+pub struct MTLDeviceObserver {
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    observer: ObjectPointer,
+    _handler: RcBlock<(ObjectPointer, ObjectPointer), ()>,
+}
+
+impl Drop for MTLDeviceObserver {
+    fn drop(&mut self) {
+        #[cfg(target_os = "macos")]
+        unsafe {
+            if !self.observer.0.is_null() {
+                externs::MTLRemoveDeviceObserver(self.observer);
+            }
+        }
+    }
+}
+
+#[allow(non_snake_case)]
+/// Copies every device like `MTLCopyAllDevices`, and calls the handler whenever a device
+/// is added, requested to be removed, or removed, until the returned observer is dropped.
+///
+/// The handler may be called on any thread, and concurrently with itself.
+///
+/// Will, if the device running this function is iOS, instead return the system default
+/// device and an observer which never calls the handler, and on other platforms, no devices.
+pub unsafe fn MTLCopyAllDevicesWithObserver<F>(handler: F) -> (Vec<MTLDevice>, MTLDeviceObserver)
+where
+    F: Fn(MTLDevice, MTLDeviceNotification) + Send + Sync + 'static,
+{
+    let block = ConcreteBlock::new(move |device: ObjectPointer, name: ObjectPointer| {
+        #[cfg(target_os = "macos")]
+        {
+            use externs::*;
+
+            let is = |notification: ObjectPointer| -> bool {
+                name.0 == notification.0 || msg_send![name, isEqualToString: notification]
+            };
+            let notification = if is(MTLDeviceWasAddedNotification) {
+                MTLDeviceNotification::WasAdded
+            } else if is(MTLDeviceRemovalRequestedNotification) {
+                MTLDeviceNotification::RemovalRequested
+            } else if is(MTLDeviceWasRemovedNotification) {
+                MTLDeviceNotification::WasRemoved
+            } else {
+                return;
+            };
+            handler(MTLDevice::from_ptr(msg_send![device, retain]), notification);
+        }
+        #[cfg(not(target_os = "macos"))]
+        {
+            let _ = (device, name, &handler);
+        }
+    })
+    .copy();
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut observer = ObjectPointer(std::ptr::null_mut());
+        let devices = externs::MTLCopyAllDevicesWithObserver(
+            &mut observer,
+            &*block as *const _ as *const c_void,
+        );
+        let list = NSArray::<MTLDevice>::from_ptr(devices).to_vec();
+        (
+            list,
+            MTLDeviceObserver {
+                observer,
+                _handler: block,
+            },
+        )
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        #[cfg(target_os = "ios")]
+        let devices = vec![MTLCreateSystemDefaultDevice()];
+        #[cfg(not(target_os = "ios"))]
+        let devices = Vec::new();
+        (
+            devices,
+            MTLDeviceObserver {
+                observer: ObjectPointer(std::ptr::null_mut()),
+                _handler: block,
+            },
+        )
+    }
 }
 
 #[allow(non_snake_case)]
 /// If the provided display id is not the valid id of a monitor on this device,
 /// the program will stall.
 pub unsafe fn CGDirectDisplayCopyCurrentMetalDevice(display_id: u32) -> MTLDevice {
-    MTLDevice::from_ptr({
-        let obj = externs::CGDirectDisplayCopyCurrentMetalDevice(display_id);
-        msg_send![obj, retain]
-    })
+    MTLDevice::from_ptr(externs::CGDirectDisplayCopyCurrentMetalDevice(display_id))
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(i64)]
+pub enum MTLGPUFamily {
+    Apple1 = 1001,
+    Apple2 = 1002,
+    Apple3 = 1003,
+    Apple4 = 1004,
+    Apple5 = 1005,
+    Apple6 = 1006,
+    Apple7 = 1007,
+    Apple8 = 1008,
+    Apple9 = 1009,
+    Mac1 = 2001,
+    Mac2 = 2002,
+    Common1 = 3001,
+    Common2 = 3002,
+    Common3 = 3003,
+    MacCatalyst1 = 4001,
+    MacCatalyst2 = 4002,
+    Metal3 = 5001,
+}
+
+impl MTLGPUFamily {
+    /// Every family, in the order of their raw values.
+    pub const ALL: [MTLGPUFamily; 17] = [
+        MTLGPUFamily::Apple1,
+        MTLGPUFamily::Apple2,
+        MTLGPUFamily::Apple3,
+        MTLGPUFamily::Apple4,
+        MTLGPUFamily::Apple5,
+        MTLGPUFamily::Apple6,
+        MTLGPUFamily::Apple7,
+        MTLGPUFamily::Apple8,
+        MTLGPUFamily::Apple9,
+        MTLGPUFamily::Mac1,
+        MTLGPUFamily::Mac2,
+        MTLGPUFamily::Common1,
+        MTLGPUFamily::Common2,
+        MTLGPUFamily::Common3,
+        MTLGPUFamily::MacCatalyst1,
+        MTLGPUFamily::MacCatalyst2,
+        MTLGPUFamily::Metal3,
+    ];
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum MTLFeatureSet {
+    iOS_GPUFamily1_v1 = 0,
+    iOS_GPUFamily2_v1 = 1,
+    iOS_GPUFamily1_v2 = 2,
+    iOS_GPUFamily2_v2 = 3,
+    iOS_GPUFamily3_v1 = 4,
+    iOS_GPUFamily1_v3 = 5,
+    iOS_GPUFamily2_v3 = 6,
+    iOS_GPUFamily3_v2 = 7,
+    iOS_GPUFamily1_v4 = 8,
+    iOS_GPUFamily2_v4 = 9,
+    iOS_GPUFamily3_v3 = 10,
+    iOS_GPUFamily4_v1 = 11,
+    iOS_GPUFamily1_v5 = 12,
+    iOS_GPUFamily2_v5 = 13,
+    iOS_GPUFamily3_v4 = 14,
+    iOS_GPUFamily4_v2 = 15,
+    iOS_GPUFamily5_v1 = 16,
+    macOS_GPUFamily1_v1 = 10000,
+    macOS_GPUFamily1_v2 = 10001,
+    macOS_ReadWriteTextureTier2 = 10002,
+    macOS_GPUFamily1_v3 = 10003,
+    macOS_GPUFamily1_v4 = 10004,
+    macOS_GPUFamily2_v1 = 10005,
+    tvOS_GPUFamily1_v1 = 30000,
+    tvOS_GPUFamily1_v2 = 30001,
+    tvOS_GPUFamily1_v3 = 30002,
+    tvOS_GPUFamily2_v1 = 30003,
+    tvOS_GPUFamily1_v4 = 30004,
+    tvOS_GPUFamily2_v2 = 30005,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u64)]
+pub enum MTLReadWriteTextureTier {
+    None = 0,
+    Tier1 = 1,
+    Tier2 = 2,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u64)]
+pub enum MTLArgumentBuffersTier {
+    Tier1 = 0,
+    Tier2 = 1,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum MTLDeviceLocation {
+    BuiltIn = 0,
+    Slot = 1,
+    External = 2,
+    Unspecified = u64::MAX,
 }
 
 pub struct MTLDevice(ObjectPointer);
 handle!(MTLDevice);
+unsafe impl Send for MTLDevice {}
+unsafe impl Sync for MTLDevice {}
 
 impl MTLDevice {
-    pub unsafe fn get_name(&self) -> &str {
-        let string = ObjectPointer(msg_send![self.get_ptr(), name]);
-        let bytes: *const u8 = msg_send![string, UTF8String];
-        let len: NSUInteger = msg_send![string, length];
-        let bytes = std::slice::from_raw_parts(bytes, len as usize);
-        std::str::from_utf8(bytes).unwrap()
+    pub unsafe fn get_name(&self) -> String {
+        NSString::copy_from(ObjectPointer(msg_send![self.get_ptr(), name])).unwrap_or_default()
     }
     pub unsafe fn is_headless(&self) -> bool {
         msg_send![self.get_ptr(), isHeadless]
@@ -133,23 +346,96 @@ impl MTLDevice {
     pub unsafe fn is_d24_s8_pixel_format_supported(&self) -> bool {
         msg_send![self.get_ptr(), isDepth24Stencil8PixelFormatSupported]
     }
+    pub unsafe fn supports_family(&self, family: MTLGPUFamily) -> bool {
+        msg_send![self.get_ptr(), supportsFamily: family]
+    }
+    pub unsafe fn supports_feature_set(&self, feature_set: MTLFeatureSet) -> bool {
+        msg_send![self.get_ptr(), supportsFeatureSet: feature_set]
+    }
+    pub unsafe fn get_read_write_texture_support(&self) -> MTLReadWriteTextureTier {
+        msg_send![self.get_ptr(), readWriteTextureSupport]
+    }
+    pub unsafe fn get_argument_buffers_support(&self) -> MTLArgumentBuffersTier {
+        msg_send![self.get_ptr(), argumentBuffersSupport]
+    }
+    pub unsafe fn get_max_argument_buffer_sampler_count(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), maxArgumentBufferSamplerCount]
+    }
+    pub unsafe fn has_unified_memory(&self) -> bool {
+        msg_send![self.get_ptr(), hasUnifiedMemory]
+    }
+    pub unsafe fn get_max_transfer_rate(&self) -> u64 {
+        msg_send![self.get_ptr(), maxTransferRate]
+    }
+    pub unsafe fn get_location(&self) -> MTLDeviceLocation {
+        msg_send![self.get_ptr(), location]
+    }
+    pub unsafe fn get_location_number(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), locationNumber]
+    }
+    pub unsafe fn get_peer_group_id(&self) -> u64 {
+        msg_send![self.get_ptr(), peerGroupID]
+    }
+    pub unsafe fn get_peer_index(&self) -> u32 {
+        msg_send![self.get_ptr(), peerIndex]
+    }
+    pub unsafe fn get_peer_count(&self) -> u32 {
+        msg_send![self.get_ptr(), peerCount]
+    }
+    pub unsafe fn get_sparse_tile_size_in_bytes(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), sparseTileSizeInBytes]
+    }
+    pub unsafe fn get_sparse_tile_size(
+        &self,
+        texture_type: MTLTextureType,
+        format: MTLPixelFormat,
+        sample_count: NSUInteger,
+    ) -> MTLSize {
+        msg_send![
+            self.get_ptr(),
+            sparseTileSizeWithTextureType:texture_type
+            pixelFormat:format
+            sampleCount:sample_count
+        ]
+    }
+    pub unsafe fn supports_raytracing(&self) -> bool {
+        msg_send![self.get_ptr(), supportsRaytracing]
+    }
+    pub unsafe fn supports_function_pointers(&self) -> bool {
+        msg_send![self.get_ptr(), supportsFunctionPointers]
+    }
+    pub unsafe fn supports_dynamic_libraries(&self) -> bool {
+        msg_send![self.get_ptr(), supportsDynamicLibraries]
+    }
+    pub unsafe fn supports_32bit_float_filtering(&self) -> bool {
+        msg_send![self.get_ptr(), supports32BitFloatFiltering]
+    }
+    pub unsafe fn supports_32bit_msaa(&self) -> bool {
+        msg_send![self.get_ptr(), supports32BitMSAA]
+    }
+    pub unsafe fn supports_bc_texture_compression(&self) -> bool {
+        msg_send![self.get_ptr(), supportsBCTextureCompression]
+    }
+    pub unsafe fn supports_query_texture_lod(&self) -> bool {
+        msg_send![self.get_ptr(), supportsQueryTextureLOD]
+    }
+    pub unsafe fn supports_pull_model_interpolation(&self) -> bool {
+        msg_send![self.get_ptr(), supportsPullModelInterpolation]
+    }
+    pub unsafe fn supports_shader_barycentric_coordinates(&self) -> bool {
+        msg_send![self.get_ptr(), supportsShaderBarycentricCoordinates]
+    }
     pub unsafe fn new_command_queue(&self) -> MTLCommandQueue {
-        MTLCommandQueue::from_ptr({
-            let k = ObjectPointer(msg_send![self.get_ptr(), newCommandQueue]);
-            msg_send![k, retain]
-        })
+        MTLCommandQueue::from_ptr(msg_send![self.get_ptr(), newCommandQueue])
     }
     pub unsafe fn new_command_queue_with_max_command_buffer_count(
         &self,
         count: NSUInteger,
     ) -> MTLCommandQueue {
-        MTLCommandQueue::from_ptr({
-            let k = ObjectPointer(msg_send![
-                self.get_ptr(),
-                newCommandQueueWithMaxCommandBufferCount: count
-            ]);
-            msg_send![k, retain]
-        })
+        MTLCommandQueue::from_ptr(msg_send![
+            self.get_ptr(),
+            newCommandQueueWithMaxCommandBufferCount: count
+        ])
     }
     pub unsafe fn new_library_with_data(&self, data: &[u8]) -> Result<MTLLibrary, NSError> {
         use externs::*;
@@ -167,7 +453,7 @@ impl MTLDevice {
         dispatch_release(dispatch_data);
 
         if !err.0.is_null() {
-            Err(NSError::from_ptr(err))
+            Err(NSError::from_ptr(msg_send![err, retain]))
         } else {
             Ok(MTLLibrary::from_ptr(lib))
         }
@@ -177,26 +463,108 @@ impl MTLDevice {
         source: &str,
         options: &MTLCompileOptions,
     ) -> Result<MTLLibrary, NSError> {
-        let cls = class!(NSString);
-        let bytes = source.as_ptr();
-        let st = ObjectPointer(msg_send![cls, alloc]);
-        let st = ObjectPointer(msg_send![
-           st,
-           initWithBytes:bytes
-           length:source.len()
-           encoding:4 // UTF-8
-        ]);
+        let source = NSString::new(source);
         let mut err = ObjectPointer(std::ptr::null_mut());
 
         let lib = ObjectPointer(msg_send![
             self.get_ptr(),
-            newLibraryWithSource:st
+            newLibraryWithSource:source.get_ptr()
             options:options.get_ptr()
             error:&mut err
         ]);
 
         if !err.0.is_null() {
-            Err(NSError::from_ptr(err))
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        } else {
+            Ok(MTLLibrary::from_ptr(lib))
+        }
+    }
+    /// Loads `default.metallib` from the app's main bundle.
+    pub unsafe fn new_default_library(&self) -> Result<MTLLibrary, NSError> {
+        let lib = ObjectPointer(msg_send![self.get_ptr(), newDefaultLibrary]);
+        if lib.0.is_null() {
+            Err(NSError::new(
+                MTL_LIBRARY_ERROR_DOMAIN,
+                MTLLibraryError::FileNotFound as NSInteger,
+                "default.metallib was not found in the main bundle",
+            ))
+        } else {
+            Ok(MTLLibrary::from_ptr(lib))
+        }
+    }
+    /// Loads `default.metallib` from the bundle.
+    pub unsafe fn new_default_library_with_bundle(
+        &self,
+        bundle: &NSBundle,
+    ) -> Result<MTLLibrary, NSError> {
+        let mut err = ObjectPointer(std::ptr::null_mut());
+
+        let lib = ObjectPointer(msg_send![
+            self.get_ptr(),
+            newDefaultLibraryWithBundle:bundle.get_ptr()
+            error:&mut err
+        ]);
+
+        if !err.0.is_null() {
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        } else {
+            Ok(MTLLibrary::from_ptr(lib))
+        }
+    }
+    /// Loads a `.metallib` file, resolving relative paths against the current directory.
+    pub unsafe fn new_library_with_url<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<MTLLibrary, NSError> {
+        let url = NSURL::from_path(path.as_ref());
+        let mut err = ObjectPointer(std::ptr::null_mut());
+
+        let lib = ObjectPointer(msg_send![
+            self.get_ptr(),
+            newLibraryWithURL:url.get_ptr()
+            error:&mut err
+        ]);
+
+        if !err.0.is_null() {
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        } else {
+            Ok(MTLLibrary::from_ptr(lib))
+        }
+    }
+    /// Loads a `.metallib` file, resolving relative paths against the current directory.
+    pub unsafe fn new_library_with_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<MTLLibrary, NSError> {
+        let path = path.as_ref();
+        let path = if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            match std::env::current_dir() {
+                Ok(dir) => dir.join(path),
+                Err(_) => {
+                    return Err(NSError::new(
+                        MTL_LIBRARY_ERROR_DOMAIN,
+                        MTLLibraryError::FileNotFound as NSInteger,
+                        &format!(
+                            "{} is relative and the current directory is unknown",
+                            path.display()
+                        ),
+                    ))
+                }
+            }
+        };
+        let path = NSString::new(&path.to_string_lossy());
+        let mut err = ObjectPointer(std::ptr::null_mut());
+
+        let lib = ObjectPointer(msg_send![
+            self.get_ptr(),
+            newLibraryWithFile:path.get_ptr()
+            error:&mut err
+        ]);
+
+        if !err.0.is_null() {
+            Err(NSError::from_ptr(msg_send![err, retain]))
         } else {
             Ok(MTLLibrary::from_ptr(lib))
         }
@@ -212,7 +580,7 @@ impl MTLDevice {
             error:&mut err
         ]);
         if !err.0.is_null() {
-            Err(NSError::from_ptr(err))
+            Err(NSError::from_ptr(msg_send![err, retain]))
         } else {
             Ok(MTLRenderPipelineState::from_ptr(b))
         }
@@ -230,7 +598,42 @@ impl MTLDevice {
         if err.0.is_null() {
             Ok(MTLComputePipelineState::from_ptr(b))
         } else {
-            Err(NSError::from_ptr(err))
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        }
+    }
+    pub unsafe fn new_compute_pipeline_state_with_descriptor(
+        &self,
+        desc: &MTLComputePipelineDescriptor,
+    ) -> Result<MTLComputePipelineState, NSError> {
+        let mut err = ObjectPointer(std::ptr::null_mut());
+        let b = ObjectPointer(msg_send![
+            self.get_ptr(),
+            newComputePipelineStateWithDescriptor:desc.get_ptr()
+            options:0 as NSUInteger
+            reflection:std::ptr::null_mut::<ObjectPointer>()
+            error:&mut err
+        ]);
+        if err.0.is_null() {
+            Ok(MTLComputePipelineState::from_ptr(b))
+        } else {
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        }
+    }
+    /// Loads the archive at the descriptor's URL, or creates an empty archive if it has none.
+    pub unsafe fn new_binary_archive_with_descriptor(
+        &self,
+        desc: &MTLBinaryArchiveDescriptor,
+    ) -> Result<MTLBinaryArchive, NSError> {
+        let mut err = ObjectPointer(std::ptr::null_mut());
+        let archive = ObjectPointer(msg_send![
+            self.get_ptr(),
+            newBinaryArchiveWithDescriptor:desc.get_ptr()
+            error:&mut err
+        ]);
+        if err.0.is_null() {
+            Ok(MTLBinaryArchive::from_ptr(archive))
+        } else {
+            Err(NSError::from_ptr(msg_send![err, retain]))
         }
     }
     pub unsafe fn get_max_buffer_length(&self) -> NSUInteger {
@@ -266,6 +669,30 @@ impl MTLDevice {
             msg_send![self.get_ptr(), newTextureWithDescriptor:descriptor.get_ptr()],
         )
     }
+    pub unsafe fn minimum_linear_texture_alignment_for_pixel_format(
+        &self,
+        format: MTLPixelFormat,
+    ) -> NSUInteger {
+        msg_send![self.get_ptr(), minimumLinearTextureAlignmentForPixelFormat: format]
+    }
+    pub unsafe fn minimum_texture_buffer_alignment_for_pixel_format(
+        &self,
+        format: MTLPixelFormat,
+    ) -> NSUInteger {
+        msg_send![self.get_ptr(), minimumTextureBufferAlignmentForPixelFormat: format]
+    }
+    /// Computes where a `width` by `height` texture of `format` may be placed in a buffer
+    /// created by this device, at or after `offset`.
+    pub unsafe fn get_linear_texture_layout(
+        &self,
+        format: MTLPixelFormat,
+        width: NSUInteger,
+        height: NSUInteger,
+        offset: NSUInteger,
+    ) -> Option<LinearTextureLayout> {
+        let alignment = self.minimum_linear_texture_alignment_for_pixel_format(format);
+        LinearTextureLayout::new(format, width, height, offset, alignment)
+    }
     pub unsafe fn new_sampler_state_with_descriptor(
         &self,
         desc: &MTLSamplerDescriptor,
@@ -301,7 +728,7 @@ pub trait DeviceCreated: Object {
         if d.0.is_null() {
             None
         } else {
-            Some(MTLDevice::from_ptr(d))
+            Some(MTLDevice::from_ptr(msg_send![d, retain]))
         }
     }
 }
@@ -0,0 +1,199 @@
+use crate::import_objc_macros::*;
+use crate::{
+    handle, DebugGroups, Labeled, MTLBuffer, MTLCommandEncoder, MTLCounterSampleBuffer,
+    MTLResource, MTLSize, MTLTexture, NSRange, NSUInteger, NSUIntegerRange, Object, ObjectPointer,
+};
+use enumflags2::BitFlags;
+
+#[derive(BitFlags, Copy, Clone, Debug, PartialEq)]
+#[repr(u64)]
+pub enum MTLBlitOption {
+    /// Copies only the depth of a combined depth stencil texture.
+    DepthFromDepthStencil = 1 << 0,
+    /// Copies only the stencil of a combined depth stencil texture.
+    StencilFromDepthStencil = 1 << 1,
+    RowLinearPVRTC = 1 << 2,
+}
+
+pub struct MTLBlitCommandEncoder(ObjectPointer);
+handle!(MTLBlitCommandEncoder);
+
+impl MTLBlitCommandEncoder {
+    pub unsafe fn synchronize_resource<T: MTLResource>(&self, resource: &T) {
+        msg_send![self.get_ptr(), synchronizeResource:resource.get_ptr()]
+    }
+    pub unsafe fn synchronize_texture(
+        &self,
+        texture: &MTLTexture,
+        slice: NSUInteger,
+        level: NSUInteger,
+    ) {
+        msg_send![self.get_ptr(), synchronizeTexture:texture.get_ptr() slice:slice level:level]
+    }
+    pub unsafe fn copy_from_buffer_to_buffer(
+        &self,
+        source: &MTLBuffer,
+        source_offset: NSUInteger,
+        destination: &MTLBuffer,
+        destination_offset: NSUInteger,
+        size: NSUInteger,
+    ) {
+        msg_send![
+            self.get_ptr(),
+            copyFromBuffer:source.get_ptr()
+            sourceOffset:source_offset
+            toBuffer:destination.get_ptr()
+            destinationOffset:destination_offset
+            size:size
+        ]
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn copy_from_buffer_to_texture(
+        &self,
+        source: &MTLBuffer,
+        source_offset: NSUInteger,
+        source_bytes_per_row: NSUInteger,
+        source_bytes_per_image: NSUInteger,
+        source_size: MTLSize,
+        destination: &MTLTexture,
+        destination_slice: NSUInteger,
+        destination_level: NSUInteger,
+        destination_origin: MTLSize,
+    ) {
+        msg_send![
+            self.get_ptr(),
+            copyFromBuffer:source.get_ptr()
+            sourceOffset:source_offset
+            sourceBytesPerRow:source_bytes_per_row
+            sourceBytesPerImage:source_bytes_per_image
+            sourceSize:source_size
+            toTexture:destination.get_ptr()
+            destinationSlice:destination_slice
+            destinationLevel:destination_level
+            destinationOrigin:destination_origin
+        ]
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn copy_from_texture_to_buffer(
+        &self,
+        source: &MTLTexture,
+        source_slice: NSUInteger,
+        source_level: NSUInteger,
+        source_origin: MTLSize,
+        source_size: MTLSize,
+        destination: &MTLBuffer,
+        destination_offset: NSUInteger,
+        destination_bytes_per_row: NSUInteger,
+        destination_bytes_per_image: NSUInteger,
+    ) {
+        msg_send![
+            self.get_ptr(),
+            copyFromTexture:source.get_ptr()
+            sourceSlice:source_slice
+            sourceLevel:source_level
+            sourceOrigin:source_origin
+            sourceSize:source_size
+            toBuffer:destination.get_ptr()
+            destinationOffset:destination_offset
+            destinationBytesPerRow:destination_bytes_per_row
+            destinationBytesPerImage:destination_bytes_per_image
+        ]
+    }
+    /// Like `copy_from_texture_to_buffer`, but combined depth stencil textures must be copied
+    /// one plane at a time, selected with `DepthFromDepthStencil` or
+    /// `StencilFromDepthStencil`.
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn copy_from_texture_to_buffer_with_options(
+        &self,
+        source: &MTLTexture,
+        source_slice: NSUInteger,
+        source_level: NSUInteger,
+        source_origin: MTLSize,
+        source_size: MTLSize,
+        destination: &MTLBuffer,
+        destination_offset: NSUInteger,
+        destination_bytes_per_row: NSUInteger,
+        destination_bytes_per_image: NSUInteger,
+        options: BitFlags<MTLBlitOption>,
+    ) {
+        msg_send![
+            self.get_ptr(),
+            copyFromTexture:source.get_ptr()
+            sourceSlice:source_slice
+            sourceLevel:source_level
+            sourceOrigin:source_origin
+            sourceSize:source_size
+            toBuffer:destination.get_ptr()
+            destinationOffset:destination_offset
+            destinationBytesPerRow:destination_bytes_per_row
+            destinationBytesPerImage:destination_bytes_per_image
+            options:options.bits()
+        ]
+    }
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn copy_from_texture_to_texture(
+        &self,
+        source: &MTLTexture,
+        source_slice: NSUInteger,
+        source_level: NSUInteger,
+        source_origin: MTLSize,
+        source_size: MTLSize,
+        destination: &MTLTexture,
+        destination_slice: NSUInteger,
+        destination_level: NSUInteger,
+        destination_origin: MTLSize,
+    ) {
+        msg_send![
+            self.get_ptr(),
+            copyFromTexture:source.get_ptr()
+            sourceSlice:source_slice
+            sourceLevel:source_level
+            sourceOrigin:source_origin
+            sourceSize:source_size
+            toTexture:destination.get_ptr()
+            destinationSlice:destination_slice
+            destinationLevel:destination_level
+            destinationOrigin:destination_origin
+        ]
+    }
+    pub unsafe fn fill_buffer(&self, buffer: &MTLBuffer, range: NSUIntegerRange, value: u8) {
+        let range = NSRange::from(range);
+        msg_send![self.get_ptr(), fillBuffer:buffer.get_ptr() range:range value:value]
+    }
+    pub unsafe fn generate_mipmaps(&self, texture: &MTLTexture) {
+        msg_send![self.get_ptr(), generateMipmapsForTexture:texture.get_ptr()]
+    }
+    /// Requires that the device supports sampling `AtBlitBoundary`.
+    pub unsafe fn sample_counters_in_buffer(
+        &self,
+        buffer: &MTLCounterSampleBuffer,
+        index: NSUInteger,
+        barrier: bool,
+    ) {
+        msg_send![
+            self.get_ptr(),
+            sampleCountersInBuffer:buffer.get_ptr()
+            atSampleIndex:index
+            withBarrier:barrier
+        ]
+    }
+}
+
+impl MTLCommandEncoder for MTLBlitCommandEncoder {}
+
+impl Labeled for MTLBlitCommandEncoder {}
+
+impl DebugGroups for MTLBlitCommandEncoder {}
+
+impl Object for MTLBlitCommandEncoder {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLBlitCommandEncoder(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
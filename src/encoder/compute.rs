@@ -1,7 +1,8 @@
 use crate::import_objc_macros::*;
 use crate::{
-    handle, MTLBuffer, MTLCommandEncoder, MTLComputePipelineState, MTLSamplerState, MTLSize,
-    MTLTexture, NSRange, NSUInteger, NSUIntegerRange, Object, ObjectPointer,
+    handle, DebugGroups, Labeled, MTLBuffer, MTLCommandEncoder, MTLComputePipelineState,
+    MTLCounterSampleBuffer, MTLSamplerState, MTLSize, MTLTexture, NSRange, NSUInteger,
+    NSUIntegerRange, Object, ObjectPointer, ThreadgroupLayout,
 };
 use std::os::raw::c_void;
 
@@ -109,10 +110,158 @@ impl MTLComputeCommandEncoder {
             threadsPerThreadgroup:threads_per_threadgroup
         ]
     }
+    /// Dispatches the plan, binding its bounds as a `uint3` at `bounds_index` if given.
+    ///
+    /// Nothing is dispatched for an empty grid.
+    pub unsafe fn dispatch_plan(&self, plan: &DispatchPlan, bounds_index: Option<NSUInteger>) {
+        if let Some(index) = bounds_index {
+            let bounds = plan.get_bounds_constant();
+            self.set_bytes(
+                bounds.as_ptr() as *const c_void,
+                std::mem::size_of_val(&bounds) as NSUInteger,
+                index,
+            );
+        }
+        if plan.is_empty() {
+            return;
+        }
+        match *plan {
+            DispatchPlan::Threads {
+                threads_per_grid,
+                threads_per_threadgroup,
+            } => self.dispatch_threads(threads_per_grid, threads_per_threadgroup),
+            DispatchPlan::Threadgroups {
+                threadgroups_per_grid,
+                threads_per_threadgroup,
+                ..
+            } => self.dispatch_threadgroups(threadgroups_per_grid, threads_per_threadgroup),
+        }
+    }
+    /// Requires that the device supports sampling `AtDispatchBoundary`.
+    pub unsafe fn sample_counters_in_buffer(
+        &self,
+        buffer: &MTLCounterSampleBuffer,
+        index: NSUInteger,
+        barrier: bool,
+    ) {
+        msg_send![
+            self.get_ptr(),
+            sampleCountersInBuffer:buffer.get_ptr()
+            atSampleIndex:index
+            withBarrier:barrier
+        ]
+    }
+}
+
+/// How to dispatch a grid of threads, depending on whether the device supports
+/// threadgroups of non-uniform size.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DispatchPlan {
+    /// Dispatches exactly the threads of the grid, with smaller threadgroups at its edges.
+    Threads {
+        threads_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    },
+    /// Dispatches whole threadgroups covering the grid. Threads outside `bounds` must be
+    /// skipped by the shader.
+    Threadgroups {
+        threadgroups_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+        bounds: MTLSize,
+    },
+}
+
+impl DispatchPlan {
+    /// Plans a dispatch over `grid` with the limits of the compute pipeline, using
+    /// `dispatch_threads` only if `non_uniform_threadgroups` is supported by the device.
+    pub fn new(
+        grid: MTLSize,
+        thread_execution_width: NSUInteger,
+        max_total_threads_per_threadgroup: NSUInteger,
+        non_uniform_threadgroups: bool,
+    ) -> DispatchPlan {
+        let layout = ThreadgroupLayout::new(
+            grid,
+            thread_execution_width,
+            max_total_threads_per_threadgroup,
+        );
+        if non_uniform_threadgroups {
+            DispatchPlan::Threads {
+                threads_per_grid: grid,
+                threads_per_threadgroup: layout.threads_per_threadgroup,
+            }
+        } else {
+            DispatchPlan::Threadgroups {
+                threadgroups_per_grid: layout.threadgroups_per_grid,
+                threads_per_threadgroup: layout.threads_per_threadgroup,
+                bounds: grid,
+            }
+        }
+    }
+    pub unsafe fn for_pipeline(
+        grid: MTLSize,
+        pipeline: &MTLComputePipelineState,
+        non_uniform_threadgroups: bool,
+    ) -> DispatchPlan {
+        DispatchPlan::new(
+            grid,
+            pipeline.get_thread_execution_width(),
+            pipeline.get_max_total_threads_per_threadgroup(),
+            non_uniform_threadgroups,
+        )
+    }
+    /// The size of the grid which the shader should process.
+    pub fn get_bounds(&self) -> MTLSize {
+        match *self {
+            DispatchPlan::Threads {
+                threads_per_grid, ..
+            } => threads_per_grid,
+            DispatchPlan::Threadgroups { bounds, .. } => bounds,
+        }
+    }
+    /// The bounds laid out as a Metal `uint3`.
+    pub fn get_bounds_constant(&self) -> [u32; 4] {
+        let bounds = self.get_bounds();
+        [
+            bounds.width as u32,
+            bounds.height as u32,
+            bounds.depth as u32,
+            0,
+        ]
+    }
+    /// The number of threads which will be dispatched, including those outside the bounds.
+    pub fn get_dispatched_threads(&self) -> MTLSize {
+        match *self {
+            DispatchPlan::Threads {
+                threads_per_grid, ..
+            } => threads_per_grid,
+            DispatchPlan::Threadgroups {
+                threadgroups_per_grid: groups,
+                threads_per_threadgroup: threads,
+                ..
+            } => MTLSize {
+                width: groups.width * threads.width,
+                height: groups.height * threads.height,
+                depth: groups.depth * threads.depth,
+            },
+        }
+    }
+    /// Whether some dispatched threads lie outside the bounds and must be skipped.
+    pub fn needs_bounds_check(&self) -> bool {
+        self.get_dispatched_threads() != self.get_bounds()
+    }
+    pub fn is_empty(&self) -> bool {
+        let bounds = self.get_bounds();
+        bounds.width == 0 || bounds.height == 0 || bounds.depth == 0
+    }
 }
 
 impl MTLCommandEncoder for MTLComputeCommandEncoder {}
 
+impl Labeled for MTLComputeCommandEncoder {}
+
+impl DebugGroups for MTLComputeCommandEncoder {}
+
 impl Object for MTLComputeCommandEncoder {
     unsafe fn from_ptr(ptr: ObjectPointer) -> Self
     where
@@ -125,3 +274,65 @@ impl Object for MTLComputeCommandEncoder {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(width: NSUInteger, height: NSUInteger, depth: NSUInteger) -> MTLSize {
+        MTLSize {
+            width,
+            height,
+            depth,
+        }
+    }
+
+    #[test]
+    fn non_uniform_threadgroups() {
+        let plan = DispatchPlan::new(size(1920, 1080, 1), 32, 1024, true);
+        assert_eq!(
+            plan,
+            DispatchPlan::Threads {
+                threads_per_grid: size(1920, 1080, 1),
+                threads_per_threadgroup: size(32, 32, 1),
+            }
+        );
+        assert_eq!(plan.get_dispatched_threads(), size(1920, 1080, 1));
+        assert!(!plan.needs_bounds_check());
+    }
+
+    #[test]
+    fn padded_threadgroups() {
+        // 1080 is not a multiple of 32, so the last row of threadgroups is padded
+        let plan = DispatchPlan::new(size(1920, 1080, 1), 32, 1024, false);
+        assert_eq!(
+            plan,
+            DispatchPlan::Threadgroups {
+                threadgroups_per_grid: size(60, 34, 1),
+                threads_per_threadgroup: size(32, 32, 1),
+                bounds: size(1920, 1080, 1),
+            }
+        );
+        assert_eq!(plan.get_dispatched_threads(), size(1920, 1088, 1));
+        assert!(plan.needs_bounds_check());
+        assert_eq!(plan.get_bounds(), size(1920, 1080, 1));
+        assert_eq!(plan.get_bounds_constant(), [1920, 1080, 1, 0]);
+
+        // 1D grids are padded to the execution width
+        let plan = DispatchPlan::new(size(100, 1, 1), 32, 1024, false);
+        assert_eq!(plan.get_dispatched_threads(), size(128, 1, 1));
+        assert_eq!(plan.get_bounds_constant(), [100, 1, 1, 0]);
+
+        // grids which are a multiple of the threadgroup size need no check
+        let plan = DispatchPlan::new(size(64, 64, 1), 32, 1024, false);
+        assert_eq!(plan.get_dispatched_threads(), size(64, 64, 1));
+        assert!(!plan.needs_bounds_check());
+    }
+
+    #[test]
+    fn empty() {
+        assert!(DispatchPlan::new(size(0, 4, 1), 32, 1024, false).is_empty());
+        assert!(DispatchPlan::new(size(4, 4, 0), 32, 1024, true).is_empty());
+        assert!(!DispatchPlan::new(size(1, 1, 1), 32, 1024, true).is_empty());
+    }
+}
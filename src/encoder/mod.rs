@@ -1,15 +1,25 @@
+mod blit;
 mod compute;
 mod pass;
 mod render;
+pub use blit::*;
 pub use compute::*;
 pub use pass::*;
 pub use render::*;
 
 use crate::import_objc_macros::*;
-use crate::Object;
+use crate::labels::LABELS_ENABLED;
+use crate::{DebugGroups, Labeled, NSString, Object};
 
-pub trait MTLCommandEncoder: Object {
+pub trait MTLCommandEncoder: Labeled + DebugGroups {
     unsafe fn end_encoding(&self) {
         msg_send![self.get_ptr(), endEncoding]
     }
+    unsafe fn insert_debug_signpost(&self, name: &str) {
+        if !LABELS_ENABLED {
+            return;
+        }
+        let string = NSString::new(name);
+        msg_send![self.get_ptr(), insertDebugSignpost:string.get_ptr()]
+    }
 }
@@ -1,5 +1,5 @@
 use crate::import_objc_macros::*;
-use crate::{handle, Array, MTLTexture, NSUInteger, Object, ObjectPointer};
+use crate::{handle, Array, MTLCounterSampleBuffer, MTLTexture, NSUInteger, Object, ObjectPointer};
 
 #[repr(u64)]
 pub enum MTLLoadAction {
@@ -214,6 +214,14 @@ impl MTLRenderPassDescriptor {
             msg_send![k, retain]
         })
     }
+    pub unsafe fn get_sample_buffer_attachments(
+        &self,
+    ) -> MTLRenderPassSampleBufferAttachmentDescriptorArray {
+        MTLRenderPassSampleBufferAttachmentDescriptorArray::from_ptr({
+            let k = ObjectPointer(msg_send![self.get_ptr(), sampleBufferAttachments]);
+            msg_send![k, retain]
+        })
+    }
 }
 
 impl Object for MTLRenderPassDescriptor {
@@ -228,3 +236,288 @@ impl Object for MTLRenderPassDescriptor {
         self.0
     }
 }
+
+/// Samples the counters of the sample buffer at the boundaries of the pass' stages, at the
+/// given indices, or not at all for `MTLCounterSampleBuffer::DONT_SAMPLE`.
+pub struct MTLRenderPassSampleBufferAttachmentDescriptor(ObjectPointer);
+handle!(MTLRenderPassSampleBufferAttachmentDescriptor);
+
+impl MTLRenderPassSampleBufferAttachmentDescriptor {
+    pub unsafe fn set_sample_buffer(&self, buffer: &MTLCounterSampleBuffer) {
+        msg_send![self.get_ptr(), setSampleBuffer:buffer.get_ptr()]
+    }
+    pub unsafe fn get_sample_buffer(&self) -> Option<MTLCounterSampleBuffer> {
+        let buffer = ObjectPointer(msg_send![self.get_ptr(), sampleBuffer]);
+        if buffer.0.is_null() {
+            None
+        } else {
+            Some(MTLCounterSampleBuffer::from_ptr(msg_send![buffer, retain]))
+        }
+    }
+    pub unsafe fn set_start_of_vertex_sample_index(&self, index: NSUInteger) {
+        msg_send![self.get_ptr(), setStartOfVertexSampleIndex: index]
+    }
+    pub unsafe fn get_start_of_vertex_sample_index(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), startOfVertexSampleIndex]
+    }
+    pub unsafe fn set_end_of_vertex_sample_index(&self, index: NSUInteger) {
+        msg_send![self.get_ptr(), setEndOfVertexSampleIndex: index]
+    }
+    pub unsafe fn get_end_of_vertex_sample_index(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), endOfVertexSampleIndex]
+    }
+    pub unsafe fn set_start_of_fragment_sample_index(&self, index: NSUInteger) {
+        msg_send![self.get_ptr(), setStartOfFragmentSampleIndex: index]
+    }
+    pub unsafe fn get_start_of_fragment_sample_index(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), startOfFragmentSampleIndex]
+    }
+    pub unsafe fn set_end_of_fragment_sample_index(&self, index: NSUInteger) {
+        msg_send![self.get_ptr(), setEndOfFragmentSampleIndex: index]
+    }
+    pub unsafe fn get_end_of_fragment_sample_index(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), endOfFragmentSampleIndex]
+    }
+}
+
+impl Object for MTLRenderPassSampleBufferAttachmentDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLRenderPassSampleBufferAttachmentDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLRenderPassSampleBufferAttachmentDescriptorArray(ObjectPointer);
+handle!(MTLRenderPassSampleBufferAttachmentDescriptorArray);
+
+impl Array<MTLRenderPassSampleBufferAttachmentDescriptor>
+    for MTLRenderPassSampleBufferAttachmentDescriptorArray
+{
+}
+
+impl Object for MTLRenderPassSampleBufferAttachmentDescriptorArray {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLRenderPassSampleBufferAttachmentDescriptorArray(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLComputePassSampleBufferAttachmentDescriptor(ObjectPointer);
+handle!(MTLComputePassSampleBufferAttachmentDescriptor);
+
+impl MTLComputePassSampleBufferAttachmentDescriptor {
+    pub unsafe fn set_sample_buffer(&self, buffer: &MTLCounterSampleBuffer) {
+        msg_send![self.get_ptr(), setSampleBuffer:buffer.get_ptr()]
+    }
+    pub unsafe fn get_sample_buffer(&self) -> Option<MTLCounterSampleBuffer> {
+        let buffer = ObjectPointer(msg_send![self.get_ptr(), sampleBuffer]);
+        if buffer.0.is_null() {
+            None
+        } else {
+            Some(MTLCounterSampleBuffer::from_ptr(msg_send![buffer, retain]))
+        }
+    }
+    pub unsafe fn set_start_of_encoder_sample_index(&self, index: NSUInteger) {
+        msg_send![self.get_ptr(), setStartOfEncoderSampleIndex: index]
+    }
+    pub unsafe fn get_start_of_encoder_sample_index(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), startOfEncoderSampleIndex]
+    }
+    pub unsafe fn set_end_of_encoder_sample_index(&self, index: NSUInteger) {
+        msg_send![self.get_ptr(), setEndOfEncoderSampleIndex: index]
+    }
+    pub unsafe fn get_end_of_encoder_sample_index(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), endOfEncoderSampleIndex]
+    }
+}
+
+impl Object for MTLComputePassSampleBufferAttachmentDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLComputePassSampleBufferAttachmentDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLComputePassSampleBufferAttachmentDescriptorArray(ObjectPointer);
+handle!(MTLComputePassSampleBufferAttachmentDescriptorArray);
+
+impl Array<MTLComputePassSampleBufferAttachmentDescriptor>
+    for MTLComputePassSampleBufferAttachmentDescriptorArray
+{
+}
+
+impl Object for MTLComputePassSampleBufferAttachmentDescriptorArray {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLComputePassSampleBufferAttachmentDescriptorArray(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum MTLDispatchType {
+    Serial = 0,
+    Concurrent = 1,
+}
+
+pub struct MTLComputePassDescriptor(ObjectPointer);
+handle!(MTLComputePassDescriptor);
+
+impl MTLComputePassDescriptor {
+    pub unsafe fn new() -> MTLComputePassDescriptor {
+        MTLComputePassDescriptor::from_ptr({
+            let k = ObjectPointer(msg_send![
+                class!(MTLComputePassDescriptor),
+                computePassDescriptor
+            ]);
+            msg_send![k, retain]
+        })
+    }
+    pub unsafe fn set_dispatch_type(&self, dispatch_type: MTLDispatchType) {
+        msg_send![self.get_ptr(), setDispatchType: dispatch_type]
+    }
+    pub unsafe fn get_dispatch_type(&self) -> MTLDispatchType {
+        msg_send![self.get_ptr(), dispatchType]
+    }
+    pub unsafe fn get_sample_buffer_attachments(
+        &self,
+    ) -> MTLComputePassSampleBufferAttachmentDescriptorArray {
+        MTLComputePassSampleBufferAttachmentDescriptorArray::from_ptr({
+            let k = ObjectPointer(msg_send![self.get_ptr(), sampleBufferAttachments]);
+            msg_send![k, retain]
+        })
+    }
+}
+
+impl Object for MTLComputePassDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLComputePassDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLBlitPassSampleBufferAttachmentDescriptor(ObjectPointer);
+handle!(MTLBlitPassSampleBufferAttachmentDescriptor);
+
+impl MTLBlitPassSampleBufferAttachmentDescriptor {
+    pub unsafe fn set_sample_buffer(&self, buffer: &MTLCounterSampleBuffer) {
+        msg_send![self.get_ptr(), setSampleBuffer:buffer.get_ptr()]
+    }
+    pub unsafe fn get_sample_buffer(&self) -> Option<MTLCounterSampleBuffer> {
+        let buffer = ObjectPointer(msg_send![self.get_ptr(), sampleBuffer]);
+        if buffer.0.is_null() {
+            None
+        } else {
+            Some(MTLCounterSampleBuffer::from_ptr(msg_send![buffer, retain]))
+        }
+    }
+    pub unsafe fn set_start_of_encoder_sample_index(&self, index: NSUInteger) {
+        msg_send![self.get_ptr(), setStartOfEncoderSampleIndex: index]
+    }
+    pub unsafe fn get_start_of_encoder_sample_index(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), startOfEncoderSampleIndex]
+    }
+    pub unsafe fn set_end_of_encoder_sample_index(&self, index: NSUInteger) {
+        msg_send![self.get_ptr(), setEndOfEncoderSampleIndex: index]
+    }
+    pub unsafe fn get_end_of_encoder_sample_index(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), endOfEncoderSampleIndex]
+    }
+}
+
+impl Object for MTLBlitPassSampleBufferAttachmentDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLBlitPassSampleBufferAttachmentDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLBlitPassSampleBufferAttachmentDescriptorArray(ObjectPointer);
+handle!(MTLBlitPassSampleBufferAttachmentDescriptorArray);
+
+impl Array<MTLBlitPassSampleBufferAttachmentDescriptor>
+    for MTLBlitPassSampleBufferAttachmentDescriptorArray
+{
+}
+
+impl Object for MTLBlitPassSampleBufferAttachmentDescriptorArray {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLBlitPassSampleBufferAttachmentDescriptorArray(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLBlitPassDescriptor(ObjectPointer);
+handle!(MTLBlitPassDescriptor);
+
+impl MTLBlitPassDescriptor {
+    pub unsafe fn new() -> MTLBlitPassDescriptor {
+        MTLBlitPassDescriptor::from_ptr({
+            let k = ObjectPointer(msg_send![class!(MTLBlitPassDescriptor), blitPassDescriptor]);
+            msg_send![k, retain]
+        })
+    }
+    pub unsafe fn get_sample_buffer_attachments(
+        &self,
+    ) -> MTLBlitPassSampleBufferAttachmentDescriptorArray {
+        MTLBlitPassSampleBufferAttachmentDescriptorArray::from_ptr({
+            let k = ObjectPointer(msg_send![self.get_ptr(), sampleBufferAttachments]);
+            msg_send![k, retain]
+        })
+    }
+}
+
+impl Object for MTLBlitPassDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLBlitPassDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
@@ -1,8 +1,10 @@
+use crate::autorelease::check_autorelease_pool;
 use crate::import_objc_macros::*;
 use crate::{
-    handle, MTLBuffer, MTLCommandEncoder, MTLDepthStencilState, MTLRenderPipelineState,
-    MTLSamplerState, MTLStoreAction, MTLStoreActionOptions, MTLTexture, NSInteger, NSRange,
-    NSUInteger, NSUIntegerRange, Object, ObjectPointer,
+    handle, DebugGroups, Labeled, MTLBuffer, MTLCommandEncoder, MTLCounterSampleBuffer,
+    MTLDepthStencilState, MTLRenderPipelineState, MTLSamplerState, MTLStoreAction,
+    MTLStoreActionOptions, MTLTexture, NSInteger, NSRange, NSUInteger, NSUIntegerRange, Object,
+    ObjectPointer,
 };
 use std::os::raw::c_void;
 
@@ -58,6 +60,7 @@ pub enum MTLPrimitiveType {
     TriangleStrip = 4,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLIndexType {
     UInt16 = 0,
@@ -317,10 +320,28 @@ impl MTLRenderCommandEncoder {
             baseInstance:base_instance
         ]
     }
+    /// Requires that the device supports sampling `AtDrawBoundary`.
+    pub unsafe fn sample_counters_in_buffer(
+        &self,
+        buffer: &MTLCounterSampleBuffer,
+        index: NSUInteger,
+        barrier: bool,
+    ) {
+        msg_send![
+            self.get_ptr(),
+            sampleCountersInBuffer:buffer.get_ptr()
+            atSampleIndex:index
+            withBarrier:barrier
+        ]
+    }
 }
 
 impl MTLCommandEncoder for MTLRenderCommandEncoder {}
 
+impl Labeled for MTLRenderCommandEncoder {}
+
+impl DebugGroups for MTLRenderCommandEncoder {}
+
 impl Object for MTLRenderCommandEncoder {
     unsafe fn from_ptr(ptr: ObjectPointer) -> Self
     where
@@ -339,6 +360,7 @@ handle!(MTLParallelRenderCommandEncoder);
 
 impl MTLParallelRenderCommandEncoder {
     pub unsafe fn new_render_command_encoder(&self) -> MTLRenderCommandEncoder {
+        check_autorelease_pool("new_render_command_encoder");
         MTLRenderCommandEncoder::from_ptr({
             let k = ObjectPointer(msg_send![self.get_ptr(), renderCommandEncoder]);
             msg_send![k, retain]
@@ -378,6 +400,10 @@ impl MTLParallelRenderCommandEncoder {
 
 impl MTLCommandEncoder for MTLParallelRenderCommandEncoder {}
 
+impl Labeled for MTLParallelRenderCommandEncoder {}
+
+impl DebugGroups for MTLParallelRenderCommandEncoder {}
+
 impl Object for MTLParallelRenderCommandEncoder {
     unsafe fn from_ptr(ptr: ObjectPointer) -> Self
     where
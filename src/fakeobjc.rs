@@ -0,0 +1,420 @@
+//! A fake Objective C runtime which counts the retains and releases of its objects, so that
+//! the ownership of handles can be checked for leaks and over-releases where the real
+//! runtime is not available.
+//!
+//! Messages are dispatched by the GNUstep ABI's `objc_msg_lookup`, which this module
+//! defines in place of the runtime library's. Only the messages the tests send are
+//! understood; any other panics.
+
+use crate::{NSUInteger, ObjectPointer};
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+pub(crate) struct FakeObject {
+    retains: AtomicUsize,
+    releases: AtomicUsize,
+    kind: FakeKind,
+}
+
+pub(crate) enum FakeKind {
+    Plain {
+        /// Objects returned at +0 by getters, by selector name.
+        getters: Vec<(&'static str, ObjectPointer)>,
+        /// Objects returned at +1 by `new` methods, in the order of their creation.
+        created: Mutex<Vec<ObjectPointer>>,
+        /// The setters and debug group messages received, with their string arguments.
+        sent: Mutex<Vec<String>>,
+    },
+    /// Doesn't retain its elements.
+    Array(Vec<ObjectPointer>),
+    /// Nul terminated UTF-8, which is also the path of a file URL.
+    String(Vec<u8>),
+}
+
+impl FakeObject {
+    /// Creates an object with one reference, owned by the caller. Objects are never freed,
+    /// so their counts may be checked after they are released.
+    pub(crate) fn create(kind: FakeKind) -> ObjectPointer {
+        let object = Box::leak(Box::new(FakeObject {
+            retains: AtomicUsize::new(0),
+            releases: AtomicUsize::new(0),
+            kind,
+        }));
+        ObjectPointer(object as *mut FakeObject as *mut _)
+    }
+    pub(crate) fn plain(getters: Vec<(&'static str, ObjectPointer)>) -> ObjectPointer {
+        FakeObject::create(FakeKind::Plain {
+            getters,
+            created: Mutex::new(Vec::new()),
+            sent: Mutex::new(Vec::new()),
+        })
+    }
+    pub(crate) fn string(string: &str) -> ObjectPointer {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.push(0);
+        FakeObject::create(FakeKind::String(bytes))
+    }
+    pub(crate) fn get(object: ObjectPointer) -> &'static FakeObject {
+        unsafe { &*(object.0 as *const FakeObject) }
+    }
+    pub(crate) fn get_retains(&self) -> usize {
+        self.retains.load(Ordering::SeqCst)
+    }
+    pub(crate) fn get_releases(&self) -> usize {
+        self.releases.load(Ordering::SeqCst)
+    }
+    /// The references left, counting the one the object was created with.
+    pub(crate) fn get_references(&self) -> isize {
+        1 + self.get_retains() as isize - self.get_releases() as isize
+    }
+    pub(crate) fn get_created(&self) -> Vec<ObjectPointer> {
+        match &self.kind {
+            FakeKind::Plain { created, .. } => created.lock().unwrap().clone(),
+            _ => Vec::new(),
+        }
+    }
+    pub(crate) fn get_sent(&self) -> Vec<String> {
+        match &self.kind {
+            FakeKind::Plain { sent, .. } => sent.lock().unwrap().clone(),
+            _ => Vec::new(),
+        }
+    }
+    fn send(&self, message: String) {
+        match &self.kind {
+            FakeKind::Plain { sent, .. } => sent.lock().unwrap().push(message),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// The addresses of the interned selector names.
+static SELECTORS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// Interns the selector's name, so the selector is a pointer to it.
+#[no_mangle]
+unsafe extern "C" fn sel_registerName(name: *const c_char) -> *const c_char {
+    let name = CStr::from_ptr(name);
+    let mut selectors = SELECTORS.lock().unwrap();
+    let interned = match selectors
+        .iter()
+        .find(|&&selector| CStr::from_ptr(selector as *const c_char) == name)
+    {
+        Some(&selector) => selector,
+        None => {
+            let selector = Box::leak(name.to_owned().into_boxed_c_str()).as_ptr() as usize;
+            selectors.push(selector);
+            selector
+        }
+    };
+    interned as *const c_char
+}
+
+#[no_mangle]
+unsafe extern "C" fn sel_getName(selector: *const c_char) -> *const c_char {
+    selector
+}
+
+/// The classes looked up by name, which are plain objects creating others with `new`.
+static CLASSES: Mutex<Vec<(&'static CStr, usize)>> = Mutex::new(Vec::new());
+
+#[no_mangle]
+unsafe extern "C" fn objc_getClass(name: *const c_char) -> ObjectPointer {
+    let name = CStr::from_ptr(name);
+    let mut classes = CLASSES.lock().unwrap();
+    let class = match classes.iter().find(|(class, _)| *class == name) {
+        Some(&(_, class)) => class,
+        None => {
+            let class = FakeObject::plain(Vec::new()).0 as usize;
+            classes.push((Box::leak(name.to_owned().into_boxed_c_str()), class));
+            class
+        }
+    };
+    ObjectPointer(class as *mut _)
+}
+
+type Imp = unsafe extern "C" fn();
+
+#[no_mangle]
+unsafe extern "C" fn objc_msg_lookup(receiver: ObjectPointer, selector: *const c_char) -> Imp {
+    unsafe extern "C" fn retain(object: ObjectPointer, _: *const c_char) -> ObjectPointer {
+        FakeObject::get(object)
+            .retains
+            .fetch_add(1, Ordering::SeqCst);
+        object
+    }
+    unsafe extern "C" fn release(object: ObjectPointer, _: *const c_char) {
+        FakeObject::get(object)
+            .releases
+            .fetch_add(1, Ordering::SeqCst);
+    }
+    unsafe extern "C" fn count(object: ObjectPointer, _: *const c_char) -> NSUInteger {
+        match &FakeObject::get(object).kind {
+            FakeKind::Array(objects) => objects.len() as NSUInteger,
+            _ => unreachable!(),
+        }
+    }
+    unsafe extern "C" fn object_at_index(
+        object: ObjectPointer,
+        _: *const c_char,
+        index: NSUInteger,
+    ) -> ObjectPointer {
+        match &FakeObject::get(object).kind {
+            FakeKind::Array(objects) => objects[index as usize],
+            _ => unreachable!(),
+        }
+    }
+    unsafe extern "C" fn length_of_bytes(
+        object: ObjectPointer,
+        _: *const c_char,
+        _encoding: NSUInteger,
+    ) -> NSUInteger {
+        match &FakeObject::get(object).kind {
+            FakeKind::String(bytes) => bytes.len() as NSUInteger - 1,
+            _ => unreachable!(),
+        }
+    }
+    unsafe extern "C" fn utf8_string(object: ObjectPointer, _: *const c_char) -> *const u8 {
+        match &FakeObject::get(object).kind {
+            FakeKind::String(bytes) => bytes.as_ptr(),
+            _ => unreachable!(),
+        }
+    }
+    unsafe extern "C" fn getter(object: ObjectPointer, selector: *const c_char) -> ObjectPointer {
+        let name = CStr::from_ptr(selector).to_str().unwrap();
+        match &FakeObject::get(object).kind {
+            FakeKind::Plain { getters, .. } => {
+                getters
+                    .iter()
+                    .find(|(getter, _)| *getter == name)
+                    .unwrap_or_else(|| panic!("unexpected message {}", name))
+                    .1
+            }
+            _ => unreachable!(),
+        }
+    }
+    unsafe extern "C" fn init_with_bytes(
+        object: ObjectPointer,
+        _: *const c_char,
+        bytes: *const u8,
+        length: NSUInteger,
+        _encoding: NSUInteger,
+    ) -> ObjectPointer {
+        // the allocated object is replaced, consuming its reference
+        release(object, std::ptr::null());
+        let mut bytes = std::slice::from_raw_parts(bytes, length as usize).to_vec();
+        bytes.push(0);
+        FakeObject::create(FakeKind::String(bytes))
+    }
+    unsafe extern "C" fn init_with_objects(
+        object: ObjectPointer,
+        _: *const c_char,
+        objects: *const ObjectPointer,
+        count: NSUInteger,
+    ) -> ObjectPointer {
+        release(object, std::ptr::null());
+        let objects = std::slice::from_raw_parts(objects, count as usize).to_vec();
+        FakeObject::create(FakeKind::Array(objects))
+    }
+    unsafe extern "C" fn init_with_path(
+        object: ObjectPointer,
+        _: *const c_char,
+        path: ObjectPointer,
+    ) -> ObjectPointer {
+        release(object, std::ptr::null());
+        match &FakeObject::get(path).kind {
+            FakeKind::String(bytes) => FakeObject::create(FakeKind::String(bytes.clone())),
+            _ => unreachable!(),
+        }
+    }
+    unsafe extern "C" fn nil(_: ObjectPointer, _: *const c_char) -> ObjectPointer {
+        ObjectPointer(std::ptr::null_mut())
+    }
+    /// Records a message taking a string or file URL and an error, which succeeds.
+    unsafe extern "C" fn send_string_with_error(
+        object: ObjectPointer,
+        selector: *const c_char,
+        string: ObjectPointer,
+        _error: *mut ObjectPointer,
+    ) -> bool {
+        send_string(object, selector, string);
+        true
+    }
+    /// Records a message taking a descriptor and an error, which succeeds.
+    unsafe extern "C" fn send_with_error(
+        object: ObjectPointer,
+        selector: *const c_char,
+        _descriptor: ObjectPointer,
+        _error: *mut ObjectPointer,
+    ) -> bool {
+        send(object, selector);
+        true
+    }
+    unsafe extern "C" fn send_string(
+        object: ObjectPointer,
+        selector: *const c_char,
+        string: ObjectPointer,
+    ) {
+        let string = match &FakeObject::get(string).kind {
+            FakeKind::String(bytes) => String::from_utf8_lossy(&bytes[..bytes.len() - 1]),
+            _ => unreachable!(),
+        };
+        let name = CStr::from_ptr(selector).to_str().unwrap();
+        FakeObject::get(object).send(format!("{} {}", name, string));
+    }
+    unsafe extern "C" fn send(object: ObjectPointer, selector: *const c_char) {
+        let name = CStr::from_ptr(selector).to_str().unwrap();
+        FakeObject::get(object).send(name.to_string());
+    }
+    unsafe extern "C" fn new(object: ObjectPointer, _: *const c_char) -> ObjectPointer {
+        let new = FakeObject::plain(Vec::new());
+        match &FakeObject::get(object).kind {
+            FakeKind::Plain { created, .. } => created.lock().unwrap().push(new),
+            _ => unreachable!(),
+        }
+        new
+    }
+
+    let name = CStr::from_ptr(selector).to_str().unwrap();
+    let imp = match name {
+        "retain" => retain as *const (),
+        // draining a pool releases it
+        "release" | "drain" => release as *const (),
+        "count" => count as *const (),
+        "objectAtIndex:" | "objectAtIndexedSubscript:" => object_at_index as *const (),
+        "lengthOfBytesUsingEncoding:" => length_of_bytes as *const (),
+        "UTF8String" => utf8_string as *const (),
+        "initWithBytes:length:encoding:" => init_with_bytes as *const (),
+        "initWithObjects:count:" => init_with_objects as *const (),
+        "initFileURLWithPath:" => init_with_path as *const (),
+        "setLabel:" | "pushDebugGroup:" | "insertDebugSignpost:" | "setUrl:" => {
+            send_string as *const ()
+        }
+        "serializeToURL:error:" => send_string_with_error as *const (),
+        "addRenderPipelineFunctionsWithDescriptor:error:"
+        | "addComputePipelineFunctionsWithDescriptor:error:" => send_with_error as *const (),
+        "popDebugGroup" | "setBinaryArchives:" => send as *const (),
+        // properties which are nil until they are set
+        "binaryArchives" => nil as *const (),
+        // the arguments of `new` methods, and the errors they could return, are ignored
+        "alloc" => new as *const (),
+        name if name.starts_with("new") => new as *const (),
+        _ => match &FakeObject::get(receiver).kind {
+            FakeKind::Plain { getters, .. }
+                if getters.iter().any(|(getter, _)| *getter == name) =>
+            {
+                getter as *const ()
+            }
+            _ => panic!("unexpected message {}", name),
+        },
+    };
+    std::mem::transmute::<*const (), Imp>(imp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DeviceCreated, MTLBuffer, MTLCommandQueue, MTLDevice, MTLLibrary, NSArray, NSString, Object,
+    };
+
+    #[test]
+    fn clone_and_drop() {
+        let object = FakeObject::plain(Vec::new());
+        let buffer = unsafe { MTLBuffer::from_ptr(object) };
+        let clone = buffer.clone();
+        assert_eq!(FakeObject::get(object).get_references(), 2);
+        drop(buffer);
+        drop(clone);
+        let object = FakeObject::get(object);
+        assert_eq!((object.get_retains(), object.get_releases()), (1, 2));
+        assert_eq!(object.get_references(), 0);
+    }
+
+    #[test]
+    fn array_copy_from() {
+        let elements = [FakeObject::plain(Vec::new()), FakeObject::plain(Vec::new())];
+        let array = FakeObject::create(FakeKind::Array(elements.to_vec()));
+        let buffers = unsafe { NSArray::<MTLBuffer>::copy_from(array) };
+        assert_eq!(buffers.len(), 2);
+        // the array isn't owned, and each element is retained once
+        assert_eq!(FakeObject::get(array).get_references(), 1);
+        for &element in &elements {
+            assert_eq!(FakeObject::get(element).get_references(), 2);
+        }
+        drop(buffers);
+        for &element in &elements {
+            assert_eq!(FakeObject::get(element).get_references(), 1);
+        }
+        let empty = unsafe { NSArray::<MTLBuffer>::copy_from(ObjectPointer(std::ptr::null_mut())) };
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn array_elements() {
+        let element = FakeObject::plain(Vec::new());
+        let array = unsafe {
+            NSArray::<MTLBuffer>::from_ptr(FakeObject::create(FakeKind::Array(vec![element])))
+        };
+        let first = unsafe { array.get(0) }.unwrap();
+        assert!(unsafe { array.get(1) }.is_none());
+        assert_eq!(FakeObject::get(element).get_references(), 2);
+        drop(first);
+        let clone = array.clone();
+        drop(array);
+        assert_eq!(FakeObject::get(clone.get_ptr()).get_references(), 1);
+        drop(clone);
+        assert_eq!(FakeObject::get(element).get_references(), 1);
+    }
+
+    #[test]
+    fn new_methods_return_owned_objects() {
+        let device = unsafe { MTLDevice::from_ptr(FakeObject::plain(Vec::new())) };
+        let queue = unsafe { device.new_command_queue() };
+        let created = FakeObject::get(device.get_ptr()).get_created();
+        assert_eq!(created.len(), 1);
+        assert_eq!(FakeObject::get(created[0]).get_references(), 1);
+        drop(queue);
+        assert_eq!(FakeObject::get(created[0]).get_references(), 0);
+    }
+
+    #[test]
+    fn getters_retain_unowned_objects() {
+        let device = FakeObject::plain(Vec::new());
+        let queue =
+            unsafe { MTLCommandQueue::from_ptr(FakeObject::plain(vec![("device", device)])) };
+        let got = unsafe { queue.get_device() }.unwrap();
+        assert_eq!(FakeObject::get(device).get_references(), 2);
+        drop(got);
+        drop(queue);
+        assert_eq!(FakeObject::get(device).get_references(), 1);
+    }
+
+    #[test]
+    fn strings_are_copied_without_retaining() {
+        let name = FakeObject::string("Fake GPU");
+        let device = unsafe { MTLDevice::from_ptr(FakeObject::plain(vec![("name", name)])) };
+        assert_eq!(unsafe { device.get_name() }, "Fake GPU");
+        assert_eq!(FakeObject::get(name).get_retains(), 0);
+        assert_eq!(FakeObject::get(name).get_releases(), 0);
+
+        let names = [FakeObject::string("vertex"), FakeObject::string("fragment")];
+        let array = FakeObject::create(FakeKind::Array(names.to_vec()));
+        let library =
+            unsafe { MTLLibrary::from_ptr(FakeObject::plain(vec![("functionNames", array)])) };
+        assert_eq!(
+            unsafe { library.get_function_names() },
+            vec!["vertex".to_string(), "fragment".to_string()]
+        );
+        assert_eq!(FakeObject::get(array).get_references(), 1);
+        for &name in &names {
+            assert_eq!(FakeObject::get(name).get_references(), 1);
+        }
+        let string = unsafe { NSString::from_ptr(FakeObject::string("owned")) };
+        assert_eq!(string.to_string(), "owned");
+        let ptr = string.get_ptr();
+        drop(string);
+        assert_eq!(FakeObject::get(ptr).get_references(), 0);
+    }
+}
@@ -0,0 +1,452 @@
+use crate::autorelease::check_autorelease_pool;
+use crate::import_objc_macros::*;
+use crate::{handle, NSInteger, NSUInteger, Object, ObjectPointer};
+use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+const NS_UTF8_STRING_ENCODING: NSUInteger = 4;
+
+/// An immutable Foundation string.
+///
+/// Lengths are counted in UTF-8 bytes; `NSString`'s own `length` counts UTF-16 code units,
+/// which differs for any character outside of ASCII.
+pub struct NSString(ObjectPointer);
+handle!(NSString);
+
+impl NSString {
+    pub unsafe fn new(string: &str) -> NSString {
+        let ns_string = ObjectPointer(msg_send![class!(NSString), alloc]);
+        NSString::from_ptr(msg_send![
+            ns_string,
+            initWithBytes:string.as_ptr()
+            length:string.len() as NSUInteger
+            encoding:NS_UTF8_STRING_ENCODING
+        ])
+    }
+    /// Copies the contents of a string which may be nil and is not owned, such as one
+    /// returned by a getter.
+    pub(crate) unsafe fn copy_from(string: ObjectPointer) -> Option<String> {
+        if string.0.is_null() {
+            return None;
+        }
+        check_autorelease_pool("An NSString getter");
+        Some(NSString::read(string))
+    }
+    unsafe fn read(string: ObjectPointer) -> String {
+        let length: NSUInteger =
+            msg_send![string, lengthOfBytesUsingEncoding: NS_UTF8_STRING_ENCODING];
+        if length == 0 {
+            return String::new();
+        }
+        let bytes: *const u8 = msg_send![string, UTF8String];
+        let utf8 = if bytes.is_null() {
+            None
+        } else {
+            Some(std::slice::from_raw_parts(bytes, length as usize))
+        };
+        decode_utf8(length as usize, utf8)
+    }
+    /// The length of the string in UTF-8 bytes.
+    pub unsafe fn get_len(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), lengthOfBytesUsingEncoding: NS_UTF8_STRING_ENCODING]
+    }
+    pub unsafe fn is_empty(&self) -> bool {
+        self.get_len() == 0
+    }
+}
+
+impl Display for NSString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&unsafe { NSString::read(self.get_ptr()) })
+    }
+}
+
+impl Debug for NSString {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&unsafe { NSString::read(self.get_ptr()) }, f)
+    }
+}
+
+impl Object for NSString {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        NSString(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+/// Decodes the first `length` bytes of the buffer returned by `UTF8String`, which is null
+/// for strings with no UTF-8 representation, such as those containing lone surrogates.
+///
+/// Exactly `length` bytes are read rather than up to the first nul, which a string may
+/// contain, unless the buffer is shorter.
+fn decode_utf8(length: usize, utf8: Option<&[u8]>) -> String {
+    match utf8 {
+        Some(utf8) if length > 0 => {
+            String::from_utf8_lossy(&utf8[..length.min(utf8.len())]).into_owned()
+        }
+        _ => String::new(),
+    }
+}
+
+/// An immutable Foundation array of objects of one type.
+pub struct NSArray<T: Object> {
+    array: ObjectPointer,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Object> NSArray<T> {
+    pub unsafe fn new(objects: &[&T]) -> NSArray<T> {
+        let pointers = objects.iter().map(|obj| obj.get_ptr()).collect::<Vec<_>>();
+        let array = ObjectPointer(msg_send![class!(NSArray), alloc]);
+        NSArray::from_ptr(msg_send![
+            array,
+            initWithObjects:pointers.as_ptr()
+            count:pointers.len() as NSUInteger
+        ])
+    }
+    /// Copies the objects of an array which may be nil and is not owned, such as one
+    /// returned by a getter.
+    pub(crate) unsafe fn copy_from(array: ObjectPointer) -> Vec<T> {
+        if array.0.is_null() {
+            return Vec::new();
+        }
+        NSArray::<T>::from_ptr(msg_send![array, retain]).to_vec()
+    }
+    pub unsafe fn get_len(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), count]
+    }
+    pub unsafe fn is_empty(&self) -> bool {
+        self.get_len() == 0
+    }
+    pub unsafe fn get(&self, index: NSUInteger) -> Option<T> {
+        if index >= self.get_len() {
+            return None;
+        }
+        let obj = ObjectPointer(msg_send![self.get_ptr(), objectAtIndex: index]);
+        Some(T::from_ptr(msg_send![obj, retain]))
+    }
+    pub unsafe fn iter(&self) -> NSArrayIter<'_, T> {
+        NSArrayIter {
+            array: self,
+            index: 0,
+            len: self.get_len(),
+        }
+    }
+    pub unsafe fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+}
+
+impl<T: Object> Drop for NSArray<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let _: () = msg_send![self.array, release];
+        }
+    }
+}
+
+impl<T: Object> Clone for NSArray<T> {
+    fn clone(&self) -> Self {
+        unsafe { NSArray::from_ptr(msg_send![self.array, retain]) }
+    }
+}
+
+impl<T: Object> Object for NSArray<T> {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        NSArray {
+            array: ptr,
+            _marker: PhantomData,
+        }
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.array
+    }
+}
+
+/// Iterates over the objects of an `NSArray`, retaining each.
+pub struct NSArrayIter<'a, T: Object> {
+    array: &'a NSArray<T>,
+    index: NSUInteger,
+    len: NSUInteger,
+}
+
+impl<'a, T: Object> Iterator for NSArrayIter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.index >= self.len {
+            return None;
+        }
+        let obj = unsafe {
+            let obj = ObjectPointer(msg_send![self.array.get_ptr(), objectAtIndex: self.index]);
+            T::from_ptr(msg_send![obj, retain])
+        };
+        self.index += 1;
+        Some(obj)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.len - self.index) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, T: Object> ExactSizeIterator for NSArrayIter<'a, T> {}
+
+/// An immutable Foundation dictionary.
+///
+/// Keys are copied into the dictionary, so they must conform to `NSCopying`, as strings
+/// and numbers do.
+pub struct NSDictionary(ObjectPointer);
+handle!(NSDictionary);
+
+impl NSDictionary {
+    pub unsafe fn new<'a, K: Object + 'a, V: Object + 'a, I>(entries: I) -> NSDictionary
+    where
+        I: IntoIterator<Item = (&'a K, &'a V)>,
+    {
+        let (keys, values): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .map(|(key, value)| (key.get_ptr(), value.get_ptr()))
+            .unzip();
+        let dictionary = ObjectPointer(msg_send![class!(NSDictionary), alloc]);
+        NSDictionary::from_ptr(msg_send![
+            dictionary,
+            initWithObjects:values.as_ptr()
+            forKeys:keys.as_ptr()
+            count:keys.len() as NSUInteger
+        ])
+    }
+    /// Creates a dictionary of strings from a map, or any other iterator of pairs.
+    pub unsafe fn from_strings<K: AsRef<str>, V: AsRef<str>, I>(entries: I) -> NSDictionary
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let (keys, values): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .map(|(key, value)| (NSString::new(key.as_ref()), NSString::new(value.as_ref())))
+            .unzip();
+        NSDictionary::new(keys.iter().zip(values.iter()))
+    }
+    pub unsafe fn get_len(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), count]
+    }
+    pub unsafe fn is_empty(&self) -> bool {
+        self.get_len() == 0
+    }
+    /// The caller must know the type of the value stored under the key.
+    pub unsafe fn get<K: Object, V: Object>(&self, key: &K) -> Option<V> {
+        let value = ObjectPointer(msg_send![self.get_ptr(), objectForKey:key.get_ptr()]);
+        if value.0.is_null() {
+            None
+        } else {
+            Some(V::from_ptr(msg_send![value, retain]))
+        }
+    }
+}
+
+impl Object for NSDictionary {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        NSDictionary(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+/// A Foundation number, which may be read as any numeric type.
+pub struct NSNumber(ObjectPointer);
+handle!(NSNumber);
+
+impl NSNumber {
+    pub unsafe fn new_bool(value: bool) -> NSNumber {
+        let number = ObjectPointer(msg_send![class!(NSNumber), alloc]);
+        NSNumber::from_ptr(msg_send![number, initWithBool: value])
+    }
+    pub unsafe fn new_integer(value: NSInteger) -> NSNumber {
+        let number = ObjectPointer(msg_send![class!(NSNumber), alloc]);
+        NSNumber::from_ptr(msg_send![number, initWithLongLong: value])
+    }
+    pub unsafe fn new_unsigned_integer(value: NSUInteger) -> NSNumber {
+        let number = ObjectPointer(msg_send![class!(NSNumber), alloc]);
+        NSNumber::from_ptr(msg_send![number, initWithUnsignedLongLong: value])
+    }
+    pub unsafe fn new_double(value: f64) -> NSNumber {
+        let number = ObjectPointer(msg_send![class!(NSNumber), alloc]);
+        NSNumber::from_ptr(msg_send![number, initWithDouble: value])
+    }
+    pub unsafe fn get_bool(&self) -> bool {
+        msg_send![self.get_ptr(), boolValue]
+    }
+    pub unsafe fn get_integer(&self) -> NSInteger {
+        msg_send![self.get_ptr(), longLongValue]
+    }
+    pub unsafe fn get_unsigned_integer(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), unsignedLongLongValue]
+    }
+    pub unsafe fn get_double(&self) -> f64 {
+        msg_send![self.get_ptr(), doubleValue]
+    }
+}
+
+impl Object for NSNumber {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        NSNumber(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+/// A Foundation URL. Only file URLs are supported.
+pub struct NSURL(ObjectPointer);
+handle!(NSURL);
+
+impl NSURL {
+    /// Relative paths are resolved against the current directory.
+    pub unsafe fn from_path(path: &Path) -> NSURL {
+        let path = NSString::new(&path.to_string_lossy());
+        let url = ObjectPointer(msg_send![class!(NSURL), alloc]);
+        NSURL::from_ptr(msg_send![url, initFileURLWithPath:path.get_ptr()])
+    }
+    /// Returns the path of a file URL.
+    pub unsafe fn get_path(&self) -> Option<PathBuf> {
+        NSString::copy_from(ObjectPointer(msg_send![self.get_ptr(), path])).map(PathBuf::from)
+    }
+}
+
+impl Object for NSURL {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        NSURL(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+/// A Foundation bundle, such as the app bundle containing the executable.
+pub struct NSBundle(ObjectPointer);
+handle!(NSBundle);
+
+impl NSBundle {
+    /// The bundle containing the executable, or the executable's directory when it is not
+    /// in a bundle.
+    pub unsafe fn main() -> NSBundle {
+        NSBundle::from_ptr({
+            let bundle = ObjectPointer(msg_send![class!(NSBundle), mainBundle]);
+            msg_send![bundle, retain]
+        })
+    }
+    pub unsafe fn from_path(path: &Path) -> Option<NSBundle> {
+        let path = NSString::new(&path.to_string_lossy());
+        let bundle = ObjectPointer(msg_send![class!(NSBundle), alloc]);
+        let bundle = ObjectPointer(msg_send![bundle, initWithPath:path.get_ptr()]);
+        if bundle.0.is_null() {
+            None
+        } else {
+            Some(NSBundle::from_ptr(bundle))
+        }
+    }
+    pub unsafe fn get_bundle_path(&self) -> Option<PathBuf> {
+        NSString::copy_from(ObjectPointer(msg_send![self.get_ptr(), bundlePath])).map(PathBuf::from)
+    }
+    pub unsafe fn get_resource_path(&self) -> Option<PathBuf> {
+        NSString::copy_from(ObjectPointer(msg_send![self.get_ptr(), resourcePath]))
+            .map(PathBuf::from)
+    }
+}
+
+impl Object for NSBundle {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        NSBundle(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(string: &str) {
+        let ns_string = unsafe { NSString::new(string) };
+        assert_eq!(unsafe { ns_string.get_len() }, string.len() as NSUInteger);
+        assert_eq!(unsafe { ns_string.is_empty() }, string.is_empty());
+        assert_eq!(ns_string.to_string(), string);
+        assert_eq!(format!("{:?}", ns_string), format!("{:?}", string));
+        assert_eq!(
+            unsafe { NSString::copy_from(ns_string.get_ptr()) }.as_deref(),
+            Some(string)
+        );
+    }
+
+    #[test]
+    fn ascii() {
+        round_trip("");
+        round_trip("vertex_main");
+    }
+
+    #[test]
+    fn non_ascii() {
+        // lengths are in UTF-8 bytes rather than UTF-16 code units
+        round_trip("caf\u{E9}");
+        round_trip("\u{1F600} \u{4E09}\u{89D2}\u{5F62}");
+    }
+
+    #[test]
+    fn embedded_nuls() {
+        round_trip("a\0b\0");
+        round_trip("\0\u{E9}\0");
+    }
+
+    #[test]
+    fn nil() {
+        assert_eq!(
+            unsafe { NSString::copy_from(ObjectPointer(std::ptr::null_mut())) },
+            None
+        );
+    }
+
+    #[test]
+    fn decode_truncated() {
+        // a buffer shorter than the length, or none at all
+        assert_eq!(decode_utf8(5, Some(b"ab")), "ab");
+        assert_eq!(decode_utf8(3, Some(b"")), "");
+        assert_eq!(decode_utf8(3, None), "");
+        // a sequence cut off by the length or by the buffer
+        assert_eq!(decode_utf8(2, Some("a\u{E9}".as_bytes())), "a\u{FFFD}");
+        assert_eq!(
+            decode_utf8(4, Some(&"a\u{E9}".as_bytes()[..2])),
+            "a\u{FFFD}"
+        );
+        assert_eq!(decode_utf8(3, Some(b"a\xFFb\0")), "a\u{FFFD}b");
+    }
+}
@@ -0,0 +1,694 @@
+use crate::{
+    MTLCompileOptions, MTLComputePipelineDescriptor, MTLComputePipelineState, MTLDevice,
+    MTLLibrary, MTLRenderPipelineDescriptor, MTLRenderPipelineState,
+};
+use iron_oxide_build::MetalBuild;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+/// A message from the Metal compiler about a line of a source.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        };
+        write!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.file, self.line, self.column, severity, self.message
+        )
+    }
+}
+
+/// Parses the `file:line:column: severity: message` lines of the Metal compiler's output,
+/// skipping the source excerpts between them.
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    output.lines().filter_map(parse_diagnostic).collect()
+}
+
+fn parse_diagnostic(line: &str) -> Option<Diagnostic> {
+    let (index, severity, label) = [
+        (Severity::Error, ": error: "),
+        (Severity::Warning, ": warning: "),
+        (Severity::Note, ": note: "),
+    ]
+    .iter()
+    .filter_map(|&(severity, label)| line.find(label).map(|index| (index, severity, label)))
+    .min_by_key(|&(index, _, _)| index)?;
+    let mut location = line[..index].rsplitn(3, ':');
+    let column = location.next()?.trim().parse().ok()?;
+    let line_number = location.next()?.trim().parse().ok()?;
+    let file = location.next()?.trim();
+    Some(Diagnostic {
+        file: file.to_string(),
+        line: line_number,
+        column,
+        severity,
+        message: line[index + label.len()..].trim().to_string(),
+    })
+}
+
+/// Compiles the libraries and builds the pipelines of a `ShaderWatcher`.
+///
+/// `MetalShaderCompiler` compiles with a device; other implementations allow the watcher
+/// to be used without one.
+pub trait ShaderCompiler {
+    type Library;
+    /// What a pipeline is rebuilt from whenever its library is recompiled.
+    type PipelineDescriptor;
+    type Pipeline;
+
+    /// Compiles a source, returning the compiler's output on failure.
+    unsafe fn compile(&self, source: &str) -> Result<Self::Library, String>;
+    unsafe fn new_pipeline(
+        &self,
+        library: &Self::Library,
+        descriptor: &Self::PipelineDescriptor,
+    ) -> Result<Self::Pipeline, String>;
+}
+
+/// Describes a pipeline whose functions are looked up by name in each new library.
+pub enum PipelineRecipe {
+    Render {
+        descriptor: MTLRenderPipelineDescriptor,
+        vertex_function: String,
+        fragment_function: Option<String>,
+    },
+    Compute {
+        descriptor: MTLComputePipelineDescriptor,
+        function: String,
+    },
+}
+
+pub enum ReloadablePipeline {
+    Render(MTLRenderPipelineState),
+    Compute(MTLComputePipelineState),
+}
+
+impl ReloadablePipeline {
+    pub fn get_render(&self) -> Option<&MTLRenderPipelineState> {
+        match self {
+            ReloadablePipeline::Render(pipeline) => Some(pipeline),
+            ReloadablePipeline::Compute(_) => None,
+        }
+    }
+    pub fn get_compute(&self) -> Option<&MTLComputePipelineState> {
+        match self {
+            ReloadablePipeline::Compute(pipeline) => Some(pipeline),
+            ReloadablePipeline::Render(_) => None,
+        }
+    }
+}
+
+/// Compiles sources with `new_library_with_source`.
+pub struct MetalShaderCompiler {
+    pub device: MTLDevice,
+    pub options: MTLCompileOptions,
+}
+
+impl MetalShaderCompiler {
+    pub unsafe fn new(device: &MTLDevice) -> MetalShaderCompiler {
+        MetalShaderCompiler {
+            device: device.clone(),
+            options: MTLCompileOptions::new(),
+        }
+    }
+}
+
+impl ShaderCompiler for MetalShaderCompiler {
+    type Library = MTLLibrary;
+    type PipelineDescriptor = PipelineRecipe;
+    type Pipeline = ReloadablePipeline;
+
+    unsafe fn compile(&self, source: &str) -> Result<MTLLibrary, String> {
+        self.device
+            .new_library_with_source(source, &self.options)
+            .map_err(|err| err.get_localized_description())
+    }
+
+    unsafe fn new_pipeline(
+        &self,
+        library: &MTLLibrary,
+        descriptor: &PipelineRecipe,
+    ) -> Result<ReloadablePipeline, String> {
+        let function = |name: &str| {
+            library
+                .new_function_with_name(name)
+                .ok_or_else(|| format!("{} was not found in the library", name))
+        };
+        match descriptor {
+            PipelineRecipe::Render {
+                descriptor,
+                vertex_function,
+                fragment_function,
+            } => {
+                descriptor.set_vertex_function(&function(vertex_function)?);
+                if let Some(fragment_function) = fragment_function {
+                    descriptor.set_fragment_function(&function(fragment_function)?);
+                }
+                self.device
+                    .new_render_pipeline_state_with_descriptor(descriptor)
+                    .map(ReloadablePipeline::Render)
+                    .map_err(|err| err.get_localized_description())
+            }
+            PipelineRecipe::Compute {
+                descriptor,
+                function: name,
+            } => {
+                descriptor.set_compute_function(&function(name)?);
+                self.device
+                    .new_compute_pipeline_state_with_descriptor(descriptor)
+                    .map(ReloadablePipeline::Compute)
+                    .map_err(|err| err.get_localized_description())
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProgramId(usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineId(usize);
+
+/// A program which failed to recompile, or whose pipelines failed to rebuild.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ReloadFailure {
+    pub program: ProgramId,
+    pub output: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Display for ReloadFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.diagnostics.is_empty() {
+            return f.write_str(&self.output);
+        }
+        for diagnostic in &self.diagnostics {
+            writeln!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+/// The modification times of a program's sources and headers when it was compiled.
+type Dependencies = Vec<(PathBuf, Option<SystemTime>)>;
+
+struct Program<L> {
+    build: MetalBuild,
+    dependencies: Dependencies,
+    library: L,
+    pipelines: Vec<usize>,
+}
+
+struct Pipeline<D, P> {
+    descriptor: D,
+    pipeline: P,
+}
+
+struct Reload<L, P> {
+    program: usize,
+    library: L,
+    pipelines: Vec<(usize, P)>,
+}
+
+/// Recompiles programs when their sources or headers change, and rebuilds their pipelines.
+///
+/// A program is the sources of a `MetalBuild`, compiled into one library with their headers
+/// and macros inlined. Nothing is replaced until `apply`, so that pipelines change only
+/// between frames, and a program which fails to compile keeps its previous library and
+/// pipelines.
+///
+/// ```no_run
+/// use iron_oxide::*;
+/// use iron_oxide_build::MetalBuild;
+///
+/// unsafe fn draw(device: &MTLDevice, descriptor: MTLRenderPipelineDescriptor) {
+///     let mut watcher = ShaderWatcher::new(MetalShaderCompiler::new(device));
+///     let program = watcher
+///         .add_program(MetalBuild::new("quad").source("shaders/quad.metal"))
+///         .unwrap();
+///     let pipeline = watcher
+///         .add_pipeline(
+///             program,
+///             PipelineRecipe::Render {
+///                 descriptor,
+///                 vertex_function: "quad_v".to_string(),
+///                 fragment_function: Some("quad_f".to_string()),
+///             },
+///         )
+///         .unwrap();
+///     loop {
+///         watcher.begin_frame();
+///         let state = watcher.get_pipeline(pipeline).get_render().unwrap();
+///         // Encode with the state.
+///     }
+/// }
+/// ```
+pub struct ShaderWatcher<C: ShaderCompiler> {
+    compiler: C,
+    programs: Vec<Program<C::Library>>,
+    pipelines: Vec<Pipeline<C::PipelineDescriptor, C::Pipeline>>,
+    pending: Vec<Reload<C::Library, C::Pipeline>>,
+}
+
+impl<C: ShaderCompiler> ShaderWatcher<C> {
+    pub fn new(compiler: C) -> ShaderWatcher<C> {
+        ShaderWatcher {
+            compiler,
+            programs: Vec::new(),
+            pipelines: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+    pub fn get_compiler(&self) -> &C {
+        &self.compiler
+    }
+    /// Compiles the program, returning the compiler's output on failure.
+    pub unsafe fn add_program(&mut self, build: MetalBuild) -> Result<ProgramId, String> {
+        let (dependencies, source) = read_program(&build)?;
+        let library = self.compiler.compile(&source)?;
+        self.programs.push(Program {
+            build,
+            dependencies,
+            library,
+            pipelines: Vec::new(),
+        });
+        Ok(ProgramId(self.programs.len() - 1))
+    }
+    /// Builds a pipeline, which is rebuilt whenever its program is recompiled.
+    pub unsafe fn add_pipeline(
+        &mut self,
+        program: ProgramId,
+        descriptor: C::PipelineDescriptor,
+    ) -> Result<PipelineId, String> {
+        let pipeline = self
+            .compiler
+            .new_pipeline(&self.programs[program.0].library, &descriptor)?;
+        self.pipelines.push(Pipeline {
+            descriptor,
+            pipeline,
+        });
+        let id = self.pipelines.len() - 1;
+        self.programs[program.0].pipelines.push(id);
+        Ok(PipelineId(id))
+    }
+    pub fn get_library(&self, program: ProgramId) -> &C::Library {
+        &self.programs[program.0].library
+    }
+    pub fn get_pipeline(&self, pipeline: PipelineId) -> &C::Pipeline {
+        &self.pipelines[pipeline.0].pipeline
+    }
+    /// Returns the sources and headers of the program as of its last compilation.
+    pub fn get_dependencies(&self, program: ProgramId) -> Vec<&Path> {
+        self.programs[program.0]
+            .dependencies
+            .iter()
+            .map(|(path, _)| path.as_path())
+            .collect()
+    }
+    /// Returns the programs with a source or header which was modified, created or
+    /// removed since they were last compiled.
+    pub fn get_changed_programs(&self) -> Vec<ProgramId> {
+        (0..self.programs.len())
+            .filter(|&index| {
+                self.programs[index]
+                    .dependencies
+                    .iter()
+                    .any(|(path, modified)| get_modified(path) != *modified)
+            })
+            .map(ProgramId)
+            .collect()
+    }
+    /// Whether reloaded programs are waiting for `apply`.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+    /// Recompiles changed programs and rebuilds their pipelines, to be swapped in by
+    /// `apply`. Failures are logged and returned, and leave the program as it was until its
+    /// files change again.
+    pub unsafe fn poll(&mut self) -> Vec<ReloadFailure> {
+        let mut failures = Vec::new();
+        for ProgramId(index) in self.get_changed_programs() {
+            match self.reload(index) {
+                Ok(reload) => {
+                    self.pending.retain(|pending| pending.program != index);
+                    self.pending.push(reload);
+                }
+                Err(output) => {
+                    let failure = ReloadFailure {
+                        program: ProgramId(index),
+                        diagnostics: parse_diagnostics(&output),
+                        output,
+                    };
+                    log::log!(
+                        log::Level::Error,
+                        "{} failed to reload; keeping its previous pipelines:\n{}",
+                        self.programs[index].build.get_name(),
+                        failure
+                    );
+                    failures.push(failure);
+                }
+            }
+        }
+        failures
+    }
+    /// Swaps in every reloaded library and pipeline, returning whether there were any.
+    ///
+    /// Call this between frames, when no command buffer is being encoded with the previous
+    /// pipelines.
+    pub fn apply(&mut self) -> bool {
+        let applied = !self.pending.is_empty();
+        for reload in self.pending.drain(..) {
+            self.programs[reload.program].library = reload.library;
+            for (index, pipeline) in reload.pipelines {
+                self.pipelines[index].pipeline = pipeline;
+            }
+            log::log!(
+                log::Level::Info,
+                "Reloaded {}",
+                self.programs[reload.program].build.get_name()
+            );
+        }
+        applied
+    }
+    /// Polls, then applies. Call this at the start of each frame.
+    pub unsafe fn begin_frame(&mut self) -> bool {
+        self.poll();
+        self.apply()
+    }
+
+    unsafe fn reload(&mut self, index: usize) -> Result<Reload<C::Library, C::Pipeline>, String> {
+        let program = &mut self.programs[index];
+        // Record the files before compiling, so that a failed compilation isn't retried
+        // until they change again.
+        let (dependencies, source) = match read_program(&program.build) {
+            Ok(read) => read,
+            Err(err) => {
+                program.dependencies = get_dependencies(program.build.get_sources());
+                return Err(err);
+            }
+        };
+        program.dependencies = dependencies;
+        let library = self.compiler.compile(&source)?;
+        let mut pipelines = Vec::with_capacity(program.pipelines.len());
+        for &pipeline in &program.pipelines {
+            let descriptor = &self.pipelines[pipeline].descriptor;
+            pipelines.push((pipeline, self.compiler.new_pipeline(&library, descriptor)?));
+        }
+        Ok(Reload {
+            program: index,
+            library,
+            pipelines,
+        })
+    }
+}
+
+fn get_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn get_dependencies(paths: &[PathBuf]) -> Dependencies {
+    paths
+        .iter()
+        .map(|path| (path.clone(), get_modified(path)))
+        .collect()
+}
+
+/// Returns the program's files with their modification times, and its source.
+fn read_program(build: &MetalBuild) -> Result<(Dependencies, String), String> {
+    let dependencies = build.get_dependencies().map_err(|err| err.to_string())?;
+    let dependencies = get_dependencies(&dependencies);
+    let source = build.get_embedded_source().map_err(|err| err.to_string())?;
+    Ok((dependencies, source))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    /// Compiles a source into itself, failing on `BROKEN`, and builds a pipeline for each
+    /// function name found in the library.
+    #[derive(Default)]
+    struct FakeCompiler {
+        compiles: Cell<usize>,
+    }
+
+    impl ShaderCompiler for FakeCompiler {
+        type Library = String;
+        type PipelineDescriptor = String;
+        type Pipeline = String;
+
+        unsafe fn compile(&self, source: &str) -> Result<String, String> {
+            self.compiles.set(self.compiles.get() + 1);
+            if source.contains("BROKEN") {
+                Err(
+                    "/shaders/quad.metal:2:5: error: use of undeclared identifier 'BROKEN'\n\
+                     \x20   BROKEN;\n\
+                     \x20   ^\n\
+                     /shaders/quad.metal:1:1: note: expanded from here\n"
+                        .to_string(),
+                )
+            } else {
+                Ok(source.to_string())
+            }
+        }
+        unsafe fn new_pipeline(
+            &self,
+            library: &String,
+            function: &String,
+        ) -> Result<String, String> {
+            if library.contains(function.as_str()) {
+                Ok(format!("{} from {} bytes", function, library.len()))
+            } else {
+                Err(format!("{} was not found in the library", function))
+            }
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "iron-oxide-hotreload-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes the file with a new modification time, as writes within the timestamp
+    /// resolution of the file system may not change it.
+    fn write(path: &Path, contents: &str) {
+        static SECONDS: AtomicU64 = AtomicU64::new(1_000_000_000);
+        std::fs::write(path, contents).unwrap();
+        let modified =
+            SystemTime::UNIX_EPOCH + Duration::from_secs(SECONDS.fetch_add(1, Ordering::SeqCst));
+        std::fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    /// A program of `quad.metal`, which includes `common.h`, with pipelines for `quad_v`
+    /// and `quad_f`.
+    unsafe fn watch(
+        dir: &Path,
+    ) -> (
+        ShaderWatcher<FakeCompiler>,
+        ProgramId,
+        PipelineId,
+        PipelineId,
+    ) {
+        write(&dir.join("common.h"), "// quad_v\n");
+        write(
+            &dir.join("quad.metal"),
+            "#include \"common.h\"\n// quad_f\n",
+        );
+        let mut watcher = ShaderWatcher::new(FakeCompiler::default());
+        let program = watcher
+            .add_program(MetalBuild::new("quad").source(dir.join("quad.metal")))
+            .unwrap();
+        let vertex = watcher.add_pipeline(program, "quad_v".to_string()).unwrap();
+        let fragment = watcher.add_pipeline(program, "quad_f".to_string()).unwrap();
+        (watcher, program, vertex, fragment)
+    }
+
+    #[test]
+    fn diagnostics() {
+        let output = "quad.metal:3:5: error: use of undeclared identifier 'x'\n\
+                      \x20   x = 1;\n\
+                      \x20   ^\n\
+                      common.h:10:2: warning: unused variable: 'y'\n\
+                      1 error generated.\n";
+        let diagnostics = parse_diagnostics(output);
+        assert_eq!(
+            diagnostics,
+            vec![
+                Diagnostic {
+                    file: "quad.metal".to_string(),
+                    line: 3,
+                    column: 5,
+                    severity: Severity::Error,
+                    message: "use of undeclared identifier 'x'".to_string(),
+                },
+                Diagnostic {
+                    file: "common.h".to_string(),
+                    line: 10,
+                    column: 2,
+                    severity: Severity::Warning,
+                    message: "unused variable: 'y'".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "quad.metal:3:5: error: use of undeclared identifier 'x'"
+        );
+        assert!(parse_diagnostics("error: no input files\nquad.metal:x:1: error: a").is_empty());
+    }
+
+    #[test]
+    fn paths_containing_colons() {
+        let diagnostics = parse_diagnostics(
+            "C:\\shaders\\quad.metal:3:5: note: here\n\
+             /tmp/a:b/quad.metal:12:1: error: expected ';'\n",
+        );
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file, "C:\\shaders\\quad.metal");
+        assert_eq!(diagnostics[0].severity, Severity::Note);
+        assert_eq!(diagnostics[1].file, "/tmp/a:b/quad.metal");
+        assert_eq!((diagnostics[1].line, diagnostics[1].column), (12, 1));
+        assert_eq!(diagnostics[1].message, "expected ';'");
+    }
+
+    #[test]
+    fn header_edits_reload() {
+        let dir = temp_dir("header");
+        unsafe {
+            let (mut watcher, program, vertex, _) = watch(&dir);
+            assert_eq!(
+                watcher.get_dependencies(program),
+                vec![dir.join("quad.metal"), dir.join("common.h")]
+            );
+            assert!(watcher.get_changed_programs().is_empty());
+            assert!(!watcher.begin_frame());
+
+            write(&dir.join("common.h"), "// quad_v, edited\n");
+            assert_eq!(watcher.get_changed_programs(), vec![program]);
+            assert!(watcher.begin_frame());
+            assert!(watcher.get_library(program).contains("edited"));
+            assert!(watcher.get_pipeline(vertex).starts_with("quad_v from"));
+            assert!(!watcher.begin_frame());
+
+            // headers included for the first time are watched too
+            write(&dir.join("lighting.h"), "// lighting\n");
+            write(
+                &dir.join("quad.metal"),
+                "#include \"common.h\"\n#include \"lighting.h\"\n// quad_f\n",
+            );
+            assert!(watcher.begin_frame());
+            assert_eq!(watcher.get_dependencies(program).len(), 3);
+            write(&dir.join("lighting.h"), "// lighting, edited\n");
+            assert_eq!(watcher.get_changed_programs(), vec![program]);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failures_keep_previous_pipelines() {
+        let dir = temp_dir("failure");
+        unsafe {
+            let (mut watcher, program, vertex, fragment) = watch(&dir);
+            let previous = (
+                watcher.get_pipeline(vertex).clone(),
+                watcher.get_pipeline(fragment).clone(),
+            );
+
+            write(&dir.join("quad.metal"), "#include \"common.h\"\nBROKEN\n");
+            let failures = watcher.poll();
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].program, program);
+            assert_eq!(failures[0].diagnostics.len(), 2);
+            assert_eq!(failures[0].diagnostics[0].line, 2);
+            assert!(!watcher.apply());
+            assert_eq!(watcher.get_pipeline(vertex), &previous.0);
+            assert_eq!(watcher.get_pipeline(fragment), &previous.1);
+
+            // not retried until the files change again
+            let compiles = watcher.get_compiler().compiles.get();
+            assert!(watcher.get_changed_programs().is_empty());
+            assert!(watcher.poll().is_empty());
+            assert_eq!(watcher.get_compiler().compiles.get(), compiles);
+
+            // a pipeline which fails to build fails the whole program
+            write(&dir.join("quad.metal"), "#include \"common.h\"\n");
+            let failures = watcher.poll();
+            assert_eq!(failures[0].output, "quad_f was not found in the library");
+            assert!(failures[0].diagnostics.is_empty());
+            assert!(!watcher.apply());
+            assert_eq!(watcher.get_pipeline(vertex), &previous.0);
+
+            write(
+                &dir.join("quad.metal"),
+                "#include \"common.h\"\n// quad_f, fixed\n",
+            );
+            assert!(watcher.begin_frame());
+            assert_ne!(watcher.get_pipeline(fragment), &previous.1);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn swaps_only_when_applied() {
+        let dir = temp_dir("apply");
+        unsafe {
+            let (mut watcher, program, vertex, _) = watch(&dir);
+            let previous = watcher.get_pipeline(vertex).clone();
+
+            write(&dir.join("common.h"), "// quad_v, edited\n");
+            assert!(watcher.poll().is_empty());
+            assert!(watcher.has_pending());
+            assert_eq!(watcher.get_pipeline(vertex), &previous);
+            assert!(!watcher.get_library(program).contains("edited"));
+
+            // a second edit before the frame boundary replaces the pending reload
+            write(&dir.join("common.h"), "// quad_v, edited twice\n");
+            assert!(watcher.poll().is_empty());
+            assert_eq!(watcher.get_pipeline(vertex), &previous);
+
+            assert!(watcher.apply());
+            assert!(!watcher.has_pending());
+            assert!(watcher.get_library(program).contains("edited twice"));
+            assert_ne!(watcher.get_pipeline(vertex), &previous);
+            assert!(!watcher.apply());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,140 @@
+use crate::import_objc_macros::*;
+use crate::misc::{get_label, set_label};
+use crate::{NSString, Object};
+
+/// Whether labels and debug groups are passed on to Metal.
+///
+/// With the `strip-labels` feature, release builds skip them entirely.
+pub(crate) const LABELS_ENABLED: bool = cfg!(any(debug_assertions, not(feature = "strip-labels")));
+
+/// Objects which may be named, so that they can be told apart in GPU captures and
+/// debugging tools.
+pub trait Labeled: Object {
+    unsafe fn set_label(&self, label: &str) {
+        set_label(self.get_ptr(), label)
+    }
+    unsafe fn get_label(&self) -> Option<String> {
+        get_label(self.get_ptr())
+    }
+}
+
+/// Objects whose commands may be grouped under names in GPU captures.
+///
+/// Every pushed group must be popped before the object finishes encoding.
+pub trait DebugGroups: Object {
+    unsafe fn push_debug_group(&self, name: &str) {
+        if !LABELS_ENABLED {
+            return;
+        }
+        let string = NSString::new(name);
+        msg_send![self.get_ptr(), pushDebugGroup:string.get_ptr()]
+    }
+    unsafe fn pop_debug_group(&self) {
+        if LABELS_ENABLED {
+            msg_send![self.get_ptr(), popDebugGroup]
+        }
+    }
+    /// Pushes a debug group which is popped when the returned guard is dropped.
+    unsafe fn debug_group(&self, name: &str) -> DebugGroup<'_, Self>
+    where
+        Self: Sized,
+    {
+        self.push_debug_group(name);
+        DebugGroup { target: self }
+    }
+}
+
+/// Pops its debug group when dropped.
+pub struct DebugGroup<'a, T: DebugGroups> {
+    target: &'a T,
+}
+
+impl<'a, T: DebugGroups> Drop for DebugGroup<'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.target.pop_debug_group() }
+    }
+}
+
+#[cfg(all(test, not(any(target_os = "macos", target_os = "ios"))))]
+mod tests {
+    use super::*;
+    use crate::fakeobjc::FakeObject;
+    use crate::{MTLBuffer, MTLCommandEncoder, MTLComputeCommandEncoder};
+
+    /// The messages the object was sent if labels are enabled, or else none.
+    fn expected(sent: &[&str]) -> Vec<String> {
+        if LABELS_ENABLED {
+            sent.iter().map(|message| message.to_string()).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn labels_enabled() {
+        assert_eq!(
+            LABELS_ENABLED,
+            cfg!(debug_assertions) || !cfg!(feature = "strip-labels")
+        );
+    }
+
+    #[test]
+    fn set_label() {
+        let buffer = unsafe { MTLBuffer::from_ptr(FakeObject::plain(Vec::new())) };
+        unsafe { buffer.set_label("vertices \u{1F600}") };
+        assert_eq!(
+            FakeObject::get(buffer.get_ptr()).get_sent(),
+            expected(&["setLabel: vertices \u{1F600}"])
+        );
+    }
+
+    #[test]
+    fn debug_groups() {
+        let encoder = unsafe { MTLComputeCommandEncoder::from_ptr(FakeObject::plain(Vec::new())) };
+        unsafe {
+            let _outer = encoder.debug_group("frame");
+            {
+                let _inner = encoder.debug_group("shadows");
+                encoder.insert_debug_signpost("cascade 0");
+            }
+            encoder.insert_debug_signpost("lighting");
+        }
+        assert_eq!(
+            FakeObject::get(encoder.get_ptr()).get_sent(),
+            expected(&[
+                "pushDebugGroup: frame",
+                "pushDebugGroup: shadows",
+                "insertDebugSignpost: cascade 0",
+                "popDebugGroup",
+                "insertDebugSignpost: lighting",
+                "popDebugGroup",
+            ])
+        );
+    }
+
+    #[test]
+    fn debug_groups_pop_on_early_return() {
+        fn encode(encoder: &MTLComputeCommandEncoder, fail: bool) -> Result<(), ()> {
+            let _group = unsafe { encoder.debug_group("blur") };
+            if fail {
+                return Err(());
+            }
+            unsafe { encoder.insert_debug_signpost("blurred") };
+            Ok(())
+        }
+
+        let encoder = unsafe { MTLComputeCommandEncoder::from_ptr(FakeObject::plain(Vec::new())) };
+        assert!(encode(&encoder, true).is_err());
+        assert!(encode(&encoder, false).is_ok());
+        assert_eq!(
+            FakeObject::get(encoder.get_ptr()).get_sent(),
+            expected(&[
+                "pushDebugGroup: blur",
+                "popDebugGroup",
+                "pushDebugGroup: blur",
+                "insertDebugSignpost: blurred",
+                "popDebugGroup",
+            ])
+        );
+    }
+}
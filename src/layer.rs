@@ -1,26 +1,25 @@
+use crate::autorelease::check_autorelease_pool;
 use crate::import_objc_macros::*;
-use crate::{
-    handle, CAMetalDrawable, CGSize, MTLDevice, MTLPixelFormat, Object, ObjectPointer,
-    ObjectPointerMarker,
-};
-use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
+use crate::{handle, CAMetalDrawable, CGSize, MTLDevice, MTLPixelFormat, Object, ObjectPointer};
+use raw_window_handle::HasRawWindowHandle;
 
 pub trait CreateCAMetalLayer {
     unsafe fn set_layer(&self, layer: &CAMetalLayer);
 }
 
 impl<T: HasRawWindowHandle> CreateCAMetalLayer for T {
+    #[cfg_attr(not(target_os = "macos"), allow(unused_variables))]
     unsafe fn set_layer(&self, layer: &CAMetalLayer) {
-        let ns_view = match self.raw_window_handle() {
-            RawWindowHandle::MacOS(handle) => {
-                ObjectPointer(handle.ns_view as *mut ObjectPointerMarker)
+        match self.raw_window_handle() {
+            #[cfg(target_os = "macos")]
+            raw_window_handle::RawWindowHandle::MacOS(handle) => {
+                let ns_view = ObjectPointer(handle.ns_view as *mut _);
+                msg_send![ns_view, setLayer:layer.get_ptr()]
             }
             _ => unimplemented!(
                 "CAMetalLayers can only be set for macOS RawWindowHandles right now."
             ),
-        };
-
-        msg_send![ns_view, setLayer:layer.get_ptr()]
+        }
     }
 }
 
@@ -50,6 +49,7 @@ impl CAMetalLayer {
         msg_send![self.get_ptr(), setWantsExtendedDynamicRangeContent: wants]
     }
     pub unsafe fn next_drawable(&self) -> Option<CAMetalDrawable> {
+        check_autorelease_pool("next_drawable");
         let dw = ObjectPointer(msg_send![self.get_ptr(), nextDrawable]);
         if dw.0.is_null() {
             None
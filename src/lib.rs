@@ -13,35 +13,82 @@
 //!
 //! See the examples directory for examples.
 //!
+//! Shaders may be compiled ahead of time by the `iron-oxide-build` crate, and loaded with
+//! `EmbeddedLibrary`. With the `hot-reload` feature, `ShaderWatcher` recompiles them and rebuilds
+//! their pipelines while the program runs.
+//!
+//! With the `strip-labels` feature, labels and debug groups are skipped in release builds.
+//!
 //! This crate is licensed under the MIT license.
 
 use objc::Message;
 use std::ops::Deref;
 
+mod autorelease;
+mod capabilities;
+mod capture;
 mod commandbuffer;
 mod commandqueue;
+mod compression;
+mod conversion;
+mod counters;
 mod depthstencil;
 mod device;
 mod drawable;
 mod encoder;
+#[cfg(all(test, not(any(target_os = "macos", target_os = "ios"))))]
+mod fakeobjc;
+mod foundation;
+#[cfg(feature = "hot-reload")]
+mod hotreload;
+mod labels;
 mod layer;
 mod library;
+#[cfg(feature = "loader")]
+mod loader;
+mod metallib;
 mod misc;
 mod pipeline;
+mod pipelinecache;
+mod pixelformat;
+mod profiler;
+mod readback;
 mod resource;
+#[cfg(feature = "safe")]
+pub mod safe;
 mod sampler;
+mod selection;
+mod statecache;
+pub use autorelease::*;
+pub use capabilities::*;
+pub use capture::*;
 pub use commandbuffer::*;
 pub use commandqueue::*;
+pub use conversion::*;
+pub use counters::*;
 pub use depthstencil::*;
 pub use device::*;
 pub use drawable::*;
 pub use encoder::*;
+pub use foundation::*;
+#[cfg(feature = "hot-reload")]
+pub use hotreload::*;
+pub use labels::*;
 pub use layer::*;
 pub use library::*;
+#[cfg(feature = "loader")]
+pub use loader::*;
+pub use metallib::*;
 pub use misc::*;
 pub use pipeline::*;
+pub use pipelinecache::*;
+pub use pixelformat::*;
+pub use profiler::*;
+pub use readback::*;
 pub use resource::*;
 pub use sampler::*;
+pub use selection::*;
+pub use statecache::*;
 
 /// Reexports important macros for sending messages from the `objc` crate:
 /// - `msg_send`
@@ -73,10 +120,22 @@ pub trait Array<T: Object>: Object {
         use crate::import_objc_macros::*;
         msg_send![self.get_ptr(), setObject:obj.get_ptr() atIndexedSubscript:index]
     }
+    /// Gets from the array the object at the specified index.
+    unsafe fn get_object_at_indexed_subscript(&self, index: NSUInteger) -> T {
+        use crate::import_objc_macros::*;
+        T::from_ptr({
+            let obj = ObjectPointer(msg_send![self.get_ptr(), objectAtIndexedSubscript: index]);
+            msg_send![obj, retain]
+        })
+    }
 }
 
 /// Represents an Objective C object.
 ///
+/// Handles to objects which Metal documents as thread-safe (devices, command queues,
+/// resources, libraries, functions and states) are `Send` and `Sync`; command buffers are
+/// only `Send`.
+///
 /// # Requirements
 ///
 /// There *must* be for an implementation of Object an implementation of Drop using
@@ -86,6 +145,11 @@ pub trait Object: Drop {
     ///
     /// The pointer provided *must* be a valid pointer to an Objective C object which can
     /// accept the messages which the used implementation of Object will send.
+    ///
+    /// The object takes ownership of one reference to the pointer, which it releases when
+    /// dropped. Objects returned by methods whose names begin with `new`, `copy`, `alloc`
+    /// or `mutableCopy`, and by functions whose names contain `Create` or `Copy`, are
+    /// already owned; any other object must be retained before being passed here.
     unsafe fn from_ptr(ptr: ObjectPointer) -> Self
     where
         Self: Sized;
@@ -113,6 +177,9 @@ pub type CGFloat = f64;
 /// `get_ptr` method returns. This ensures that the object to which the implementor points
 /// lives only for the lifetime of the implementor.
 ///
+/// Also provides an implementation of `Clone` which increments the reference count, so
+/// that the clone shares the object.
+///
 /// # Requirements
 ///
 /// The ident passed into `handle!` must be the correct local name of a struct or enum which
@@ -142,9 +209,20 @@ macro_rules! handle {
     ($name:ident) => {
         impl Drop for $name {
             fn drop(&mut self) {
-                use crate::import_objc_macros::*;
+                use $crate::import_objc_macros::*;
+                unsafe {
+                    let _: () = msg_send![$crate::Object::get_ptr(self), release];
+                }
+            }
+        }
+        impl Clone for $name {
+            fn clone(&self) -> Self {
+                use $crate::import_objc_macros::*;
                 unsafe {
-                    let _: () = msg_send![self.get_ptr(), release];
+                    <$name as $crate::Object>::from_ptr(msg_send![
+                        $crate::Object::get_ptr(self),
+                        retain
+                    ])
                 }
             }
         }
@@ -1,35 +1,21 @@
 use crate::import_objc_macros::*;
-use crate::{handle, DeviceCreated, NSUInteger, Object, ObjectPointer};
+use crate::{handle, DeviceCreated, Labeled, NSArray, NSString, Object, ObjectPointer};
 
 pub struct MTLLibrary(ObjectPointer);
 handle!(MTLLibrary);
+unsafe impl Send for MTLLibrary {}
+unsafe impl Sync for MTLLibrary {}
 
 impl MTLLibrary {
-    pub unsafe fn get_function_names(&self) -> Vec<&str> {
-        let names = ObjectPointer(msg_send![self.get_ptr(), functionNames]);
-        let length: NSUInteger = msg_send![names, count];
-        (0..length)
-            .map(|index| {
-                let obj = ObjectPointer(msg_send![names, objectAtIndex: index]);
-                let obj = ObjectPointer(msg_send![obj, retain]);
-                let bytes: *const u8 = msg_send![obj, UTF8String];
-                let len: NSUInteger = msg_send![obj, length];
-                let bytes = std::slice::from_raw_parts(bytes, len as usize);
-                std::str::from_utf8(bytes).unwrap()
-            })
+    pub unsafe fn get_function_names(&self) -> Vec<String> {
+        NSArray::<NSString>::copy_from(ObjectPointer(msg_send![self.get_ptr(), functionNames]))
+            .iter()
+            .map(|name| name.to_string())
             .collect()
     }
     pub unsafe fn new_function_with_name(&self, name: &str) -> Option<MTLFunction> {
-        let cls = class!(NSString);
-        let bytes = name.as_ptr();
-        let st = ObjectPointer(msg_send![cls, alloc]);
-        let st = ObjectPointer(msg_send![
-           st,
-           initWithBytes:bytes
-           length:name.len()
-           encoding:4 // UTF-8
-        ]);
-        let obj = ObjectPointer(msg_send![self.get_ptr(), newFunctionWithName: st]);
+        let name = NSString::new(name);
+        let obj = ObjectPointer(msg_send![self.get_ptr(), newFunctionWithName:name.get_ptr()]);
         if obj.0.is_null() {
             None
         } else {
@@ -53,6 +39,22 @@ impl Object for MTLLibrary {
 
 impl DeviceCreated for MTLLibrary {}
 
+impl Labeled for MTLLibrary {}
+
+/// The domain of errors created by libraries and by devices loading them.
+pub const MTL_LIBRARY_ERROR_DOMAIN: &str = "MTLLibraryErrorDomain";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum MTLLibraryError {
+    Unsupported = 1,
+    Internal = 2,
+    CompileFailure = 3,
+    CompileWarning = 4,
+    FunctionNotFound = 5,
+    FileNotFound = 6,
+}
+
 #[repr(u64)]
 pub enum MTLFunctionType {
     Vertex = 1,
@@ -62,17 +64,15 @@ pub enum MTLFunctionType {
 
 pub struct MTLFunction(ObjectPointer);
 handle!(MTLFunction);
+unsafe impl Send for MTLFunction {}
+unsafe impl Sync for MTLFunction {}
 
 impl MTLFunction {
     pub unsafe fn get_function_type(&self) -> MTLFunctionType {
         msg_send![self.get_ptr(), functionType]
     }
-    pub unsafe fn get_name(&self) -> &str {
-        let string = ObjectPointer(msg_send![self.get_ptr(), name]);
-        let bytes: *const u8 = msg_send![string, UTF8String];
-        let len: NSUInteger = msg_send![string, length];
-        let bytes = std::slice::from_raw_parts(bytes, len as usize);
-        std::str::from_utf8(bytes).unwrap()
+    pub unsafe fn get_name(&self) -> String {
+        NSString::copy_from(ObjectPointer(msg_send![self.get_ptr(), name])).unwrap_or_default()
     }
 }
 
@@ -91,6 +91,8 @@ impl Object for MTLFunction {
 
 impl DeviceCreated for MTLFunction {}
 
+impl Labeled for MTLFunction {}
+
 #[repr(u64)]
 pub enum MTLLanguageVersion {
     V10 = 1 << 16,
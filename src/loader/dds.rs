@@ -0,0 +1,278 @@
+use super::{
+    read_bytes, read_u32, texture_type, validate_subresources, LevelLayout, LoaderError,
+    ASTC_FORMATS,
+};
+use crate::{MTLPixelFormat, MTLSize, NSUInteger, TextureImage, TextureSubresource};
+
+/// The first 4 bytes of every DDS file.
+pub const DDS_MAGIC: [u8; 4] = *b"DDS ";
+
+const DDS_HEADER_LENGTH: usize = 4 + 124;
+const DDS_DX10_HEADER_LENGTH: usize = 20;
+
+const DDPF_ALPHA: u32 = 0x2;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x2_0000;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const DDS_DIMENSION_TEXTURE1D: u32 = 2;
+const DDS_DIMENSION_TEXTURE3D: u32 = 4;
+
+/// Maps a `DXGI_FORMAT`, as stored in the DX10 header of DDS files, to a
+/// `MTLPixelFormat`.
+pub fn pixel_format_from_dxgi(format: u32) -> Option<MTLPixelFormat> {
+    use MTLPixelFormat::*;
+
+    Some(match format {
+        2 => RGBA32Float,
+        3 => RGBA32Uint,
+        4 => RGBA32Sint,
+        10 => RGBA16Float,
+        11 => RGBA16Unorm,
+        12 => RGBA16Uint,
+        13 => RGBA16Snorm,
+        14 => RGBA16Sint,
+        16 => RG32Float,
+        17 => RG32Uint,
+        18 => RG32Sint,
+        20 => Depth32Float_Stencil8,
+        24 => RGB10A2Unorm,
+        25 => RGB10A2Uint,
+        26 => RG11B10Float,
+        28 => RGBA8Unorm,
+        29 => RGBA8Unorm_sRGB,
+        30 => RGBA8Uint,
+        31 => RGBA8Snorm,
+        32 => RGBA8Sint,
+        34 => RG16Float,
+        35 => RG16Unorm,
+        36 => RG16Uint,
+        37 => RG16Snorm,
+        38 => RG16Sint,
+        40 => Depth32Float,
+        41 => R32Float,
+        42 => R32Uint,
+        43 => R32Sint,
+        45 => Depth24Unorm_Stencil8,
+        49 => RG8Unorm,
+        50 => RG8Uint,
+        51 => RG8Snorm,
+        52 => RG8Sint,
+        54 => R16Float,
+        55 => Depth16Unorm,
+        56 => R16Unorm,
+        57 => R16Uint,
+        58 => R16Snorm,
+        59 => R16Sint,
+        61 => R8Unorm,
+        62 => R8Uint,
+        63 => R8Snorm,
+        64 => R8Sint,
+        65 => A8Unorm,
+        67 => RGB9E5Float,
+        70 | 71 => BC1_RGBA,
+        72 => BC1_RGBA_sRGB,
+        73 | 74 => BC2_RGBA,
+        75 => BC2_RGBA_sRGB,
+        76 | 77 => BC3_RGBA,
+        78 => BC3_RGBA_sRGB,
+        79 | 80 => BC4_RUnorm,
+        81 => BC4_RSnorm,
+        82 | 83 => BC5_RGUnorm,
+        84 => BC5_RGSnorm,
+        85 => B5G6R5Unorm,
+        86 => BGR5A1Unorm,
+        87 => BGRA8Unorm,
+        90 | 91 => BGRA8Unorm_sRGB,
+        94 | 95 => BC6H_RGBUfloat,
+        96 => BC6H_RGBFloat,
+        97 | 98 => BC7_RGBAUnorm,
+        99 => BC7_RGBAUnorm_sRGB,
+        // each ASTC block size has a typeless, a unorm and an sRGB format, 4 codes apart
+        133..=187 if (format - 133) % 4 != 3 => {
+            let (linear, srgb) = ASTC_FORMATS[(format - 133) as usize / 4];
+            if (format - 133) % 4 == 2 {
+                srgb
+            } else {
+                linear
+            }
+        }
+        _ => return None,
+    })
+}
+
+/// Maps a DDS four character code, or a legacy `D3DFORMAT` stored in its place, to a
+/// `MTLPixelFormat`.
+pub fn pixel_format_from_dds_four_cc(four_cc: u32) -> Option<MTLPixelFormat> {
+    use MTLPixelFormat::*;
+
+    Some(match &four_cc.to_le_bytes() {
+        b"DXT1" => BC1_RGBA,
+        b"DXT2" | b"DXT3" => BC2_RGBA,
+        b"DXT4" | b"DXT5" => BC3_RGBA,
+        b"ATI1" | b"BC4U" => BC4_RUnorm,
+        b"BC4S" => BC4_RSnorm,
+        b"ATI2" | b"BC5U" => BC5_RGUnorm,
+        b"BC5S" => BC5_RGSnorm,
+        _ => match four_cc {
+            36 => RGBA16Unorm,
+            110 => RGBA16Snorm,
+            111 => R16Float,
+            112 => RG16Float,
+            113 => RGBA16Float,
+            114 => R32Float,
+            115 => RG32Float,
+            116 => RGBA32Float,
+            _ => return None,
+        },
+    })
+}
+
+/// Maps the channel masks of an uncompressed DDS pixel format to a `MTLPixelFormat`.
+fn pixel_format_from_dds_masks(flags: u32, bits: u32, masks: [u32; 4]) -> Option<MTLPixelFormat> {
+    use MTLPixelFormat::*;
+
+    Some(match (bits, masks) {
+        (32, [0xFF, 0xFF00, 0xFF_0000, _]) => RGBA8Unorm,
+        (32, [0xFF_0000, 0xFF00, 0xFF, _]) => BGRA8Unorm,
+        (32, [0x3FF, 0xF_FC00, 0x3FF0_0000, _]) => RGB10A2Unorm,
+        (32, [0xFFFF, 0xFFFF_0000, 0, 0]) => RG16Unorm,
+        (32, [0xFFFF_FFFF, 0, 0, 0]) => R32Float,
+        (16, [0xF800, 0x7E0, 0x1F, 0]) => B5G6R5Unorm,
+        (16, [0x7C00, 0x3E0, 0x1F, 0x8000]) => BGR5A1Unorm,
+        (16, [0xFF, 0xFF00, 0, 0]) if flags & DDPF_LUMINANCE == 0 => RG8Unorm,
+        (16, [0xFFFF, 0, 0, 0]) => R16Unorm,
+        (8, [0xFF, 0, 0, 0]) => R8Unorm,
+        (8, [0, 0, 0, 0xFF]) if flags & DDPF_ALPHA != 0 => A8Unorm,
+        _ => return None,
+    })
+}
+
+impl TextureImage {
+    /// Parses a DDS file, with or without a DX10 header.
+    pub fn from_dds(bytes: &[u8]) -> Result<TextureImage, LoaderError> {
+        if !bytes.starts_with(&DDS_MAGIC) {
+            return Err(LoaderError::UnknownContainer);
+        }
+        let field = |offset: usize| read_u32(bytes, 4 + offset, false);
+        let height = field(8)?;
+        let width = field(12)?;
+        let depth = field(20)?;
+        let levels = field(24)?.max(1);
+        let pf_flags = field(76)?;
+        let four_cc = field(80)?;
+        let caps2 = field(108)?;
+
+        let mut offset = DDS_HEADER_LENGTH;
+        let (format, height, depth, layers, faces) =
+            if pf_flags & DDPF_FOURCC != 0 && &four_cc.to_le_bytes() == b"DX10" {
+                let dx10 = |index: usize| read_u32(bytes, DDS_HEADER_LENGTH + index * 4, false);
+                let dxgi_format = dx10(0)?;
+                let dimension = dx10(1)?;
+                let cube = dx10(2)? & DDS_RESOURCE_MISC_TEXTURECUBE != 0;
+                let array_size = dx10(3)?.max(1);
+                offset += DDS_DX10_HEADER_LENGTH;
+
+                let format = pixel_format_from_dxgi(dxgi_format).ok_or_else(|| {
+                    LoaderError::UnsupportedFormat(format!("DXGI format {}", dxgi_format))
+                })?;
+                (
+                    format,
+                    if dimension == DDS_DIMENSION_TEXTURE1D {
+                        0
+                    } else {
+                        height
+                    },
+                    if dimension == DDS_DIMENSION_TEXTURE3D {
+                        depth
+                    } else {
+                        0
+                    },
+                    // single layer textures are not arrays
+                    if array_size > 1 { array_size } else { 0 },
+                    if cube { 6 } else { 1 },
+                )
+            } else {
+                let format = if pf_flags & DDPF_FOURCC != 0 {
+                    pixel_format_from_dds_four_cc(four_cc)
+                } else if pf_flags & (DDPF_RGB | DDPF_LUMINANCE | DDPF_ALPHA) != 0 {
+                    let masks = [field(88)?, field(92)?, field(96)?, field(100)?];
+                    pixel_format_from_dds_masks(pf_flags, field(84)?, masks)
+                } else {
+                    None
+                };
+                let format = format.ok_or_else(|| {
+                    LoaderError::UnsupportedFormat(format!("DDS pixel format {:#X}", four_cc))
+                })?;
+                (
+                    format,
+                    height,
+                    if caps2 & DDSCAPS2_VOLUME != 0 {
+                        depth
+                    } else {
+                        0
+                    },
+                    0,
+                    if caps2 & DDSCAPS2_CUBEMAP != 0 { 6 } else { 1 },
+                )
+            };
+        if width == 0 {
+            return Err(LoaderError::Malformed("invalid DDS dimensions"));
+        }
+
+        let texture_type = texture_type(height, depth, layers, faces);
+        let array_length = layers.max(1) as NSUInteger;
+        let slices = array_length * faces as NSUInteger;
+        let (width, height, depth) = (
+            width as NSUInteger,
+            height.max(1) as NSUInteger,
+            depth.max(1) as NSUInteger,
+        );
+
+        validate_subresources(
+            format,
+            MTLSize {
+                width,
+                height,
+                depth,
+            },
+            levels,
+            slices,
+            bytes.len().saturating_sub(offset),
+        )?;
+
+        // DDS stores every level of a slice before moving on to the next slice
+        let mut subresources = Vec::with_capacity((levels as NSUInteger * slices) as usize);
+        for slice in 0..slices {
+            for level in 0..levels as NSUInteger {
+                let layout = LevelLayout::new(format, width, height, depth, level)?;
+                let length = layout.length() as usize;
+                subresources.push(TextureSubresource {
+                    level,
+                    slice,
+                    width: layout.size.width,
+                    height: layout.size.height,
+                    depth: layout.size.depth,
+                    bytes_per_row: layout.bytes_per_row,
+                    bytes_per_image: layout.bytes_per_image(),
+                    data: read_bytes(bytes, offset, length)?.to_vec(),
+                });
+                offset += length;
+            }
+        }
+        subresources.sort_by_key(|sub| (sub.level, sub.slice));
+
+        Ok(TextureImage {
+            format,
+            texture_type,
+            width,
+            height,
+            depth,
+            mipmap_level_count: levels as NSUInteger,
+            array_length,
+            subresources,
+        })
+    }
+}
@@ -0,0 +1,234 @@
+use super::{
+    read_bytes, read_u32, texture_type, validate_subresources, LevelLayout, LoaderError,
+    ASTC_FORMATS,
+};
+use crate::{align_up, MTLPixelFormat, MTLSize, NSUInteger, TextureImage, TextureSubresource};
+
+/// The first 12 bytes of every KTX (version 1) file.
+pub const KTX_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+const KTX_HEADER_LENGTH: usize = 64;
+const KTX_ENDIANNESS: u32 = 0x0403_0201;
+
+/// Maps an OpenGL sized internal format, as stored in KTX files, to a `MTLPixelFormat`.
+pub fn pixel_format_from_gl(internal_format: u32) -> Option<MTLPixelFormat> {
+    use MTLPixelFormat::*;
+
+    Some(match internal_format {
+        0x803C => A8Unorm,
+        0x8229 => R8Unorm,
+        0x8FBD => R8Unorm_sRGB,
+        0x8F94 => R8Snorm,
+        0x8232 => R8Uint,
+        0x8231 => R8Sint,
+        0x822A => R16Unorm,
+        0x8F98 => R16Snorm,
+        0x8234 => R16Uint,
+        0x8233 => R16Sint,
+        0x822D => R16Float,
+        0x822B => RG8Unorm,
+        0x8FBE => RG8Unorm_sRGB,
+        0x8F95 => RG8Snorm,
+        0x8238 => RG8Uint,
+        0x8237 => RG8Sint,
+        0x8D62 => B5G6R5Unorm,
+        0x8236 => R32Uint,
+        0x8235 => R32Sint,
+        0x822E => R32Float,
+        0x822C => RG16Unorm,
+        0x8F99 => RG16Snorm,
+        0x823A => RG16Uint,
+        0x8239 => RG16Sint,
+        0x822F => RG16Float,
+        0x8058 => RGBA8Unorm,
+        0x8C43 => RGBA8Unorm_sRGB,
+        0x8F97 => RGBA8Snorm,
+        0x8D7C => RGBA8Uint,
+        0x8D8E => RGBA8Sint,
+        0x93A1 => BGRA8Unorm,
+        0x8059 => RGB10A2Unorm,
+        0x906F => RGB10A2Uint,
+        0x8C3A => RG11B10Float,
+        0x8C3D => RGB9E5Float,
+        0x823C => RG32Uint,
+        0x823B => RG32Sint,
+        0x8230 => RG32Float,
+        0x805B => RGBA16Unorm,
+        0x8F9B => RGBA16Snorm,
+        0x8D76 => RGBA16Uint,
+        0x8D88 => RGBA16Sint,
+        0x881A => RGBA16Float,
+        0x8D70 => RGBA32Uint,
+        0x8D82 => RGBA32Sint,
+        0x8814 => RGBA32Float,
+        0x83F0 | 0x83F1 => BC1_RGBA,
+        0x8C4C | 0x8C4D => BC1_RGBA_sRGB,
+        0x83F2 => BC2_RGBA,
+        0x8C4E => BC2_RGBA_sRGB,
+        0x83F3 => BC3_RGBA,
+        0x8C4F => BC3_RGBA_sRGB,
+        0x8DBB => BC4_RUnorm,
+        0x8DBC => BC4_RSnorm,
+        0x8DBD => BC5_RGUnorm,
+        0x8DBE => BC5_RGSnorm,
+        0x8E8E => BC6H_RGBFloat,
+        0x8E8F => BC6H_RGBUfloat,
+        0x8E8C => BC7_RGBAUnorm,
+        0x8E8D => BC7_RGBAUnorm_sRGB,
+        0x8C01 => PVRTC_RGB_2BPP,
+        0x8A54 => PVRTC_RGB_2BPP_sRGB,
+        0x8C00 => PVRTC_RGB_4BPP,
+        0x8A55 => PVRTC_RGB_4BPP_sRGB,
+        0x8C03 => PVRTC_RGBA_2BPP,
+        0x8A56 => PVRTC_RGBA_2BPP_sRGB,
+        0x8C02 => PVRTC_RGBA_4BPP,
+        0x8A57 => PVRTC_RGBA_4BPP_sRGB,
+        0x9270 => EAC_R11Unorm,
+        0x9271 => EAC_R11Snorm,
+        0x9272 => EAC_RG11Unorm,
+        0x9273 => EAC_RG11Snorm,
+        0x9278 => EAC_RGBA8,
+        0x9279 => EAC_RGBA8_sRGB,
+        // ETC1 is a subset of ETC2
+        0x8D64 | 0x9274 => ETC2_RGB8,
+        0x9275 => ETC2_RGB8_sRGB,
+        0x9276 => ETC2_RGB8A1,
+        0x9277 => ETC2_RGB8A1_sRGB,
+        0x93B0..=0x93BD => ASTC_FORMATS[(internal_format - 0x93B0) as usize].0,
+        0x93D0..=0x93DD => ASTC_FORMATS[(internal_format - 0x93D0) as usize].1,
+        0x81A5 => Depth16Unorm,
+        0x8CAC => Depth32Float,
+        0x8D48 => Stencil8,
+        0x88F0 => Depth24Unorm_Stencil8,
+        0x8CAD => Depth32Float_Stencil8,
+        _ => return None,
+    })
+}
+
+impl TextureImage {
+    /// Parses a KTX (version 1) file.
+    pub fn from_ktx(bytes: &[u8]) -> Result<TextureImage, LoaderError> {
+        if !bytes.starts_with(&KTX_IDENTIFIER) {
+            return Err(LoaderError::UnknownContainer);
+        }
+        let big_endian = match read_u32(bytes, 12, false)? {
+            KTX_ENDIANNESS => false,
+            e if e.swap_bytes() == KTX_ENDIANNESS => true,
+            _ => return Err(LoaderError::Malformed("invalid KTX endianness")),
+        };
+        let field = |index: usize| read_u32(bytes, 16 + index * 4, big_endian);
+        let type_size = field(1)?;
+        let internal_format = field(3)?;
+        let width = field(5)?;
+        let height = field(6)?;
+        let depth = field(7)?;
+        let layers = field(8)?;
+        let faces = field(9)?;
+        let levels = field(10)?.max(1);
+        let key_value_length = field(11)? as usize;
+
+        let format = pixel_format_from_gl(internal_format).ok_or_else(|| {
+            LoaderError::UnsupportedFormat(format!("GL internal format {:#X}", internal_format))
+        })?;
+        if width == 0 || (faces != 1 && faces != 6) {
+            return Err(LoaderError::Malformed("invalid KTX dimensions"));
+        }
+
+        let texture_type = texture_type(height, depth, layers, faces);
+        let array_length = layers.max(1) as NSUInteger;
+        let slices_per_level = array_length * faces as NSUInteger;
+        let (width, height, depth) = (
+            width as NSUInteger,
+            height.max(1) as NSUInteger,
+            depth.max(1) as NSUInteger,
+        );
+
+        let mut offset = KTX_HEADER_LENGTH + key_value_length;
+        validate_subresources(
+            format,
+            MTLSize {
+                width,
+                height,
+                depth,
+            },
+            levels,
+            slices_per_level,
+            bytes.len().saturating_sub(offset),
+        )?;
+        let mut subresources =
+            Vec::with_capacity((levels as NSUInteger * slices_per_level) as usize);
+        for level in 0..levels as NSUInteger {
+            let image_size = read_u32(bytes, offset, big_endian)? as NSUInteger;
+            offset += 4;
+
+            let layout = LevelLayout::new(format, width, height, depth, level)?;
+            let (size, rows) = (layout.size, layout.rows);
+            // non-array cube maps store the size of one face and pad each face to 4 bytes,
+            // everything else stores the whole level unpadded
+            let cube_padding = layers == 0 && faces == 6;
+            let slice_length = if cube_padding {
+                image_size
+            } else {
+                image_size / slices_per_level
+            };
+            // uncompressed rows are padded to 4 bytes, but not every writer follows this
+            let bytes_per_row = [align_up(layout.bytes_per_row, 4), layout.bytes_per_row]
+                .iter()
+                .cloned()
+                .find(|stride| {
+                    stride
+                        .checked_mul(rows)
+                        .and_then(|bytes| bytes.checked_mul(size.depth))
+                        == Some(slice_length)
+                })
+                .ok_or(LoaderError::Malformed(
+                    "KTX image size does not match its format",
+                ))?;
+            let bytes_per_image = bytes_per_row * rows;
+
+            for slice in 0..slices_per_level {
+                let mut data = read_bytes(bytes, offset, slice_length as usize)?.to_vec();
+                if big_endian {
+                    swap_endianness(&mut data, type_size);
+                }
+                offset += if cube_padding {
+                    align_up(slice_length, 4)
+                } else {
+                    slice_length
+                } as usize;
+                subresources.push(TextureSubresource {
+                    level,
+                    slice,
+                    width: size.width,
+                    height: size.height,
+                    depth: size.depth,
+                    bytes_per_row,
+                    bytes_per_image,
+                    data,
+                });
+            }
+            offset = align_up(offset as NSUInteger, 4) as usize;
+        }
+
+        Ok(TextureImage {
+            format,
+            texture_type,
+            width,
+            height,
+            depth,
+            mipmap_level_count: levels as NSUInteger,
+            array_length,
+            subresources,
+        })
+    }
+}
+
+/// Reverses the byte order of every `type_size` sized element of `data`.
+fn swap_endianness(data: &mut [u8], type_size: u32) {
+    if type_size == 2 || type_size == 4 {
+        data.chunks_exact_mut(type_size as usize)
+            .for_each(|element| element.reverse());
+    }
+}
@@ -0,0 +1,205 @@
+use super::{
+    read_bytes, read_u32, read_u64, texture_type, validate_subresources, LevelLayout, LoaderError,
+    ASTC_FORMATS,
+};
+use crate::{MTLPixelFormat, MTLSize, NSUInteger, TextureImage, TextureSubresource};
+
+/// The first 12 bytes of every KTX2 file.
+pub const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+const KTX2_LEVEL_INDEX_OFFSET: usize = 80;
+const KTX2_LEVEL_INDEX_STRIDE: usize = 24;
+
+/// Maps a `VkFormat`, as stored in KTX2 files, to a `MTLPixelFormat`.
+pub fn pixel_format_from_vk(format: u32) -> Option<MTLPixelFormat> {
+    use MTLPixelFormat::*;
+
+    Some(match format {
+        4 => B5G6R5Unorm,
+        9 => R8Unorm,
+        10 => R8Snorm,
+        13 => R8Uint,
+        14 => R8Sint,
+        15 => R8Unorm_sRGB,
+        16 => RG8Unorm,
+        17 => RG8Snorm,
+        20 => RG8Uint,
+        21 => RG8Sint,
+        22 => RG8Unorm_sRGB,
+        37 => RGBA8Unorm,
+        38 => RGBA8Snorm,
+        41 => RGBA8Uint,
+        42 => RGBA8Sint,
+        43 => RGBA8Unorm_sRGB,
+        44 => BGRA8Unorm,
+        50 => BGRA8Unorm_sRGB,
+        58 => BGR10A2Unorm,
+        64 => RGB10A2Unorm,
+        68 => RGB10A2Uint,
+        70 => R16Unorm,
+        71 => R16Snorm,
+        74 => R16Uint,
+        75 => R16Sint,
+        76 => R16Float,
+        77 => RG16Unorm,
+        78 => RG16Snorm,
+        81 => RG16Uint,
+        82 => RG16Sint,
+        83 => RG16Float,
+        91 => RGBA16Unorm,
+        92 => RGBA16Snorm,
+        95 => RGBA16Uint,
+        96 => RGBA16Sint,
+        97 => RGBA16Float,
+        98 => R32Uint,
+        99 => R32Sint,
+        100 => R32Float,
+        101 => RG32Uint,
+        102 => RG32Sint,
+        103 => RG32Float,
+        107 => RGBA32Uint,
+        108 => RGBA32Sint,
+        109 => RGBA32Float,
+        122 => RG11B10Float,
+        123 => RGB9E5Float,
+        124 => Depth16Unorm,
+        126 => Depth32Float,
+        127 => Stencil8,
+        129 => Depth24Unorm_Stencil8,
+        130 => Depth32Float_Stencil8,
+        131 | 133 => BC1_RGBA,
+        132 | 134 => BC1_RGBA_sRGB,
+        135 => BC2_RGBA,
+        136 => BC2_RGBA_sRGB,
+        137 => BC3_RGBA,
+        138 => BC3_RGBA_sRGB,
+        139 => BC4_RUnorm,
+        140 => BC4_RSnorm,
+        141 => BC5_RGUnorm,
+        142 => BC5_RGSnorm,
+        143 => BC6H_RGBUfloat,
+        144 => BC6H_RGBFloat,
+        145 => BC7_RGBAUnorm,
+        146 => BC7_RGBAUnorm_sRGB,
+        147 => ETC2_RGB8,
+        148 => ETC2_RGB8_sRGB,
+        149 => ETC2_RGB8A1,
+        150 => ETC2_RGB8A1_sRGB,
+        151 => EAC_RGBA8,
+        152 => EAC_RGBA8_sRGB,
+        153 => EAC_R11Unorm,
+        154 => EAC_R11Snorm,
+        155 => EAC_RG11Unorm,
+        156 => EAC_RG11Snorm,
+        157..=184 => {
+            let (linear, srgb) = ASTC_FORMATS[(format - 157) as usize / 2];
+            if format % 2 == 1 {
+                linear
+            } else {
+                srgb
+            }
+        }
+        1_000_054_000 => PVRTC_RGBA_2BPP,
+        1_000_054_001 => PVRTC_RGBA_4BPP,
+        1_000_054_004 => PVRTC_RGBA_2BPP_sRGB,
+        1_000_054_005 => PVRTC_RGBA_4BPP_sRGB,
+        _ => return None,
+    })
+}
+
+impl TextureImage {
+    /// Parses a KTX2 file.
+    ///
+    /// Supercompressed (Basis Universal, Zstandard or zlib) files are not supported.
+    pub fn from_ktx2(bytes: &[u8]) -> Result<TextureImage, LoaderError> {
+        if !bytes.starts_with(&KTX2_IDENTIFIER) {
+            return Err(LoaderError::UnknownContainer);
+        }
+        let field = |index: usize| read_u32(bytes, 12 + index * 4, false);
+        let vk_format = field(0)?;
+        let width = field(2)?;
+        let height = field(3)?;
+        let depth = field(4)?;
+        let layers = field(5)?;
+        let faces = field(6)?;
+        let levels = field(7)?.max(1);
+        let supercompression = field(8)?;
+
+        if supercompression != 0 {
+            return Err(LoaderError::UnsupportedFormat(format!(
+                "KTX2 supercompression scheme {}",
+                supercompression
+            )));
+        }
+        let format = pixel_format_from_vk(vk_format)
+            .ok_or_else(|| LoaderError::UnsupportedFormat(format!("VkFormat {}", vk_format)))?;
+        if width == 0 || (faces != 1 && faces != 6) {
+            return Err(LoaderError::Malformed("invalid KTX2 dimensions"));
+        }
+
+        let texture_type = texture_type(height, depth, layers, faces);
+        let array_length = layers.max(1) as NSUInteger;
+        let slices_per_level = array_length * faces as NSUInteger;
+        let (width, height, depth) = (
+            width as NSUInteger,
+            height.max(1) as NSUInteger,
+            depth.max(1) as NSUInteger,
+        );
+
+        validate_subresources(
+            format,
+            MTLSize {
+                width,
+                height,
+                depth,
+            },
+            levels,
+            slices_per_level,
+            bytes.len(),
+        )?;
+        let mut subresources =
+            Vec::with_capacity((levels as NSUInteger * slices_per_level) as usize);
+        for level in 0..levels as NSUInteger {
+            let index = KTX2_LEVEL_INDEX_OFFSET + level as usize * KTX2_LEVEL_INDEX_STRIDE;
+            let level_offset = read_u64(bytes, index)? as usize;
+            let level_length = read_u64(bytes, index + 8)?;
+
+            let layout = LevelLayout::new(format, width, height, depth, level)?;
+            let slice_length = layout.length();
+            if slice_length.checked_mul(slices_per_level) != Some(level_length) {
+                return Err(LoaderError::Malformed(
+                    "KTX2 level length does not match its format",
+                ));
+            }
+
+            for slice in 0..slices_per_level {
+                let offset = level_offset
+                    .checked_add((slice * slice_length) as usize)
+                    .ok_or(LoaderError::Truncated)?;
+                subresources.push(TextureSubresource {
+                    level,
+                    slice,
+                    width: layout.size.width,
+                    height: layout.size.height,
+                    depth: layout.size.depth,
+                    bytes_per_row: layout.bytes_per_row,
+                    bytes_per_image: layout.bytes_per_image(),
+                    data: read_bytes(bytes, offset, slice_length as usize)?.to_vec(),
+                });
+            }
+        }
+
+        Ok(TextureImage {
+            format,
+            texture_type,
+            width,
+            height,
+            depth,
+            mipmap_level_count: levels as NSUInteger,
+            array_length,
+            subresources,
+        })
+    }
+}
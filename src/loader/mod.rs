@@ -0,0 +1,327 @@
+use crate::{
+    MTLDevice, MTLPixelFormat, MTLRegion, MTLSize, MTLTexture, MTLTextureDescriptor,
+    MTLTextureType, MTLTextureUsage, NSUInteger,
+};
+use enumflags2::BitFlags;
+use std::fmt::{Display, Formatter};
+use std::os::raw::c_void;
+use std::path::Path;
+
+mod dds;
+mod ktx;
+mod ktx2;
+mod png;
+pub use self::png::*;
+pub use dds::*;
+pub use ktx::*;
+pub use ktx2::*;
+
+#[derive(Debug)]
+pub enum LoaderError {
+    Io(std::io::Error),
+    Png(::png::DecodingError),
+    /// The data does not start with the signature of a supported container.
+    UnknownContainer,
+    /// The data ends before the container says it should.
+    Truncated,
+    /// The container is inconsistent with itself.
+    Malformed(&'static str),
+    /// The container stores its pixels in a format with no `MTLPixelFormat` equivalent.
+    UnsupportedFormat(String),
+}
+
+impl Display for LoaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoaderError::Io(err) => write!(f, "{}", err),
+            LoaderError::Png(err) => write!(f, "{}", err),
+            LoaderError::UnknownContainer => f.write_str("unknown image container"),
+            LoaderError::Truncated => f.write_str("image data is truncated"),
+            LoaderError::Malformed(reason) => write!(f, "malformed image: {}", reason),
+            LoaderError::UnsupportedFormat(format) => {
+                write!(f, "unsupported pixel format: {}", format)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoaderError {}
+
+impl From<std::io::Error> for LoaderError {
+    fn from(err: std::io::Error) -> Self {
+        LoaderError::Io(err)
+    }
+}
+
+impl From<::png::DecodingError> for LoaderError {
+    fn from(err: ::png::DecodingError) -> Self {
+        LoaderError::Png(err)
+    }
+}
+
+/// The bytes of one mipmap level of one slice of a `TextureImage`.
+pub struct TextureSubresource {
+    pub level: NSUInteger,
+    /// The array layer, cube face (`layer * 6 + face` for cube arrays) or 0 for 3D
+    /// textures.
+    pub slice: NSUInteger,
+    pub width: NSUInteger,
+    pub height: NSUInteger,
+    pub depth: NSUInteger,
+    /// The stride between rows of texels, or of blocks for compressed formats.
+    pub bytes_per_row: NSUInteger,
+    /// The stride between depth planes.
+    pub bytes_per_image: NSUInteger,
+    pub data: Vec<u8>,
+}
+
+/// A texture parsed or decoded on the CPU, ready to be uploaded to a `MTLTexture` with
+/// `new_texture`.
+///
+/// PNG, KTX, KTX2 and DDS files can be loaded, including every mipmap level, array layer
+/// and cube face they contain. Pre-compressed payloads (BC, ETC / EAC, ASTC, PVRTC) are
+/// kept compressed.
+///
+/// Only available with the `loader` feature.
+pub struct TextureImage {
+    pub format: MTLPixelFormat,
+    pub texture_type: MTLTextureType,
+    pub width: NSUInteger,
+    pub height: NSUInteger,
+    pub depth: NSUInteger,
+    pub mipmap_level_count: NSUInteger,
+    /// The number of array layers; cube textures count each cube as one layer.
+    pub array_length: NSUInteger,
+    /// Every subresource, ordered by level and then by slice.
+    pub subresources: Vec<TextureSubresource>,
+}
+
+impl TextureImage {
+    /// Parses an image, detecting its container from its leading bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<TextureImage, LoaderError> {
+        if bytes.starts_with(&KTX_IDENTIFIER) {
+            TextureImage::from_ktx(bytes)
+        } else if bytes.starts_with(&KTX2_IDENTIFIER) {
+            TextureImage::from_ktx2(bytes)
+        } else if bytes.starts_with(&DDS_MAGIC) {
+            TextureImage::from_dds(bytes)
+        } else if bytes.starts_with(&PNG_SIGNATURE) {
+            TextureImage::from_png(bytes)
+        } else {
+            Err(LoaderError::UnknownContainer)
+        }
+    }
+    /// Reads and parses the image at the given path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<TextureImage, LoaderError> {
+        TextureImage::from_bytes(&std::fs::read(path)?)
+    }
+    /// The number of slices per mipmap level: six per cube, one per array layer otherwise.
+    pub fn slice_count(&self) -> NSUInteger {
+        match self.texture_type {
+            MTLTextureType::Cube | MTLTextureType::CubeArray => self.array_length * 6,
+            _ => self.array_length,
+        }
+    }
+    /// Returns the subresource for the given level and slice, if it exists.
+    pub fn get_subresource(
+        &self,
+        level: NSUInteger,
+        slice: NSUInteger,
+    ) -> Option<&TextureSubresource> {
+        self.subresources
+            .iter()
+            .find(|sub| sub.level == level && sub.slice == slice)
+    }
+    /// Creates a texture matching this image and uploads every subresource to it.
+    ///
+    /// The texture uses the descriptor's default storage mode, which the CPU can write.
+    pub unsafe fn new_texture(
+        &self,
+        device: &MTLDevice,
+        usage: BitFlags<MTLTextureUsage>,
+    ) -> MTLTexture {
+        let texture = device.new_texture_with_descriptor(&{
+            let desc = MTLTextureDescriptor::new();
+            desc.set_texture_type(self.texture_type);
+            desc.set_pixel_format(self.format);
+            desc.set_width(self.width);
+            desc.set_height(self.height);
+            desc.set_depth(self.depth);
+            desc.set_mipmap_level_count(self.mipmap_level_count);
+            desc.set_array_length(self.array_length);
+            desc.set_usage(usage);
+            desc
+        });
+        for sub in self.subresources.iter() {
+            texture.replace_region(
+                MTLRegion {
+                    origin: MTLSize {
+                        width: 0,
+                        height: 0,
+                        depth: 0,
+                    },
+                    size: MTLSize {
+                        width: sub.width,
+                        height: sub.height,
+                        depth: sub.depth,
+                    },
+                },
+                sub.level,
+                sub.slice,
+                sub.data.as_ptr() as *const c_void,
+                // PVRTC data is uploaded a whole level at a time
+                if self.format.is_pvrtc() {
+                    0
+                } else {
+                    sub.bytes_per_row
+                },
+                match self.texture_type {
+                    MTLTextureType::D3 => sub.bytes_per_image,
+                    _ => 0,
+                },
+            );
+        }
+        texture
+    }
+}
+
+/// Reads the image at the given path and uploads it to a new texture which shaders
+/// can read.
+pub unsafe fn load_texture<P: AsRef<Path>>(
+    device: &MTLDevice,
+    path: P,
+) -> Result<MTLTexture, LoaderError> {
+    let image = TextureImage::from_path(path)?;
+    Ok(image.new_texture(device, MTLTextureUsage::ShaderRead.into()))
+}
+
+/// The ASTC formats in the order used by the KTX, KTX2 and DDS format codes, as pairs
+/// of linear and sRGB formats.
+const ASTC_FORMATS: [(MTLPixelFormat, MTLPixelFormat); 14] = {
+    use MTLPixelFormat::*;
+    [
+        (ASTC_4x4_LDR, ASTC_4x4_sRGB),
+        (ASTC_5x4_LDR, ASTC_5x4_sRGB),
+        (ASTC_5x5_LDR, ASTC_5x5_sRGB),
+        (ASTC_6x5_LDR, ASTC_6x5_sRGB),
+        (ASTC_6x6_LDR, ASTC_6x6_sRGB),
+        (ASTC_8x5_LDR, ASTC_8x5_sRGB),
+        (ASTC_8x6_LDR, ASTC_8x6_sRGB),
+        (ASTC_8x8_LDR, ASTC_8x8_sRGB),
+        (ASTC_10x5_LDR, ASTC_10x5_sRGB),
+        (ASTC_10x6_LDR, ASTC_10x6_sRGB),
+        (ASTC_10x8_LDR, ASTC_10x8_sRGB),
+        (ASTC_10x10_LDR, ASTC_10x10_sRGB),
+        (ASTC_12x10_LDR, ASTC_12x10_sRGB),
+        (ASTC_12x12_LDR, ASTC_12x12_sRGB),
+    ]
+};
+
+/// Picks the texture type for an image of the given shape.
+fn texture_type(height: u32, depth: u32, layers: u32, faces: u32) -> MTLTextureType {
+    match (height, depth, layers, faces) {
+        (_, d, _, _) if d > 1 => MTLTextureType::D3,
+        (_, _, 0, 6) => MTLTextureType::Cube,
+        (_, _, _, 6) => MTLTextureType::CubeArray,
+        (0, _, 0, _) => MTLTextureType::D1,
+        (0, _, _, _) => MTLTextureType::D1Array,
+        (_, _, 0, _) => MTLTextureType::D2,
+        _ => MTLTextureType::D2Array,
+    }
+}
+
+/// Checks the number of mipmap levels and slices a container declares against the extent
+/// of the texture and the length of the file, before anything is allocated for them.
+fn validate_subresources(
+    format: MTLPixelFormat,
+    size: MTLSize,
+    levels: u32,
+    slices: NSUInteger,
+    file_length: usize,
+) -> Result<(), LoaderError> {
+    let largest = size.width.max(size.height).max(size.depth).max(1);
+    if levels > 64 - largest.leading_zeros() {
+        return Err(LoaderError::Malformed("too many mipmap levels"));
+    }
+    let info = format
+        .info()
+        .ok_or(LoaderError::Malformed("invalid pixel format"))?;
+    // every subresource occupies at least one block
+    match (levels as NSUInteger)
+        .checked_mul(slices)
+        .and_then(|count| count.checked_mul(info.bytes_per_block))
+    {
+        Some(length) if length <= file_length as NSUInteger => Ok(()),
+        _ => Err(LoaderError::Truncated),
+    }
+}
+
+/// The extent and minimum strides of a mipmap level.
+struct LevelLayout {
+    size: MTLSize,
+    bytes_per_row: NSUInteger,
+    /// The number of rows of blocks in each depth plane.
+    rows: NSUInteger,
+}
+
+impl LevelLayout {
+    fn new(
+        format: MTLPixelFormat,
+        width: NSUInteger,
+        height: NSUInteger,
+        depth: NSUInteger,
+        level: NSUInteger,
+    ) -> Result<LevelLayout, LoaderError> {
+        let info = format
+            .info()
+            .ok_or(LoaderError::Malformed("invalid pixel format"))?;
+        let extent = |length: NSUInteger| length.checked_shr(level as u32).unwrap_or(0).max(1);
+        let size = MTLSize {
+            width: extent(width),
+            height: extent(height),
+            depth: extent(depth),
+        };
+        // PVRTC levels always occupy at least 2x2 blocks
+        let min_blocks = if format.is_pvrtc() { 2 } else { 1 };
+        let bytes_per_row = info.blocks_wide(size.width).max(min_blocks) * info.bytes_per_block;
+        let rows = info.blocks_high(size.height).max(min_blocks);
+        bytes_per_row
+            .checked_mul(rows)
+            .and_then(|bytes| bytes.checked_mul(size.depth))
+            .ok_or(LoaderError::Malformed("texture is too large"))?;
+        Ok(LevelLayout {
+            size,
+            bytes_per_row,
+            rows,
+        })
+    }
+    fn bytes_per_image(&self) -> NSUInteger {
+        self.bytes_per_row * self.rows
+    }
+    fn length(&self) -> NSUInteger {
+        self.bytes_per_image() * self.size.depth
+    }
+}
+
+fn read_bytes(bytes: &[u8], offset: usize, length: usize) -> Result<&[u8], LoaderError> {
+    offset
+        .checked_add(length)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or(LoaderError::Truncated)
+}
+
+fn read_u32(bytes: &[u8], offset: usize, big_endian: bool) -> Result<u32, LoaderError> {
+    let mut word = [0; 4];
+    word.copy_from_slice(read_bytes(bytes, offset, 4)?);
+    Ok(if big_endian {
+        u32::from_be_bytes(word)
+    } else {
+        u32::from_le_bytes(word)
+    })
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, LoaderError> {
+    let mut word = [0; 8];
+    word.copy_from_slice(read_bytes(bytes, offset, 8)?);
+    Ok(u64::from_le_bytes(word))
+}
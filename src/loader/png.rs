@@ -0,0 +1,85 @@
+use super::LoaderError;
+use crate::{MTLPixelFormat, MTLTextureType, NSUInteger, TextureImage, TextureSubresource};
+use png::{BitDepth, ColorType, Decoder, Transformations};
+
+/// The first 8 bytes of every PNG file.
+pub const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+
+impl TextureImage {
+    /// Decodes a PNG file into a single level 2D `RGBA8Unorm` image, or `RGBA16Unorm` if
+    /// the file has 16 bits per channel.
+    ///
+    /// Palette, grayscale and alpha-less images are expanded to RGBA.
+    pub fn from_png(bytes: &[u8]) -> Result<TextureImage, LoaderError> {
+        let mut decoder = Decoder::new(bytes);
+        decoder.set_transformations(Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info()?;
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels)?;
+
+        let (width, height) = (info.width as NSUInteger, info.height as NSUInteger);
+        let channel_size = match info.bit_depth {
+            BitDepth::Sixteen => 2,
+            _ => 1,
+        };
+        let channels = match info.color_type {
+            ColorType::Grayscale => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::RGB => 3,
+            ColorType::RGBA => 4,
+            ColorType::Indexed => return Err(LoaderError::Malformed("unexpanded PNG palette")),
+        };
+        let max = if channel_size == 2 {
+            [0xFF, 0xFF]
+        } else {
+            [0xFF, 0]
+        };
+
+        let mut data = Vec::with_capacity((width * height * 4 * channel_size) as usize);
+        for row in pixels.chunks_exact(info.line_size) {
+            for pixel in row[..(width * channels * channel_size) as usize]
+                .chunks_exact((channels * channel_size) as usize)
+            {
+                let channel =
+                    |index: usize| &pixel[index * channel_size as usize..][..channel_size as usize];
+                let (rgb, alpha) = match channels {
+                    1 => ([channel(0); 3], &max[..channel_size as usize]),
+                    2 => ([channel(0); 3], channel(1)),
+                    3 => (
+                        [channel(0), channel(1), channel(2)],
+                        &max[..channel_size as usize],
+                    ),
+                    _ => ([channel(0), channel(1), channel(2)], channel(3)),
+                };
+                // PNG stores 16 bit channels big endian
+                for component in rgb.iter().chain(std::iter::once(&alpha)) {
+                    data.extend(component.iter().rev());
+                }
+            }
+        }
+
+        Ok(TextureImage {
+            format: if channel_size == 2 {
+                MTLPixelFormat::RGBA16Unorm
+            } else {
+                MTLPixelFormat::RGBA8Unorm
+            },
+            texture_type: MTLTextureType::D2,
+            width,
+            height,
+            depth: 1,
+            mipmap_level_count: 1,
+            array_length: 1,
+            subresources: vec![TextureSubresource {
+                level: 0,
+                slice: 0,
+                width,
+                height,
+                depth: 1,
+                bytes_per_row: width * 4 * channel_size,
+                bytes_per_image: width * height * 4 * channel_size,
+                data,
+            }],
+        })
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    MTLCompileOptions, MTLDevice, MTLLibrary, MTLLibraryError, NSError, NSInteger,
+    MTL_LIBRARY_ERROR_DOMAIN,
+};
+use std::path::{Path, PathBuf};
+
+/// Finds compiled `.metallib` files shipped with the executable.
+///
+/// Libraries are searched for next to the executable and, when the executable is in a macOS
+/// app bundle (`<name>.app/Contents/MacOS/<executable>`), in the bundle's `Resources`
+/// directory. iOS bundles keep their resources next to the executable.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MetallibLocator {
+    search_dirs: Vec<PathBuf>,
+}
+
+impl MetallibLocator {
+    /// Searches the directories of the running executable, or none if its path is unknown.
+    pub fn from_current_exe() -> MetallibLocator {
+        std::env::current_exe()
+            .map(|exe| MetallibLocator::from_executable(&exe))
+            .unwrap_or_default()
+    }
+    /// Searches the directories of the executable at the path, without checking that it
+    /// exists.
+    pub fn from_executable(exe: &Path) -> MetallibLocator {
+        let mut locator = MetallibLocator::default();
+        if let Some(dir) = exe.parent() {
+            locator = locator.search_dir(dir);
+            if let Some(resources) = bundle_resources_dir(dir) {
+                locator = locator.search_dir(resources);
+            }
+        }
+        locator
+    }
+    /// Adds a directory to search after those already added.
+    pub fn search_dir<P: Into<PathBuf>>(mut self, dir: P) -> MetallibLocator {
+        let dir = dir.into();
+        if !self.search_dirs.contains(&dir) {
+            self.search_dirs.push(dir);
+        }
+        self
+    }
+    pub fn get_search_dirs(&self) -> &[PathBuf] {
+        &self.search_dirs
+    }
+    /// Returns every path which `locate` checks for the library, in order.
+    ///
+    /// The `.metallib` extension is added to names without an extension.
+    pub fn get_candidates(&self, name: &str) -> Vec<PathBuf> {
+        let file = metallib_file_name(name);
+        self.search_dirs.iter().map(|dir| dir.join(&file)).collect()
+    }
+    /// Returns the first candidate which is a file.
+    pub fn locate(&self, name: &str) -> Option<PathBuf> {
+        self.get_candidates(name)
+            .into_iter()
+            .find(|candidate| candidate.is_file())
+    }
+    /// Locates the library and loads it into the device.
+    pub unsafe fn new_library(
+        &self,
+        device: &MTLDevice,
+        name: &str,
+    ) -> Result<MTLLibrary, NSError> {
+        match self.locate(name) {
+            Some(path) => device.new_library_with_url(path),
+            None => Err(NSError::new(
+                MTL_LIBRARY_ERROR_DOMAIN,
+                MTLLibraryError::FileNotFound as NSInteger,
+                &format!("{} was not found", metallib_file_name(name)),
+            )),
+        }
+    }
+}
+
+fn metallib_file_name(name: &str) -> String {
+    if Path::new(name).extension().is_some() {
+        name.to_string()
+    } else {
+        format!("{}.metallib", name)
+    }
+}
+
+/// Returns `<name>.app/Contents/Resources` if the directory is `<name>.app/Contents/MacOS`.
+fn bundle_resources_dir(exe_dir: &Path) -> Option<PathBuf> {
+    let contents = exe_dir.parent()?;
+    let is_bundle = exe_dir.file_name()? == "MacOS"
+        && contents.file_name()? == "Contents"
+        && contents.parent()?.extension()? == "app";
+    if is_bundle {
+        Some(contents.join("Resources"))
+    } else {
+        None
+    }
+}
+
+/// A library embedded in the executable, usually by `iron-oxide-build`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EmbeddedLibrary {
+    /// The contents of a compiled `.metallib`.
+    Metallib(&'static [u8]),
+    /// Source to compile when the library is loaded, for builds on hosts without the Metal
+    /// compiler.
+    Source(&'static str),
+}
+
+impl EmbeddedLibrary {
+    pub unsafe fn new_library(&self, device: &MTLDevice) -> Result<MTLLibrary, NSError> {
+        match self {
+            EmbeddedLibrary::Metallib(data) => device.new_library_with_data(data),
+            EmbeddedLibrary::Source(source) => {
+                device.new_library_with_source(source, &MTLCompileOptions::new())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_executable() {
+        let locator = MetallibLocator::from_executable(Path::new("/opt/game/bin/game"));
+        assert_eq!(locator.get_search_dirs(), &[PathBuf::from("/opt/game/bin")]);
+        assert_eq!(
+            locator.get_candidates("shaders"),
+            vec![PathBuf::from("/opt/game/bin/shaders.metallib")]
+        );
+        // a path without a parent has no directory to search
+        assert!(MetallibLocator::from_executable(Path::new("/"))
+            .get_search_dirs()
+            .is_empty());
+    }
+
+    #[test]
+    fn macos_bundle() {
+        let locator = MetallibLocator::from_executable(Path::new(
+            "/Applications/Game.app/Contents/MacOS/game",
+        ));
+        assert_eq!(
+            locator.get_search_dirs(),
+            &[
+                PathBuf::from("/Applications/Game.app/Contents/MacOS"),
+                PathBuf::from("/Applications/Game.app/Contents/Resources"),
+            ]
+        );
+        assert_eq!(
+            locator.get_candidates("shaders")[1],
+            PathBuf::from("/Applications/Game.app/Contents/Resources/shaders.metallib")
+        );
+        // only app bundles have a resources directory
+        for exe in &[
+            "/opt/Game/Contents/MacOS/game",
+            "/opt/Game.app/Contents/bin/game",
+        ] {
+            let locator = MetallibLocator::from_executable(Path::new(exe));
+            assert_eq!(locator.get_search_dirs().len(), 1);
+        }
+    }
+
+    #[test]
+    fn ios_bundle() {
+        let locator = MetallibLocator::from_executable(Path::new("/var/containers/Game.app/game"));
+        assert_eq!(
+            locator.get_search_dirs(),
+            &[PathBuf::from("/var/containers/Game.app")]
+        );
+    }
+
+    #[test]
+    fn names_with_extensions() {
+        let locator = MetallibLocator::default().search_dir("/shaders");
+        assert_eq!(
+            locator.get_candidates("default.metallib"),
+            vec![PathBuf::from("/shaders/default.metallib")]
+        );
+        assert_eq!(
+            locator.get_candidates("precompiled.lib"),
+            vec![PathBuf::from("/shaders/precompiled.lib")]
+        );
+        assert_eq!(
+            locator.get_candidates("lighting.v2"),
+            vec![PathBuf::from("/shaders/lighting.v2")]
+        );
+        assert_eq!(metallib_file_name("default"), "default.metallib");
+    }
+
+    #[test]
+    fn search_order() {
+        let locator = MetallibLocator::default()
+            .search_dir("/a")
+            .search_dir("/b")
+            .search_dir("/a");
+        assert_eq!(
+            locator.get_candidates("shaders"),
+            vec![
+                PathBuf::from("/a/shaders.metallib"),
+                PathBuf::from("/b/shaders.metallib"),
+            ]
+        );
+    }
+
+    #[test]
+    fn locate() {
+        let root = std::env::temp_dir().join(format!("iron-oxide-metallib-{}", std::process::id()));
+        let macos = root.join("Game.app/Contents/MacOS");
+        let resources = root.join("Game.app/Contents/Resources");
+        std::fs::create_dir_all(&macos).unwrap();
+        std::fs::create_dir_all(resources.join("shaders.metallib.d")).unwrap();
+        std::fs::write(resources.join("shaders.metallib"), b"").unwrap();
+        std::fs::write(macos.join("shaders.metallib"), b"").unwrap();
+        // directories aren't libraries
+        std::fs::create_dir_all(macos.join("post.metallib")).unwrap();
+        std::fs::write(resources.join("post.metallib"), b"").unwrap();
+
+        let locator = MetallibLocator::from_executable(&macos.join("game"));
+        assert_eq!(
+            locator.locate("shaders"),
+            Some(macos.join("shaders.metallib"))
+        );
+        assert_eq!(
+            locator.locate("post"),
+            Some(resources.join("post.metallib"))
+        );
+        assert_eq!(locator.locate("missing"), None);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
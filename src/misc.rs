@@ -1,5 +1,8 @@
 use crate::import_objc_macros::*;
-use crate::{handle, CGFloat, NSInteger, NSUInteger, Object, ObjectPointer};
+use crate::labels::LABELS_ENABLED;
+use crate::{
+    handle, CGFloat, NSDictionary, NSInteger, NSString, NSUInteger, Object, ObjectPointer,
+};
 use std::fmt::{Debug, Display, Formatter};
 
 /// Takes an implementor of `Object` and logs its description and retain count.
@@ -12,29 +15,40 @@ use std::fmt::{Debug, Display, Formatter};
 /// # Example
 ///
 /// ```
+/// # #[cfg(any(target_os = "macos", target_os = "ios"))]
 /// fn main() {
 ///     use iron_oxide::{MTLCreateSystemDefaultDevice, debug};
 ///
 ///     let device = unsafe { MTLCreateSystemDefaultDevice() };
 ///     unsafe { debug(&device) };
 /// }
+/// # #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+/// # fn main() {}
 /// ```
 pub unsafe fn debug<T: Object>(obj: &T) {
     use crate::import_objc_macros::*;
 
     let count: NSUInteger = msg_send![obj.get_ptr(), retainCount];
-    let description = ObjectPointer(msg_send![obj.get_ptr(), description]);
-    let description = {
-        let bytes: *const u8 = msg_send![description, UTF8String];
-        let len: NSUInteger = msg_send![description, length];
-        let bytes = std::slice::from_raw_parts(bytes, len as usize);
-        std::str::from_utf8(bytes).unwrap()
-    };
+    let description = NSString::copy_from(ObjectPointer(msg_send![obj.get_ptr(), description]))
+        .unwrap_or_default();
 
     log::log!(log::Level::Info, "{}", description);
     log::log!(log::Level::Info, "Retain count of {}", count);
 }
 
+pub(crate) unsafe fn get_label(obj: ObjectPointer) -> Option<String> {
+    NSString::copy_from(ObjectPointer(msg_send![obj, label]))
+}
+
+pub(crate) unsafe fn set_label(obj: ObjectPointer, label: &str) {
+    if !LABELS_ENABLED {
+        return;
+    }
+    let string = NSString::new(label);
+    msg_send![obj, setLabel:string.get_ptr()]
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLCompareFunction {
     Never = 0,
@@ -48,6 +62,7 @@ pub enum MTLCompareFunction {
 }
 
 #[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLPixelFormat {
     Invalid = 0,
@@ -181,29 +196,38 @@ pub struct NSError(ObjectPointer);
 handle!(NSError);
 
 impl NSError {
+    /// Creates an error for failures which Metal reports without one.
+    pub unsafe fn new(domain: &str, code: NSInteger, description: &str) -> NSError {
+        let domain = NSString::new(domain);
+        let user_info =
+            NSDictionary::from_strings(std::iter::once(("NSLocalizedDescription", description)));
+        let error = ObjectPointer(msg_send![class!(NSError), alloc]);
+        NSError::from_ptr(msg_send![
+            error,
+            initWithDomain:domain.get_ptr()
+            code:code
+            userInfo:user_info.get_ptr()
+        ])
+    }
     pub unsafe fn get_code(&self) -> NSInteger {
         msg_send![self.get_ptr(), code]
     }
-    pub unsafe fn get_domain(&self) -> &str {
-        let domain = ObjectPointer(msg_send![self.get_ptr(), domain]);
-        let bytes: *const u8 = msg_send![domain, UTF8String];
-        let len: NSUInteger = msg_send![domain, length];
-        let bytes = std::slice::from_raw_parts(bytes, len as usize);
-        std::str::from_utf8(bytes).unwrap()
+    pub unsafe fn get_domain(&self) -> String {
+        NSString::copy_from(ObjectPointer(msg_send![self.get_ptr(), domain])).unwrap_or_default()
     }
-    pub unsafe fn get_localized_description(&self) -> &str {
-        let desc = ObjectPointer(msg_send![self.get_ptr(), localizedDescription]);
-        let bytes: *const u8 = msg_send![desc, UTF8String];
-        let len: NSUInteger = msg_send![desc, length];
-        let bytes = std::slice::from_raw_parts(bytes, len as usize);
-        std::str::from_utf8(bytes).unwrap()
+    pub unsafe fn get_localized_description(&self) -> String {
+        NSString::copy_from(ObjectPointer(msg_send![
+            self.get_ptr(),
+            localizedDescription
+        ]))
+        .unwrap_or_default()
     }
-    pub unsafe fn get_localized_failure_reason(&self) -> &str {
-        let reason = ObjectPointer(msg_send![self.get_ptr(), localizedFailureReason]);
-        let bytes: *const u8 = msg_send![reason, UTF8String];
-        let len: NSUInteger = msg_send![reason, length];
-        let bytes = std::slice::from_raw_parts(bytes, len as usize);
-        std::str::from_utf8(bytes).unwrap()
+    pub unsafe fn get_localized_failure_reason(&self) -> String {
+        NSString::copy_from(ObjectPointer(msg_send![
+            self.get_ptr(),
+            localizedFailureReason
+        ]))
+        .unwrap_or_default()
     }
 }
 
@@ -246,12 +270,23 @@ pub struct MTLRegion {
     pub size: MTLSize,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(C)]
 pub struct NSRange {
     pub location: NSUInteger,
     pub length: NSUInteger,
 }
 
+impl From<NSUIntegerRange> for NSRange {
+    fn from(range: NSUIntegerRange) -> Self {
+        NSRange {
+            location: range.start,
+            length: range.end.saturating_sub(range.start),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(C)]
 pub struct MTLSize {
     pub width: NSUInteger,
@@ -0,0 +1,129 @@
+use crate::import_objc_macros::*;
+use crate::{
+    handle, DeviceCreated, Labeled, MTLComputePipelineDescriptor, MTLRenderPipelineDescriptor,
+    NSError, Object, ObjectPointer, NSURL,
+};
+use std::path::{Path, PathBuf};
+
+/// The domain of the errors of binary archives.
+pub const MTL_BINARY_ARCHIVE_DOMAIN: &str = "MTLBinaryArchiveDomain";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum MTLBinaryArchiveError {
+    None = 0,
+    InvalidFile = 1,
+    UnexpectedElement = 2,
+    CompilationFailure = 3,
+    InternalError = 4,
+}
+
+pub struct MTLBinaryArchiveDescriptor(ObjectPointer);
+handle!(MTLBinaryArchiveDescriptor);
+
+impl MTLBinaryArchiveDescriptor {
+    pub unsafe fn new() -> MTLBinaryArchiveDescriptor {
+        MTLBinaryArchiveDescriptor::from_ptr(msg_send![class!(MTLBinaryArchiveDescriptor), new])
+    }
+    /// Sets the path of a serialized archive to load. Without one, the archive is created
+    /// empty.
+    pub unsafe fn set_url<P: AsRef<Path>>(&self, path: P) {
+        let url = NSURL::from_path(path.as_ref());
+        msg_send![self.get_ptr(), setUrl:url.get_ptr()]
+    }
+    pub unsafe fn get_url(&self) -> Option<PathBuf> {
+        let url = ObjectPointer(msg_send![self.get_ptr(), url]);
+        if url.0.is_null() {
+            return None;
+        }
+        NSURL::from_ptr(msg_send![url, retain]).get_path()
+    }
+}
+
+impl Object for MTLBinaryArchiveDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLBinaryArchiveDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLBinaryArchive(ObjectPointer);
+handle!(MTLBinaryArchive);
+unsafe impl Send for MTLBinaryArchive {}
+unsafe impl Sync for MTLBinaryArchive {}
+
+impl MTLBinaryArchive {
+    /// Compiles the functions of the pipeline into the archive.
+    pub unsafe fn add_render_pipeline_functions(
+        &self,
+        desc: &MTLRenderPipelineDescriptor,
+    ) -> Result<(), NSError> {
+        let mut err = ObjectPointer(std::ptr::null_mut());
+        let added: bool = msg_send![
+            self.get_ptr(),
+            addRenderPipelineFunctionsWithDescriptor:desc.get_ptr()
+            error:&mut err
+        ];
+        if added {
+            Ok(())
+        } else {
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        }
+    }
+    /// Compiles the function of the pipeline into the archive.
+    pub unsafe fn add_compute_pipeline_functions(
+        &self,
+        desc: &MTLComputePipelineDescriptor,
+    ) -> Result<(), NSError> {
+        let mut err = ObjectPointer(std::ptr::null_mut());
+        let added: bool = msg_send![
+            self.get_ptr(),
+            addComputePipelineFunctionsWithDescriptor:desc.get_ptr()
+            error:&mut err
+        ];
+        if added {
+            Ok(())
+        } else {
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        }
+    }
+    /// Writes the archive to a file, which can be loaded on later launches with
+    /// `MTLBinaryArchiveDescriptor::set_url`.
+    pub unsafe fn serialize_to_url<P: AsRef<Path>>(&self, path: P) -> Result<(), NSError> {
+        let url = NSURL::from_path(path.as_ref());
+        let mut err = ObjectPointer(std::ptr::null_mut());
+        let serialized: bool = msg_send![
+            self.get_ptr(),
+            serializeToURL:url.get_ptr()
+            error:&mut err
+        ];
+        if serialized {
+            Ok(())
+        } else {
+            Err(NSError::from_ptr(msg_send![err, retain]))
+        }
+    }
+}
+
+impl Object for MTLBinaryArchive {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLBinaryArchive(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+impl DeviceCreated for MTLBinaryArchive {}
+
+impl Labeled for MTLBinaryArchive {}
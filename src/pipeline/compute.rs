@@ -1,8 +1,218 @@
 use crate::import_objc_macros::*;
-use crate::{handle, DeviceCreated, Object, ObjectPointer};
+use crate::misc::get_label;
+use crate::{
+    handle, DeviceCreated, Labeled, MTLBinaryArchive, MTLFunction, MTLIndexType,
+    MTLPipelineBufferDescriptorArray, MTLSize, NSArray, NSUInteger, Object, ObjectPointer,
+};
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+pub struct MTLStageInputOutputDescriptor(ObjectPointer);
+handle!(MTLStageInputOutputDescriptor);
+
+impl MTLStageInputOutputDescriptor {
+    pub unsafe fn new() -> MTLStageInputOutputDescriptor {
+        MTLStageInputOutputDescriptor::from_ptr(msg_send![
+            class!(MTLStageInputOutputDescriptor),
+            new
+        ])
+    }
+    pub unsafe fn set_index_buffer_index(&self, index: NSUInteger) {
+        msg_send![self.get_ptr(), setIndexBufferIndex: index]
+    }
+    pub unsafe fn get_index_buffer_index(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), indexBufferIndex]
+    }
+    pub unsafe fn set_index_type(&self, index_type: MTLIndexType) {
+        msg_send![self.get_ptr(), setIndexType: index_type]
+    }
+    pub unsafe fn get_index_type(&self) -> MTLIndexType {
+        msg_send![self.get_ptr(), indexType]
+    }
+    pub unsafe fn reset(&self) {
+        msg_send![self.get_ptr(), reset]
+    }
+}
+
+impl Object for MTLStageInputOutputDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLStageInputOutputDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLLinkedFunctions(ObjectPointer);
+handle!(MTLLinkedFunctions);
+
+impl MTLLinkedFunctions {
+    pub unsafe fn new() -> MTLLinkedFunctions {
+        MTLLinkedFunctions::from_ptr(msg_send![class!(MTLLinkedFunctions), new])
+    }
+    pub unsafe fn set_functions(&self, functions: &[&MTLFunction]) {
+        let array = NSArray::new(functions);
+        msg_send![self.get_ptr(), setFunctions:array.get_ptr()]
+    }
+    pub unsafe fn get_functions(&self) -> Vec<MTLFunction> {
+        NSArray::copy_from(ObjectPointer(msg_send![self.get_ptr(), functions]))
+    }
+    pub unsafe fn set_binary_functions(&self, functions: &[&MTLFunction]) {
+        let array = NSArray::new(functions);
+        msg_send![self.get_ptr(), setBinaryFunctions:array.get_ptr()]
+    }
+    pub unsafe fn get_binary_functions(&self) -> Vec<MTLFunction> {
+        NSArray::copy_from(ObjectPointer(msg_send![self.get_ptr(), binaryFunctions]))
+    }
+}
+
+impl Object for MTLLinkedFunctions {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLLinkedFunctions(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLComputePipelineDescriptor(ObjectPointer);
+handle!(MTLComputePipelineDescriptor);
+
+impl MTLComputePipelineDescriptor {
+    pub unsafe fn new() -> MTLComputePipelineDescriptor {
+        MTLComputePipelineDescriptor::from_ptr(msg_send![class!(MTLComputePipelineDescriptor), new])
+    }
+    pub unsafe fn set_compute_function(&self, function: &MTLFunction) {
+        msg_send![self.get_ptr(), setComputeFunction:function.get_ptr()]
+    }
+    pub unsafe fn get_compute_function(&self) -> Option<MTLFunction> {
+        let function = ObjectPointer(msg_send![self.get_ptr(), computeFunction]);
+        if function.0.is_null() {
+            None
+        } else {
+            Some(MTLFunction::from_ptr(msg_send![function, retain]))
+        }
+    }
+    pub unsafe fn set_thread_group_size_is_multiple_of_thread_execution_width(
+        &self,
+        multiple: bool,
+    ) {
+        msg_send![
+            self.get_ptr(),
+            setThreadGroupSizeIsMultipleOfThreadExecutionWidth: multiple
+        ]
+    }
+    pub unsafe fn get_thread_group_size_is_multiple_of_thread_execution_width(&self) -> bool {
+        msg_send![
+            self.get_ptr(),
+            threadGroupSizeIsMultipleOfThreadExecutionWidth
+        ]
+    }
+    /// A maximum of 0 lets Metal choose the maximum.
+    pub unsafe fn set_max_total_threads_per_threadgroup(&self, max: NSUInteger) {
+        msg_send![self.get_ptr(), setMaxTotalThreadsPerThreadgroup: max]
+    }
+    pub unsafe fn get_max_total_threads_per_threadgroup(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), maxTotalThreadsPerThreadgroup]
+    }
+    pub unsafe fn set_stage_input_descriptor(&self, desc: &MTLStageInputOutputDescriptor) {
+        msg_send![self.get_ptr(), setStageInputDescriptor:desc.get_ptr()]
+    }
+    pub unsafe fn get_stage_input_descriptor(&self) -> Option<MTLStageInputOutputDescriptor> {
+        let desc = ObjectPointer(msg_send![self.get_ptr(), stageInputDescriptor]);
+        if desc.0.is_null() {
+            None
+        } else {
+            Some(MTLStageInputOutputDescriptor::from_ptr(msg_send![
+                desc, retain
+            ]))
+        }
+    }
+    /// The mutability of the buffers bound to the compute function at each index.
+    pub unsafe fn get_buffers(&self) -> MTLPipelineBufferDescriptorArray {
+        MTLPipelineBufferDescriptorArray::from_ptr({
+            let ptr = ObjectPointer(msg_send![self.get_ptr(), buffers]);
+            msg_send![ptr, retain]
+        })
+    }
+    pub unsafe fn set_linked_functions(&self, functions: &MTLLinkedFunctions) {
+        msg_send![self.get_ptr(), setLinkedFunctions:functions.get_ptr()]
+    }
+    pub unsafe fn get_linked_functions(&self) -> MTLLinkedFunctions {
+        MTLLinkedFunctions::from_ptr({
+            let ptr = ObjectPointer(msg_send![self.get_ptr(), linkedFunctions]);
+            msg_send![ptr, retain]
+        })
+    }
+    pub unsafe fn set_supports_indirect_command_buffers(&self, supports: bool) {
+        msg_send![self.get_ptr(), setSupportIndirectCommandBuffers: supports]
+    }
+    pub unsafe fn get_supports_indirect_command_buffers(&self) -> bool {
+        msg_send![self.get_ptr(), supportIndirectCommandBuffers]
+    }
+    /// Sets the archives which are searched for already compiled pipeline functions.
+    pub unsafe fn set_binary_archives(&self, archives: &[&MTLBinaryArchive]) {
+        let array = NSArray::new(archives);
+        msg_send![self.get_ptr(), setBinaryArchives:array.get_ptr()]
+    }
+    pub unsafe fn get_binary_archives(&self) -> Vec<MTLBinaryArchive> {
+        NSArray::copy_from(ObjectPointer(msg_send![self.get_ptr(), binaryArchives]))
+    }
+    pub unsafe fn reset(&self) {
+        msg_send![self.get_ptr(), reset]
+    }
+}
+
+impl Object for MTLComputePipelineDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLComputePipelineDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+impl Labeled for MTLComputePipelineDescriptor {}
 
 pub struct MTLComputePipelineState(ObjectPointer);
 handle!(MTLComputePipelineState);
+unsafe impl Send for MTLComputePipelineState {}
+unsafe impl Sync for MTLComputePipelineState {}
+
+impl MTLComputePipelineState {
+    pub unsafe fn get_label(&self) -> Option<String> {
+        get_label(self.get_ptr())
+    }
+    pub unsafe fn get_thread_execution_width(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), threadExecutionWidth]
+    }
+    pub unsafe fn get_max_total_threads_per_threadgroup(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), maxTotalThreadsPerThreadgroup]
+    }
+    pub unsafe fn get_static_threadgroup_memory_length(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), staticThreadgroupMemoryLength]
+    }
+    /// Lays out threadgroups of this pipeline over a grid of `grid` threads.
+    pub unsafe fn get_threadgroup_layout(&self, grid: MTLSize) -> ThreadgroupLayout {
+        ThreadgroupLayout::new(
+            grid,
+            self.get_thread_execution_width(),
+            self.get_max_total_threads_per_threadgroup(),
+        )
+    }
+}
 
 impl DeviceCreated for MTLComputePipelineState {}
 
@@ -18,3 +228,173 @@ impl Object for MTLComputePipelineState {
         self.0
     }
 }
+
+/// The size of each threadgroup and the number of threadgroups needed to cover a grid of
+/// threads.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ThreadgroupLayout {
+    pub threads_per_threadgroup: MTLSize,
+    pub threadgroups_per_grid: MTLSize,
+}
+
+impl ThreadgroupLayout {
+    /// Picks threadgroups as large as the pipeline allows. 1D threadgroups are rounded to a
+    /// multiple of the thread execution width, and 2D and 3D threadgroups are exactly that
+    /// wide. Threadgroups are not made taller or deeper than the grid.
+    ///
+    /// Grids with a height and depth of 1 are treated as 1D, and grids with a depth of 1 as
+    /// 2D.
+    pub fn new(
+        grid: MTLSize,
+        thread_execution_width: NSUInteger,
+        max_total_threads_per_threadgroup: NSUInteger,
+    ) -> ThreadgroupLayout {
+        let max = max_total_threads_per_threadgroup.max(1);
+        let execution_width = thread_execution_width.clamp(1, max);
+
+        let width = if grid.height <= 1 && grid.depth <= 1 {
+            grid.width.max(1).next_multiple_of(execution_width).min(max)
+        } else {
+            execution_width
+        };
+        let height = (max / width).min(grid.height.max(1));
+        let depth = (max / (width * height)).min(grid.depth.max(1));
+        let threads_per_threadgroup = MTLSize {
+            width,
+            height,
+            depth,
+        };
+        ThreadgroupLayout {
+            threads_per_threadgroup,
+            threadgroups_per_grid: MTLSize {
+                width: grid.width.div_ceil(width),
+                height: grid.height.div_ceil(height),
+                depth: grid.depth.div_ceil(depth),
+            },
+        }
+    }
+}
+
+/// A plain description of a compute pipeline, which can be compared and hashed to reuse
+/// compute pipeline states.
+///
+/// The function is compared by identity, so descriptions only match if they share the
+/// same `MTLFunction` object.
+#[derive(Clone)]
+pub struct ComputePipelineDesc {
+    pub function: Rc<MTLFunction>,
+    pub threadgroup_size_is_multiple_of_thread_execution_width: bool,
+    /// A maximum of 0 lets Metal choose the maximum.
+    pub max_total_threads_per_threadgroup: NSUInteger,
+}
+
+impl ComputePipelineDesc {
+    pub fn new(function: Rc<MTLFunction>) -> ComputePipelineDesc {
+        ComputePipelineDesc {
+            function,
+            threadgroup_size_is_multiple_of_thread_execution_width: false,
+            max_total_threads_per_threadgroup: 0,
+        }
+    }
+    pub unsafe fn to_descriptor(&self) -> MTLComputePipelineDescriptor {
+        let desc = MTLComputePipelineDescriptor::new();
+        desc.set_compute_function(&self.function);
+        desc.set_thread_group_size_is_multiple_of_thread_execution_width(
+            self.threadgroup_size_is_multiple_of_thread_execution_width,
+        );
+        desc.set_max_total_threads_per_threadgroup(self.max_total_threads_per_threadgroup);
+        desc
+    }
+}
+
+impl PartialEq for ComputePipelineDesc {
+    fn eq(&self, other: &Self) -> bool {
+        self.function.get_ptr().0 == other.function.get_ptr().0
+            && self.threadgroup_size_is_multiple_of_thread_execution_width
+                == other.threadgroup_size_is_multiple_of_thread_execution_width
+            && self.max_total_threads_per_threadgroup == other.max_total_threads_per_threadgroup
+    }
+}
+
+impl Eq for ComputePipelineDesc {}
+
+impl Hash for ComputePipelineDesc {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.function.get_ptr().0 as usize).hash(state);
+        self.threadgroup_size_is_multiple_of_thread_execution_width
+            .hash(state);
+        self.max_total_threads_per_threadgroup.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(width: NSUInteger, height: NSUInteger, depth: NSUInteger) -> MTLSize {
+        MTLSize {
+            width,
+            height,
+            depth,
+        }
+    }
+
+    fn layout(grid: MTLSize, execution_width: NSUInteger, max: NSUInteger) -> (MTLSize, MTLSize) {
+        let layout = ThreadgroupLayout::new(grid, execution_width, max);
+        (layout.threads_per_threadgroup, layout.threadgroups_per_grid)
+    }
+
+    #[test]
+    fn one_dimensional() {
+        // rounded up to the execution width
+        assert_eq!(
+            layout(size(100, 1, 1), 32, 1024),
+            (size(128, 1, 1), size(1, 1, 1))
+        );
+        // limited by the maximum, with a partial last threadgroup
+        assert_eq!(
+            layout(size(1000, 1, 1), 32, 256),
+            (size(256, 1, 1), size(4, 1, 1))
+        );
+        assert_eq!(
+            layout(size(4096, 1, 1), 32, 1024),
+            (size(1024, 1, 1), size(4, 1, 1))
+        );
+    }
+
+    #[test]
+    fn two_dimensional() {
+        assert_eq!(
+            layout(size(1920, 1080, 1), 32, 1024),
+            (size(32, 32, 1), size(60, 34, 1))
+        );
+        // not taller than the grid
+        assert_eq!(
+            layout(size(1920, 3, 1), 32, 1024),
+            (size(32, 3, 1), size(60, 1, 1))
+        );
+    }
+
+    #[test]
+    fn three_dimensional() {
+        assert_eq!(
+            layout(size(64, 4, 64), 32, 512),
+            (size(32, 4, 4), size(2, 1, 16))
+        );
+        assert_eq!(
+            layout(size(8, 8, 8), 8, 1024),
+            (size(8, 8, 8), size(1, 1, 1))
+        );
+    }
+
+    #[test]
+    fn degenerate_limits() {
+        // an execution width above the maximum is clamped to it
+        assert_eq!(
+            layout(size(10, 10, 1), 64, 48),
+            (size(48, 1, 1), size(1, 10, 1))
+        );
+        // zero limits and an empty grid
+        assert_eq!(layout(size(0, 0, 0), 0, 0), (size(1, 1, 1), size(0, 0, 0)));
+    }
+}
@@ -1,4 +1,60 @@
+use crate::import_objc_macros::*;
+use crate::{handle, Array, Object, ObjectPointer};
+
+mod archive;
 mod compute;
 mod render;
+pub use archive::*;
 pub use compute::*;
 pub use render::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum MTLMutability {
+    Default = 0,
+    Mutable = 1,
+    Immutable = 2,
+}
+
+pub struct MTLPipelineBufferDescriptor(ObjectPointer);
+handle!(MTLPipelineBufferDescriptor);
+
+impl MTLPipelineBufferDescriptor {
+    pub unsafe fn set_mutability(&self, mutability: MTLMutability) {
+        msg_send![self.get_ptr(), setMutability: mutability]
+    }
+    pub unsafe fn get_mutability(&self) -> MTLMutability {
+        msg_send![self.get_ptr(), mutability]
+    }
+}
+
+impl Object for MTLPipelineBufferDescriptor {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLPipelineBufferDescriptor(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
+
+pub struct MTLPipelineBufferDescriptorArray(ObjectPointer);
+handle!(MTLPipelineBufferDescriptorArray);
+
+impl Array<MTLPipelineBufferDescriptor> for MTLPipelineBufferDescriptorArray {}
+
+impl Object for MTLPipelineBufferDescriptorArray {
+    unsafe fn from_ptr(ptr: ObjectPointer) -> Self
+    where
+        Self: Sized,
+    {
+        MTLPipelineBufferDescriptorArray(ptr)
+    }
+
+    fn get_ptr(&self) -> ObjectPointer {
+        self.0
+    }
+}
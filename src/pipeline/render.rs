@@ -1,8 +1,12 @@
 use crate::import_objc_macros::*;
+use crate::misc::get_label;
 use crate::{
-    handle, Array, DeviceCreated, MTLFunction, MTLPixelFormat, NSUInteger, Object, ObjectPointer,
+    handle, Array, DeviceCreated, Labeled, MTLBinaryArchive, MTLFunction,
+    MTLPipelineBufferDescriptorArray, MTLPixelFormat, NSArray, NSUInteger, Object, ObjectPointer,
 };
 use enumflags2::BitFlags;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
 
 pub struct MTLVertexDescriptor(ObjectPointer);
 handle!(MTLVertexDescriptor);
@@ -48,7 +52,7 @@ impl Object for MTLRenderPipelineColorAttachmentDescriptorArray {
     }
 }
 
-#[derive(BitFlags, Copy, Clone, Debug, PartialEq)]
+#[derive(BitFlags, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLColorWriteMask {
     Red = 0x1 << 3,
@@ -57,6 +61,7 @@ pub enum MTLColorWriteMask {
     Alpha = 0x1,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLBlendOperation {
     Add = 0,
@@ -66,6 +71,7 @@ pub enum MTLBlendOperation {
     Max = 4,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLBlendFactor {
     Zero = 0,
@@ -102,30 +108,58 @@ impl MTLRenderPipelineColorAttachmentDescriptor {
     pub unsafe fn set_write_mask(&self, mask: BitFlags<MTLColorWriteMask>) {
         msg_send![self.get_ptr(), setWriteMask:mask.bits()]
     }
+    pub unsafe fn get_write_mask(&self) -> BitFlags<MTLColorWriteMask> {
+        let bits: u64 = msg_send![self.get_ptr(), writeMask];
+        BitFlags::from_bits_truncate(bits)
+    }
     pub unsafe fn set_pixel_format(&self, format: MTLPixelFormat) {
         msg_send![self.get_ptr(), setPixelFormat: format]
     }
+    pub unsafe fn get_pixel_format(&self) -> MTLPixelFormat {
+        msg_send![self.get_ptr(), pixelFormat]
+    }
     pub unsafe fn set_blending_enabled(&self, enabled: bool) {
         msg_send![self.get_ptr(), setBlendingEnabled: enabled]
     }
+    pub unsafe fn is_blending_enabled(&self) -> bool {
+        msg_send![self.get_ptr(), isBlendingEnabled]
+    }
     pub unsafe fn set_alpha_blend_operation(&self, operation: MTLBlendOperation) {
         msg_send![self.get_ptr(), setAlphaBlendOperation: operation]
     }
+    pub unsafe fn get_alpha_blend_operation(&self) -> MTLBlendOperation {
+        msg_send![self.get_ptr(), alphaBlendOperation]
+    }
     pub unsafe fn set_rgb_blend_operation(&self, operation: MTLBlendOperation) {
         msg_send![self.get_ptr(), setRgbBlendOperation: operation]
     }
+    pub unsafe fn get_rgb_blend_operation(&self) -> MTLBlendOperation {
+        msg_send![self.get_ptr(), rgbBlendOperation]
+    }
     pub unsafe fn set_destination_alpha_blend_factor(&self, factor: MTLBlendFactor) {
         msg_send![self.get_ptr(), setDestinationAlphaBlendFactor: factor]
     }
+    pub unsafe fn get_destination_alpha_blend_factor(&self) -> MTLBlendFactor {
+        msg_send![self.get_ptr(), destinationAlphaBlendFactor]
+    }
     pub unsafe fn set_destination_rgb_blend_factor(&self, factor: MTLBlendFactor) {
         msg_send![self.get_ptr(), setDestinationRGBBlendFactor: factor]
     }
+    pub unsafe fn get_destination_rgb_blend_factor(&self) -> MTLBlendFactor {
+        msg_send![self.get_ptr(), destinationRGBBlendFactor]
+    }
     pub unsafe fn set_source_alpha_blend_factor(&self, factor: MTLBlendFactor) {
         msg_send![self.get_ptr(), setSourceAlphaBlendFactor: factor]
     }
+    pub unsafe fn get_source_alpha_blend_factor(&self) -> MTLBlendFactor {
+        msg_send![self.get_ptr(), sourceAlphaBlendFactor]
+    }
     pub unsafe fn set_source_rgb_blend_factor(&self, factor: MTLBlendFactor) {
         msg_send![self.get_ptr(), setSourceRGBBlendFactor: factor]
     }
+    pub unsafe fn get_source_rgb_blend_factor(&self) -> MTLBlendFactor {
+        msg_send![self.get_ptr(), sourceRGBBlendFactor]
+    }
 }
 
 impl Object for MTLRenderPipelineColorAttachmentDescriptor {
@@ -141,6 +175,7 @@ impl Object for MTLRenderPipelineColorAttachmentDescriptor {
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLPrimitiveTopologyClass {
     Unspecified = 0,
@@ -162,12 +197,36 @@ impl MTLRenderPipelineDescriptor {
     pub unsafe fn set_vertex_function(&self, function: &MTLFunction) {
         msg_send![self.get_ptr(), setVertexFunction:function.get_ptr()]
     }
+    pub unsafe fn get_vertex_function(&self) -> Option<MTLFunction> {
+        let function = ObjectPointer(msg_send![self.get_ptr(), vertexFunction]);
+        if function.0.is_null() {
+            None
+        } else {
+            Some(MTLFunction::from_ptr(msg_send![function, retain]))
+        }
+    }
     pub unsafe fn set_fragment_function(&self, function: &MTLFunction) {
         msg_send![self.get_ptr(), setFragmentFunction:function.get_ptr()]
     }
+    pub unsafe fn get_fragment_function(&self) -> Option<MTLFunction> {
+        let function = ObjectPointer(msg_send![self.get_ptr(), fragmentFunction]);
+        if function.0.is_null() {
+            None
+        } else {
+            Some(MTLFunction::from_ptr(msg_send![function, retain]))
+        }
+    }
     pub unsafe fn set_vertex_descriptor(&self, desc: &MTLVertexDescriptor) {
         msg_send![self.get_ptr(), setVertexDescriptor:desc.get_ptr()]
     }
+    pub unsafe fn get_vertex_descriptor(&self) -> Option<MTLVertexDescriptor> {
+        let desc = ObjectPointer(msg_send![self.get_ptr(), vertexDescriptor]);
+        if desc.0.is_null() {
+            None
+        } else {
+            Some(MTLVertexDescriptor::from_ptr(msg_send![desc, retain]))
+        }
+    }
     pub unsafe fn get_color_attachments(&self) -> MTLRenderPipelineColorAttachmentDescriptorArray {
         MTLRenderPipelineColorAttachmentDescriptorArray::from_ptr({
             let ptr = ObjectPointer(msg_send![self.get_ptr(), colorAttachments]);
@@ -178,30 +237,91 @@ impl MTLRenderPipelineDescriptor {
             ptr
         })
     }
+    /// The mutability of the buffers bound to the vertex function at each index.
+    pub unsafe fn get_vertex_buffers(&self) -> MTLPipelineBufferDescriptorArray {
+        MTLPipelineBufferDescriptorArray::from_ptr({
+            let ptr = ObjectPointer(msg_send![self.get_ptr(), vertexBuffers]);
+            msg_send![ptr, retain]
+        })
+    }
+    /// The mutability of the buffers bound to the fragment function at each index.
+    pub unsafe fn get_fragment_buffers(&self) -> MTLPipelineBufferDescriptorArray {
+        MTLPipelineBufferDescriptorArray::from_ptr({
+            let ptr = ObjectPointer(msg_send![self.get_ptr(), fragmentBuffers]);
+            msg_send![ptr, retain]
+        })
+    }
     pub unsafe fn reset(&self) {
         msg_send![self.get_ptr(), reset]
     }
     pub unsafe fn set_depth_attachment_pixel_format(&self, format: MTLPixelFormat) {
         msg_send![self.get_ptr(), setDepthAttachmentPixelFormat: format]
     }
+    pub unsafe fn get_depth_attachment_pixel_format(&self) -> MTLPixelFormat {
+        msg_send![self.get_ptr(), depthAttachmentPixelFormat]
+    }
     pub unsafe fn set_stencil_attachment_pixel_format(&self, format: MTLPixelFormat) {
         msg_send![self.get_ptr(), setStencilAttachmentPixelFormat: format]
     }
+    pub unsafe fn get_stencil_attachment_pixel_format(&self) -> MTLPixelFormat {
+        msg_send![self.get_ptr(), stencilAttachmentPixelFormat]
+    }
     pub unsafe fn set_sample_count(&self, count: NSUInteger) {
         msg_send![self.get_ptr(), setSampleCount: count]
     }
+    pub unsafe fn get_sample_count(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), sampleCount]
+    }
+    pub unsafe fn set_raster_sample_count(&self, count: NSUInteger) {
+        msg_send![self.get_ptr(), setRasterSampleCount: count]
+    }
+    pub unsafe fn get_raster_sample_count(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), rasterSampleCount]
+    }
     pub unsafe fn set_alpha_to_coverage_enabled(&self, enabled: bool) {
         msg_send![self.get_ptr(), setAlphaToCoverageEnabled: enabled]
     }
+    pub unsafe fn is_alpha_to_coverage_enabled(&self) -> bool {
+        msg_send![self.get_ptr(), isAlphaToCoverageEnabled]
+    }
     pub unsafe fn set_alpha_to_one_enabled(&self, enabled: bool) {
         msg_send![self.get_ptr(), setAlphaToOneEnabled: enabled]
     }
+    pub unsafe fn is_alpha_to_one_enabled(&self) -> bool {
+        msg_send![self.get_ptr(), isAlphaToOneEnabled]
+    }
     pub unsafe fn set_rasterization_enabled(&self, enabled: bool) {
-        msg_send![self.get_ptr(), setRasteriationEnabled: enabled]
+        msg_send![self.get_ptr(), setRasterizationEnabled: enabled]
+    }
+    pub unsafe fn is_rasterization_enabled(&self) -> bool {
+        msg_send![self.get_ptr(), isRasterizationEnabled]
     }
     pub unsafe fn set_input_primitive_topology(&self, topology: MTLPrimitiveTopologyClass) {
         msg_send![self.get_ptr(), setInputPrimitiveTopology: topology]
     }
+    pub unsafe fn get_input_primitive_topology(&self) -> MTLPrimitiveTopologyClass {
+        msg_send![self.get_ptr(), inputPrimitiveTopology]
+    }
+    pub unsafe fn set_max_vertex_amplification_count(&self, count: NSUInteger) {
+        msg_send![self.get_ptr(), setMaxVertexAmplificationCount: count]
+    }
+    pub unsafe fn get_max_vertex_amplification_count(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), maxVertexAmplificationCount]
+    }
+    pub unsafe fn set_supports_indirect_command_buffers(&self, supports: bool) {
+        msg_send![self.get_ptr(), setSupportIndirectCommandBuffers: supports]
+    }
+    pub unsafe fn get_supports_indirect_command_buffers(&self) -> bool {
+        msg_send![self.get_ptr(), supportIndirectCommandBuffers]
+    }
+    /// Sets the archives which are searched for already compiled pipeline functions.
+    pub unsafe fn set_binary_archives(&self, archives: &[&MTLBinaryArchive]) {
+        let array = NSArray::new(archives);
+        msg_send![self.get_ptr(), setBinaryArchives:array.get_ptr()]
+    }
+    pub unsafe fn get_binary_archives(&self) -> Vec<MTLBinaryArchive> {
+        NSArray::copy_from(ObjectPointer(msg_send![self.get_ptr(), binaryArchives]))
+    }
 }
 
 impl Object for MTLRenderPipelineDescriptor {
@@ -217,11 +337,26 @@ impl Object for MTLRenderPipelineDescriptor {
     }
 }
 
+impl Labeled for MTLRenderPipelineDescriptor {}
+
 pub struct MTLRenderPipelineState(ObjectPointer);
 handle!(MTLRenderPipelineState);
+unsafe impl Send for MTLRenderPipelineState {}
+unsafe impl Sync for MTLRenderPipelineState {}
 
 impl MTLRenderPipelineState {
-    //
+    pub unsafe fn get_label(&self) -> Option<String> {
+        get_label(self.get_ptr())
+    }
+    pub unsafe fn get_max_total_threads_per_threadgroup(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), maxTotalThreadsPerThreadgroup]
+    }
+    pub unsafe fn get_threadgroup_size_matches_tile_size(&self) -> bool {
+        msg_send![self.get_ptr(), threadgroupSizeMatchesTileSize]
+    }
+    pub unsafe fn get_imageblock_sample_length(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), imageblockSampleLength]
+    }
 }
 
 impl DeviceCreated for MTLRenderPipelineState {}
@@ -238,3 +373,156 @@ impl Object for MTLRenderPipelineState {
         self.0
     }
 }
+
+/// A plain description of how a render pipeline writes and blends one color attachment.
+///
+/// The default matches the default `MTLRenderPipelineColorAttachmentDescriptor`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RenderPipelineColorAttachmentDesc {
+    pub pixel_format: MTLPixelFormat,
+    pub write_mask: BitFlags<MTLColorWriteMask>,
+    pub blending_enabled: bool,
+    pub rgb_blend_operation: MTLBlendOperation,
+    pub alpha_blend_operation: MTLBlendOperation,
+    pub source_rgb_blend_factor: MTLBlendFactor,
+    pub source_alpha_blend_factor: MTLBlendFactor,
+    pub destination_rgb_blend_factor: MTLBlendFactor,
+    pub destination_alpha_blend_factor: MTLBlendFactor,
+}
+
+impl Default for RenderPipelineColorAttachmentDesc {
+    fn default() -> Self {
+        RenderPipelineColorAttachmentDesc {
+            pixel_format: MTLPixelFormat::Invalid,
+            write_mask: BitFlags::all(),
+            blending_enabled: false,
+            rgb_blend_operation: MTLBlendOperation::Add,
+            alpha_blend_operation: MTLBlendOperation::Add,
+            source_rgb_blend_factor: MTLBlendFactor::One,
+            source_alpha_blend_factor: MTLBlendFactor::One,
+            destination_rgb_blend_factor: MTLBlendFactor::Zero,
+            destination_alpha_blend_factor: MTLBlendFactor::Zero,
+        }
+    }
+}
+
+impl RenderPipelineColorAttachmentDesc {
+    pub unsafe fn to_descriptor(&self) -> MTLRenderPipelineColorAttachmentDescriptor {
+        let desc = MTLRenderPipelineColorAttachmentDescriptor::new();
+        desc.set_pixel_format(self.pixel_format);
+        desc.set_write_mask(self.write_mask);
+        desc.set_blending_enabled(self.blending_enabled);
+        desc.set_rgb_blend_operation(self.rgb_blend_operation);
+        desc.set_alpha_blend_operation(self.alpha_blend_operation);
+        desc.set_source_rgb_blend_factor(self.source_rgb_blend_factor);
+        desc.set_source_alpha_blend_factor(self.source_alpha_blend_factor);
+        desc.set_destination_rgb_blend_factor(self.destination_rgb_blend_factor);
+        desc.set_destination_alpha_blend_factor(self.destination_alpha_blend_factor);
+        desc
+    }
+}
+
+/// A plain description of a render pipeline, which can be compared and hashed to reuse
+/// render pipeline states.
+///
+/// Functions are compared by identity, so descriptions only match if they share the same
+/// `MTLFunction` objects.
+#[derive(Clone)]
+pub struct RenderPipelineDesc {
+    pub vertex_function: Option<Rc<MTLFunction>>,
+    pub fragment_function: Option<Rc<MTLFunction>>,
+    /// The attachment at each index of the vector describes the color attachment at
+    /// that index.
+    pub color_attachments: Vec<RenderPipelineColorAttachmentDesc>,
+    pub depth_attachment_pixel_format: MTLPixelFormat,
+    pub stencil_attachment_pixel_format: MTLPixelFormat,
+    pub sample_count: NSUInteger,
+    pub alpha_to_coverage_enabled: bool,
+    pub alpha_to_one_enabled: bool,
+    pub rasterization_enabled: bool,
+    pub input_primitive_topology: MTLPrimitiveTopologyClass,
+}
+
+impl Default for RenderPipelineDesc {
+    fn default() -> Self {
+        RenderPipelineDesc {
+            vertex_function: None,
+            fragment_function: None,
+            color_attachments: Vec::new(),
+            depth_attachment_pixel_format: MTLPixelFormat::Invalid,
+            stencil_attachment_pixel_format: MTLPixelFormat::Invalid,
+            sample_count: 1,
+            alpha_to_coverage_enabled: false,
+            alpha_to_one_enabled: false,
+            rasterization_enabled: true,
+            input_primitive_topology: MTLPrimitiveTopologyClass::Unspecified,
+        }
+    }
+}
+
+impl RenderPipelineDesc {
+    pub unsafe fn to_descriptor(&self) -> MTLRenderPipelineDescriptor {
+        let desc = MTLRenderPipelineDescriptor::new();
+        if let Some(function) = &self.vertex_function {
+            desc.set_vertex_function(function);
+        }
+        if let Some(function) = &self.fragment_function {
+            desc.set_fragment_function(function);
+        }
+        let attachments = desc.get_color_attachments();
+        for (index, attachment) in self.color_attachments.iter().enumerate() {
+            attachments
+                .set_object_at_indexed_subscript(index as NSUInteger, &attachment.to_descriptor());
+        }
+        desc.set_depth_attachment_pixel_format(self.depth_attachment_pixel_format);
+        desc.set_stencil_attachment_pixel_format(self.stencil_attachment_pixel_format);
+        desc.set_sample_count(self.sample_count);
+        desc.set_alpha_to_coverage_enabled(self.alpha_to_coverage_enabled);
+        desc.set_alpha_to_one_enabled(self.alpha_to_one_enabled);
+        desc.set_rasterization_enabled(self.rasterization_enabled);
+        desc.set_input_primitive_topology(self.input_primitive_topology);
+        desc
+    }
+}
+
+impl PartialEq for RenderPipelineDesc {
+    fn eq(&self, other: &Self) -> bool {
+        same_function(&self.vertex_function, &other.vertex_function)
+            && same_function(&self.fragment_function, &other.fragment_function)
+            && self.color_attachments == other.color_attachments
+            && self.depth_attachment_pixel_format == other.depth_attachment_pixel_format
+            && self.stencil_attachment_pixel_format == other.stencil_attachment_pixel_format
+            && self.sample_count == other.sample_count
+            && self.alpha_to_coverage_enabled == other.alpha_to_coverage_enabled
+            && self.alpha_to_one_enabled == other.alpha_to_one_enabled
+            && self.rasterization_enabled == other.rasterization_enabled
+            && self.input_primitive_topology == other.input_primitive_topology
+    }
+}
+
+impl Eq for RenderPipelineDesc {}
+
+impl Hash for RenderPipelineDesc {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        function_address(&self.vertex_function).hash(state);
+        function_address(&self.fragment_function).hash(state);
+        self.color_attachments.hash(state);
+        self.depth_attachment_pixel_format.hash(state);
+        self.stencil_attachment_pixel_format.hash(state);
+        self.sample_count.hash(state);
+        self.alpha_to_coverage_enabled.hash(state);
+        self.alpha_to_one_enabled.hash(state);
+        self.rasterization_enabled.hash(state);
+        self.input_primitive_topology.hash(state);
+    }
+}
+
+fn function_address(function: &Option<Rc<MTLFunction>>) -> usize {
+    function
+        .as_ref()
+        .map_or(0, |function| function.get_ptr().0 as usize)
+}
+
+fn same_function(a: &Option<Rc<MTLFunction>>, b: &Option<Rc<MTLFunction>>) -> bool {
+    function_address(a) == function_address(b)
+}
@@ -0,0 +1,673 @@
+use crate::{
+    MTLBinaryArchive, MTLBinaryArchiveDescriptor, MTLComputePipelineDescriptor,
+    MTLComputePipelineState, MTLDevice, MTLRenderPipelineDescriptor, MTLRenderPipelineState,
+    NSError, Object,
+};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+const MANIFEST_HEADER: &str = "# iron-oxide pipeline manifest 1";
+
+/// The name of the manifest in a `PipelineCache`'s directory.
+pub const PIPELINE_MANIFEST_FILE: &str = "pipelines.manifest";
+/// The name of the binary archive in a `PipelineCache`'s directory.
+pub const PIPELINE_ARCHIVE_FILE: &str = "pipelines.metallib";
+
+#[derive(Debug)]
+pub enum PipelineCacheError {
+    Io(std::io::Error),
+    /// A line of a manifest couldn't be parsed.
+    Malformed {
+        line: usize,
+        reason: String,
+    },
+    Metal(NSError),
+}
+
+impl Display for PipelineCacheError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PipelineCacheError::Io(err) => write!(f, "{}", err),
+            PipelineCacheError::Malformed { line, reason } => {
+                write!(
+                    f,
+                    "malformed pipeline manifest at line {}: {}",
+                    line, reason
+                )
+            }
+            PipelineCacheError::Metal(err) => {
+                write!(f, "{}", unsafe { err.get_localized_description() })
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineCacheError {}
+
+impl From<std::io::Error> for PipelineCacheError {
+    fn from(err: std::io::Error) -> Self {
+        PipelineCacheError::Io(err)
+    }
+}
+
+impl From<NSError> for PipelineCacheError {
+    fn from(err: NSError) -> Self {
+        PipelineCacheError::Metal(err)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PipelineKind {
+    Render,
+    Compute,
+}
+
+impl PipelineKind {
+    fn get_keyword(&self) -> &'static str {
+        match self {
+            PipelineKind::Render => "render",
+            PipelineKind::Compute => "compute",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ManifestEntry {
+    pub kind: PipelineKind,
+    pub name: String,
+    /// The number of sessions which have ended since the pipeline was last created.
+    pub unused_sessions: u32,
+}
+
+/// The pipelines created during past sessions, in the order they should be pre-warmed.
+///
+/// Pipelines are identified by a kind and a name chosen by the application, which must
+/// fit on one line. The manifest is a text file with a line per pipeline:
+///
+/// ```text
+/// # iron-oxide pipeline manifest 1
+/// render 0 quad
+/// compute 2 life step
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PipelineManifest {
+    entries: Vec<ManifestEntry>,
+}
+
+impl PipelineManifest {
+    pub fn new() -> PipelineManifest {
+        PipelineManifest::default()
+    }
+    pub fn parse(text: &str) -> Result<PipelineManifest, PipelineCacheError> {
+        let mut lines = text.lines().enumerate();
+        match lines.next() {
+            Some((_, MANIFEST_HEADER)) => {}
+            _ => {
+                return Err(PipelineCacheError::Malformed {
+                    line: 1,
+                    reason: "unsupported header".to_string(),
+                })
+            }
+        }
+        let mut manifest = PipelineManifest::new();
+        for (index, line) in lines.filter(|(_, line)| !line.is_empty()) {
+            let malformed = |reason: &str| PipelineCacheError::Malformed {
+                line: index + 1,
+                reason: reason.to_string(),
+            };
+            let mut fields = line.splitn(3, ' ');
+            let kind = match fields.next() {
+                Some("render") => PipelineKind::Render,
+                Some("compute") => PipelineKind::Compute,
+                _ => return Err(malformed("unknown pipeline kind")),
+            };
+            let unused_sessions = fields
+                .next()
+                .and_then(|sessions| sessions.parse().ok())
+                .ok_or_else(|| malformed("invalid session count"))?;
+            let name = fields.next().ok_or_else(|| malformed("missing name"))?;
+            if !manifest.contains(kind, name) {
+                manifest.entries.push(ManifestEntry {
+                    kind,
+                    name: name.to_string(),
+                    unused_sessions,
+                });
+            }
+        }
+        Ok(manifest)
+    }
+    /// Loads the manifest at the path, or returns an empty manifest if there is no file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PipelineManifest, PipelineCacheError> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => PipelineManifest::parse(&text),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(PipelineManifest::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+    /// Writes the manifest, replacing the file only once it has been written completely.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, self.to_string())?;
+        std::fs::rename(&temporary, path)
+    }
+    /// Adds a pipeline created during this session, returning whether it was new.
+    ///
+    /// Names containing line breaks can't be written, and are not added.
+    pub fn record(&mut self, kind: PipelineKind, name: &str) -> bool {
+        if name.contains(['\n', '\r']) || self.contains(kind, name) {
+            return false;
+        }
+        self.entries.push(ManifestEntry {
+            kind,
+            name: name.to_string(),
+            unused_sessions: 0,
+        });
+        true
+    }
+    pub fn contains(&self, kind: PipelineKind, name: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.kind == kind && entry.name == name)
+    }
+    /// Returns the pipelines in the order they should be pre-warmed.
+    pub fn get_entries(&self) -> &[ManifestEntry] {
+        &self.entries
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// Returns the manifest to save at the end of a session which recorded `session`.
+    ///
+    /// The pipelines created during the session come first, in the order they were
+    /// created, so that those needed to draw the first frames are pre-warmed first. They are
+    /// followed by the pipelines of this manifest which weren't created, in their previous
+    /// order, until they have been unused for more than `max_unused_sessions` sessions.
+    pub fn merge(&self, session: &PipelineManifest, max_unused_sessions: u32) -> PipelineManifest {
+        let mut merged = PipelineManifest::new();
+        for entry in &session.entries {
+            merged.record(entry.kind, &entry.name);
+        }
+        for entry in &self.entries {
+            let unused_sessions = entry.unused_sessions.saturating_add(1);
+            if unused_sessions <= max_unused_sessions && !merged.contains(entry.kind, &entry.name) {
+                merged.entries.push(ManifestEntry {
+                    unused_sessions,
+                    ..entry.clone()
+                });
+            }
+        }
+        merged
+    }
+}
+
+impl Display for PipelineManifest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", MANIFEST_HEADER)?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{} {} {}",
+                entry.kind.get_keyword(),
+                entry.unused_sessions,
+                entry.name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The descriptor of a pipeline to pre-warm.
+pub enum CachedPipelineDescriptor {
+    Render(MTLRenderPipelineDescriptor),
+    Compute(MTLComputePipelineDescriptor),
+}
+
+/// Creates named pipelines with a binary archive, and remembers which were created so that
+/// they can be pre-warmed on the next launch.
+///
+/// The cache keeps its manifest and archive in a directory, and writes them with `save`,
+/// usually when the application exits. Pipelines found in the archive are created without
+/// compiling their functions again.
+///
+/// ```no_run
+/// use iron_oxide::*;
+///
+/// unsafe fn run(device: &MTLDevice, library: &MTLLibrary) -> Result<(), PipelineCacheError> {
+///     let describe = |name: &str| {
+///         let desc = MTLComputePipelineDescriptor::new();
+///         desc.set_compute_function(&library.new_function_with_name(name)?);
+///         Some(desc)
+///     };
+///     let mut cache = PipelineCache::open(device, "cache")?;
+///     cache.prewarm(|kind, name| match kind {
+///         PipelineKind::Compute => describe(name).map(CachedPipelineDescriptor::Compute),
+///         PipelineKind::Render => None,
+///     });
+///     let pipeline = cache.new_compute_pipeline_state("sum", &describe("sum").unwrap())?;
+///     // Run the application.
+///     cache.save()
+/// }
+/// ```
+pub struct PipelineCache {
+    device: MTLDevice,
+    archive: MTLBinaryArchive,
+    archive_path: PathBuf,
+    manifest_path: PathBuf,
+    previous: PipelineManifest,
+    session: PipelineManifest,
+    render: HashMap<String, MTLRenderPipelineState>,
+    compute: HashMap<String, MTLComputePipelineState>,
+    archive_changed: bool,
+    max_unused_sessions: u32,
+}
+
+impl PipelineCache {
+    /// Loads the manifest and archive in the directory, if there are any.
+    ///
+    /// A manifest or archive which can't be loaded, such as one written by another version
+    /// of the application or OS, is replaced.
+    pub unsafe fn open<P: AsRef<Path>>(
+        device: &MTLDevice,
+        dir: P,
+    ) -> Result<PipelineCache, PipelineCacheError> {
+        let dir = dir.as_ref();
+        let manifest_path = dir.join(PIPELINE_MANIFEST_FILE);
+        let archive_path = dir.join(PIPELINE_ARCHIVE_FILE);
+        let previous = match PipelineManifest::load(&manifest_path) {
+            Err(PipelineCacheError::Malformed { line, reason }) => {
+                log::log!(
+                    log::Level::Warn,
+                    "Ignoring {}, which is malformed at line {}: {}",
+                    manifest_path.display(),
+                    line,
+                    reason
+                );
+                PipelineManifest::new()
+            }
+            manifest => manifest?,
+        };
+        let mut archive = None;
+        if archive_path.is_file() {
+            let desc = MTLBinaryArchiveDescriptor::new();
+            desc.set_url(&archive_path);
+            match device.new_binary_archive_with_descriptor(&desc) {
+                Ok(loaded) => archive = Some(loaded),
+                Err(err) => log::log!(
+                    log::Level::Warn,
+                    "Ignoring {}, which couldn't be loaded: {}",
+                    archive_path.display(),
+                    err.get_localized_description()
+                ),
+            }
+        }
+        let archive_changed = archive.is_none();
+        let archive = match archive {
+            Some(archive) => archive,
+            None => device.new_binary_archive_with_descriptor(&MTLBinaryArchiveDescriptor::new())?,
+        };
+        Ok(PipelineCache {
+            device: device.clone(),
+            archive,
+            archive_path,
+            manifest_path,
+            previous,
+            session: PipelineManifest::new(),
+            render: HashMap::new(),
+            compute: HashMap::new(),
+            archive_changed,
+            max_unused_sessions: 4,
+        })
+    }
+    /// Sets how many sessions a pipeline stays in the manifest without being created.
+    /// Defaults to 4.
+    pub fn set_max_unused_sessions(&mut self, sessions: u32) {
+        self.max_unused_sessions = sessions;
+    }
+    pub fn get_archive(&self) -> &MTLBinaryArchive {
+        &self.archive
+    }
+    /// Returns the manifest loaded when the cache was opened.
+    pub fn get_previous_manifest(&self) -> &PipelineManifest {
+        &self.previous
+    }
+    /// Returns the pipelines created since the cache was opened.
+    pub fn get_session_manifest(&self) -> &PipelineManifest {
+        &self.session
+    }
+    /// Creates the pipelines of the previous manifest in order, returning how many were
+    /// created.
+    ///
+    /// `describe` returns the descriptor of a pipeline, or `None` to skip it. Pre-warmed
+    /// pipelines are returned by the `new_*_pipeline_state` methods with the same name, and
+    /// are only kept in the manifest as used if they are.
+    pub unsafe fn prewarm<F>(&mut self, mut describe: F) -> usize
+    where
+        F: FnMut(PipelineKind, &str) -> Option<CachedPipelineDescriptor>,
+    {
+        let mut created = 0;
+        for entry in self.previous.entries.clone() {
+            let result = match describe(entry.kind, &entry.name) {
+                Some(CachedPipelineDescriptor::Render(desc)) => {
+                    self.create_render(&entry.name, &desc).map(|pipeline| {
+                        self.render.insert(entry.name.clone(), pipeline);
+                    })
+                }
+                Some(CachedPipelineDescriptor::Compute(desc)) => {
+                    self.create_compute(&entry.name, &desc).map(|pipeline| {
+                        self.compute.insert(entry.name.clone(), pipeline);
+                    })
+                }
+                None => continue,
+            };
+            match result {
+                Ok(()) => created += 1,
+                Err(err) => log::log!(
+                    log::Level::Warn,
+                    "Couldn't pre-warm {}: {}",
+                    entry.name,
+                    err.get_localized_description()
+                ),
+            }
+        }
+        created
+    }
+    /// Returns the pipeline with the name if it was already created or pre-warmed, or else
+    /// creates it with the archive.
+    pub unsafe fn new_render_pipeline_state(
+        &mut self,
+        name: &str,
+        desc: &MTLRenderPipelineDescriptor,
+    ) -> Result<MTLRenderPipelineState, NSError> {
+        let pipeline = match self.render.get(name) {
+            Some(pipeline) => pipeline.clone(),
+            None => {
+                let pipeline = self.create_render(name, desc)?;
+                self.render.insert(name.to_string(), pipeline.clone());
+                pipeline
+            }
+        };
+        self.session.record(PipelineKind::Render, name);
+        Ok(pipeline)
+    }
+    /// Returns the pipeline with the name if it was already created or pre-warmed, or else
+    /// creates it with the archive.
+    pub unsafe fn new_compute_pipeline_state(
+        &mut self,
+        name: &str,
+        desc: &MTLComputePipelineDescriptor,
+    ) -> Result<MTLComputePipelineState, NSError> {
+        let pipeline = match self.compute.get(name) {
+            Some(pipeline) => pipeline.clone(),
+            None => {
+                let pipeline = self.create_compute(name, desc)?;
+                self.compute.insert(name.to_string(), pipeline.clone());
+                pipeline
+            }
+        };
+        self.session.record(PipelineKind::Compute, name);
+        Ok(pipeline)
+    }
+    /// Writes the archive, if pipelines which weren't in the previous manifest were added to
+    /// it, and the manifest of this session merged with the previous one, creating the
+    /// directory if needed.
+    pub unsafe fn save(&self) -> Result<(), PipelineCacheError> {
+        if let Some(dir) = self.manifest_path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        if self.archive_changed {
+            self.archive.serialize_to_url(&self.archive_path)?;
+        }
+        self.previous
+            .merge(&self.session, self.max_unused_sessions)
+            .save(&self.manifest_path)?;
+        Ok(())
+    }
+
+    unsafe fn create_render(
+        &mut self,
+        name: &str,
+        desc: &MTLRenderPipelineDescriptor,
+    ) -> Result<MTLRenderPipelineState, NSError> {
+        let archives = desc.get_binary_archives();
+        desc.set_binary_archives(&self.with_archive(&archives));
+        let pipeline = self
+            .device
+            .new_render_pipeline_state_with_descriptor(desc)?;
+        let added = log_add_failure(self.archive.add_render_pipeline_functions(desc));
+        self.archive_changed |= added && !self.previous.contains(PipelineKind::Render, name);
+        Ok(pipeline)
+    }
+    unsafe fn create_compute(
+        &mut self,
+        name: &str,
+        desc: &MTLComputePipelineDescriptor,
+    ) -> Result<MTLComputePipelineState, NSError> {
+        let archives = desc.get_binary_archives();
+        desc.set_binary_archives(&self.with_archive(&archives));
+        let pipeline = self
+            .device
+            .new_compute_pipeline_state_with_descriptor(desc)?;
+        let added = log_add_failure(self.archive.add_compute_pipeline_functions(desc));
+        self.archive_changed |= added && !self.previous.contains(PipelineKind::Compute, name);
+        Ok(pipeline)
+    }
+    fn with_archive<'a>(&'a self, archives: &'a [MTLBinaryArchive]) -> Vec<&'a MTLBinaryArchive> {
+        let mut archives = archives.iter().collect::<Vec<_>>();
+        if !archives
+            .iter()
+            .any(|archive| archive.get_ptr().0 == self.archive.get_ptr().0)
+        {
+            archives.push(&self.archive);
+        }
+        archives
+    }
+}
+
+/// Logs a failure to add functions to the archive, which only makes the next launch slower,
+/// returning whether they were added.
+unsafe fn log_add_failure(result: Result<(), NSError>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(err) => {
+            log::log!(
+                log::Level::Warn,
+                "Couldn't add pipeline functions to the archive: {}",
+                err.get_localized_description()
+            );
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(manifest: &PipelineManifest) -> Vec<(PipelineKind, &str, u32)> {
+        manifest
+            .get_entries()
+            .iter()
+            .map(|entry| (entry.kind, entry.name.as_str(), entry.unused_sessions))
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut manifest = PipelineManifest::new();
+        assert!(manifest.record(PipelineKind::Render, "quad"));
+        assert!(manifest.record(PipelineKind::Compute, "life step"));
+        assert!(manifest.record(PipelineKind::Compute, "quad"));
+        let text = manifest.to_string();
+        assert_eq!(
+            text,
+            "# iron-oxide pipeline manifest 1\nrender 0 quad\ncompute 0 life step\ncompute 0 quad\n"
+        );
+        assert_eq!(PipelineManifest::parse(&text).unwrap(), manifest);
+        assert!(
+            PipelineManifest::parse(&PipelineManifest::new().to_string())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn names_with_spaces() {
+        let manifest =
+            PipelineManifest::parse("# iron-oxide pipeline manifest 1\ncompute 2  blur  x 2 \n")
+                .unwrap();
+        assert_eq!(
+            entries(&manifest),
+            [(PipelineKind::Compute, " blur  x 2 ", 2)]
+        );
+        let mut recorded = PipelineManifest::new();
+        assert!(!recorded.record(PipelineKind::Render, "two\nlines"));
+        assert!(!recorded.record(PipelineKind::Render, "two\rlines"));
+        assert!(recorded.is_empty());
+    }
+
+    #[test]
+    fn duplicate_lines() {
+        let manifest = PipelineManifest::parse(
+            "# iron-oxide pipeline manifest 1\nrender 1 quad\ncompute 0 quad\nrender 3 quad\n\n",
+        )
+        .unwrap();
+        assert_eq!(
+            entries(&manifest),
+            [
+                (PipelineKind::Render, "quad", 1),
+                (PipelineKind::Compute, "quad", 0)
+            ]
+        );
+        let mut recorded = manifest.clone();
+        assert!(!recorded.record(PipelineKind::Render, "quad"));
+        assert_eq!(recorded, manifest);
+    }
+
+    #[test]
+    fn malformed() {
+        let line = |text: &str| match PipelineManifest::parse(text) {
+            Err(PipelineCacheError::Malformed { line, .. }) => line,
+            other => panic!("expected a malformed manifest, got {:?}", other),
+        };
+        assert_eq!(line(""), 1);
+        assert_eq!(line("# iron-oxide pipeline manifest 2\nrender 0 quad\n"), 1);
+        assert_eq!(line("render 0 quad\n"), 1);
+        let header = "# iron-oxide pipeline manifest 1\n";
+        assert_eq!(line(&format!("{}\nblit 0 quad\n", header)), 3);
+        assert_eq!(line(&format!("{}render -1 quad\n", header)), 2);
+        assert_eq!(line(&format!("{}render 0\n", header)), 2);
+    }
+
+    #[test]
+    fn merge_order() {
+        let mut previous = PipelineManifest::new();
+        previous.record(PipelineKind::Render, "menu");
+        previous.record(PipelineKind::Compute, "blur");
+        previous.record(PipelineKind::Render, "quad");
+        let mut session = PipelineManifest::new();
+        session.record(PipelineKind::Render, "quad");
+        session.record(PipelineKind::Compute, "sum");
+        assert_eq!(
+            entries(&previous.merge(&session, 4)),
+            [
+                (PipelineKind::Render, "quad", 0),
+                (PipelineKind::Compute, "sum", 0),
+                (PipelineKind::Render, "menu", 1),
+                (PipelineKind::Compute, "blur", 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn max_unused_sessions() {
+        let mut manifest = PipelineManifest::new();
+        manifest.record(PipelineKind::Render, "quad");
+        let empty = PipelineManifest::new();
+        for sessions in 1..=2 {
+            manifest = manifest.merge(&empty, 2);
+            assert_eq!(
+                entries(&manifest),
+                [(PipelineKind::Render, "quad", sessions)]
+            );
+        }
+        assert!(manifest.merge(&empty, 2).is_empty());
+        // creating the pipeline again resets its count
+        let mut session = PipelineManifest::new();
+        session.record(PipelineKind::Render, "quad");
+        assert_eq!(
+            entries(&manifest.merge(&session, 2)),
+            [(PipelineKind::Render, "quad", 0)]
+        );
+        assert!(session.merge(&empty, 0).is_empty());
+        assert_eq!(session.merge(&empty, u32::MAX).len(), 1);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir =
+            std::env::temp_dir().join(format!("iron-oxide-pipelinecache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(PIPELINE_MANIFEST_FILE);
+        assert!(PipelineManifest::load(&path).unwrap().is_empty());
+        let mut manifest = PipelineManifest::new();
+        manifest.record(PipelineKind::Compute, "life step");
+        manifest.save(&path).unwrap();
+        assert_eq!(PipelineManifest::load(&path).unwrap(), manifest);
+        assert!(!path.with_extension("tmp").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+    fn unchanged_archives_are_not_rewritten() {
+        use crate::fakeobjc::FakeObject;
+
+        let dir = std::env::temp_dir().join(format!(
+            "iron-oxide-pipelinecache-archive-{}",
+            std::process::id()
+        ));
+        let archive_path = dir.join(PIPELINE_ARCHIVE_FILE);
+        let device = unsafe { MTLDevice::from_ptr(FakeObject::plain(Vec::new())) };
+        // runs a session creating the pipelines, returning the messages sent to its archive
+        let session = |names: &[&str]| unsafe {
+            let mut cache = PipelineCache::open(&device, &dir).unwrap();
+            cache.prewarm(|kind, _| match kind {
+                PipelineKind::Compute => Some(CachedPipelineDescriptor::Compute(
+                    MTLComputePipelineDescriptor::new(),
+                )),
+                PipelineKind::Render => None,
+            });
+            for name in names {
+                let desc = MTLComputePipelineDescriptor::new();
+                cache.new_compute_pipeline_state(name, &desc).unwrap();
+            }
+            cache.save().unwrap();
+            FakeObject::get(cache.get_archive().get_ptr()).get_sent()
+        };
+        let serialized = format!("serializeToURL:error: {}", archive_path.display());
+
+        assert_eq!(
+            session(&["sum"]),
+            [
+                "addComputePipelineFunctionsWithDescriptor:error:".to_string(),
+                serialized.clone()
+            ]
+        );
+        // the fake archive isn't written, so that it can be loaded
+        std::fs::write(&archive_path, "archive").unwrap();
+        // pre-warmed pipelines are added again, which leaves the archive unchanged
+        let sent = session(&["sum"]);
+        assert_eq!(sent.len(), 1);
+        assert!(!sent.contains(&serialized));
+        assert!(!session(&[]).contains(&serialized));
+        assert!(session(&["sum", "scan"]).contains(&serialized));
+        assert_eq!(std::fs::read(&archive_path).unwrap(), b"archive");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
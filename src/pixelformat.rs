@@ -0,0 +1,462 @@
+use crate::{MTLPixelFormat, NSUInteger};
+
+/// The memory layout of a single block of a `MTLPixelFormat`.
+///
+/// Uncompressed formats have 1x1 blocks; block compressed formats (BC, ETC, EAC, ASTC,
+/// PVRTC) and the subsampled 422 formats have larger blocks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PixelFormatInfo {
+    pub bytes_per_block: NSUInteger,
+    pub block_width: NSUInteger,
+    pub block_height: NSUInteger,
+}
+
+impl PixelFormatInfo {
+    const fn new(
+        bytes_per_block: NSUInteger,
+        block_width: NSUInteger,
+        block_height: NSUInteger,
+    ) -> Self {
+        PixelFormatInfo {
+            bytes_per_block,
+            block_width,
+            block_height,
+        }
+    }
+    /// The number of blocks needed to cover the given width.
+    pub fn blocks_wide(&self, width: NSUInteger) -> NSUInteger {
+        width.div_ceil(self.block_width)
+    }
+    /// The number of blocks needed to cover the given height.
+    pub fn blocks_high(&self, height: NSUInteger) -> NSUInteger {
+        height.div_ceil(self.block_height)
+    }
+    /// The minimum number of bytes a row of blocks of the given width occupies.
+    pub fn bytes_per_row(&self, width: NSUInteger) -> NSUInteger {
+        self.blocks_wide(width) * self.bytes_per_block
+    }
+    /// The minimum number of bytes an image of the given width and height occupies.
+    pub fn bytes_per_image(&self, width: NSUInteger, height: NSUInteger) -> NSUInteger {
+        self.bytes_per_row(width) * self.blocks_high(height)
+    }
+}
+
+impl MTLPixelFormat {
+    /// Returns the block layout of this format, or `None` for `Invalid`.
+    pub fn info(&self) -> Option<PixelFormatInfo> {
+        use MTLPixelFormat::*;
+
+        let info = PixelFormatInfo::new;
+        Some(match self {
+            Invalid => return None,
+            A8Unorm | R8Unorm | R8Unorm_sRGB | R8Snorm | R8Uint | R8Sint | Stencil8 => {
+                info(1, 1, 1)
+            }
+            R16Unorm | R16Snorm | R16Uint | R16Sint | R16Float | RG8Unorm | RG8Unorm_sRGB
+            | RG8Snorm | RG8Uint | RG8Sint | B5G6R5Unorm | A1BGR5Unorm | ABGR4Unorm
+            | BGR5A1Unorm | Depth16Unorm => info(2, 1, 1),
+            R32Uint
+            | R32Sint
+            | R32Float
+            | RG16Unorm
+            | RG16Snorm
+            | RG16Uint
+            | RG16Sint
+            | RG16Float
+            | RGBA8Unorm
+            | RGBA8Unorm_sRGB
+            | RGBA8Snorm
+            | RGBA8Uint
+            | RGBA8Sint
+            | BGRA8Unorm
+            | BGRA8Unorm_sRGB
+            | RGB10A2Unorm
+            | RGB10A2Uint
+            | RG11B10Float
+            | RGB9E5Float
+            | BGR10A2Unorm
+            | BGR10_XR
+            | BGR10_XR_SRGB
+            | Depth32Float
+            | Depth24Unorm_Stencil8
+            | X24_Stencil8 => info(4, 1, 1),
+            RG32Uint
+            | RG32Sint
+            | RG32Float
+            | RGBA16Unorm
+            | RGBA16Snorm
+            | RGBA16Uint
+            | RGBA16Sint
+            | RGBA16Float
+            | BGRA10_XR
+            | BGRA10_XR_SRGB
+            | Depth32Float_Stencil8
+            | X32_Stencil8 => info(8, 1, 1),
+            RGBA32Uint | RGBA32Sint | RGBA32Float => info(16, 1, 1),
+            GBGR422 | BGRG422 => info(4, 2, 1),
+            BC1_RGBA | BC1_RGBA_sRGB | BC4_RUnorm | BC4_RSnorm => info(8, 4, 4),
+            BC2_RGBA | BC2_RGBA_sRGB | BC3_RGBA | BC3_RGBA_sRGB | BC5_RGUnorm | BC5_RGSnorm
+            | BC6H_RGBFloat | BC6H_RGBUfloat | BC7_RGBAUnorm | BC7_RGBAUnorm_sRGB => info(16, 4, 4),
+            PVRTC_RGB_2BPP | PVRTC_RGB_2BPP_sRGB | PVRTC_RGBA_2BPP | PVRTC_RGBA_2BPP_sRGB => {
+                info(8, 8, 4)
+            }
+            PVRTC_RGB_4BPP | PVRTC_RGB_4BPP_sRGB | PVRTC_RGBA_4BPP | PVRTC_RGBA_4BPP_sRGB => {
+                info(8, 4, 4)
+            }
+            EAC_R11Unorm | EAC_R11Snorm | ETC2_RGB8 | ETC2_RGB8_sRGB | ETC2_RGB8A1
+            | ETC2_RGB8A1_sRGB => info(8, 4, 4),
+            EAC_RG11Unorm | EAC_RG11Snorm | EAC_RGBA8 | EAC_RGBA8_sRGB => info(16, 4, 4),
+            ASTC_4x4_sRGB | ASTC_4x4_LDR => info(16, 4, 4),
+            ASTC_5x4_sRGB | ASTC_5x4_LDR => info(16, 5, 4),
+            ASTC_5x5_sRGB | ASTC_5x5_LDR => info(16, 5, 5),
+            ASTC_6x5_sRGB | ASTC_6x5_LDR => info(16, 6, 5),
+            ASTC_6x6_sRGB | ASTC_6x6_LDR => info(16, 6, 6),
+            ASTC_8x5_sRGB | ASTC_8x5_LDR => info(16, 8, 5),
+            ASTC_8x6_sRGB | ASTC_8x6_LDR => info(16, 8, 6),
+            ASTC_8x8_sRGB | ASTC_8x8_LDR => info(16, 8, 8),
+            ASTC_10x5_sRGB | ASTC_10x5_LDR => info(16, 10, 5),
+            ASTC_10x6_sRGB | ASTC_10x6_LDR => info(16, 10, 6),
+            ASTC_10x8_sRGB | ASTC_10x8_LDR => info(16, 10, 8),
+            ASTC_10x10_sRGB | ASTC_10x10_LDR => info(16, 10, 10),
+            ASTC_12x10_sRGB | ASTC_12x10_LDR => info(16, 12, 10),
+            ASTC_12x12_sRGB | ASTC_12x12_LDR => info(16, 12, 12),
+        })
+    }
+    /// Whether this format stores its texels in compressed blocks.
+    pub fn is_compressed(&self) -> bool {
+        match self.info() {
+            Some(info) => info.block_width * info.block_height > 1 && !self.is_422(),
+            None => false,
+        }
+    }
+    /// Whether this format is one of the subsampled 422 formats.
+    pub fn is_422(&self) -> bool {
+        matches!(self, MTLPixelFormat::GBGR422 | MTLPixelFormat::BGRG422)
+    }
+    /// Whether this format is one of the PVRTC formats.
+    pub fn is_pvrtc(&self) -> bool {
+        matches!(
+            self,
+            MTLPixelFormat::PVRTC_RGB_2BPP
+                | MTLPixelFormat::PVRTC_RGB_2BPP_sRGB
+                | MTLPixelFormat::PVRTC_RGB_4BPP
+                | MTLPixelFormat::PVRTC_RGB_4BPP_sRGB
+                | MTLPixelFormat::PVRTC_RGBA_2BPP
+                | MTLPixelFormat::PVRTC_RGBA_2BPP_sRGB
+                | MTLPixelFormat::PVRTC_RGBA_4BPP
+                | MTLPixelFormat::PVRTC_RGBA_4BPP_sRGB
+        )
+    }
+    /// Whether this format has a depth component.
+    pub fn is_depth(&self) -> bool {
+        matches!(
+            self,
+            MTLPixelFormat::Depth16Unorm
+                | MTLPixelFormat::Depth32Float
+                | MTLPixelFormat::Depth24Unorm_Stencil8
+                | MTLPixelFormat::Depth32Float_Stencil8
+        )
+    }
+    /// Whether this format has a stencil component.
+    pub fn is_stencil(&self) -> bool {
+        matches!(
+            self,
+            MTLPixelFormat::Stencil8
+                | MTLPixelFormat::Depth24Unorm_Stencil8
+                | MTLPixelFormat::Depth32Float_Stencil8
+                | MTLPixelFormat::X32_Stencil8
+                | MTLPixelFormat::X24_Stencil8
+        )
+    }
+}
+
+/// Pairs of linear formats and their sRGB counterparts.
+const SRGB_PAIRS: &[(MTLPixelFormat, MTLPixelFormat)] = {
+    use MTLPixelFormat::*;
+    &[
+        (R8Unorm, R8Unorm_sRGB),
+        (RG8Unorm, RG8Unorm_sRGB),
+        (RGBA8Unorm, RGBA8Unorm_sRGB),
+        (BGRA8Unorm, BGRA8Unorm_sRGB),
+        (BGR10_XR, BGR10_XR_SRGB),
+        (BGRA10_XR, BGRA10_XR_SRGB),
+        (BC1_RGBA, BC1_RGBA_sRGB),
+        (BC2_RGBA, BC2_RGBA_sRGB),
+        (BC3_RGBA, BC3_RGBA_sRGB),
+        (BC7_RGBAUnorm, BC7_RGBAUnorm_sRGB),
+        (PVRTC_RGB_2BPP, PVRTC_RGB_2BPP_sRGB),
+        (PVRTC_RGB_4BPP, PVRTC_RGB_4BPP_sRGB),
+        (PVRTC_RGBA_2BPP, PVRTC_RGBA_2BPP_sRGB),
+        (PVRTC_RGBA_4BPP, PVRTC_RGBA_4BPP_sRGB),
+        (EAC_RGBA8, EAC_RGBA8_sRGB),
+        (ETC2_RGB8, ETC2_RGB8_sRGB),
+        (ETC2_RGB8A1, ETC2_RGB8A1_sRGB),
+        (ASTC_4x4_LDR, ASTC_4x4_sRGB),
+        (ASTC_5x4_LDR, ASTC_5x4_sRGB),
+        (ASTC_5x5_LDR, ASTC_5x5_sRGB),
+        (ASTC_6x5_LDR, ASTC_6x5_sRGB),
+        (ASTC_6x6_LDR, ASTC_6x6_sRGB),
+        (ASTC_8x5_LDR, ASTC_8x5_sRGB),
+        (ASTC_8x6_LDR, ASTC_8x6_sRGB),
+        (ASTC_8x8_LDR, ASTC_8x8_sRGB),
+        (ASTC_10x5_LDR, ASTC_10x5_sRGB),
+        (ASTC_10x6_LDR, ASTC_10x6_sRGB),
+        (ASTC_10x8_LDR, ASTC_10x8_sRGB),
+        (ASTC_10x10_LDR, ASTC_10x10_sRGB),
+        (ASTC_12x10_LDR, ASTC_12x10_sRGB),
+        (ASTC_12x12_LDR, ASTC_12x12_sRGB),
+    ]
+};
+
+/// The class of formats a texture of some format may be viewed as with
+/// `MTLTexture::new_texture_view_with_pixel_format`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PixelFormatViewClass {
+    /// Ordinary formats of the given number of bytes per pixel, which may all be viewed
+    /// as one another.
+    Ordinary(NSUInteger),
+    /// A compressed, extended range or 422 format, which may only be viewed as itself or
+    /// its sRGB / linear counterpart (identified by the linear format).
+    Exact(MTLPixelFormat),
+    /// A depth and / or stencil format, which may only be viewed as itself or, if it has
+    /// both depth and stencil, as its stencil-only counterpart (identified by the
+    /// combined format).
+    DepthStencil(MTLPixelFormat),
+}
+
+impl MTLPixelFormat {
+    /// Whether this format applies the sRGB transfer function on read and write.
+    pub fn is_srgb(&self) -> bool {
+        SRGB_PAIRS.iter().any(|(_, srgb)| srgb == self)
+    }
+    /// Returns the linear counterpart of this format if it is an sRGB format, or itself
+    /// otherwise.
+    pub fn to_linear(&self) -> MTLPixelFormat {
+        SRGB_PAIRS
+            .iter()
+            .find(|(_, srgb)| srgb == self)
+            .map(|(linear, _)| *linear)
+            .unwrap_or(*self)
+    }
+    /// Returns the sRGB counterpart of this format, if there is one.
+    pub fn to_srgb(&self) -> Option<MTLPixelFormat> {
+        if self.is_srgb() {
+            return Some(*self);
+        }
+        SRGB_PAIRS
+            .iter()
+            .find(|(linear, _)| linear == self)
+            .map(|(_, srgb)| *srgb)
+    }
+    /// Returns the view class of this format, or `None` for `Invalid`.
+    pub fn view_class(&self) -> Option<PixelFormatViewClass> {
+        use MTLPixelFormat::*;
+
+        let info = self.info()?;
+        Some(match self {
+            X32_Stencil8 => PixelFormatViewClass::DepthStencil(Depth32Float_Stencil8),
+            X24_Stencil8 => PixelFormatViewClass::DepthStencil(Depth24Unorm_Stencil8),
+            _ if self.is_depth() || self.is_stencil() => PixelFormatViewClass::DepthStencil(*self),
+            BGR10_XR | BGR10_XR_SRGB | BGRA10_XR | BGRA10_XR_SRGB => {
+                PixelFormatViewClass::Exact(self.to_linear())
+            }
+            _ if self.is_compressed() || self.is_422() => {
+                PixelFormatViewClass::Exact(self.to_linear())
+            }
+            _ => PixelFormatViewClass::Ordinary(info.bytes_per_block),
+        })
+    }
+    /// Whether a texture of this format may be viewed as a texture of the `view` format.
+    pub fn is_view_compatible(&self, view: MTLPixelFormat) -> bool {
+        match (self.view_class(), view.view_class()) {
+            (
+                Some(PixelFormatViewClass::DepthStencil(a)),
+                Some(PixelFormatViewClass::DepthStencil(b)),
+            ) => {
+                // only the combined format may be viewed as its stencil-only counterpart
+                a == b && (*self == view || *self == a)
+            }
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Rounds `value` up to the nearest multiple of `alignment`.
+///
+/// An `alignment` of 0 leaves `value` unchanged.
+pub fn align_up(value: NSUInteger, alignment: NSUInteger) -> NSUInteger {
+    if alignment == 0 {
+        value
+    } else {
+        value.next_multiple_of(alignment)
+    }
+}
+
+/// A legal placement of a linear 2D texture within a `MTLBuffer`, suitable for
+/// `MTLBuffer::new_texture_with_descriptor`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LinearTextureLayout {
+    /// The offset of the first texel from the start of the buffer.
+    pub offset: NSUInteger,
+    /// The stride between rows of texels.
+    pub bytes_per_row: NSUInteger,
+    /// The number of bytes from `offset` to the end of the last row.
+    pub length: NSUInteger,
+}
+
+impl LinearTextureLayout {
+    /// Computes the layout of a `width` by `height` texture of `format` placed at or after
+    /// `offset`, with both the offset and the row stride aligned to `alignment`.
+    ///
+    /// `alignment` should come from
+    /// `MTLDevice::minimum_linear_texture_alignment_for_pixel_format`.
+    ///
+    /// Returns `None` for formats which cannot back a linear texture (compressed, 422,
+    /// depth, stencil and invalid formats).
+    pub fn new(
+        format: MTLPixelFormat,
+        width: NSUInteger,
+        height: NSUInteger,
+        offset: NSUInteger,
+        alignment: NSUInteger,
+    ) -> Option<LinearTextureLayout> {
+        if format.is_compressed() || format.is_422() || format.is_depth() || format.is_stencil() {
+            return None;
+        }
+        let info = format.info()?;
+        let bytes_per_row = align_up(info.bytes_per_row(width), alignment);
+        Some(LinearTextureLayout {
+            offset: align_up(offset, alignment),
+            bytes_per_row,
+            length: bytes_per_row * height,
+        })
+    }
+    /// The offset one past the last byte of the texture within the buffer.
+    pub fn end(&self) -> NSUInteger {
+        self.offset + self.length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info() {
+        let rgba8 = MTLPixelFormat::RGBA8Unorm.info().unwrap();
+        assert_eq!(rgba8, PixelFormatInfo::new(4, 1, 1));
+        assert_eq!(rgba8.bytes_per_image(3, 2), 24);
+        let bc1 = MTLPixelFormat::BC1_RGBA.info().unwrap();
+        assert_eq!(bc1.blocks_wide(5), 2);
+        assert_eq!(bc1.blocks_high(4), 1);
+        assert_eq!(bc1.bytes_per_image(5, 5), 32);
+        let astc = MTLPixelFormat::ASTC_12x10_LDR.info().unwrap();
+        assert_eq!(astc.bytes_per_row(13), 32);
+        assert_eq!(
+            MTLPixelFormat::PVRTC_RGBA_2BPP.info(),
+            Some(PixelFormatInfo::new(8, 8, 4))
+        );
+        assert_eq!(MTLPixelFormat::Invalid.info(), None);
+    }
+
+    #[test]
+    fn format_kinds() {
+        assert!(MTLPixelFormat::BC7_RGBAUnorm.is_compressed());
+        assert!(!MTLPixelFormat::GBGR422.is_compressed());
+        assert!(MTLPixelFormat::GBGR422.is_422());
+        assert!(MTLPixelFormat::PVRTC_RGB_4BPP_sRGB.is_pvrtc());
+        assert!(MTLPixelFormat::Depth32Float_Stencil8.is_depth());
+        assert!(MTLPixelFormat::Depth32Float_Stencil8.is_stencil());
+        assert!(!MTLPixelFormat::X32_Stencil8.is_depth());
+        assert!(!MTLPixelFormat::Invalid.is_compressed());
+    }
+
+    #[test]
+    fn linear_texture_layout() {
+        let layout = LinearTextureLayout::new(MTLPixelFormat::RGBA8Unorm, 10, 3, 100, 64).unwrap();
+        assert_eq!(
+            layout,
+            LinearTextureLayout {
+                offset: 128,
+                bytes_per_row: 64,
+                length: 192,
+            }
+        );
+        assert_eq!(layout.end(), 320);
+        let unaligned = LinearTextureLayout::new(MTLPixelFormat::R8Unorm, 3, 2, 5, 0).unwrap();
+        assert_eq!(unaligned.offset, 5);
+        assert_eq!(unaligned.bytes_per_row, 3);
+        for format in [
+            MTLPixelFormat::BC1_RGBA,
+            MTLPixelFormat::BGRG422,
+            MTLPixelFormat::Depth32Float,
+            MTLPixelFormat::Stencil8,
+            MTLPixelFormat::Invalid,
+        ] {
+            assert_eq!(LinearTextureLayout::new(format, 4, 4, 0, 16), None);
+        }
+    }
+
+    #[test]
+    fn view_compatible_same_class() {
+        use MTLPixelFormat::*;
+
+        assert!(RGBA8Unorm.is_view_compatible(RGBA8Unorm));
+        assert!(RGBA8Unorm.is_view_compatible(R32Float));
+        assert!(RG16Float.is_view_compatible(BGRA8Unorm));
+        assert!(RGBA16Float.is_view_compatible(RG32Uint));
+        assert!(R8Uint.is_view_compatible(A8Unorm));
+        assert_eq!(
+            R32Float.view_class(),
+            Some(PixelFormatViewClass::Ordinary(4))
+        );
+        assert!(BC1_RGBA.is_view_compatible(BC1_RGBA));
+        assert!(Depth32Float.is_view_compatible(Depth32Float));
+        assert!(Depth32Float_Stencil8.is_view_compatible(X32_Stencil8));
+        assert!(Depth24Unorm_Stencil8.is_view_compatible(X24_Stencil8));
+    }
+
+    #[test]
+    fn view_compatible_srgb() {
+        use MTLPixelFormat::*;
+
+        for &(linear, srgb) in SRGB_PAIRS {
+            assert!(linear.is_view_compatible(srgb), "{:?}", linear);
+            assert!(srgb.is_view_compatible(linear), "{:?}", srgb);
+            assert_eq!(srgb.to_linear(), linear);
+            assert_eq!(linear.to_srgb(), Some(srgb));
+            assert!(srgb.is_srgb() && !linear.is_srgb());
+        }
+        assert!(BGRA8Unorm_sRGB.is_view_compatible(RGBA8Unorm));
+        assert_eq!(R32Float.to_srgb(), None);
+    }
+
+    #[test]
+    fn view_incompatible() {
+        use MTLPixelFormat::*;
+
+        assert!(!RGBA8Unorm.is_view_compatible(RG32Float));
+        assert!(!R16Float.is_view_compatible(R32Float));
+        assert!(!RGBA8Unorm.is_view_compatible(BC1_RGBA));
+        assert!(!BC1_RGBA.is_view_compatible(BC3_RGBA));
+        assert!(!BC7_RGBAUnorm.is_view_compatible(RGBA32Float));
+        assert!(!ASTC_4x4_LDR.is_view_compatible(ASTC_5x5_sRGB));
+        assert!(!BGR10_XR.is_view_compatible(RGBA8Unorm));
+        assert!(!BGR10_XR.is_view_compatible(BGRA10_XR));
+        assert!(!GBGR422.is_view_compatible(BGRG422));
+        assert!(!Depth32Float.is_view_compatible(R32Float));
+        assert!(!X32_Stencil8.is_view_compatible(Depth32Float_Stencil8));
+        assert!(!Depth32Float_Stencil8.is_view_compatible(X24_Stencil8));
+        assert!(!RGBA8Unorm.is_view_compatible(Invalid));
+        assert!(!Invalid.is_view_compatible(Invalid));
+    }
+
+    #[test]
+    fn alignment() {
+        assert_eq!(align_up(0, 16), 0);
+        assert_eq!(align_up(17, 16), 32);
+        assert_eq!(align_up(32, 16), 32);
+        assert_eq!(align_up(7, 0), 7);
+    }
+}
@@ -0,0 +1,344 @@
+use crate::{MTLCounterSampleBuffer, NSUInteger, NSUIntegerRange};
+use std::fmt::Write;
+
+/// Maps GPU timestamps onto the CPU clock, in nanoseconds, from two pairs of timestamps
+/// returned by `MTLDevice::sample_timestamps`.
+///
+/// The pairs should be sampled before and after the profiled work, as far apart as
+/// possible, since the clocks may drift.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TimestampCorrelation {
+    /// The CPU and GPU timestamps sampled first.
+    pub start: (u64, u64),
+    /// The CPU and GPU timestamps sampled last.
+    pub end: (u64, u64),
+}
+
+impl TimestampCorrelation {
+    pub fn new(start: (u64, u64), end: (u64, u64)) -> TimestampCorrelation {
+        TimestampCorrelation { start, end }
+    }
+    /// Interpolates linearly between the pairs, extrapolating beyond them.
+    pub fn to_cpu_time(&self, gpu_timestamp: u64) -> u64 {
+        let (cpu_start, gpu_start) = (self.start.0 as i128, self.start.1 as i128);
+        let (cpu_end, gpu_end) = (self.end.0 as i128, self.end.1 as i128);
+        let offset = gpu_timestamp as i128 - gpu_start;
+        let cpu = if gpu_end == gpu_start {
+            cpu_start + offset
+        } else {
+            cpu_start + offset * (cpu_end - cpu_start) / (gpu_end - gpu_start)
+        };
+        cpu.max(0).min(u64::MAX as i128) as u64
+    }
+}
+
+/// The sample indices allocated to a scope, to be sampled when it begins and ends.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScopeSamples {
+    pub start_index: NSUInteger,
+    pub end_index: NSUInteger,
+}
+
+struct ProfileScope {
+    label: String,
+    samples: ScopeSamples,
+    parent: Option<usize>,
+}
+
+/// The time spent in a profiled scope and the scopes nested in it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TimingNode {
+    pub label: String,
+    /// In nanoseconds, or GPU ticks if no correlation was given.
+    pub start: u64,
+    pub end: u64,
+    pub children: Vec<TimingNode>,
+}
+
+impl TimingNode {
+    pub fn get_duration(&self) -> u64 {
+        self.end - self.start
+    }
+}
+
+/// Allocates the samples of a timestamp counter sample buffer to nested scopes, such as
+/// passes and the work within them, and turns the resolved samples into a timing tree.
+///
+/// Each scope takes two samples, which may be taken with `sample_counters_in_buffer` or at
+/// the stage boundaries of a pass through its sample buffer attachments.
+pub struct GPUProfiler {
+    scopes: Vec<ProfileScope>,
+    open: Vec<usize>,
+    sample_count: NSUInteger,
+}
+
+impl GPUProfiler {
+    /// Creates a profiler for a sample buffer of the given number of samples.
+    pub fn new(sample_count: NSUInteger) -> GPUProfiler {
+        GPUProfiler {
+            scopes: Vec::new(),
+            open: Vec::new(),
+            sample_count,
+        }
+    }
+    /// Begins a scope nested in the innermost open scope, or returns None if the sample
+    /// buffer is full.
+    pub fn begin_scope(&mut self, label: &str) -> Option<ScopeSamples> {
+        let start_index = self.get_used_samples().end;
+        if start_index + 2 > self.sample_count {
+            return None;
+        }
+        let samples = ScopeSamples {
+            start_index,
+            end_index: start_index + 1,
+        };
+        self.open.push(self.scopes.len());
+        self.scopes.push(ProfileScope {
+            label: label.to_string(),
+            samples,
+            parent: self.open.iter().rev().nth(1).cloned(),
+        });
+        Some(samples)
+    }
+    /// Ends the innermost open scope, returning its samples.
+    pub fn end_scope(&mut self) -> Option<ScopeSamples> {
+        self.open.pop().map(|scope| self.scopes[scope].samples)
+    }
+    /// The samples allocated so far, which are those to resolve.
+    pub fn get_used_samples(&self) -> NSUIntegerRange {
+        0..self.scopes.len() as NSUInteger * 2
+    }
+    pub fn get_sample_count(&self) -> NSUInteger {
+        self.sample_count
+    }
+    /// Forgets every scope, so the sample buffer may be reused.
+    pub fn reset(&mut self) {
+        self.scopes.clear();
+        self.open.clear();
+    }
+    /// Builds the timing tree from the resolved timestamps of the used samples.
+    ///
+    /// Scopes whose samples are missing, failed, were never taken or are out of order are
+    /// left out, their children taking their place.
+    pub fn resolve(
+        &self,
+        timestamps: &[u64],
+        correlation: Option<&TimestampCorrelation>,
+    ) -> Vec<TimingNode> {
+        let sample = |index: NSUInteger| -> Option<u64> {
+            match timestamps.get(index as usize).cloned() {
+                None | Some(0) | Some(MTLCounterSampleBuffer::ERROR_VALUE) => None,
+                Some(timestamp) => Some(match correlation {
+                    Some(correlation) => correlation.to_cpu_time(timestamp),
+                    None => timestamp,
+                }),
+            }
+        };
+        self.build(None, &sample)
+    }
+    /// Resolves the used samples of the buffer and builds the timing tree from them.
+    ///
+    /// The buffer must be of the timestamp counter set, and no longer be written to.
+    pub unsafe fn resolve_buffer(
+        &self,
+        buffer: &MTLCounterSampleBuffer,
+        correlation: Option<&TimestampCorrelation>,
+    ) -> Option<Vec<TimingNode>> {
+        let used = self.get_used_samples();
+        if used.start == used.end {
+            return Some(Vec::new());
+        }
+        let timestamps = buffer.resolve_timestamps(used)?;
+        Some(self.resolve(&timestamps, correlation))
+    }
+    fn build(
+        &self,
+        parent: Option<usize>,
+        sample: &dyn Fn(NSUInteger) -> Option<u64>,
+    ) -> Vec<TimingNode> {
+        let mut nodes = Vec::new();
+        for (index, scope) in self.scopes.iter().enumerate() {
+            if scope.parent != parent {
+                continue;
+            }
+            let children = self.build(Some(index), sample);
+            match (
+                sample(scope.samples.start_index),
+                sample(scope.samples.end_index),
+            ) {
+                (Some(start), Some(end)) if start <= end => nodes.push(TimingNode {
+                    label: scope.label.clone(),
+                    start,
+                    end,
+                    children,
+                }),
+                _ => nodes.extend(children),
+            }
+        }
+        nodes
+    }
+}
+
+/// Writes the timing trees as Chrome trace event JSON, which `chrome://tracing` and
+/// Perfetto can display.
+///
+/// Times are assumed to be in nanoseconds.
+pub fn to_chrome_trace(nodes: &[TimingNode]) -> String {
+    fn write_events(json: &mut String, nodes: &[TimingNode], first: &mut bool) {
+        for node in nodes {
+            if !*first {
+                json.push(',');
+            }
+            *first = false;
+            json.push_str("{\"name\":\"");
+            for c in node.label.chars() {
+                match c {
+                    '"' => json.push_str("\\\""),
+                    '\\' => json.push_str("\\\\"),
+                    '\n' => json.push_str("\\n"),
+                    c if (c as u32) < 0x20 => {
+                        let _ = write!(json, "\\u{:04x}", c as u32);
+                    }
+                    c => json.push(c),
+                }
+            }
+            let _ = write!(
+                json,
+                "\",\"ph\":\"X\",\"pid\":0,\"tid\":0,\"ts\":{:.3},\"dur\":{:.3}}}",
+                node.start as f64 / 1000.0,
+                node.get_duration() as f64 / 1000.0
+            );
+            write_events(json, &node.children, first);
+        }
+    }
+
+    let mut json = String::from("{\"traceEvents\":[");
+    write_events(&mut json, nodes, &mut true);
+    json.push_str("]}");
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn correlation() {
+        let correlation = TimestampCorrelation::new((1000, 10), (3000, 20));
+        assert_eq!(correlation.to_cpu_time(10), 1000);
+        assert_eq!(correlation.to_cpu_time(15), 2000);
+        assert_eq!(correlation.to_cpu_time(20), 3000);
+        // extrapolated beyond the pairs, and clamped at zero
+        assert_eq!(correlation.to_cpu_time(25), 4000);
+        assert_eq!(correlation.to_cpu_time(5), 0);
+        assert_eq!(correlation.to_cpu_time(0), 0);
+        // a single pair only offsets the timestamps
+        let correlation = TimestampCorrelation::new((1000, 10), (1000, 10));
+        assert_eq!(correlation.to_cpu_time(15), 1005);
+        // large timestamps don't overflow
+        let start = 1 << 62;
+        let correlation = TimestampCorrelation::new(
+            (start, start),
+            (start + 2_000_000_000, start + 1_000_000_000),
+        );
+        assert_eq!(correlation.to_cpu_time(start + 500), start + 1000);
+    }
+
+    #[test]
+    fn sample_allocation() {
+        let mut profiler = GPUProfiler::new(5);
+        let frame = profiler.begin_scope("frame").unwrap();
+        assert_eq!(
+            frame,
+            ScopeSamples {
+                start_index: 0,
+                end_index: 1,
+            }
+        );
+        let pass = profiler.begin_scope("pass").unwrap();
+        assert_eq!(pass.start_index, 2);
+        // the fifth sample can't hold a whole scope
+        assert!(profiler.begin_scope("full").is_none());
+        assert_eq!(profiler.end_scope(), Some(pass));
+        assert_eq!(profiler.end_scope(), Some(frame));
+        assert_eq!(profiler.end_scope(), None);
+        assert_eq!(profiler.get_used_samples(), 0..4);
+        profiler.reset();
+        assert_eq!(profiler.get_used_samples(), 0..0);
+        assert_eq!(profiler.begin_scope("frame"), Some(frame));
+    }
+
+    #[test]
+    fn timing_tree() {
+        let mut profiler = GPUProfiler::new(8);
+        profiler.begin_scope("frame");
+        profiler.begin_scope("shadows");
+        profiler.end_scope();
+        profiler.begin_scope("lost");
+        profiler.begin_scope("inner");
+        profiler.end_scope();
+        profiler.end_scope();
+        profiler.end_scope();
+
+        // the failed scope's child takes its place
+        let timestamps = [
+            100,
+            900,
+            200,
+            300,
+            MTLCounterSampleBuffer::ERROR_VALUE,
+            500,
+            400,
+            450,
+        ];
+        let nodes = profiler.resolve(&timestamps, None);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].label, "frame");
+        assert_eq!(nodes[0].get_duration(), 800);
+        let children = &nodes[0].children;
+        assert_eq!(children.len(), 2);
+        assert_eq!((children[0].start, children[0].end), (200, 300));
+        assert_eq!(children[1].label, "inner");
+        assert!(children[1].children.is_empty());
+
+        // samples which were never taken, are missing or out of order are left out
+        assert_eq!(
+            profiler.resolve(&[0, 900, 200, 300], None)[0].label,
+            "shadows"
+        );
+        assert_eq!(profiler.resolve(&[100, 900], None)[0].children, vec![]);
+        assert_eq!(profiler.resolve(&[900, 100, 200, 300], None).len(), 1);
+
+        let correlation = TimestampCorrelation::new((1000, 100), (2600, 900));
+        let nodes = profiler.resolve(&timestamps, Some(&correlation));
+        assert_eq!((nodes[0].start, nodes[0].end), (1000, 2600));
+        assert_eq!(nodes[0].children[0].start, 1200);
+    }
+
+    #[test]
+    fn chrome_trace() {
+        assert_eq!(to_chrome_trace(&[]), "{\"traceEvents\":[]}");
+        let nodes = [TimingNode {
+            label: "frame \"1\"\n".to_string(),
+            start: 1500,
+            end: 3000,
+            children: vec![TimingNode {
+                label: "pass\u{1}".to_string(),
+                start: 2000,
+                end: 2250,
+                children: Vec::new(),
+            }],
+        }];
+        assert_eq!(
+            to_chrome_trace(&nodes),
+            concat!(
+                "{\"traceEvents\":[",
+                "{\"name\":\"frame \\\"1\\\"\\n\",\"ph\":\"X\",\"pid\":0,\"tid\":0,",
+                "\"ts\":1.500,\"dur\":1.500},",
+                "{\"name\":\"pass\\u0001\",\"ph\":\"X\",\"pid\":0,\"tid\":0,",
+                "\"ts\":2.000,\"dur\":0.250}",
+                "]}"
+            )
+        );
+    }
+}
@@ -0,0 +1,777 @@
+use crate::{
+    Array, ConversionError, MTLBlitCommandEncoder, MTLBlitOption, MTLClearColor,
+    MTLCommandBufferStatus, MTLCommandEncoder, MTLCommandQueue, MTLDevice, MTLLoadAction,
+    MTLPixelFormat, MTLRegion, MTLRenderCommandEncoder, MTLRenderPassAttachmentDescriptor,
+    MTLRenderPassColorAttachmentDescriptor, MTLRenderPassDescriptor, MTLResource,
+    MTLResourceOptions, MTLSize, MTLStorageMode, MTLStoreAction, MTLTexture, MTLTextureDescriptor,
+    MTLTextureUsage, NSUInteger,
+};
+use enumflags2::BitFlags;
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::os::raw::c_void;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ReadbackError {
+    Io(std::io::Error),
+    #[cfg(feature = "png")]
+    Png(png::EncodingError),
+    /// The pixel format cannot be read back or converted.
+    UnsupportedFormat(MTLPixelFormat),
+    /// Memoryless textures have no contents to read back.
+    Memoryless,
+    /// The command buffer copying the texture did not complete.
+    CommandBufferFailed,
+    /// The data is shorter than the image it should contain.
+    Truncated,
+    /// The file extension does not name a format which can be written.
+    UnsupportedFileType(String),
+}
+
+impl Display for ReadbackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadbackError::Io(err) => write!(f, "{}", err),
+            #[cfg(feature = "png")]
+            ReadbackError::Png(err) => write!(f, "{}", err),
+            ReadbackError::UnsupportedFormat(format) => {
+                write!(f, "unsupported pixel format {:?}", format)
+            }
+            ReadbackError::Memoryless => f.write_str("cannot read back a memoryless texture"),
+            ReadbackError::CommandBufferFailed => f.write_str("the readback command buffer failed"),
+            ReadbackError::Truncated => f.write_str("texture data is truncated"),
+            ReadbackError::UnsupportedFileType(extension) => {
+                write!(f, "cannot write images with extension {:?}", extension)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReadbackError {}
+
+impl From<std::io::Error> for ReadbackError {
+    fn from(err: std::io::Error) -> Self {
+        ReadbackError::Io(err)
+    }
+}
+
+impl From<ConversionError> for ReadbackError {
+    fn from(err: ConversionError) -> Self {
+        match err {
+            ConversionError::UnsupportedFormat(format) => ReadbackError::UnsupportedFormat(format),
+            ConversionError::Truncated => ReadbackError::Truncated,
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+impl From<png::EncodingError> for ReadbackError {
+    fn from(err: png::EncodingError) -> Self {
+        ReadbackError::Png(err)
+    }
+}
+
+/// The pixels of a `ReadbackImage`, tightly packed row by row from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImagePixels {
+    /// Four 8 bit normalized channels per pixel, in RGBA order.
+    Rgba8(Vec<u8>),
+    /// Four 32 bit float channels per pixel, in RGBA order.
+    Rgba32Float(Vec<f32>),
+    /// One 32 bit float channel per pixel, as read from depth and single channel textures.
+    R32Float(Vec<f32>),
+}
+
+/// A 2D image copied from a texture to the CPU, which can be compared and written to
+/// PNG, EXR or raw files.
+///
+/// PNG files can only be written with the `png` (or `loader`) feature.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReadbackImage {
+    pub width: NSUInteger,
+    pub height: NSUInteger,
+    pub pixels: ImagePixels,
+}
+
+impl ReadbackImage {
+    /// Whether textures of the given pixel format can be read back, which is whether it
+    /// `is_convertible`.
+    pub fn supports_format(format: MTLPixelFormat) -> bool {
+        format.is_convertible()
+    }
+    /// Converts pixels laid out as in a texture of the given format to an image.
+    ///
+    /// RGBA8 and BGRA8 pixels are read as RGBA8, with sRGB values left encoded, R32Float and
+    /// depth and stencil pixels as R32Float and all other formats as RGBA32Float, through
+    /// `MTLPixelFormat::read_texel`.
+    pub fn from_bytes(
+        format: MTLPixelFormat,
+        width: NSUInteger,
+        height: NSUInteger,
+        bytes_per_row: NSUInteger,
+        bytes: &[u8],
+    ) -> Result<ReadbackImage, ReadbackError> {
+        use MTLPixelFormat::*;
+
+        if !ReadbackImage::supports_format(format) {
+            return Err(ReadbackError::UnsupportedFormat(format));
+        }
+        let pixel_size = format.info().unwrap().bytes_per_block;
+        let row_length = (width * pixel_size) as usize;
+        if bytes_per_row < width * pixel_size
+            || height > 0
+                && (bytes.len() as NSUInteger) < bytes_per_row * (height - 1) + width * pixel_size
+        {
+            return Err(ReadbackError::Truncated);
+        }
+        let rows = (0..height as usize)
+            .map(|row| &bytes[row * bytes_per_row as usize..][..row_length])
+            .collect::<Vec<_>>();
+
+        let pixels = match format {
+            RGBA8Unorm | RGBA8Unorm_sRGB => ImagePixels::Rgba8(rows.concat()),
+            BGRA8Unorm | BGRA8Unorm_sRGB => ImagePixels::Rgba8(
+                rows.iter()
+                    .flat_map(|row| row.chunks_exact(4))
+                    .flat_map(|bgra| vec![bgra[2], bgra[1], bgra[0], bgra[3]])
+                    .collect(),
+            ),
+            RGBA32Float => ImagePixels::Rgba32Float(read_f32s(&rows)),
+            R32Float | Depth32Float => ImagePixels::R32Float(read_f32s(&rows)),
+            _ => {
+                let mut pixels = Vec::with_capacity((width * height) as usize * 4);
+                for row in rows {
+                    for texel in format.read_row(row, width)? {
+                        pixels.extend_from_slice(&texel.to_float());
+                    }
+                }
+                if format.is_depth() || format.is_stencil() {
+                    ImagePixels::R32Float(pixels.chunks_exact(4).map(|texel| texel[0]).collect())
+                } else {
+                    ImagePixels::Rgba32Float(pixels)
+                }
+            }
+        };
+        Ok(ReadbackImage {
+            width,
+            height,
+            pixels,
+        })
+    }
+    /// Copies a mipmap level of a slice of the texture to the CPU, waiting until the copy
+    /// has completed.
+    ///
+    /// Private textures are copied through a shared staging buffer and managed textures are
+    /// synchronized before being read. Only the depth of combined depth stencil textures is
+    /// read; use `from_texture_stencil` for their stencil.
+    pub unsafe fn from_texture(
+        device: &MTLDevice,
+        queue: &MTLCommandQueue,
+        texture: &MTLTexture,
+        level: NSUInteger,
+        slice: NSUInteger,
+    ) -> Result<ReadbackImage, ReadbackError> {
+        use MTLPixelFormat::*;
+
+        let format = texture.get_pixel_format();
+        match format {
+            Depth32Float_Stencil8 | Depth24Unorm_Stencil8 => {
+                // both depth planes are copied as 4 bytes per pixel
+                let (size, bytes) = copy_level(
+                    device,
+                    queue,
+                    texture,
+                    level,
+                    slice,
+                    4,
+                    MTLBlitOption::DepthFromDepthStencil.into(),
+                )?;
+                ReadbackImage::from_depth_plane(format, size.width, size.height, &bytes)
+            }
+            _ if ReadbackImage::supports_format(format) => {
+                let pixel_size = format.info().unwrap().bytes_per_block;
+                let (size, bytes) = copy_level(
+                    device,
+                    queue,
+                    texture,
+                    level,
+                    slice,
+                    pixel_size,
+                    BitFlags::empty(),
+                )?;
+                ReadbackImage::from_bytes(
+                    format,
+                    size.width,
+                    size.height,
+                    size.width * pixel_size,
+                    &bytes,
+                )
+            }
+            _ => Err(ReadbackError::UnsupportedFormat(format)),
+        }
+    }
+    /// Copies the stencil of a mipmap level of a slice of a `Stencil8` or combined depth
+    /// stencil texture to the CPU, as an `R32Float` image of the stencil values.
+    pub unsafe fn from_texture_stencil(
+        device: &MTLDevice,
+        queue: &MTLCommandQueue,
+        texture: &MTLTexture,
+        level: NSUInteger,
+        slice: NSUInteger,
+    ) -> Result<ReadbackImage, ReadbackError> {
+        use MTLPixelFormat::*;
+
+        let options = match texture.get_pixel_format() {
+            Stencil8 => BitFlags::empty(),
+            Depth32Float_Stencil8 | Depth24Unorm_Stencil8 => {
+                MTLBlitOption::StencilFromDepthStencil.into()
+            }
+            format => return Err(ReadbackError::UnsupportedFormat(format)),
+        };
+        let (size, bytes) = copy_level(device, queue, texture, level, slice, 1, options)?;
+        ReadbackImage::from_bytes(Stencil8, size.width, size.height, size.width, &bytes)
+    }
+    /// Converts the depth plane of a combined depth stencil format, as copied with
+    /// `MTLBlitOption::DepthFromDepthStencil`, to an `R32Float` image.
+    ///
+    /// Both formats' planes have 4 bytes per pixel, tightly packed: a float for
+    /// `Depth32Float_Stencil8` and a normalized value in the low 24 bits for
+    /// `Depth24Unorm_Stencil8`.
+    pub fn from_depth_plane(
+        format: MTLPixelFormat,
+        width: NSUInteger,
+        height: NSUInteger,
+        bytes: &[u8],
+    ) -> Result<ReadbackImage, ReadbackError> {
+        match format {
+            MTLPixelFormat::Depth32Float_Stencil8 => {
+                return ReadbackImage::from_bytes(
+                    MTLPixelFormat::Depth32Float,
+                    width,
+                    height,
+                    width * 4,
+                    bytes,
+                );
+            }
+            MTLPixelFormat::Depth24Unorm_Stencil8 => (),
+            _ => return Err(ReadbackError::UnsupportedFormat(format)),
+        }
+        let length = (width * height) as usize;
+        if bytes.len() < length * 4 {
+            return Err(ReadbackError::Truncated);
+        }
+        let pixels = bytes[..length * 4]
+            .chunks_exact(4)
+            .map(|texel| {
+                let depth = u32::from_le_bytes([texel[0], texel[1], texel[2], texel[3]]);
+                (depth & 0xFF_FFFF) as f32 / 0xFF_FFFF as f32
+            })
+            .collect();
+        Ok(ReadbackImage {
+            width,
+            height,
+            pixels: ImagePixels::R32Float(pixels),
+        })
+    }
+    /// Converts the image to RGBA8, clamping float channels to `[0, 1]`. Single channel
+    /// images become opaque grayscale.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let quantize = |value: &f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        match &self.pixels {
+            ImagePixels::Rgba8(pixels) => pixels.clone(),
+            ImagePixels::Rgba32Float(pixels) => pixels.iter().map(quantize).collect(),
+            ImagePixels::R32Float(pixels) => pixels
+                .iter()
+                .flat_map(|value| {
+                    let gray = quantize(value);
+                    vec![gray, gray, gray, 0xFF]
+                })
+                .collect(),
+        }
+    }
+    /// Returns the largest difference between any two corresponding channels, with
+    /// RGBA8 channels normalized to `[0, 1]`, or `None` if the images differ in size or
+    /// pixel layout.
+    pub fn max_difference(&self, other: &ReadbackImage) -> Option<f32> {
+        if self.width != other.width || self.height != other.height {
+            return None;
+        }
+        let max = |differences: &mut dyn Iterator<Item = f32>| differences.fold(0.0, f32::max);
+        match (&self.pixels, &other.pixels) {
+            (ImagePixels::Rgba8(a), ImagePixels::Rgba8(b)) => Some(max(&mut a
+                .iter()
+                .zip(b.iter())
+                .map(|(a, b)| (*a as f32 - *b as f32).abs() / 255.0))),
+            (ImagePixels::Rgba32Float(a), ImagePixels::Rgba32Float(b))
+            | (ImagePixels::R32Float(a), ImagePixels::R32Float(b)) => {
+                Some(max(&mut a.iter().zip(b.iter()).map(|(a, b)| (a - b).abs())))
+            }
+            _ => None,
+        }
+    }
+    /// Writes the image as an 8 bit RGBA PNG, converting it with `to_rgba8`.
+    #[cfg(feature = "png")]
+    pub fn write_png<W: Write>(&self, w: W) -> Result<(), ReadbackError> {
+        let mut encoder = png::Encoder::new(w, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.to_rgba8())?;
+        Ok(())
+    }
+    /// Writes the image as an uncompressed OpenEXR file with 32 bit float channels.
+    ///
+    /// RGBA8 channels are normalized to `[0, 1]` and single channel images are written as
+    /// luminance (`Y`).
+    pub fn write_exr<W: Write>(&self, mut w: W) -> Result<(), ReadbackError> {
+        let pixels = match &self.pixels {
+            ImagePixels::Rgba8(pixels) => pixels
+                .iter()
+                .map(|channel| *channel as f32 / 255.0)
+                .collect(),
+            ImagePixels::Rgba32Float(pixels) | ImagePixels::R32Float(pixels) => pixels.clone(),
+        };
+        // channels must be listed and stored in alphabetical order
+        let channels: &[(&str, usize)] = match &self.pixels {
+            ImagePixels::R32Float(_) => &[("Y", 0)],
+            _ => &[("A", 3), ("B", 2), ("G", 1), ("R", 0)],
+        };
+        let (width, height) = (self.width as usize, self.height as usize);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&[0x76, 0x2F, 0x31, 0x01, 2, 0, 0, 0]);
+        let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+            header.extend_from_slice(name.as_bytes());
+            header.push(0);
+            header.extend_from_slice(kind.as_bytes());
+            header.push(0);
+            header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+            header.extend_from_slice(value);
+        };
+        let mut channel_list = Vec::new();
+        for (name, _) in channels {
+            channel_list.extend_from_slice(name.as_bytes());
+            // FLOAT pixels, not perceptually linear, sampled every pixel
+            channel_list.extend_from_slice(&[0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        }
+        channel_list.push(0);
+        let window = [0, 0, width as i32 - 1, height as i32 - 1]
+            .iter()
+            .flat_map(|coordinate| coordinate.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        attribute("channels", "chlist", &channel_list);
+        attribute("compression", "compression", &[0]);
+        attribute("dataWindow", "box2i", &window);
+        attribute("displayWindow", "box2i", &window);
+        attribute("lineOrder", "lineOrder", &[0]);
+        attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+        attribute("screenWindowCenter", "v2f", &[0; 8]);
+        attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+        header.push(0);
+
+        let line_length = width * channels.len() * 4;
+        let first_line = header.len() + height * 8;
+        for y in 0..height {
+            header.extend_from_slice(&((first_line + y * (8 + line_length)) as u64).to_le_bytes());
+        }
+        w.write_all(&header)?;
+
+        let mut line = Vec::with_capacity(8 + line_length);
+        for y in 0..height {
+            line.clear();
+            line.extend_from_slice(&(y as i32).to_le_bytes());
+            line.extend_from_slice(&(line_length as i32).to_le_bytes());
+            let row = &pixels[y * width * channels.len()..][..width * channels.len()];
+            for (_, index) in channels {
+                for pixel in row.chunks_exact(channels.len()) {
+                    line.extend_from_slice(&pixel[*index].to_le_bytes());
+                }
+            }
+            w.write_all(&line)?;
+        }
+        Ok(())
+    }
+    /// Writes the pixels tightly packed with no header, floats in little endian.
+    pub fn write_raw<W: Write>(&self, mut w: W) -> Result<(), ReadbackError> {
+        match &self.pixels {
+            ImagePixels::Rgba8(pixels) => w.write_all(pixels)?,
+            ImagePixels::Rgba32Float(pixels) | ImagePixels::R32Float(pixels) => {
+                for value in pixels {
+                    w.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Writes the image to the given path, picking PNG, EXR or raw from the extension
+    /// (`png`, `exr` or `raw`).
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ReadbackError> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        let open = || std::fs::File::create(path).map(std::io::BufWriter::new);
+        match extension.as_str() {
+            #[cfg(feature = "png")]
+            "png" => self.write_png(open()?),
+            "exr" => self.write_exr(open()?),
+            "raw" => self.write_raw(open()?),
+            _ => Err(ReadbackError::UnsupportedFileType(extension)),
+        }
+    }
+}
+
+/// Renders a single pass into a new texture and reads the result back, for golden image
+/// tests and headless screenshots.
+///
+/// The color attachment is cleared to `clear_color` before `encode` is called with the
+/// pass' encoder; `encode` must not end encoding.
+pub unsafe fn render_to_image<F: FnOnce(&MTLRenderCommandEncoder)>(
+    device: &MTLDevice,
+    queue: &MTLCommandQueue,
+    format: MTLPixelFormat,
+    width: NSUInteger,
+    height: NSUInteger,
+    clear_color: MTLClearColor,
+    encode: F,
+) -> Result<ReadbackImage, ReadbackError> {
+    if !ReadbackImage::supports_format(format) {
+        return Err(ReadbackError::UnsupportedFormat(format));
+    }
+    let texture = device.new_texture_with_descriptor(&{
+        let desc = MTLTextureDescriptor::new();
+        desc.set_pixel_format(format);
+        desc.set_width(width);
+        desc.set_height(height);
+        desc.set_resource_options(
+            MTLResourceOptions::new().set_storage_mode(MTLStorageMode::Private),
+        );
+        desc.set_usage(MTLTextureUsage::RenderTarget | MTLTextureUsage::ShaderRead);
+        desc
+    });
+
+    let command_buffer = queue.new_command_buffer(true);
+    let encoder = command_buffer.new_render_command_encoder_with_descriptor(&{
+        let desc = MTLRenderPassDescriptor::new();
+        desc.get_color_attachments()
+            .set_object_at_indexed_subscript(0, &{
+                let desc = MTLRenderPassColorAttachmentDescriptor::new();
+                desc.set_texture(&texture);
+                desc.set_load_action(MTLLoadAction::Clear);
+                desc.set_store_action(MTLStoreAction::Store);
+                desc.set_clear_color(clear_color);
+                desc
+            });
+        desc
+    });
+    encode(&encoder);
+    encoder.end_encoding();
+    command_buffer.commit();
+
+    // the readback is committed to the same queue, so runs after the pass
+    ReadbackImage::from_texture(device, queue, &texture, 0, 0)
+}
+
+/// Copies a mipmap level of a slice of the texture, with `pixel_size` bytes per pixel, to
+/// the CPU, returning the level's size and its tightly packed rows.
+///
+/// Copies with options go through a staging buffer, since `get_bytes` cannot select a
+/// plane.
+unsafe fn copy_level(
+    device: &MTLDevice,
+    queue: &MTLCommandQueue,
+    texture: &MTLTexture,
+    level: NSUInteger,
+    slice: NSUInteger,
+    pixel_size: NSUInteger,
+    options: BitFlags<MTLBlitOption>,
+) -> Result<(MTLSize, Vec<u8>), ReadbackError> {
+    let size = MTLSize {
+        width: (texture.get_width() >> level).max(1),
+        height: (texture.get_height() >> level).max(1),
+        depth: 1,
+    };
+    let bytes_per_row = size.width * pixel_size;
+    let length = bytes_per_row * size.height;
+    let origin = MTLSize {
+        width: 0,
+        height: 0,
+        depth: 0,
+    };
+
+    let blit = |encode: &dyn Fn(&MTLBlitCommandEncoder)| {
+        let command_buffer = queue.new_command_buffer(true);
+        let encoder = command_buffer.new_blit_command_encoder();
+        encode(&encoder);
+        encoder.end_encoding();
+        command_buffer.commit();
+        command_buffer.wait_until_completed();
+        match command_buffer.get_status() {
+            MTLCommandBufferStatus::Completed => Ok(()),
+            _ => Err(ReadbackError::CommandBufferFailed),
+        }
+    };
+
+    let mut bytes = vec![0u8; length as usize];
+    match texture.get_storage_mode() {
+        MTLStorageMode::Memoryless => return Err(ReadbackError::Memoryless),
+        mode if mode == MTLStorageMode::Private || !options.is_empty() => {
+            let buffer = device.new_buffer_with_length(
+                length,
+                MTLResourceOptions::new().set_storage_mode(MTLStorageMode::Shared),
+            );
+            blit(&|encoder| {
+                encoder.copy_from_texture_to_buffer_with_options(
+                    texture,
+                    slice,
+                    level,
+                    origin,
+                    size,
+                    &buffer,
+                    0,
+                    bytes_per_row,
+                    length,
+                    options,
+                )
+            })?;
+            bytes.copy_from_slice(std::slice::from_raw_parts(
+                buffer.get_contents() as *const u8,
+                length as usize,
+            ));
+        }
+        mode => {
+            if mode == MTLStorageMode::Managed {
+                blit(&|encoder| encoder.synchronize_texture(texture, slice, level))?;
+            }
+            texture.get_bytes(
+                bytes.as_mut_ptr() as *mut c_void,
+                bytes_per_row,
+                0,
+                MTLRegion { origin, size },
+                level,
+                slice,
+            );
+        }
+    }
+    Ok((size, bytes))
+}
+
+fn read_f32s(rows: &[&[u8]]) -> Vec<f32> {
+    rows.iter()
+        .flat_map(|row| row.chunks_exact(4))
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgba8() -> ReadbackImage {
+        ReadbackImage {
+            width: 2,
+            height: 1,
+            pixels: ImagePixels::Rgba8(vec![255, 0, 51, 255, 0, 102, 0, 0]),
+        }
+    }
+
+    fn floats(bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()
+    }
+
+    /// Returns the header's attributes and the pixel data following the line offsets.
+    fn parse_exr(bytes: &[u8], height: usize) -> (Vec<(String, Vec<u8>)>, &[u8]) {
+        assert_eq!(bytes[..8], [0x76, 0x2F, 0x31, 0x01, 2, 0, 0, 0]);
+        let mut attributes = Vec::new();
+        let mut offset = 8;
+        let string = |offset: &mut usize| {
+            let end = *offset + bytes[*offset..].iter().position(|b| *b == 0).unwrap();
+            let string = String::from_utf8(bytes[*offset..end].to_vec()).unwrap();
+            *offset = end + 1;
+            string
+        };
+        loop {
+            let name = string(&mut offset);
+            if name.is_empty() {
+                break;
+            }
+            string(&mut offset);
+            let length = i32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ]) as usize;
+            attributes.push((name, bytes[offset + 4..][..length].to_vec()));
+            offset += 4 + length;
+        }
+        // each line's offset points past the offsets
+        for y in 0..height {
+            let mut line_offset = [0; 8];
+            line_offset.copy_from_slice(&bytes[offset + y * 8..][..8]);
+            assert!(u64::from_le_bytes(line_offset) as usize >= offset + height * 8);
+        }
+        (attributes, &bytes[offset + height * 8..])
+    }
+
+    #[test]
+    fn from_bytes() {
+        let bgra = [1, 2, 3, 4, 0, 0, 5, 6, 7, 8, 0, 0];
+        let image = ReadbackImage::from_bytes(MTLPixelFormat::BGRA8Unorm, 1, 2, 6, &bgra).unwrap();
+        assert_eq!(
+            image.pixels,
+            ImagePixels::Rgba8(vec![3, 2, 1, 4, 7, 6, 5, 8])
+        );
+        assert!(matches!(
+            ReadbackImage::from_bytes(MTLPixelFormat::RGBA8Unorm, 2, 2, 8, &[0; 15]),
+            Err(ReadbackError::Truncated)
+        ));
+        assert!(matches!(
+            ReadbackImage::from_bytes(MTLPixelFormat::BC1_RGBA, 4, 4, 8, &[0; 8]),
+            Err(ReadbackError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn depth_plane() {
+        let d32 = [0.25f32.to_le_bytes(), 1f32.to_le_bytes()].concat();
+        let image =
+            ReadbackImage::from_depth_plane(MTLPixelFormat::Depth32Float_Stencil8, 2, 1, &d32)
+                .unwrap();
+        assert_eq!(image.pixels, ImagePixels::R32Float(vec![0.25, 1.0]));
+
+        // the high byte of 24 bit depth is ignored
+        let d24 = [0xFFFF_FFFFu32.to_le_bytes(), 0xAB00_0000u32.to_le_bytes()].concat();
+        let image =
+            ReadbackImage::from_depth_plane(MTLPixelFormat::Depth24Unorm_Stencil8, 2, 1, &d24)
+                .unwrap();
+        assert_eq!(image.pixels, ImagePixels::R32Float(vec![1.0, 0.0]));
+        assert!(matches!(
+            ReadbackImage::from_depth_plane(MTLPixelFormat::Depth24Unorm_Stencil8, 2, 2, &d24),
+            Err(ReadbackError::Truncated)
+        ));
+        assert!(ReadbackImage::from_depth_plane(MTLPixelFormat::Depth32Float, 2, 1, &d32).is_err());
+    }
+
+    #[test]
+    fn to_rgba8() {
+        let image = ReadbackImage {
+            width: 2,
+            height: 1,
+            pixels: ImagePixels::R32Float(vec![-1.0, 0.5]),
+        };
+        assert_eq!(image.to_rgba8(), [0, 0, 0, 255, 128, 128, 128, 255]);
+        assert_eq!(image.max_difference(&rgba8()), None);
+    }
+
+    #[test]
+    fn raw() {
+        let mut raw = Vec::new();
+        rgba8().write_raw(&mut raw).unwrap();
+        assert_eq!(raw, [255, 0, 51, 255, 0, 102, 0, 0]);
+
+        let image = ReadbackImage {
+            width: 1,
+            height: 1,
+            pixels: ImagePixels::Rgba32Float(vec![1.0, -2.0, 0.5, 0.0]),
+        };
+        raw.clear();
+        image.write_raw(&mut raw).unwrap();
+        assert_eq!(floats(&raw), [1.0, -2.0, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn exr_channel_order() {
+        let mut exr = Vec::new();
+        rgba8().write_exr(&mut exr).unwrap();
+        let (attributes, lines) = parse_exr(&exr, 1);
+
+        let names = attributes
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "channels",
+                "compression",
+                "dataWindow",
+                "displayWindow",
+                "lineOrder",
+                "pixelAspectRatio",
+                "screenWindowCenter",
+                "screenWindowWidth"
+            ]
+        );
+        let channels = &attributes[0].1;
+        let channel_names = channels
+            .chunks(18)
+            .filter(|channel| channel.len() == 18)
+            .map(|channel| channel[0] as char)
+            .collect::<String>();
+        assert_eq!(channel_names, "ABGR");
+        // xMin, yMin, xMax and yMax
+        assert_eq!(
+            attributes[2].1,
+            [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        // the line's number and length, then each channel for every pixel of the line
+        assert_eq!(lines[..4], 0i32.to_le_bytes());
+        assert_eq!(lines[4..8], 32i32.to_le_bytes());
+        assert_eq!(
+            floats(&lines[8..]),
+            [1.0, 0.0, 0.2, 0.0, 0.0, 0.4, 1.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn exr_luminance() {
+        let image = ReadbackImage {
+            width: 1,
+            height: 2,
+            pixels: ImagePixels::R32Float(vec![0.5, 2.0]),
+        };
+        let mut exr = Vec::new();
+        image.write_exr(&mut exr).unwrap();
+        let (attributes, lines) = parse_exr(&exr, 2);
+        assert_eq!(attributes[0].1[..2], *b"Y\0");
+        assert_eq!(attributes[0].1.len(), 19);
+        assert_eq!(lines.len(), 2 * 12);
+        assert_eq!(lines[12..16], 1i32.to_le_bytes());
+        assert_eq!(floats(&lines[20..]), [2.0]);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png() {
+        let mut bytes = Vec::new();
+        rgba8().write_png(&mut bytes).unwrap();
+        let (info, mut reader) = png::Decoder::new(&bytes[..]).read_info().unwrap();
+        assert_eq!((info.width, info.height), (2, 1));
+        assert_eq!(info.color_type, png::ColorType::RGBA);
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, [255, 0, 51, 255, 0, 102, 0, 0]);
+    }
+
+    #[test]
+    fn save() {
+        let path = std::env::temp_dir().join(format!("readback-{}.raw", std::process::id()));
+        rgba8().save(&path).unwrap();
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            [255, 0, 51, 255, 0, 102, 0, 0]
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            rgba8().save("image.bmp"),
+            Err(ReadbackError::UnsupportedFileType(_))
+        ));
+    }
+}
@@ -1,11 +1,14 @@
 use crate::import_objc_macros::*;
 use crate::{
-    handle, DeviceCreated, MTLResource, NSRange, NSUInteger, NSUIntegerRange, Object, ObjectPointer,
+    handle, DeviceCreated, Labeled, MTLResource, MTLTexture, MTLTextureDescriptor, NSRange,
+    NSUInteger, NSUIntegerRange, Object, ObjectPointer,
 };
 use std::os::raw::c_void;
 
 pub struct MTLBuffer(ObjectPointer);
 handle!(MTLBuffer);
+unsafe impl Send for MTLBuffer {}
+unsafe impl Sync for MTLBuffer {}
 
 impl MTLBuffer {
     pub unsafe fn get_contents(&self) -> *mut c_void {
@@ -21,12 +24,31 @@ impl MTLBuffer {
     pub unsafe fn get_length(&self) -> NSUInteger {
         msg_send![self.get_ptr(), length]
     }
+    /// Creates a texture which shares its storage with this buffer.
+    ///
+    /// `offset` and `bytes_per_row` must be aligned to the device's
+    /// `minimum_linear_texture_alignment_for_pixel_format`; see `LinearTextureLayout`.
+    pub unsafe fn new_texture_with_descriptor(
+        &self,
+        descriptor: &MTLTextureDescriptor,
+        offset: NSUInteger,
+        bytes_per_row: NSUInteger,
+    ) -> MTLTexture {
+        MTLTexture::from_ptr(msg_send![
+            self.get_ptr(),
+            newTextureWithDescriptor:descriptor.get_ptr()
+            offset:offset
+            bytesPerRow:bytes_per_row
+        ])
+    }
 }
 
 impl MTLResource for MTLBuffer {}
 
 impl DeviceCreated for MTLBuffer {}
 
+impl Labeled for MTLBuffer {}
+
 impl Object for MTLBuffer {
     unsafe fn from_ptr(ptr: ObjectPointer) -> Self
     where
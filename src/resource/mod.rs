@@ -12,6 +12,7 @@ pub enum MTLCPUCacheMode {
     WriteCombined = 1,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLStorageMode {
     Shared = 0,
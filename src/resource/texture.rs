@@ -1,11 +1,12 @@
 use crate::import_objc_macros::*;
 use crate::{
-    handle, DeviceCreated, MTLPixelFormat, MTLRegion, MTLResource, MTLResourceOptions, NSUInteger,
-    NSUIntegerRange, Object, ObjectPointer,
+    handle, DeviceCreated, Labeled, MTLPixelFormat, MTLRegion, MTLResource, MTLResourceOptions,
+    NSRange, NSUInteger, NSUIntegerRange, Object, ObjectPointer,
 };
 use enumflags2::BitFlags;
 use std::os::raw::c_void;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLTextureType {
     D1 = 0,
@@ -29,6 +30,36 @@ pub enum MTLTextureUsage {
     PixelFormatView = 0x0010,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum MTLTextureSwizzle {
+    Zero = 0,
+    One = 1,
+    Red = 2,
+    Green = 3,
+    Blue = 4,
+    Alpha = 5,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub struct MTLTextureSwizzleChannels {
+    pub red: MTLTextureSwizzle,
+    pub green: MTLTextureSwizzle,
+    pub blue: MTLTextureSwizzle,
+    pub alpha: MTLTextureSwizzle,
+}
+
+impl MTLTextureSwizzleChannels {
+    /// The swizzle which leaves every channel in place.
+    pub const IDENTITY: MTLTextureSwizzleChannels = MTLTextureSwizzleChannels {
+        red: MTLTextureSwizzle::Red,
+        green: MTLTextureSwizzle::Green,
+        blue: MTLTextureSwizzle::Blue,
+        alpha: MTLTextureSwizzle::Alpha,
+    };
+}
+
 pub struct MTLTextureDescriptor(ObjectPointer);
 handle!(MTLTextureDescriptor);
 
@@ -69,6 +100,9 @@ impl MTLTextureDescriptor {
     pub unsafe fn set_usage(&self, usage: BitFlags<MTLTextureUsage>) {
         msg_send![self.get_ptr(), setUsage: usage.bits()]
     }
+    pub unsafe fn set_swizzle(&self, swizzle: MTLTextureSwizzleChannels) {
+        msg_send![self.get_ptr(), setSwizzle: swizzle]
+    }
 }
 
 impl Object for MTLTextureDescriptor {
@@ -86,6 +120,8 @@ impl Object for MTLTextureDescriptor {
 
 pub struct MTLTexture(ObjectPointer);
 handle!(MTLTexture);
+unsafe impl Send for MTLTexture {}
+unsafe impl Sync for MTLTexture {}
 
 impl MTLTexture {
     pub unsafe fn replace_region(
@@ -126,26 +162,107 @@ impl MTLTexture {
             slice:slice
         ]
     }
+    pub unsafe fn get_texture_type(&self) -> MTLTextureType {
+        msg_send![self.get_ptr(), textureType]
+    }
+    pub unsafe fn get_pixel_format(&self) -> MTLPixelFormat {
+        msg_send![self.get_ptr(), pixelFormat]
+    }
+    pub unsafe fn get_width(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), width]
+    }
+    pub unsafe fn get_height(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), height]
+    }
+    pub unsafe fn get_depth(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), depth]
+    }
+    pub unsafe fn get_mipmap_level_count(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), mipmapLevelCount]
+    }
+    pub unsafe fn get_array_length(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), arrayLength]
+    }
+    pub unsafe fn get_sample_count(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), sampleCount]
+    }
+    pub unsafe fn get_usage(&self) -> BitFlags<MTLTextureUsage> {
+        let bits: u64 = msg_send![self.get_ptr(), usage];
+        BitFlags::from_bits_truncate(bits)
+    }
+    pub unsafe fn get_swizzle(&self) -> MTLTextureSwizzleChannels {
+        msg_send![self.get_ptr(), swizzle]
+    }
+    /// The number of slices a view of this texture may address: six per cube, or one
+    /// per array element otherwise.
+    pub unsafe fn get_slice_count(&self) -> NSUInteger {
+        match self.get_texture_type() {
+            MTLTextureType::Cube | MTLTextureType::CubeArray => self.get_array_length() * 6,
+            _ => self.get_array_length(),
+        }
+    }
     pub unsafe fn new_texture_view_with_pixel_format(&self, format: MTLPixelFormat) -> MTLTexture {
         MTLTexture::from_ptr(msg_send![
             self.get_ptr(),
             newTextureViewWithPixelFormat: format
         ])
     }
+    /// Like `new_texture_view_with_pixel_format`, but first checks that the format is
+    /// compatible with the texture's.
+    pub unsafe fn try_new_texture_view_with_pixel_format(
+        &self,
+        format: MTLPixelFormat,
+    ) -> Result<MTLTexture, TextureViewError> {
+        validate_texture_view_format(self.get_pixel_format(), format)?;
+        Ok(self.new_texture_view_with_pixel_format(format))
+    }
     pub unsafe fn new_texture_view_with_pixel_format_and_texture_type(
         &self,
         format: MTLPixelFormat,
         texture_type: MTLTextureType,
         levels: NSUIntegerRange,
         slices: NSUIntegerRange,
-    ) -> MTLTexture {
-        MTLTexture::from_ptr(msg_send![
+    ) -> Result<MTLTexture, TextureViewError> {
+        let (levels, slices) = self.validate_texture_view(format, levels, slices)?;
+        Ok(MTLTexture::from_ptr(msg_send![
             self.get_ptr(),
             newTextureViewWithPixelFormat:format
             textureType:texture_type
             levels:levels
             slices:slices
-        ])
+        ]))
+    }
+    pub unsafe fn new_texture_view_with_pixel_format_texture_type_and_swizzle(
+        &self,
+        format: MTLPixelFormat,
+        texture_type: MTLTextureType,
+        levels: NSUIntegerRange,
+        slices: NSUIntegerRange,
+        swizzle: MTLTextureSwizzleChannels,
+    ) -> Result<MTLTexture, TextureViewError> {
+        let (levels, slices) = self.validate_texture_view(format, levels, slices)?;
+        Ok(MTLTexture::from_ptr(msg_send![
+            self.get_ptr(),
+            newTextureViewWithPixelFormat:format
+            textureType:texture_type
+            levels:levels
+            slices:slices
+            swizzle:swizzle
+        ]))
+    }
+    unsafe fn validate_texture_view(
+        &self,
+        format: MTLPixelFormat,
+        levels: NSUIntegerRange,
+        slices: NSUIntegerRange,
+    ) -> Result<(NSRange, NSRange), TextureViewError> {
+        validate_texture_view_format(self.get_pixel_format(), format)?;
+        Ok((
+            validate_texture_view_range(levels, self.get_mipmap_level_count())
+                .map_err(TextureViewError::LevelsOutOfBounds)?,
+            validate_texture_view_range(slices, self.get_slice_count())
+                .map_err(TextureViewError::SlicesOutOfBounds)?,
+        ))
     }
 }
 
@@ -153,6 +270,8 @@ impl MTLResource for MTLTexture {}
 
 impl DeviceCreated for MTLTexture {}
 
+impl Labeled for MTLTexture {}
+
 impl Object for MTLTexture {
     unsafe fn from_ptr(ptr: ObjectPointer) -> Self
     where
@@ -165,3 +284,78 @@ impl Object for MTLTexture {
         self.0
     }
 }
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TextureViewError {
+    /// The view format is not in the same `PixelFormatViewClass` as the texture's format.
+    IncompatiblePixelFormat {
+        texture: MTLPixelFormat,
+        view: MTLPixelFormat,
+    },
+    /// The level range is empty or extends beyond the texture's mipmap levels.
+    LevelsOutOfBounds(NSUIntegerRange),
+    /// The slice range is empty or extends beyond the texture's slices.
+    SlicesOutOfBounds(NSUIntegerRange),
+}
+
+/// Checks that a texture of `texture` format may be viewed as `view` format.
+pub fn validate_texture_view_format(
+    texture: MTLPixelFormat,
+    view: MTLPixelFormat,
+) -> Result<(), TextureViewError> {
+    if texture.is_view_compatible(view) {
+        Ok(())
+    } else {
+        Err(TextureViewError::IncompatiblePixelFormat { texture, view })
+    }
+}
+
+/// Checks that `range` is non-empty and lies within `0..count`, converting it to the
+/// `NSRange` Metal expects.
+pub fn validate_texture_view_range(
+    range: NSUIntegerRange,
+    count: NSUInteger,
+) -> Result<NSRange, NSUIntegerRange> {
+    if range.start < range.end && range.end <= count {
+        Ok(NSRange::from(range))
+    } else {
+        Err(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_format() {
+        assert_eq!(
+            validate_texture_view_format(MTLPixelFormat::RGBA8Unorm, MTLPixelFormat::R32Float),
+            Ok(())
+        );
+        assert_eq!(
+            validate_texture_view_format(MTLPixelFormat::RGBA8Unorm, MTLPixelFormat::RG32Float),
+            Err(TextureViewError::IncompatiblePixelFormat {
+                texture: MTLPixelFormat::RGBA8Unorm,
+                view: MTLPixelFormat::RG32Float,
+            })
+        );
+    }
+
+    #[test]
+    fn view_range() {
+        assert_eq!(
+            validate_texture_view_range(1..3, 4),
+            Ok(NSRange {
+                location: 1,
+                length: 2,
+            })
+        );
+        assert_eq!(
+            validate_texture_view_range(0..4, 4).map(|r| r.length),
+            Ok(4)
+        );
+        assert_eq!(validate_texture_view_range(2..5, 4), Err(2..5));
+        assert_eq!(validate_texture_view_range(2..2, 4), Err(2..2));
+    }
+}
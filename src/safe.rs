@@ -0,0 +1,522 @@
+//! Safe wrappers over devices, command queues, command buffers and encoders.
+//!
+//! Their lifetimes enforce what Metal otherwise only checks at runtime, if at all:
+//! - an encoder borrows its command buffer mutably, so only one encoder may be encoding
+//!   on a command buffer at a time, and the buffer cannot be committed while it is;
+//! - `end_encoding` consumes the encoder, which also ends encoding when dropped;
+//! - `commit` consumes the command buffer, so nothing can be encoded into it afterwards;
+//! - a command buffer's debug group borrows it mutably, so encoders are created through
+//!   the group and the buffer cannot be committed until the group is popped.
+//!
+//! Resources bound to an encoder are retained by its command buffer until it completes.
+//! What shaders do with them remains their responsibility.
+//!
+//! The underlying bindings are available through `as_raw` for anything not wrapped here.
+//!
+//! # Examples
+//!
+//! Encoders within a command buffer's debug group are created through the group:
+//!
+//! ```no_run
+//! use iron_oxide::safe::Device;
+//!
+//! let device = Device::system_default().unwrap();
+//! let queue = device.new_command_queue().unwrap();
+//! let mut command_buffer = queue.new_command_buffer().unwrap();
+//! {
+//!     let mut group = command_buffer.debug_group("frame");
+//!     group.new_compute_encoder().unwrap().end_encoding();
+//! }
+//! command_buffer.commit().wait_until_completed();
+//! ```
+//!
+//! An encoder cannot be used after it has ended encoding:
+//!
+//! ```compile_fail
+//! use iron_oxide::safe::Device;
+//!
+//! let device = Device::system_default().unwrap();
+//! let queue = device.new_command_queue().unwrap();
+//! let mut command_buffer = queue.new_command_buffer().unwrap();
+//! let encoder = command_buffer.new_compute_encoder().unwrap();
+//! encoder.end_encoding();
+//! encoder.set_bytes(&[0u32], 0).unwrap();
+//! ```
+//!
+//! Only one encoder may encode at a time:
+//!
+//! ```compile_fail
+//! use iron_oxide::safe::Device;
+//!
+//! let device = Device::system_default().unwrap();
+//! let queue = device.new_command_queue().unwrap();
+//! let mut command_buffer = queue.new_command_buffer().unwrap();
+//! let compute = command_buffer.new_compute_encoder().unwrap();
+//! let blit = command_buffer.new_blit_encoder().unwrap();
+//! compute.end_encoding();
+//! blit.end_encoding();
+//! ```
+//!
+//! A command buffer cannot be committed while an encoder is encoding:
+//!
+//! ```compile_fail
+//! use iron_oxide::safe::Device;
+//!
+//! let device = Device::system_default().unwrap();
+//! let queue = device.new_command_queue().unwrap();
+//! let mut command_buffer = queue.new_command_buffer().unwrap();
+//! let encoder = command_buffer.new_compute_encoder().unwrap();
+//! command_buffer.commit();
+//! encoder.end_encoding();
+//! ```
+//!
+//! Nothing can be encoded after committing:
+//!
+//! ```compile_fail
+//! use iron_oxide::safe::Device;
+//!
+//! let device = Device::system_default().unwrap();
+//! let queue = device.new_command_queue().unwrap();
+//! let mut command_buffer = queue.new_command_buffer().unwrap();
+//! let committed = command_buffer.commit();
+//! command_buffer.new_compute_encoder().unwrap();
+//! committed.wait_until_completed();
+//! ```
+
+use crate::{
+    DebugGroup, DebugGroups, DeviceCapabilities, DispatchPlan, Labeled, MTLBlitCommandEncoder,
+    MTLBuffer, MTLCommandBuffer, MTLCommandBufferStatus, MTLCommandEncoder, MTLCommandQueue,
+    MTLComputeCommandEncoder, MTLComputePipelineState, MTLCopyAllDevices,
+    MTLCreateSystemDefaultDevice, MTLDepthStencilState, MTLDevice, MTLDrawable, MTLPrimitiveType,
+    MTLRenderCommandEncoder, MTLRenderPassDescriptor, MTLRenderPipelineState, MTLResourceOptions,
+    MTLSize, NSError, NSUInteger, Object,
+};
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_void;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Error {
+    /// There is no Metal device.
+    NoDevice,
+    /// Metal failed to create the named object.
+    CreationFailed(&'static str),
+    /// An offset or length lies outside of a buffer, or inline data is too long.
+    OutOfBounds,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NoDevice => write!(f, "no Metal device is available"),
+            Error::CreationFailed(object) => write!(f, "failed to create a {}", object),
+            Error::OutOfBounds => write!(f, "the range lies outside of the buffer or limit"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The most data which may be set inline with `set_bytes`.
+pub const MAX_SET_BYTES_LENGTH: usize = 4096;
+
+unsafe fn check<T: Object>(object: T, name: &'static str) -> Result<T, Error> {
+    if object.get_ptr().0.is_null() {
+        // releasing nil does nothing
+        Err(Error::CreationFailed(name))
+    } else {
+        Ok(object)
+    }
+}
+
+fn check_range(buffer: &MTLBuffer, offset: NSUInteger, length: NSUInteger) -> Result<(), Error> {
+    let buffer_length = unsafe { buffer.get_length() };
+    match offset.checked_add(length) {
+        Some(end) if end <= buffer_length => Ok(()),
+        _ => Err(Error::OutOfBounds),
+    }
+}
+
+pub struct Device {
+    device: MTLDevice,
+}
+
+impl Device {
+    pub fn system_default() -> Result<Device, Error> {
+        unsafe {
+            let device = MTLCreateSystemDefaultDevice();
+            if device.get_ptr().0.is_null() {
+                Err(Error::NoDevice)
+            } else {
+                Ok(Device { device })
+            }
+        }
+    }
+    pub fn all() -> Vec<Device> {
+        unsafe { MTLCopyAllDevices() }
+            .into_iter()
+            .map(|device| Device { device })
+            .collect()
+    }
+    /// Wraps a device, which must not be nil.
+    pub unsafe fn from_raw(device: MTLDevice) -> Device {
+        Device { device }
+    }
+    pub fn as_raw(&self) -> &MTLDevice {
+        &self.device
+    }
+    pub fn get_name(&self) -> String {
+        unsafe { self.device.get_name() }
+    }
+    pub fn get_registry_id(&self) -> u64 {
+        unsafe { self.device.get_registry_id() }
+    }
+    pub fn get_capabilities(&self) -> DeviceCapabilities {
+        unsafe { self.device.get_capabilities() }
+    }
+    pub fn new_command_queue(&self) -> Result<CommandQueue, Error> {
+        unsafe {
+            let queue = check(self.device.new_command_queue(), "command queue")?;
+            Ok(CommandQueue { queue })
+        }
+    }
+    pub fn new_buffer(
+        &self,
+        length: NSUInteger,
+        options: MTLResourceOptions,
+    ) -> Result<MTLBuffer, Error> {
+        unsafe {
+            check(
+                self.device.new_buffer_with_length(length, options),
+                "buffer",
+            )
+        }
+    }
+    pub fn new_buffer_with_data<T: Copy>(
+        &self,
+        data: &[T],
+        options: MTLResourceOptions,
+    ) -> Result<MTLBuffer, Error> {
+        unsafe {
+            check(
+                self.device.new_buffer_with_bytes(
+                    data.as_ptr() as *const c_void,
+                    std::mem::size_of_val(data) as NSUInteger,
+                    options,
+                ),
+                "buffer",
+            )
+        }
+    }
+}
+
+pub struct CommandQueue {
+    queue: MTLCommandQueue,
+}
+
+impl CommandQueue {
+    pub fn as_raw(&self) -> &MTLCommandQueue {
+        &self.queue
+    }
+    pub fn set_label(&self, label: &str) {
+        unsafe { self.queue.set_label(label) }
+    }
+    /// Creates a command buffer which retains the resources used by its commands.
+    pub fn new_command_buffer(&self) -> Result<CommandBuffer, Error> {
+        unsafe {
+            let buffer = check(self.queue.new_command_buffer(true), "command buffer")?;
+            Ok(CommandBuffer { buffer })
+        }
+    }
+}
+
+pub struct CommandBuffer {
+    buffer: MTLCommandBuffer,
+}
+
+impl CommandBuffer {
+    pub fn as_raw(&self) -> &MTLCommandBuffer {
+        &self.buffer
+    }
+    pub fn set_label(&self, label: &str) {
+        unsafe { self.buffer.set_label(label) }
+    }
+    /// Pushes a debug group which is popped when the returned guard is dropped.
+    ///
+    /// The guard dereferences to the command buffer, so encoders within the group are
+    /// created through it.
+    pub fn debug_group(&mut self, name: &str) -> CommandBufferDebugGroup<'_> {
+        unsafe { self.buffer.push_debug_group(name) };
+        CommandBufferDebugGroup { buffer: self }
+    }
+    pub fn new_render_encoder(
+        &mut self,
+        desc: &MTLRenderPassDescriptor,
+    ) -> Result<RenderEncoder<'_>, Error> {
+        unsafe {
+            let encoder = check(
+                self.buffer.new_render_command_encoder_with_descriptor(desc),
+                "render command encoder",
+            )?;
+            Ok(RenderEncoder {
+                encoder,
+                _buffer: PhantomData,
+            })
+        }
+    }
+    pub fn new_compute_encoder(&mut self) -> Result<ComputeEncoder<'_>, Error> {
+        unsafe {
+            let encoder = check(self.buffer.new_compute_encoder(), "compute command encoder")?;
+            Ok(ComputeEncoder {
+                encoder,
+                _buffer: PhantomData,
+            })
+        }
+    }
+    pub fn new_blit_encoder(&mut self) -> Result<BlitEncoder<'_>, Error> {
+        unsafe {
+            let encoder = check(
+                self.buffer.new_blit_command_encoder(),
+                "blit command encoder",
+            )?;
+            Ok(BlitEncoder {
+                encoder,
+                _buffer: PhantomData,
+            })
+        }
+    }
+    pub fn present_drawable<T: MTLDrawable>(&mut self, drawable: &T) {
+        unsafe { self.buffer.present_drawable(drawable) }
+    }
+    pub fn commit(self) -> CommittedCommandBuffer {
+        unsafe { self.buffer.commit() };
+        CommittedCommandBuffer {
+            buffer: self.buffer,
+        }
+    }
+}
+
+/// Pops its debug group when dropped.
+pub struct CommandBufferDebugGroup<'a> {
+    buffer: &'a mut CommandBuffer,
+}
+
+impl Deref for CommandBufferDebugGroup<'_> {
+    type Target = CommandBuffer;
+
+    fn deref(&self) -> &CommandBuffer {
+        self.buffer
+    }
+}
+
+impl DerefMut for CommandBufferDebugGroup<'_> {
+    fn deref_mut(&mut self) -> &mut CommandBuffer {
+        self.buffer
+    }
+}
+
+impl Drop for CommandBufferDebugGroup<'_> {
+    fn drop(&mut self) {
+        unsafe { self.buffer.buffer.pop_debug_group() }
+    }
+}
+
+/// A command buffer which can only be waited on.
+pub struct CommittedCommandBuffer {
+    buffer: MTLCommandBuffer,
+}
+
+impl CommittedCommandBuffer {
+    pub fn as_raw(&self) -> &MTLCommandBuffer {
+        &self.buffer
+    }
+    pub fn get_status(&self) -> MTLCommandBufferStatus {
+        unsafe { self.buffer.get_status() }
+    }
+    pub fn get_error(&self) -> Option<NSError> {
+        unsafe { self.buffer.get_error() }
+    }
+    pub fn wait_until_scheduled(&self) {
+        unsafe { self.buffer.wait_until_scheduled() }
+    }
+    /// Returns the final status, which is either `Completed` or `Error`.
+    pub fn wait_until_completed(&self) -> MTLCommandBufferStatus {
+        unsafe {
+            self.buffer.wait_until_completed();
+            self.buffer.get_status()
+        }
+    }
+}
+
+/// Ends encoding when dropped.
+pub struct RenderEncoder<'a> {
+    encoder: MTLRenderCommandEncoder,
+    _buffer: PhantomData<&'a mut CommandBuffer>,
+}
+
+impl RenderEncoder<'_> {
+    pub fn as_raw(&self) -> &MTLRenderCommandEncoder {
+        &self.encoder
+    }
+    pub fn set_label(&self, label: &str) {
+        unsafe { self.encoder.set_label(label) }
+    }
+    pub fn debug_group(&self, name: &str) -> DebugGroup<'_, MTLRenderCommandEncoder> {
+        unsafe { self.encoder.debug_group(name) }
+    }
+    pub fn set_render_pipeline_state(&self, state: &MTLRenderPipelineState) {
+        unsafe { self.encoder.set_render_pipeline_state(state) }
+    }
+    pub fn set_depth_stencil_state(&self, state: &MTLDepthStencilState) {
+        unsafe { self.encoder.set_depth_stencil_state(state) }
+    }
+    pub fn set_vertex_buffer(
+        &self,
+        buffer: &MTLBuffer,
+        offset: NSUInteger,
+        index: NSUInteger,
+    ) -> Result<(), Error> {
+        check_range(buffer, offset, 0)?;
+        unsafe { self.encoder.set_vertex_buffer(buffer, offset, index) };
+        Ok(())
+    }
+    pub fn set_fragment_buffer(
+        &self,
+        buffer: &MTLBuffer,
+        offset: NSUInteger,
+        index: NSUInteger,
+    ) -> Result<(), Error> {
+        check_range(buffer, offset, 0)?;
+        unsafe { self.encoder.set_fragment_buffer(buffer, offset, index) };
+        Ok(())
+    }
+    pub fn draw_primitives(
+        &self,
+        primitive: MTLPrimitiveType,
+        start: NSUInteger,
+        vertices: NSUInteger,
+        instances: NSUInteger,
+    ) {
+        unsafe {
+            self.encoder
+                .draw_primitives(primitive, start, vertices, instances, 0)
+        }
+    }
+    pub fn end_encoding(self) {}
+}
+
+impl Drop for RenderEncoder<'_> {
+    fn drop(&mut self) {
+        unsafe { self.encoder.end_encoding() }
+    }
+}
+
+/// Ends encoding when dropped.
+pub struct ComputeEncoder<'a> {
+    encoder: MTLComputeCommandEncoder,
+    _buffer: PhantomData<&'a mut CommandBuffer>,
+}
+
+impl ComputeEncoder<'_> {
+    pub fn as_raw(&self) -> &MTLComputeCommandEncoder {
+        &self.encoder
+    }
+    pub fn set_label(&self, label: &str) {
+        unsafe { self.encoder.set_label(label) }
+    }
+    pub fn debug_group(&self, name: &str) -> DebugGroup<'_, MTLComputeCommandEncoder> {
+        unsafe { self.encoder.debug_group(name) }
+    }
+    pub fn set_compute_pipeline_state(&self, state: &MTLComputePipelineState) {
+        unsafe { self.encoder.set_compute_pipeline_state(state) }
+    }
+    pub fn set_buffer(
+        &self,
+        buffer: &MTLBuffer,
+        offset: NSUInteger,
+        index: NSUInteger,
+    ) -> Result<(), Error> {
+        check_range(buffer, offset, 0)?;
+        unsafe { self.encoder.set_buffer(buffer, offset, index) };
+        Ok(())
+    }
+    /// Copies the data into the command buffer, up to `MAX_SET_BYTES_LENGTH` bytes.
+    pub fn set_bytes<T: Copy>(&self, data: &[T], index: NSUInteger) -> Result<(), Error> {
+        let length = std::mem::size_of_val(data);
+        if length > MAX_SET_BYTES_LENGTH {
+            return Err(Error::OutOfBounds);
+        }
+        unsafe {
+            self.encoder
+                .set_bytes(data.as_ptr() as *const c_void, length as NSUInteger, index)
+        };
+        Ok(())
+    }
+    pub fn dispatch_threadgroups(
+        &self,
+        threadgroups_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    ) {
+        unsafe {
+            self.encoder
+                .dispatch_threadgroups(threadgroups_per_grid, threads_per_threadgroup)
+        }
+    }
+    pub fn dispatch_plan(&self, plan: &DispatchPlan, bounds_index: Option<NSUInteger>) {
+        unsafe { self.encoder.dispatch_plan(plan, bounds_index) }
+    }
+    pub fn end_encoding(self) {}
+}
+
+impl Drop for ComputeEncoder<'_> {
+    fn drop(&mut self) {
+        unsafe { self.encoder.end_encoding() }
+    }
+}
+
+/// Ends encoding when dropped.
+pub struct BlitEncoder<'a> {
+    encoder: MTLBlitCommandEncoder,
+    _buffer: PhantomData<&'a mut CommandBuffer>,
+}
+
+impl BlitEncoder<'_> {
+    pub fn as_raw(&self) -> &MTLBlitCommandEncoder {
+        &self.encoder
+    }
+    pub fn set_label(&self, label: &str) {
+        unsafe { self.encoder.set_label(label) }
+    }
+    pub fn debug_group(&self, name: &str) -> DebugGroup<'_, MTLBlitCommandEncoder> {
+        unsafe { self.encoder.debug_group(name) }
+    }
+    pub fn copy_from_buffer_to_buffer(
+        &self,
+        source: &MTLBuffer,
+        source_offset: NSUInteger,
+        destination: &MTLBuffer,
+        destination_offset: NSUInteger,
+        size: NSUInteger,
+    ) -> Result<(), Error> {
+        check_range(source, source_offset, size)?;
+        check_range(destination, destination_offset, size)?;
+        unsafe {
+            self.encoder.copy_from_buffer_to_buffer(
+                source,
+                source_offset,
+                destination,
+                destination_offset,
+                size,
+            )
+        };
+        Ok(())
+    }
+    pub fn end_encoding(self) {}
+}
+
+impl Drop for BlitEncoder<'_> {
+    fn drop(&mut self) {
+        unsafe { self.encoder.end_encoding() }
+    }
+}
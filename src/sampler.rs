@@ -1,6 +1,13 @@
 use crate::import_objc_macros::*;
-use crate::{handle, MTLCompareFunction, NSUInteger, Object, ObjectPointer};
+use crate::misc::get_label;
+use crate::{
+    handle, DeviceCreated, Labeled, MTLCompareFunction, MTLDevice, NSUInteger, Object,
+    ObjectPointer, SamplerCache,
+};
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLSamplerAddressMode {
     ClampToEdge = 0,
@@ -11,6 +18,7 @@ pub enum MTLSamplerAddressMode {
     ClampToBorderColor = 5,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLSamplerBorderColor {
     /// 0, 0, 0, 0,
@@ -21,12 +29,21 @@ pub enum MTLSamplerBorderColor {
     OpaqueWhite = 2,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u64)]
 pub enum MTLSamplerMinMagFilter {
     Nearest = 0,
     Linear = 1,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u64)]
+pub enum MTLSamplerMipFilter {
+    NotMipmapped = 0,
+    Nearest = 1,
+    Linear = 2,
+}
+
 pub struct MTLSamplerDescriptor(ObjectPointer);
 handle!(MTLSamplerDescriptor);
 
@@ -37,45 +54,89 @@ impl MTLSamplerDescriptor {
     pub unsafe fn set_normalized_coords(&self, normalized: bool) {
         msg_send![self.get_ptr(), setNormalizedCoordinates: normalized]
     }
+    pub unsafe fn get_normalized_coords(&self) -> bool {
+        msg_send![self.get_ptr(), normalizedCoordinates]
+    }
     pub unsafe fn set_r_address_mode(&self, mode: MTLSamplerAddressMode) {
         msg_send![self.get_ptr(), setRAddressMode: mode]
     }
+    pub unsafe fn get_r_address_mode(&self) -> MTLSamplerAddressMode {
+        msg_send![self.get_ptr(), rAddressMode]
+    }
     pub unsafe fn set_s_address_mode(&self, mode: MTLSamplerAddressMode) {
         msg_send![self.get_ptr(), setSAddressMode: mode]
     }
+    pub unsafe fn get_s_address_mode(&self) -> MTLSamplerAddressMode {
+        msg_send![self.get_ptr(), sAddressMode]
+    }
     pub unsafe fn set_t_address_mode(&self, mode: MTLSamplerAddressMode) {
         msg_send![self.get_ptr(), setTAddressMode: mode]
     }
+    pub unsafe fn get_t_address_mode(&self) -> MTLSamplerAddressMode {
+        msg_send![self.get_ptr(), tAddressMode]
+    }
     pub unsafe fn set_border_color(&self, color: MTLSamplerBorderColor) {
         msg_send![self.get_ptr(), setBorderColor: color]
     }
+    pub unsafe fn get_border_color(&self) -> MTLSamplerBorderColor {
+        msg_send![self.get_ptr(), borderColor]
+    }
     pub unsafe fn set_min_filter(&self, filter: MTLSamplerMinMagFilter) {
         msg_send![self.get_ptr(), setMinFilter: filter]
     }
+    pub unsafe fn get_min_filter(&self) -> MTLSamplerMinMagFilter {
+        msg_send![self.get_ptr(), minFilter]
+    }
     pub unsafe fn set_mag_filter(&self, filter: MTLSamplerMinMagFilter) {
         msg_send![self.get_ptr(), setMagFilter: filter]
     }
-    pub unsafe fn set_map_filter(&self, filter: MTLSamplerMinMagFilter) {
+    pub unsafe fn get_mag_filter(&self) -> MTLSamplerMinMagFilter {
+        msg_send![self.get_ptr(), magFilter]
+    }
+    pub unsafe fn set_mip_filter(&self, filter: MTLSamplerMipFilter) {
         msg_send![self.get_ptr(), setMipFilter: filter]
     }
+    pub unsafe fn get_mip_filter(&self) -> MTLSamplerMipFilter {
+        msg_send![self.get_ptr(), mipFilter]
+    }
     pub unsafe fn set_lod_min_clamp(&self, clamp: f32) {
         msg_send![self.get_ptr(), setLodMinClamp: clamp]
     }
+    pub unsafe fn get_lod_min_clamp(&self) -> f32 {
+        msg_send![self.get_ptr(), lodMinClamp]
+    }
     pub unsafe fn set_lod_max_clamp(&self, clamp: f32) {
         msg_send![self.get_ptr(), setLodMaxClamp: clamp]
     }
+    pub unsafe fn get_lod_max_clamp(&self) -> f32 {
+        msg_send![self.get_ptr(), lodMaxClamp]
+    }
+    /// Only available on iOS.
     pub unsafe fn set_lod_average(&self, average: bool) {
         msg_send![self.get_ptr(), setLodAverage: average]
     }
+    /// Only available on iOS.
+    pub unsafe fn get_lod_average(&self) -> bool {
+        msg_send![self.get_ptr(), lodAverage]
+    }
     pub unsafe fn set_max_anisotropy(&self, max: NSUInteger) {
         msg_send![self.get_ptr(), setMaxAnisotropy: max]
     }
+    pub unsafe fn get_max_anisotropy(&self) -> NSUInteger {
+        msg_send![self.get_ptr(), maxAnisotropy]
+    }
     pub unsafe fn set_compare_function(&self, function: MTLCompareFunction) {
         msg_send![self.get_ptr(), setCompareFunction: function]
     }
+    pub unsafe fn get_compare_function(&self) -> MTLCompareFunction {
+        msg_send![self.get_ptr(), compareFunction]
+    }
     pub unsafe fn set_supports_argument_buffers(&self, supports: bool) {
         msg_send![self.get_ptr(), setSupportArgumentBuffers: supports]
     }
+    pub unsafe fn get_supports_argument_buffers(&self) -> bool {
+        msg_send![self.get_ptr(), supportArgumentBuffers]
+    }
 }
 
 impl Object for MTLSamplerDescriptor {
@@ -91,8 +152,18 @@ impl Object for MTLSamplerDescriptor {
     }
 }
 
+impl Labeled for MTLSamplerDescriptor {}
+
 pub struct MTLSamplerState(ObjectPointer);
 handle!(MTLSamplerState);
+unsafe impl Send for MTLSamplerState {}
+unsafe impl Sync for MTLSamplerState {}
+
+impl MTLSamplerState {
+    pub unsafe fn get_label(&self) -> Option<String> {
+        get_label(self.get_ptr())
+    }
+}
 
 impl Object for MTLSamplerState {
     unsafe fn from_ptr(ptr: ObjectPointer) -> Self
@@ -106,3 +177,290 @@ impl Object for MTLSamplerState {
         self.0
     }
 }
+
+impl DeviceCreated for MTLSamplerState {}
+
+#[derive(Debug)]
+pub enum SamplerDescError {
+    /// The maximum anisotropy must be between 1 and 16.
+    InvalidAnisotropy(NSUInteger),
+    /// The minimum level of detail must be positive and no greater than the maximum.
+    InvalidLodRange,
+    /// Samplers with a compare function cannot be encoded into argument buffers on every
+    /// device.
+    CompareFunctionInArgumentBuffer,
+    /// Samplers without normalized coordinates must clamp to the edge or zero, filter
+    /// minification and magnification the same way, and cannot use mipmaps, anisotropy or
+    /// a compare function.
+    InvalidUnnormalizedCoords,
+}
+
+impl Display for SamplerDescError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SamplerDescError::InvalidAnisotropy(max) => {
+                write!(f, "max anisotropy {} is not between 1 and 16", max)
+            }
+            SamplerDescError::InvalidLodRange => f.write_str("invalid level of detail range"),
+            SamplerDescError::CompareFunctionInArgumentBuffer => {
+                f.write_str("compare functions are not supported in argument buffers")
+            }
+            SamplerDescError::InvalidUnnormalizedCoords => {
+                f.write_str("sampler options are unsupported with unnormalized coordinates")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SamplerDescError {}
+
+/// A plain description of a sampler, which can be compared and hashed to reuse sampler
+/// states.
+///
+/// The default matches the default `MTLSamplerDescriptor`.
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerDesc {
+    pub normalized_coords: bool,
+    pub r_address_mode: MTLSamplerAddressMode,
+    pub s_address_mode: MTLSamplerAddressMode,
+    pub t_address_mode: MTLSamplerAddressMode,
+    pub border_color: MTLSamplerBorderColor,
+    pub min_filter: MTLSamplerMinMagFilter,
+    pub mag_filter: MTLSamplerMinMagFilter,
+    pub mip_filter: MTLSamplerMipFilter,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+    /// Only available on iOS.
+    pub lod_average: bool,
+    pub max_anisotropy: NSUInteger,
+    pub compare_function: MTLCompareFunction,
+    pub supports_argument_buffers: bool,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        SamplerDesc {
+            normalized_coords: true,
+            r_address_mode: MTLSamplerAddressMode::ClampToEdge,
+            s_address_mode: MTLSamplerAddressMode::ClampToEdge,
+            t_address_mode: MTLSamplerAddressMode::ClampToEdge,
+            border_color: MTLSamplerBorderColor::TransparentBlack,
+            min_filter: MTLSamplerMinMagFilter::Nearest,
+            mag_filter: MTLSamplerMinMagFilter::Nearest,
+            mip_filter: MTLSamplerMipFilter::NotMipmapped,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: f32::MAX,
+            lod_average: false,
+            max_anisotropy: 1,
+            compare_function: MTLCompareFunction::Never,
+            supports_argument_buffers: false,
+        }
+    }
+}
+
+impl SamplerDesc {
+    /// Linearly filters between texels and mipmaps, clamping to the edge.
+    pub fn linear_clamp() -> SamplerDesc {
+        SamplerDesc {
+            min_filter: MTLSamplerMinMagFilter::Linear,
+            mag_filter: MTLSamplerMinMagFilter::Linear,
+            mip_filter: MTLSamplerMipFilter::Linear,
+            ..Default::default()
+        }
+    }
+    /// Samples the nearest texel and mipmap, repeating the texture.
+    pub fn nearest_repeat() -> SamplerDesc {
+        SamplerDesc {
+            r_address_mode: MTLSamplerAddressMode::Repeat,
+            s_address_mode: MTLSamplerAddressMode::Repeat,
+            t_address_mode: MTLSamplerAddressMode::Repeat,
+            mip_filter: MTLSamplerMipFilter::Nearest,
+            ..Default::default()
+        }
+    }
+    /// Compares against a depth texture with the given function, linearly filtering the
+    /// results.
+    pub fn shadow(function: MTLCompareFunction) -> SamplerDesc {
+        SamplerDesc {
+            min_filter: MTLSamplerMinMagFilter::Linear,
+            mag_filter: MTLSamplerMinMagFilter::Linear,
+            compare_function: function,
+            ..Default::default()
+        }
+    }
+    /// Filters linearly with up to `max_anisotropy` samples, repeating the texture.
+    pub fn anisotropic(max_anisotropy: NSUInteger) -> SamplerDesc {
+        SamplerDesc {
+            r_address_mode: MTLSamplerAddressMode::Repeat,
+            s_address_mode: MTLSamplerAddressMode::Repeat,
+            t_address_mode: MTLSamplerAddressMode::Repeat,
+            max_anisotropy,
+            ..SamplerDesc::linear_clamp()
+        }
+    }
+    /// Checks for combinations which Metal rejects or which are not portable.
+    pub fn validate(&self) -> Result<(), SamplerDescError> {
+        if self.max_anisotropy < 1 || self.max_anisotropy > 16 {
+            return Err(SamplerDescError::InvalidAnisotropy(self.max_anisotropy));
+        }
+        if !(self.lod_min_clamp >= 0.0 && self.lod_min_clamp <= self.lod_max_clamp) {
+            return Err(SamplerDescError::InvalidLodRange);
+        }
+        if self.supports_argument_buffers && self.compare_function != MTLCompareFunction::Never {
+            return Err(SamplerDescError::CompareFunctionInArgumentBuffer);
+        }
+        if !self.normalized_coords {
+            let clamped = |mode: MTLSamplerAddressMode| {
+                mode == MTLSamplerAddressMode::ClampToEdge
+                    || mode == MTLSamplerAddressMode::ClampToZero
+            };
+            if !clamped(self.s_address_mode)
+                || !clamped(self.t_address_mode)
+                || self.min_filter != self.mag_filter
+                || self.mip_filter != MTLSamplerMipFilter::NotMipmapped
+                || self.max_anisotropy != 1
+                || self.compare_function != MTLCompareFunction::Never
+            {
+                return Err(SamplerDescError::InvalidUnnormalizedCoords);
+            }
+        }
+        Ok(())
+    }
+    pub unsafe fn to_descriptor(&self) -> MTLSamplerDescriptor {
+        let desc = MTLSamplerDescriptor::new();
+        desc.set_normalized_coords(self.normalized_coords);
+        desc.set_r_address_mode(self.r_address_mode);
+        desc.set_s_address_mode(self.s_address_mode);
+        desc.set_t_address_mode(self.t_address_mode);
+        desc.set_border_color(self.border_color);
+        desc.set_min_filter(self.min_filter);
+        desc.set_mag_filter(self.mag_filter);
+        desc.set_mip_filter(self.mip_filter);
+        desc.set_lod_min_clamp(self.lod_min_clamp);
+        desc.set_lod_max_clamp(self.lod_max_clamp);
+        // the property does not exist on macOS
+        if self.lod_average {
+            desc.set_lod_average(true);
+        }
+        desc.set_max_anisotropy(self.max_anisotropy);
+        desc.set_compare_function(self.compare_function);
+        desc.set_supports_argument_buffers(self.supports_argument_buffers);
+        desc
+    }
+}
+
+impl PartialEq for SamplerDesc {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized_coords == other.normalized_coords
+            && self.r_address_mode == other.r_address_mode
+            && self.s_address_mode == other.s_address_mode
+            && self.t_address_mode == other.t_address_mode
+            && self.border_color == other.border_color
+            && self.min_filter == other.min_filter
+            && self.mag_filter == other.mag_filter
+            && self.mip_filter == other.mip_filter
+            && self.lod_min_clamp.to_bits() == other.lod_min_clamp.to_bits()
+            && self.lod_max_clamp.to_bits() == other.lod_max_clamp.to_bits()
+            && self.lod_average == other.lod_average
+            && self.max_anisotropy == other.max_anisotropy
+            && self.compare_function == other.compare_function
+            && self.supports_argument_buffers == other.supports_argument_buffers
+    }
+}
+
+impl Eq for SamplerDesc {}
+
+impl Hash for SamplerDesc {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized_coords.hash(state);
+        self.r_address_mode.hash(state);
+        self.s_address_mode.hash(state);
+        self.t_address_mode.hash(state);
+        self.border_color.hash(state);
+        self.min_filter.hash(state);
+        self.mag_filter.hash(state);
+        self.mip_filter.hash(state);
+        self.lod_min_clamp.to_bits().hash(state);
+        self.lod_max_clamp.to_bits().hash(state);
+        self.lod_average.hash(state);
+        self.max_anisotropy.hash(state);
+        self.compare_function.hash(state);
+        self.supports_argument_buffers.hash(state);
+    }
+}
+
+impl MTLDevice {
+    /// Validates the description and creates a sampler state from it.
+    pub unsafe fn new_sampler_state(
+        &self,
+        desc: &SamplerDesc,
+    ) -> Result<MTLSamplerState, SamplerDescError> {
+        desc.validate()?;
+        Ok(self.new_sampler_state_with_descriptor(&desc.to_descriptor()))
+    }
+    /// Creates an empty sampler cache for this device.
+    pub unsafe fn new_sampler_cache(&self) -> SamplerCache {
+        self.new_state_cache()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn presets_are_valid() {
+        assert!(SamplerDesc::default().validate().is_ok());
+        assert!(SamplerDesc::linear_clamp().validate().is_ok());
+        assert!(SamplerDesc::nearest_repeat().validate().is_ok());
+        assert!(SamplerDesc::shadow(MTLCompareFunction::LessEqual)
+            .validate()
+            .is_ok());
+        assert!(SamplerDesc::anisotropic(16).validate().is_ok());
+    }
+
+    #[test]
+    fn validate() {
+        assert!(matches!(
+            SamplerDesc::anisotropic(17).validate(),
+            Err(SamplerDescError::InvalidAnisotropy(17))
+        ));
+        assert!(SamplerDesc::anisotropic(0).validate().is_err());
+
+        let mut desc = SamplerDesc::shadow(MTLCompareFunction::Less);
+        desc.supports_argument_buffers = true;
+        assert!(matches!(
+            desc.validate(),
+            Err(SamplerDescError::CompareFunctionInArgumentBuffer)
+        ));
+
+        let desc = SamplerDesc {
+            lod_min_clamp: 2.0,
+            lod_max_clamp: 1.0,
+            ..Default::default()
+        };
+        assert!(desc.validate().is_err());
+
+        let desc = SamplerDesc {
+            normalized_coords: false,
+            ..SamplerDesc::nearest_repeat()
+        };
+        assert!(matches!(
+            desc.validate(),
+            Err(SamplerDescError::InvalidUnnormalizedCoords)
+        ));
+    }
+
+    #[test]
+    fn hash() {
+        let descs = vec![
+            SamplerDesc::linear_clamp(),
+            SamplerDesc::linear_clamp(),
+            SamplerDesc::nearest_repeat(),
+        ]
+        .into_iter()
+        .collect::<HashSet<_>>();
+        assert_eq!(descs.len(), 2);
+    }
+}
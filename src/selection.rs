@@ -0,0 +1,170 @@
+use crate::{CGDirectDisplayCopyCurrentMetalDevice, DeviceCapabilities, MTLDevice, MTLGPUFamily};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DevicePreference {
+    /// Prefers devices which are not low power.
+    Discrete,
+    LowPower,
+    /// Prefers devices without a display, as for compute work.
+    Headless,
+    /// Prefers the device with the given registry id, such as the one driving a display.
+    RegistryId(u64),
+}
+
+impl DevicePreference {
+    fn matches(&self, device: &DeviceCapabilities) -> bool {
+        match *self {
+            DevicePreference::Discrete => !device.is_low_power,
+            DevicePreference::LowPower => device.is_low_power,
+            DevicePreference::Headless => device.is_headless,
+            DevicePreference::RegistryId(id) => device.registry_id == id,
+        }
+    }
+}
+
+/// Ranks devices by a list of preferences, earlier preferences outweighing later ones.
+///
+/// Devices which match the same preferences are ranked by registry id, so the choice is
+/// the same on every run.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeviceSelector {
+    pub preferences: Vec<DevicePreference>,
+    /// Devices which do not support this family are never selected.
+    pub minimum_family: Option<MTLGPUFamily>,
+}
+
+impl DeviceSelector {
+    pub fn new() -> DeviceSelector {
+        DeviceSelector::default()
+    }
+    pub fn prefer(mut self, preference: DevicePreference) -> DeviceSelector {
+        self.preferences.push(preference);
+        self
+    }
+    /// Prefers the device currently driving the display.
+    ///
+    /// The display id must be valid; see `CGDirectDisplayCopyCurrentMetalDevice`.
+    pub unsafe fn prefer_display(self, display_id: u32) -> DeviceSelector {
+        let device = CGDirectDisplayCopyCurrentMetalDevice(display_id);
+        self.prefer(DevicePreference::RegistryId(device.get_registry_id()))
+    }
+    pub fn require_family(mut self, family: MTLGPUFamily) -> DeviceSelector {
+        self.minimum_family = Some(family);
+        self
+    }
+    /// Returns the indices of the acceptable devices, best first.
+    pub fn rank(&self, devices: &[DeviceCapabilities]) -> Vec<usize> {
+        let mut indices = (0..devices.len())
+            .filter(|&index| match self.minimum_family {
+                Some(family) => devices[index].supports_family(family),
+                None => true,
+            })
+            .collect::<Vec<_>>();
+        let key = |index: &usize| {
+            let device = &devices[*index];
+            let misses = self
+                .preferences
+                .iter()
+                .map(|preference| !preference.matches(device))
+                .collect::<Vec<_>>();
+            (misses, device.registry_id)
+        };
+        indices.sort_by_key(key);
+        indices
+    }
+    /// Selects the best acceptable device, if any.
+    pub unsafe fn select(&self, devices: Vec<MTLDevice>) -> Option<MTLDevice> {
+        let capabilities = devices
+            .iter()
+            .map(|device| device.get_capabilities())
+            .collect::<Vec<_>>();
+        let best = *self.rank(&capabilities).first()?;
+        devices.into_iter().nth(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MTLArgumentBuffersTier, MTLDeviceLocation, MTLReadWriteTextureTier, MTLSize};
+
+    fn device(
+        registry_id: u64,
+        is_low_power: bool,
+        is_headless: bool,
+        families: &[MTLGPUFamily],
+    ) -> DeviceCapabilities {
+        DeviceCapabilities {
+            name: format!("Device {}", registry_id),
+            registry_id,
+            families: families.to_vec(),
+            is_low_power,
+            is_headless,
+            is_removable: false,
+            has_unified_memory: false,
+            location: MTLDeviceLocation::Unspecified,
+            location_number: 0,
+            peer_group_id: 0,
+            read_write_texture_tier: MTLReadWriteTextureTier::None,
+            argument_buffers_tier: MTLArgumentBuffersTier::Tier1,
+            max_argument_buffer_sampler_count: 0,
+            max_threads_per_threadgroup: MTLSize {
+                width: 1024,
+                height: 1024,
+                depth: 1024,
+            },
+            max_threadgroup_memory_length: 0,
+            max_buffer_length: 0,
+            recommended_max_working_set_size: 0,
+            sparse_tile_size_in_bytes: 0,
+            supports_raytracing: false,
+            supports_function_pointers: false,
+            supports_32bit_float_filtering: false,
+            supports_bc_texture_compression: false,
+            are_raster_order_groups_supported: false,
+            is_d24_s8_pixel_format_supported: false,
+        }
+    }
+
+    fn devices() -> Vec<DeviceCapabilities> {
+        vec![
+            device(5, true, false, &[MTLGPUFamily::Mac2]),
+            device(3, false, true, &[MTLGPUFamily::Mac2]),
+            device(1, false, false, &[MTLGPUFamily::Mac1]),
+        ]
+    }
+
+    #[test]
+    fn registry_id_breaks_ties() {
+        assert_eq!(DeviceSelector::new().rank(&devices()), vec![2, 1, 0]);
+        assert_eq!(DeviceSelector::new().rank(&[]), Vec::<usize>::new());
+        let selector = DeviceSelector::new().prefer(DevicePreference::Discrete);
+        assert_eq!(selector.rank(&devices()), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn earlier_preferences_outweigh_later_ones() {
+        let selector = DeviceSelector::new()
+            .prefer(DevicePreference::Headless)
+            .prefer(DevicePreference::Discrete);
+        assert_eq!(selector.rank(&devices()), vec![1, 2, 0]);
+        let selector = DeviceSelector::new()
+            .prefer(DevicePreference::LowPower)
+            .prefer(DevicePreference::Headless);
+        assert_eq!(selector.rank(&devices()), vec![0, 1, 2]);
+        let selector = DeviceSelector::new()
+            .prefer(DevicePreference::RegistryId(3))
+            .prefer(DevicePreference::LowPower);
+        assert_eq!(selector.rank(&devices()), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn minimum_family() {
+        let selector = DeviceSelector::new()
+            .prefer(DevicePreference::LowPower)
+            .require_family(MTLGPUFamily::Mac2);
+        assert_eq!(selector.rank(&devices()), vec![0, 1]);
+        let selector = DeviceSelector::new().require_family(MTLGPUFamily::Apple7);
+        assert!(selector.rank(&devices()).is_empty());
+    }
+}
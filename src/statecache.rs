@@ -0,0 +1,233 @@
+use crate::{
+    ComputePipelineDesc, DepthStencilDesc, MTLComputePipelineState, MTLDepthStencilState,
+    MTLDevice, MTLRenderPipelineState, MTLSamplerState, NSError, RenderPipelineDesc, SamplerDesc,
+    SamplerDescError,
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::hash::Hash;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Creates state objects from plain descriptions of type `D`.
+///
+/// `MTLDevice` creates sampler, depth stencil and pipeline states; other implementations
+/// may be used to cache anything else keyed by a description.
+pub trait StateCreator<D> {
+    type State;
+    type Error;
+
+    unsafe fn create_state(&self, desc: &D) -> Result<Self::State, Self::Error>;
+}
+
+impl StateCreator<SamplerDesc> for MTLDevice {
+    type State = MTLSamplerState;
+    type Error = SamplerDescError;
+
+    unsafe fn create_state(&self, desc: &SamplerDesc) -> Result<MTLSamplerState, SamplerDescError> {
+        self.new_sampler_state(desc)
+    }
+}
+
+impl StateCreator<DepthStencilDesc> for MTLDevice {
+    type State = MTLDepthStencilState;
+    type Error = Infallible;
+
+    unsafe fn create_state(
+        &self,
+        desc: &DepthStencilDesc,
+    ) -> Result<MTLDepthStencilState, Infallible> {
+        Ok(self.new_depth_stencil_state_with_descriptor(&desc.to_descriptor()))
+    }
+}
+
+impl StateCreator<RenderPipelineDesc> for MTLDevice {
+    type State = MTLRenderPipelineState;
+    type Error = NSError;
+
+    unsafe fn create_state(
+        &self,
+        desc: &RenderPipelineDesc,
+    ) -> Result<MTLRenderPipelineState, NSError> {
+        self.new_render_pipeline_state_with_descriptor(&desc.to_descriptor())
+    }
+}
+
+impl StateCreator<ComputePipelineDesc> for MTLDevice {
+    type State = MTLComputePipelineState;
+    type Error = NSError;
+
+    unsafe fn create_state(
+        &self,
+        desc: &ComputePipelineDesc,
+    ) -> Result<MTLComputePipelineState, NSError> {
+        self.new_compute_pipeline_state_with_descriptor(&desc.to_descriptor())
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct StateCacheStats {
+    /// The number of lookups which found an existing state.
+    pub hits: u64,
+    /// The number of lookups which had to create a state, including failed creations.
+    pub misses: u64,
+    /// The total time spent creating states.
+    pub creation_time: Duration,
+}
+
+/// Creates each distinct state only once, sharing it between every lookup of an equal
+/// description.
+pub struct StateCache<D, C: StateCreator<D>> {
+    creator: C,
+    states: HashMap<D, Rc<C::State>>,
+    stats: StateCacheStats,
+}
+
+pub type SamplerCache = StateCache<SamplerDesc, MTLDevice>;
+pub type DepthStencilCache = StateCache<DepthStencilDesc, MTLDevice>;
+pub type RenderPipelineCache = StateCache<RenderPipelineDesc, MTLDevice>;
+pub type ComputePipelineCache = StateCache<ComputePipelineDesc, MTLDevice>;
+
+impl<D: Hash + Eq + Clone, C: StateCreator<D>> StateCache<D, C> {
+    pub fn new(creator: C) -> StateCache<D, C> {
+        StateCache {
+            creator,
+            states: HashMap::new(),
+            stats: StateCacheStats::default(),
+        }
+    }
+    /// Returns the state matching the description, creating it if this cache has not yet.
+    ///
+    /// Failed creations are not cached.
+    pub unsafe fn get(&mut self, desc: &D) -> Result<Rc<C::State>, C::Error> {
+        if let Some(state) = self.states.get(desc) {
+            self.stats.hits += 1;
+            return Ok(state.clone());
+        }
+        self.stats.misses += 1;
+        let start = Instant::now();
+        let state = self.creator.create_state(desc);
+        self.stats.creation_time += start.elapsed();
+        let state = Rc::new(state?);
+        self.states.insert(desc.clone(), state.clone());
+        Ok(state)
+    }
+    pub fn contains(&self, desc: &D) -> bool {
+        self.states.contains_key(desc)
+    }
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+    /// Removes every state from the cache. States still in use elsewhere are released
+    /// once their last handle is dropped.
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+    pub fn get_stats(&self) -> StateCacheStats {
+        self.stats
+    }
+    pub fn reset_stats(&mut self) {
+        self.stats = StateCacheStats::default();
+    }
+    pub fn get_creator(&self) -> &C {
+        &self.creator
+    }
+}
+
+impl MTLDevice {
+    /// Creates an empty state cache which creates its states with this device.
+    pub unsafe fn new_state_cache<D: Hash + Eq + Clone>(&self) -> StateCache<D, MTLDevice>
+    where
+        MTLDevice: StateCreator<D>,
+    {
+        StateCache::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Creates numbered states, recording every description it is asked for.
+    #[derive(Default)]
+    struct FakeCreator {
+        created: RefCell<Vec<SamplerDesc>>,
+    }
+
+    impl StateCreator<SamplerDesc> for FakeCreator {
+        type State = usize;
+        type Error = SamplerDescError;
+
+        unsafe fn create_state(&self, desc: &SamplerDesc) -> Result<usize, SamplerDescError> {
+            self.created.borrow_mut().push(*desc);
+            desc.validate()?;
+            Ok(self.created.borrow().len())
+        }
+    }
+
+    #[test]
+    fn deduplicates() {
+        let mut cache = StateCache::new(FakeCreator::default());
+        unsafe {
+            let first = cache.get(&SamplerDesc::linear_clamp()).unwrap();
+            let second = cache.get(&SamplerDesc::linear_clamp()).unwrap();
+            assert!(Rc::ptr_eq(&first, &second));
+            let other = cache.get(&SamplerDesc::nearest_repeat()).unwrap();
+            assert!(!Rc::ptr_eq(&first, &other));
+            assert_eq!((*first, *other), (1, 2));
+        }
+        assert_eq!(
+            *cache.get_creator().created.borrow(),
+            [SamplerDesc::linear_clamp(), SamplerDesc::nearest_repeat()]
+        );
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains(&SamplerDesc::nearest_repeat()));
+    }
+
+    #[test]
+    fn stats() {
+        let mut cache = StateCache::new(FakeCreator::default());
+        unsafe {
+            cache.get(&SamplerDesc::linear_clamp()).unwrap();
+            cache.get(&SamplerDesc::linear_clamp()).unwrap();
+            cache.get(&SamplerDesc::linear_clamp()).unwrap();
+            cache.get(&SamplerDesc::nearest_repeat()).unwrap();
+        }
+        let stats = cache.get_stats();
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+        cache.reset_stats();
+        assert_eq!(cache.get_stats(), StateCacheStats::default());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn failures_are_not_cached() {
+        let mut cache = StateCache::new(FakeCreator::default());
+        let invalid = SamplerDesc::anisotropic(99);
+        unsafe {
+            assert!(cache.get(&invalid).is_err());
+            assert!(cache.get(&invalid).is_err());
+        }
+        // each lookup retries, and counts as a miss
+        assert_eq!(cache.get_creator().created.borrow().len(), 2);
+        assert_eq!(cache.get_stats().misses, 2);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn clear_keeps_states_alive() {
+        let mut cache = StateCache::new(FakeCreator::default());
+        let state = unsafe { cache.get(&SamplerDesc::linear_clamp()).unwrap() };
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(Rc::strong_count(&state), 1);
+        // the next lookup creates a new state
+        let recreated = unsafe { cache.get(&SamplerDesc::linear_clamp()).unwrap() };
+        assert!(!Rc::ptr_eq(&state, &recreated));
+        assert_eq!(*recreated, 2);
+    }
+}
@@ -0,0 +1,196 @@
+#![cfg(feature = "loader")]
+
+use iron_oxide::*;
+
+const RGB8_PNG: &[u8] = include_bytes!("fixtures/rgb8_2x2.png");
+const GRAY16_PNG: &[u8] = include_bytes!("fixtures/gray16_2x1.png");
+const R8_KTX: &[u8] = include_bytes!("fixtures/r8_3x2.ktx");
+const R8_ARRAY_KTX: &[u8] = include_bytes!("fixtures/r8_3x3_array.ktx");
+const RGBA8_KTX2: &[u8] = include_bytes!("fixtures/rgba8_4x4_mips.ktx2");
+const BC1_CUBE_DDS: &[u8] = include_bytes!("fixtures/bc1_cube_8x8.dds");
+const RGBA8_ARRAY_DDS: &[u8] = include_bytes!("fixtures/rgba8_2x2_array.dds");
+
+fn load(bytes: &[u8]) -> TextureImage {
+    TextureImage::from_bytes(bytes).unwrap()
+}
+
+fn with_u32(bytes: &[u8], offset: usize, value: u32) -> Vec<u8> {
+    let mut bytes = bytes.to_vec();
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn png() {
+    let image = load(RGB8_PNG);
+    assert_eq!(image.format, MTLPixelFormat::RGBA8Unorm);
+    assert_eq!(image.texture_type, MTLTextureType::D2);
+    assert_eq!((image.width, image.height), (2, 2));
+    let sub = &image.subresources[0];
+    assert_eq!(sub.bytes_per_row, 8);
+    assert_eq!(
+        sub.data,
+        [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 255]
+    );
+
+    let image = load(GRAY16_PNG);
+    assert_eq!(image.format, MTLPixelFormat::RGBA16Unorm);
+    assert_eq!(
+        image.subresources[0].data,
+        [
+            0x34, 0x12, 0x34, 0x12, 0x34, 0x12, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            0xFF, 0xFF
+        ]
+    );
+}
+
+#[test]
+fn ktx() {
+    let image = load(R8_KTX);
+    assert_eq!(image.format, MTLPixelFormat::R8Unorm);
+    assert_eq!(image.texture_type, MTLTextureType::D2);
+    assert_eq!((image.width, image.height), (3, 2));
+    let sub = &image.subresources[0];
+    assert_eq!(sub.bytes_per_row, 4);
+    assert_eq!(sub.data, [1, 2, 3, 0, 4, 5, 6, 0]);
+}
+
+#[test]
+fn ktx_unpadded_array() {
+    // an odd slice size with unpadded rows, so the layers are not padded either
+    let image = load(R8_ARRAY_KTX);
+    assert_eq!(image.texture_type, MTLTextureType::D2Array);
+    assert_eq!(image.array_length, 2);
+    assert_eq!(image.subresources.len(), 2);
+    for (slice, sub) in image.subresources.iter().enumerate() {
+        assert_eq!((sub.slice, sub.bytes_per_row), (slice as u64, 3));
+        let first = slice as u8 * 9 + 1;
+        assert_eq!(sub.data, (first..first + 9).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn ktx2() {
+    let image = load(RGBA8_KTX2);
+    assert_eq!(image.format, MTLPixelFormat::RGBA8Unorm);
+    assert_eq!(image.mipmap_level_count, 3);
+    assert_eq!(image.subresources.len(), 3);
+    for (level, sub) in image.subresources.iter().enumerate() {
+        let size = 4 >> level;
+        assert_eq!(
+            (sub.level, sub.width, sub.height),
+            (level as u64, size, size)
+        );
+        assert_eq!(sub.data, vec![level as u8 + 1; (size * size * 4) as usize]);
+    }
+}
+
+#[test]
+fn dds() {
+    let image = load(BC1_CUBE_DDS);
+    assert_eq!(image.format, MTLPixelFormat::BC1_RGBA);
+    assert_eq!(image.texture_type, MTLTextureType::Cube);
+    assert_eq!(image.slice_count(), 6);
+    assert_eq!(image.subresources.len(), 12);
+    // reordered from face-major to level-major
+    let face = image.get_subresource(0, 3).unwrap();
+    assert_eq!(face.data, [3; 32]);
+    let face = image.get_subresource(1, 3).unwrap();
+    assert_eq!((face.width, face.bytes_per_row), (4, 8));
+    assert_eq!(face.data, [0x13; 8]);
+
+    let image = load(RGBA8_ARRAY_DDS);
+    assert_eq!(image.format, MTLPixelFormat::RGBA8Unorm);
+    assert_eq!(image.texture_type, MTLTextureType::D2Array);
+    assert_eq!(image.array_length, 2);
+    assert_eq!(image.get_subresource(0, 1).unwrap().data[0], 100);
+}
+
+#[test]
+fn truncated() {
+    for length in [16, RGB8_PNG.len() / 2] {
+        assert!(TextureImage::from_bytes(&RGB8_PNG[..length]).is_err());
+    }
+    for fixture in [R8_KTX, RGBA8_KTX2, BC1_CUBE_DDS, RGBA8_ARRAY_DDS] {
+        for length in [16, fixture.len() / 2, fixture.len() - 1] {
+            assert!(
+                TextureImage::from_bytes(&fixture[..length]).is_err(),
+                "{} of {} bytes",
+                length,
+                fixture.len()
+            );
+        }
+    }
+    assert!(matches!(
+        TextureImage::from_bytes(&R8_KTX[..70]),
+        Err(LoaderError::Truncated)
+    ));
+}
+
+#[test]
+fn malformed_headers() {
+    assert!(matches!(
+        TextureImage::from_bytes(b"not an image"),
+        Err(LoaderError::UnknownContainer)
+    ));
+    // a KTX file with an invalid endianness marker
+    assert!(matches!(
+        TextureImage::from_bytes(&with_u32(R8_KTX, 12, 0x1234_5678)),
+        Err(LoaderError::Malformed(_))
+    ));
+    // a KTX2 file with an unknown VkFormat
+    assert!(matches!(
+        TextureImage::from_bytes(&with_u32(RGBA8_KTX2, 12, 0xFFFF)),
+        Err(LoaderError::UnsupportedFormat(_))
+    ));
+    // a KTX2 file with 5 faces
+    assert!(matches!(
+        TextureImage::from_bytes(&with_u32(RGBA8_KTX2, 36, 5)),
+        Err(LoaderError::Malformed(_))
+    ));
+    // a DDS file with a zero width
+    assert!(matches!(
+        TextureImage::from_bytes(&with_u32(BC1_CUBE_DDS, 16, 0)),
+        Err(LoaderError::Malformed(_))
+    ));
+}
+
+#[test]
+fn too_many_levels() {
+    // a 1x1 BC1 DDS declaring 70 mipmap levels
+    let mut dds = with_u32(BC1_CUBE_DDS, 12, 1);
+    dds = with_u32(&dds, 16, 1);
+    dds = with_u32(&dds, 28, 70);
+    dds = with_u32(&dds, 112, 0);
+    dds.resize(560, 0);
+    assert!(matches!(
+        TextureImage::from_bytes(&dds),
+        Err(LoaderError::Malformed("too many mipmap levels"))
+    ));
+    // a 4x4 KTX2 declaring 4 levels
+    assert!(matches!(
+        TextureImage::from_bytes(&with_u32(RGBA8_KTX2, 40, 4)),
+        Err(LoaderError::Malformed("too many mipmap levels"))
+    ));
+}
+
+#[test]
+fn oversized_slice_counts() {
+    // layer counts which would need far more data than the files hold are rejected
+    // before anything is allocated for them
+    assert!(matches!(
+        TextureImage::from_bytes(&with_u32(RGBA8_KTX2, 32, u32::MAX)),
+        Err(LoaderError::Truncated)
+    ));
+    assert!(matches!(
+        TextureImage::from_bytes(&with_u32(R8_KTX, 48, u32::MAX)),
+        Err(LoaderError::Truncated)
+    ));
+    assert!(matches!(
+        TextureImage::from_bytes(&with_u32(RGBA8_ARRAY_DDS, 128 + 12, u32::MAX)),
+        Err(LoaderError::Truncated)
+    ));
+    // dimensions whose level sizes overflow
+    let huge = with_u32(&with_u32(RGBA8_KTX2, 20, u32::MAX), 24, u32::MAX);
+    assert!(TextureImage::from_bytes(&with_u32(&huge, 28, u32::MAX)).is_err());
+}
@@ -0,0 +1,11 @@
+use iron_oxide::safe::Device;
+
+fn main() {
+    let device = Device::system_default().unwrap();
+    let queue = device.new_command_queue().unwrap();
+    let mut command_buffer = queue.new_command_buffer().unwrap();
+    let mut group = command_buffer.debug_group("frame");
+    group.new_compute_encoder().unwrap().end_encoding();
+    command_buffer.commit();
+    drop(group);
+}
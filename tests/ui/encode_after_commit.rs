@@ -0,0 +1,10 @@
+use iron_oxide::safe::Device;
+
+fn main() {
+    let device = Device::system_default().unwrap();
+    let queue = device.new_command_queue().unwrap();
+    let mut command_buffer = queue.new_command_buffer().unwrap();
+    let committed = command_buffer.commit();
+    command_buffer.new_compute_encoder().unwrap();
+    committed.wait_until_completed();
+}
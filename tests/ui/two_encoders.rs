@@ -0,0 +1,11 @@
+use iron_oxide::safe::Device;
+
+fn main() {
+    let device = Device::system_default().unwrap();
+    let queue = device.new_command_queue().unwrap();
+    let mut command_buffer = queue.new_command_buffer().unwrap();
+    let compute = command_buffer.new_compute_encoder().unwrap();
+    let blit = command_buffer.new_blit_encoder().unwrap();
+    compute.end_encoding();
+    blit.end_encoding();
+}